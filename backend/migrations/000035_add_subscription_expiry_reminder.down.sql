@@ -0,0 +1 @@
+ALTER TABLE user_subscriptions DROP COLUMN IF EXISTS expiry_reminder_sent_at;
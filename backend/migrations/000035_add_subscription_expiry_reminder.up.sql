@@ -0,0 +1,2 @@
+-- Track when the "subscription expiring soon" reminder was sent
+ALTER TABLE user_subscriptions ADD COLUMN expiry_reminder_sent_at TIMESTAMPTZ;
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS alliance_treasury_log;
+DROP TYPE IF EXISTS treasury_action;
+DROP TABLE IF EXISTS alliance_treasury;
@@ -0,0 +1,34 @@
+-- Alliance treasury: pooled gold and resources
+CREATE TABLE alliance_treasury (
+    alliance_id UUID PRIMARY KEY REFERENCES alliances(id) ON DELETE CASCADE,
+    gold INT NOT NULL DEFAULT 0,
+    wood INT NOT NULL DEFAULT 0,
+    clay INT NOT NULL DEFAULT 0,
+    iron INT NOT NULL DEFAULT 0,
+    crop INT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    CONSTRAINT treasury_non_negative CHECK (
+        gold >= 0 AND wood >= 0 AND clay >= 0 AND iron >= 0 AND crop >= 0
+    )
+);
+
+-- Treasury action
+CREATE TYPE treasury_action AS ENUM ('deposit', 'withdraw');
+
+-- Every deposit and withdrawal
+CREATE TABLE alliance_treasury_log (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    alliance_id UUID NOT NULL REFERENCES alliances(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id),
+    village_id UUID REFERENCES villages(id) ON DELETE SET NULL,
+    action treasury_action NOT NULL,
+    gold INT NOT NULL DEFAULT 0,
+    wood INT NOT NULL DEFAULT 0,
+    clay INT NOT NULL DEFAULT 0,
+    iron INT NOT NULL DEFAULT 0,
+    crop INT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_alliance_treasury_log_alliance ON alliance_treasury_log(alliance_id, created_at DESC);
@@ -0,0 +1,5 @@
+-- Remove processing buildings
+DELETE FROM buildings WHERE building_type IN ('sawmill', 'brickyard', 'iron_foundry', 'grain_mill');
+
+-- Note: Cannot remove enum values in PostgreSQL without recreating the type
+-- The enum values will remain but the buildings are deleted
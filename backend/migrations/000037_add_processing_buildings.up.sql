@@ -0,0 +1,5 @@
+-- Processing buildings that boost resource field production
+ALTER TYPE building_type ADD VALUE 'sawmill';
+ALTER TYPE building_type ADD VALUE 'brickyard';
+ALTER TYPE building_type ADD VALUE 'iron_foundry';
+ALTER TYPE building_type ADD VALUE 'grain_mill';
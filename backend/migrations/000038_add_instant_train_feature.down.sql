@@ -0,0 +1,2 @@
+-- Note: PostgreSQL does not support removing enum values directly
+-- The instant_train value will remain in the enum but be unused
@@ -0,0 +1,3 @@
+-- Add instant_train to gold_feature enum
+-- Note: Can't use the new value in the same transaction, so INSERT is in migration 39
+ALTER TYPE gold_feature ADD VALUE IF NOT EXISTS 'instant_train';
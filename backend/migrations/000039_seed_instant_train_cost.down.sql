@@ -0,0 +1,2 @@
+-- Remove instant_train from gold_feature_costs
+DELETE FROM gold_feature_costs WHERE feature = 'instant_train';
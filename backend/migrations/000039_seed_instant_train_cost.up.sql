@@ -0,0 +1,4 @@
+-- Add instant_train to gold_feature_costs (after enum value was committed in 000038)
+INSERT INTO gold_feature_costs (feature, base_cost, description) VALUES
+    ('instant_train', 0, 'Cost calculated based on training time')
+ON CONFLICT (feature) DO NOTHING;
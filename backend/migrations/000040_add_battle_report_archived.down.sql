@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_battle_reports_prunable;
+ALTER TABLE battle_reports DROP COLUMN IF EXISTS archived;
@@ -0,0 +1,4 @@
+-- Let players keep important battle reports out of the retention cleanup
+ALTER TABLE battle_reports ADD COLUMN archived BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX idx_battle_reports_prunable ON battle_reports(created_at) WHERE archived = FALSE;
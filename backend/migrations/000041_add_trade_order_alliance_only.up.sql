@@ -0,0 +1,2 @@
+-- Alliance-only orders are visible to and fillable by members of the owner's alliance only
+ALTER TABLE trade_orders ADD COLUMN alliance_only BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Enum values cannot be dropped; fold cancelled invitations into expired instead
+UPDATE alliance_invitations SET status = 'expired' WHERE status = 'cancelled';
@@ -0,0 +1,2 @@
+-- Invitations withdrawn by the inviting alliance before the invitee responded
+ALTER TYPE invitation_status ADD VALUE IF NOT EXISTS 'cancelled';
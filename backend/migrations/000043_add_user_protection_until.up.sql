@@ -0,0 +1,2 @@
+-- Beginner protection: villages of this player can't be attacked until this time
+ALTER TABLE users ADD COLUMN protection_until TIMESTAMPTZ;
@@ -0,0 +1,2 @@
+ALTER TABLE conversations DROP COLUMN IF EXISTS user_2_muted;
+ALTER TABLE conversations DROP COLUMN IF EXISTS user_1_muted;
@@ -0,0 +1,4 @@
+-- Per-user mute tracking: muted conversations still receive messages
+-- but are left out of the unread badge
+ALTER TABLE conversations ADD COLUMN user_1_muted BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE conversations ADD COLUMN user_2_muted BOOLEAN NOT NULL DEFAULT FALSE;
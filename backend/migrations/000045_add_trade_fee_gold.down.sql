@@ -0,0 +1,2 @@
+ALTER TABLE trade_transactions DROP CONSTRAINT IF EXISTS non_negative_tx_fee;
+ALTER TABLE trade_transactions DROP COLUMN IF EXISTS fee_gold;
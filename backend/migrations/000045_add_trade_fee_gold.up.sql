@@ -0,0 +1,3 @@
+-- Market tax taken from each fill; this gold leaves the economy
+ALTER TABLE trade_transactions ADD COLUMN fee_gold INT NOT NULL DEFAULT 0;
+ALTER TABLE trade_transactions ADD CONSTRAINT non_negative_tx_fee CHECK (fee_gold >= 0);
@@ -0,0 +1 @@
+ALTER TABLE villages DROP COLUMN IF EXISTS note;
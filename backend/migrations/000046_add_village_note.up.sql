@@ -0,0 +1,2 @@
+-- Private note the owner can keep on a village (build plans, purpose)
+ALTER TABLE villages ADD COLUMN note TEXT;
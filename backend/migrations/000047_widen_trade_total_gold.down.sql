@@ -0,0 +1 @@
+ALTER TABLE trade_transactions ALTER COLUMN total_gold TYPE INT;
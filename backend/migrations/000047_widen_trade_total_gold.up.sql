@@ -0,0 +1,2 @@
+-- quantity * price_per_unit can exceed the INT range
+ALTER TABLE trade_transactions ALTER COLUMN total_gold TYPE BIGINT;
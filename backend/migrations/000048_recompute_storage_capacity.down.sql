@@ -0,0 +1 @@
+-- Capacities are derived data; nothing to undo
@@ -0,0 +1,13 @@
+-- Bring every village's storage capacity back in line with its finished
+-- Warehouse/Granary levels (base 800 + 400 * 1.2^level per building)
+UPDATE villages v
+SET warehouse_capacity = 800 + COALESCE((
+        SELECT SUM(FLOOR(400 * POWER(1.2, b.level)))::INT
+        FROM buildings b
+        WHERE b.village_id = v.id AND b.building_type = 'warehouse' AND b.level > 0
+    ), 0),
+    granary_capacity = 800 + COALESCE((
+        SELECT SUM(FLOOR(400 * POWER(1.2, b.level)))::INT
+        FROM buildings b
+        WHERE b.village_id = v.id AND b.building_type = 'granary' AND b.level > 0
+    ), 0);
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_trade_tx_pair;
+DROP TABLE IF EXISTS trade_flagged_pairs;
+ALTER TABLE trade_transactions DROP COLUMN IF EXISTS seller_ip;
+ALTER TABLE trade_transactions DROP COLUMN IF EXISTS buyer_ip;
+ALTER TABLE trade_orders DROP COLUMN IF EXISTS client_ip;
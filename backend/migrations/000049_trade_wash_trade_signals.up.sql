@@ -0,0 +1,28 @@
+-- Signals used to keep self-dealing trades out of public market prices
+
+-- Client IP recorded when an order is placed
+ALTER TABLE trade_orders ADD COLUMN client_ip TEXT;
+
+-- Client IPs of both parties at the time of the trade
+ALTER TABLE trade_transactions ADD COLUMN buyer_ip TEXT;
+ALTER TABLE trade_transactions ADD COLUMN seller_ip TEXT;
+
+-- Player pairs an admin has flagged as trading with each other to move prices
+CREATE TABLE trade_flagged_pairs (
+    user_1_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_2_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    reason TEXT,
+    flagged_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (user_1_id, user_2_id),
+    -- Each pair is stored once, smaller id first
+    CONSTRAINT ordered_flagged_pair CHECK (user_1_id < user_2_id)
+);
+
+-- Counting recent trades between the same two players
+CREATE INDEX idx_trade_tx_pair ON trade_transactions (
+    LEAST(buyer_id, seller_id),
+    GREATEST(buyer_id, seller_id),
+    created_at
+);
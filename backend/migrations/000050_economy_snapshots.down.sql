@@ -0,0 +1 @@
+DROP TABLE IF EXISTS economy_snapshots;
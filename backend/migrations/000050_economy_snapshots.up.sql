@@ -0,0 +1,24 @@
+-- Hourly snapshots of server-wide economy totals, used for 24h deltas
+CREATE TABLE economy_snapshots (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+
+    -- Gold held by players, plus gold escrowed by open buy orders
+    gold_in_circulation BIGINT NOT NULL,
+    gold_in_escrow BIGINT NOT NULL,
+
+    -- Resources stored across all villages
+    total_wood BIGINT NOT NULL,
+    total_clay BIGINT NOT NULL,
+    total_iron BIGINT NOT NULL,
+    total_crop BIGINT NOT NULL,
+
+    -- Rolling 24h activity at capture time
+    trade_count_24h BIGINT NOT NULL,
+    trade_quantity_24h BIGINT NOT NULL,
+    trade_gold_24h BIGINT NOT NULL,
+    gold_purchased_24h BIGINT NOT NULL,
+
+    captured_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_economy_snapshots_captured_at ON economy_snapshots (captured_at DESC);
@@ -0,0 +1,24 @@
+-- Merchants carrying traded resources to the buyer's village
+CREATE TABLE resource_deliveries (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    trade_transaction_id UUID NOT NULL REFERENCES trade_transactions(id) ON DELETE CASCADE,
+
+    -- Route: seller's village to buyer's village
+    from_village_id UUID NOT NULL REFERENCES villages(id) ON DELETE CASCADE,
+    to_village_id UUID NOT NULL REFERENCES villages(id) ON DELETE CASCADE,
+    recipient_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+
+    -- Cargo
+    resource_type trade_resource_type NOT NULL,
+    quantity INT NOT NULL CHECK (quantity > 0),
+
+    -- Timing
+    departed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    arrives_at TIMESTAMPTZ NOT NULL,
+    delivered_at TIMESTAMPTZ
+);
+
+-- Deliveries still on the road, soonest first
+CREATE INDEX idx_resource_deliveries_pending ON resource_deliveries(arrives_at)
+    WHERE delivered_at IS NULL;
+CREATE INDEX idx_resource_deliveries_to_village ON resource_deliveries(to_village_id);
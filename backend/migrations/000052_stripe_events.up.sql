@@ -0,0 +1,6 @@
+-- Stripe webhook events already handled, so retried deliveries are ignored
+CREATE TABLE stripe_events (
+    event_id VARCHAR(255) PRIMARY KEY,
+    event_type VARCHAR(100) NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,2 @@
+-- When the current upgrade started, so cancelling can refund by actual progress
+ALTER TABLE buildings ADD COLUMN upgrade_started_at TIMESTAMPTZ;
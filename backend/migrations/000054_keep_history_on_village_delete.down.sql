@@ -0,0 +1,42 @@
+-- Rows left without a village or order can't satisfy the old constraints
+DELETE FROM trade_transactions
+WHERE buy_order_id IS NULL OR sell_order_id IS NULL
+    OR buyer_village_id IS NULL OR seller_village_id IS NULL;
+DELETE FROM battle_reports WHERE attacker_village_id IS NULL;
+DELETE FROM scout_reports WHERE attacker_village_id IS NULL;
+
+ALTER TABLE trade_transactions
+    DROP CONSTRAINT trade_transactions_buy_order_id_fkey,
+    DROP CONSTRAINT trade_transactions_sell_order_id_fkey,
+    DROP CONSTRAINT trade_transactions_buyer_village_id_fkey,
+    DROP CONSTRAINT trade_transactions_seller_village_id_fkey,
+    ADD CONSTRAINT trade_transactions_buy_order_id_fkey
+        FOREIGN KEY (buy_order_id) REFERENCES trade_orders(id),
+    ADD CONSTRAINT trade_transactions_sell_order_id_fkey
+        FOREIGN KEY (sell_order_id) REFERENCES trade_orders(id),
+    ADD CONSTRAINT trade_transactions_buyer_village_id_fkey
+        FOREIGN KEY (buyer_village_id) REFERENCES villages(id),
+    ADD CONSTRAINT trade_transactions_seller_village_id_fkey
+        FOREIGN KEY (seller_village_id) REFERENCES villages(id),
+    ALTER COLUMN buy_order_id SET NOT NULL,
+    ALTER COLUMN sell_order_id SET NOT NULL,
+    ALTER COLUMN buyer_village_id SET NOT NULL,
+    ALTER COLUMN seller_village_id SET NOT NULL;
+
+ALTER TABLE battle_reports
+    DROP CONSTRAINT battle_reports_attacker_village_id_fkey,
+    DROP CONSTRAINT battle_reports_defender_village_id_fkey,
+    ADD CONSTRAINT battle_reports_attacker_village_id_fkey
+        FOREIGN KEY (attacker_village_id) REFERENCES villages(id),
+    ADD CONSTRAINT battle_reports_defender_village_id_fkey
+        FOREIGN KEY (defender_village_id) REFERENCES villages(id),
+    ALTER COLUMN attacker_village_id SET NOT NULL;
+
+ALTER TABLE scout_reports
+    DROP CONSTRAINT scout_reports_attacker_village_id_fkey,
+    DROP CONSTRAINT scout_reports_defender_village_id_fkey,
+    ADD CONSTRAINT scout_reports_attacker_village_id_fkey
+        FOREIGN KEY (attacker_village_id) REFERENCES villages(id),
+    ADD CONSTRAINT scout_reports_defender_village_id_fkey
+        FOREIGN KEY (defender_village_id) REFERENCES villages(id),
+    ALTER COLUMN attacker_village_id SET NOT NULL;
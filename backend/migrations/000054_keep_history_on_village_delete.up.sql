@@ -0,0 +1,38 @@
+-- Trades and reports are shared with other players, so deleting a village
+-- only clears its reference instead of removing their history
+
+ALTER TABLE trade_transactions
+    ALTER COLUMN buy_order_id DROP NOT NULL,
+    ALTER COLUMN sell_order_id DROP NOT NULL,
+    ALTER COLUMN buyer_village_id DROP NOT NULL,
+    ALTER COLUMN seller_village_id DROP NOT NULL,
+    DROP CONSTRAINT trade_transactions_buy_order_id_fkey,
+    DROP CONSTRAINT trade_transactions_sell_order_id_fkey,
+    DROP CONSTRAINT trade_transactions_buyer_village_id_fkey,
+    DROP CONSTRAINT trade_transactions_seller_village_id_fkey,
+    ADD CONSTRAINT trade_transactions_buy_order_id_fkey
+        FOREIGN KEY (buy_order_id) REFERENCES trade_orders(id) ON DELETE SET NULL,
+    ADD CONSTRAINT trade_transactions_sell_order_id_fkey
+        FOREIGN KEY (sell_order_id) REFERENCES trade_orders(id) ON DELETE SET NULL,
+    ADD CONSTRAINT trade_transactions_buyer_village_id_fkey
+        FOREIGN KEY (buyer_village_id) REFERENCES villages(id) ON DELETE SET NULL,
+    ADD CONSTRAINT trade_transactions_seller_village_id_fkey
+        FOREIGN KEY (seller_village_id) REFERENCES villages(id) ON DELETE SET NULL;
+
+ALTER TABLE battle_reports
+    ALTER COLUMN attacker_village_id DROP NOT NULL,
+    DROP CONSTRAINT battle_reports_attacker_village_id_fkey,
+    DROP CONSTRAINT battle_reports_defender_village_id_fkey,
+    ADD CONSTRAINT battle_reports_attacker_village_id_fkey
+        FOREIGN KEY (attacker_village_id) REFERENCES villages(id) ON DELETE SET NULL,
+    ADD CONSTRAINT battle_reports_defender_village_id_fkey
+        FOREIGN KEY (defender_village_id) REFERENCES villages(id) ON DELETE SET NULL;
+
+ALTER TABLE scout_reports
+    ALTER COLUMN attacker_village_id DROP NOT NULL,
+    DROP CONSTRAINT scout_reports_attacker_village_id_fkey,
+    DROP CONSTRAINT scout_reports_defender_village_id_fkey,
+    ADD CONSTRAINT scout_reports_attacker_village_id_fkey
+        FOREIGN KEY (attacker_village_id) REFERENCES villages(id) ON DELETE SET NULL,
+    ADD CONSTRAINT scout_reports_defender_village_id_fkey
+        FOREIGN KEY (defender_village_id) REFERENCES villages(id) ON DELETE SET NULL;
@@ -0,0 +1,15 @@
+DELETE FROM resource_deliveries WHERE from_village_id IS NULL OR to_village_id IS NULL;
+
+ALTER TABLE resource_deliveries
+    DROP CONSTRAINT pending_delivery_has_destination,
+    DROP CONSTRAINT resource_deliveries_trade_transaction_id_fkey,
+    DROP CONSTRAINT resource_deliveries_from_village_id_fkey,
+    DROP CONSTRAINT resource_deliveries_to_village_id_fkey,
+    ADD CONSTRAINT resource_deliveries_trade_transaction_id_fkey
+        FOREIGN KEY (trade_transaction_id) REFERENCES trade_transactions(id) ON DELETE CASCADE,
+    ADD CONSTRAINT resource_deliveries_from_village_id_fkey
+        FOREIGN KEY (from_village_id) REFERENCES villages(id) ON DELETE CASCADE,
+    ADD CONSTRAINT resource_deliveries_to_village_id_fkey
+        FOREIGN KEY (to_village_id) REFERENCES villages(id) ON DELETE CASCADE,
+    ALTER COLUMN from_village_id SET NOT NULL,
+    ALTER COLUMN to_village_id SET NOT NULL;
@@ -0,0 +1,18 @@
+-- Deliveries are paid for, so deleting a village must not silently drop them.
+-- The village is cleared from finished deliveries; ones still on the road
+-- have to be redirected or refunded before their destination can go.
+
+ALTER TABLE resource_deliveries
+    ALTER COLUMN from_village_id DROP NOT NULL,
+    ALTER COLUMN to_village_id DROP NOT NULL,
+    DROP CONSTRAINT resource_deliveries_trade_transaction_id_fkey,
+    DROP CONSTRAINT resource_deliveries_from_village_id_fkey,
+    DROP CONSTRAINT resource_deliveries_to_village_id_fkey,
+    ADD CONSTRAINT resource_deliveries_trade_transaction_id_fkey
+        FOREIGN KEY (trade_transaction_id) REFERENCES trade_transactions(id) ON DELETE RESTRICT,
+    ADD CONSTRAINT resource_deliveries_from_village_id_fkey
+        FOREIGN KEY (from_village_id) REFERENCES villages(id) ON DELETE SET NULL,
+    ADD CONSTRAINT resource_deliveries_to_village_id_fkey
+        FOREIGN KEY (to_village_id) REFERENCES villages(id) ON DELETE SET NULL,
+    ADD CONSTRAINT pending_delivery_has_destination
+        CHECK (delivered_at IS NOT NULL OR to_village_id IS NOT NULL);
@@ -0,0 +1,7 @@
+-- Enum values can't be dropped; recreate the type without 'alliance_treasury'
+DELETE FROM transactions WHERE transaction_type = 'alliance_treasury';
+ALTER TYPE transaction_type RENAME TO transaction_type_old;
+CREATE TYPE transaction_type AS ENUM ('gold_purchase', 'subscription', 'gold_spend', 'gold_refund', 'gold_gift');
+ALTER TABLE transactions
+    ALTER COLUMN transaction_type TYPE transaction_type USING transaction_type::text::transaction_type;
+DROP TYPE transaction_type_old;
@@ -0,0 +1,2 @@
+-- Treasury deposits and withdrawals of gold go in the player's gold ledger
+ALTER TYPE transaction_type ADD VALUE IF NOT EXISTS 'alliance_treasury';
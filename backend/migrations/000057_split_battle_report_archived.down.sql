@@ -0,0 +1,8 @@
+ALTER TABLE battle_reports ADD COLUMN archived BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE battle_reports SET archived = TRUE WHERE attacker_archived OR defender_archived;
+
+DROP INDEX IF EXISTS idx_battle_reports_prunable;
+ALTER TABLE battle_reports DROP COLUMN attacker_archived, DROP COLUMN defender_archived;
+
+CREATE INDEX idx_battle_reports_prunable ON battle_reports(created_at) WHERE archived = FALSE;
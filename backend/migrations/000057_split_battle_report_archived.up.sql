@@ -0,0 +1,12 @@
+-- Each side archives its own copy of a battle report
+ALTER TABLE battle_reports
+    ADD COLUMN attacker_archived BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN defender_archived BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE battle_reports SET attacker_archived = archived, defender_archived = archived WHERE archived;
+
+DROP INDEX IF EXISTS idx_battle_reports_prunable;
+ALTER TABLE battle_reports DROP COLUMN archived;
+
+CREATE INDEX idx_battle_reports_prunable ON battle_reports(created_at)
+    WHERE attacker_archived = FALSE AND defender_archived = FALSE;
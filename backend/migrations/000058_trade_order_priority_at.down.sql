@@ -0,0 +1 @@
+ALTER TABLE trade_orders DROP COLUMN IF EXISTS priority_at;
@@ -0,0 +1,4 @@
+-- Time priority for matching, reset when an order is repriced; created_at stays the placement time
+ALTER TABLE trade_orders ADD COLUMN priority_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+UPDATE trade_orders SET priority_at = created_at;
@@ -0,0 +1,25 @@
+-- Enum values can't be dropped; recreate the type without 'system'
+DROP INDEX IF EXISTS idx_messages_recipient;
+DROP INDEX IF EXISTS idx_messages_alliance;
+DROP INDEX IF EXISTS idx_messages_unread;
+ALTER TABLE messages DROP CONSTRAINT IF EXISTS private_message_has_recipient;
+ALTER TABLE messages DROP CONSTRAINT IF EXISTS alliance_message_has_alliance;
+ALTER TABLE messages ALTER COLUMN message_type DROP DEFAULT;
+
+ALTER TYPE message_type RENAME TO message_type_old;
+CREATE TYPE message_type AS ENUM ('private', 'alliance');
+ALTER TABLE messages
+    ALTER COLUMN message_type TYPE message_type USING message_type::text::message_type;
+DROP TYPE message_type_old;
+
+ALTER TABLE messages ALTER COLUMN message_type SET DEFAULT 'private';
+ALTER TABLE messages ADD CONSTRAINT private_message_has_recipient
+    CHECK (message_type != 'private' OR recipient_id IS NOT NULL);
+ALTER TABLE messages ADD CONSTRAINT alliance_message_has_alliance
+    CHECK (message_type != 'alliance' OR alliance_id IS NOT NULL);
+CREATE INDEX idx_messages_recipient ON messages(recipient_id, created_at DESC)
+    WHERE message_type = 'private' AND recipient_deleted = FALSE;
+CREATE INDEX idx_messages_alliance ON messages(alliance_id, created_at DESC)
+    WHERE message_type = 'alliance';
+CREATE INDEX idx_messages_unread ON messages(recipient_id, is_read)
+    WHERE message_type = 'private' AND recipient_deleted = FALSE AND is_read = FALSE;
@@ -0,0 +1,3 @@
+-- Notices from the game itself; added on its own because a new enum value
+-- can't be used in the transaction that adds it
+ALTER TYPE message_type ADD VALUE IF NOT EXISTS 'system';
@@ -0,0 +1,16 @@
+DROP INDEX IF EXISTS idx_messages_unread;
+CREATE INDEX idx_messages_unread ON messages(recipient_id, is_read)
+    WHERE message_type = 'private' AND recipient_deleted = FALSE AND is_read = FALSE;
+
+DROP INDEX IF EXISTS idx_messages_recipient;
+CREATE INDEX idx_messages_recipient ON messages(recipient_id, created_at DESC)
+    WHERE message_type = 'private' AND recipient_deleted = FALSE;
+
+ALTER TABLE messages DROP CONSTRAINT IF EXISTS system_message_has_recipient;
+ALTER TABLE messages DROP CONSTRAINT IF EXISTS message_has_sender;
+
+UPDATE messages
+SET message_type = 'private', sender_id = recipient_id, sender_deleted = TRUE
+WHERE message_type = 'system';
+
+ALTER TABLE messages ALTER COLUMN sender_id SET NOT NULL;
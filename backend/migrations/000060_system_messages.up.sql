@@ -0,0 +1,23 @@
+-- System notices have no sender; they used to be private messages a player sent themselves
+ALTER TABLE messages ALTER COLUMN sender_id DROP NOT NULL;
+
+UPDATE messages
+SET message_type = 'system', sender_id = NULL, sender_deleted = FALSE
+WHERE message_type = 'private'
+    AND sender_id = recipient_id
+    AND sender_deleted = TRUE
+    AND conversation_id IS NULL;
+
+ALTER TABLE messages ADD CONSTRAINT message_has_sender
+    CHECK (message_type = 'system' OR sender_id IS NOT NULL);
+ALTER TABLE messages ADD CONSTRAINT system_message_has_recipient
+    CHECK (message_type != 'system' OR (recipient_id IS NOT NULL AND sender_id IS NULL));
+
+-- The inbox lists private and system messages together
+DROP INDEX IF EXISTS idx_messages_recipient;
+CREATE INDEX idx_messages_recipient ON messages(recipient_id, created_at DESC)
+    WHERE message_type IN ('private', 'system') AND recipient_deleted = FALSE;
+
+DROP INDEX IF EXISTS idx_messages_unread;
+CREATE INDEX idx_messages_unread ON messages(recipient_id, is_read)
+    WHERE message_type IN ('private', 'system') AND recipient_deleted = FALSE AND is_read = FALSE;
@@ -0,0 +1 @@
+ALTER TABLE trade_orders DROP COLUMN IF EXISTS is_taker_fill;
@@ -0,0 +1,3 @@
+-- Orders created only to record the acceptor's side of an accepted order.
+-- Older ones can't be told apart from real immediate orders, so they stay unmarked.
+ALTER TABLE trade_orders ADD COLUMN is_taker_fill BOOLEAN NOT NULL DEFAULT FALSE;
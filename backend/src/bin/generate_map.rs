@@ -6,16 +6,25 @@
 //! Options:
 //!   --clear    Clear existing Natarian villages before generating
 //!   --count N  Number of villages to generate (default: 80)
-
-use rand::Rng;
-use sqlx::PgPool;
+//!   --seed N   Seed for the random generator; the same seed produces the same map
+//!   --target N Top up to N Natarian villages in total, keeping existing ones (overrides --count)
+//!   --map-size N       Map goes from -N to +N on both axes (default: 200)
+//!   --min-distance N   Minimum tiles between generated villages (default: 5)
+//!   --center-radius N  Keep the center ±N area free for players (default: 10)
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use sqlx::{PgPool, Postgres, Transaction};
 use std::collections::HashSet;
+use std::time::Instant;
 use uuid::Uuid;
 
 // Map configuration
-const MAP_SIZE: i32 = 200; // Map goes from -MAP_SIZE to +MAP_SIZE
+const DEFAULT_MAP_SIZE: i32 = 200; // Map goes from -map_size to +map_size
+const DEFAULT_MIN_DISTANCE: i32 = 5;
+const DEFAULT_CENTER_RADIUS: i32 = 10;
+const MAX_MAP_SIZE: i32 = 1000;
 const DEFAULT_VILLAGE_COUNT: usize = 80;
-const NATARIAN_FIREBASE_UID: &str = "natarian-npc-system";
+const NATARIAN_FIREBASE_UID: &str = "natarian-npc-system"; // Must match army_service::NATARIAN_FIREBASE_UID
 const NATARIAN_DISPLAY_NAME: &str = "Natarian";
 
 // Village name prefixes and suffixes for variety
@@ -79,26 +88,51 @@ impl BuildingType {
     }
 }
 
+/// Map layout settings
+#[derive(Debug, Clone, Copy)]
+struct MapConfig {
+    map_size: i32,
+    min_distance: i32,
+    center_radius: i32,
+}
+
+impl MapConfig {
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.map_size <= 0 || self.map_size > MAX_MAP_SIZE {
+            anyhow::bail!("--map-size must be between 1 and {}", MAX_MAP_SIZE);
+        }
+        if self.min_distance <= 0 || self.min_distance > self.map_size {
+            anyhow::bail!("--min-distance must be between 1 and the map size");
+        }
+        if self.center_radius <= 0 || self.center_radius >= self.map_size {
+            anyhow::bail!("--center-radius must be positive and smaller than the map size");
+        }
+        Ok(())
+    }
+}
+
 /// Village difficulty tier based on distance from center
 #[derive(Debug, Clone, Copy)]
 enum VillageTier {
-    /// Close to center (0-50): Very strong, for experienced players
+    /// Close to center (first quarter of the map radius): Very strong, for experienced players
     Elite,
-    /// Medium distance (50-100): Moderate difficulty
+    /// Medium distance (second quarter): Moderate difficulty
     Veteran,
-    /// Far from center (100-150): Easier targets
+    /// Far from center (third quarter): Easier targets
     Regular,
-    /// Edge of map (150-200): Beginner friendly
+    /// Edge of map (last quarter): Beginner friendly
     Beginner,
 }
 
 impl VillageTier {
-    fn from_distance(distance: f64) -> Self {
-        if distance < 50.0 {
+    /// Tiers scale with map size (0-50-100-150-200 on the default map)
+    fn from_distance(distance: f64, map_size: i32) -> Self {
+        let ratio = distance / map_size as f64;
+        if ratio < 0.25 {
             VillageTier::Elite
-        } else if distance < 100.0 {
+        } else if ratio < 0.5 {
             VillageTier::Veteran
-        } else if distance < 150.0 {
+        } else if ratio < 0.75 {
             VillageTier::Regular
         } else {
             VillageTier::Beginner
@@ -291,14 +325,15 @@ fn calculate_distance(x: i32, y: i32) -> f64 {
 fn generate_coordinates(
     rng: &mut impl Rng,
     existing: &HashSet<(i32, i32)>,
-    min_distance: i32,
+    map: &MapConfig,
 ) -> Option<(i32, i32)> {
+    let min_distance = map.min_distance;
     for _ in 0..1000 {
-        let x = rng.gen_range(-MAP_SIZE..=MAP_SIZE);
-        let y = rng.gen_range(-MAP_SIZE..=MAP_SIZE);
+        let x = rng.gen_range(-map.map_size..=map.map_size);
+        let y = rng.gen_range(-map.map_size..=map.map_size);
 
         // Skip center area (reserved for players)
-        if x.abs() < 10 && y.abs() < 10 {
+        if x.abs() < map.center_radius && y.abs() < map.center_radius {
             continue;
         }
 
@@ -317,25 +352,13 @@ fn generate_coordinates(
 }
 
 async fn get_or_create_natarian_user(pool: &PgPool) -> anyhow::Result<Uuid> {
-    // Check if Natarian user exists
-    let existing: Option<(Uuid,)> = sqlx::query_as(
-        "SELECT id FROM users WHERE firebase_uid = $1"
-    )
-    .bind(NATARIAN_FIREBASE_UID)
-    .fetch_optional(pool)
-    .await?;
-
-    if let Some((id,)) = existing {
-        println!("Found existing Natarian user: {}", id);
-        return Ok(id);
-    }
-
-    // Create Natarian user
-    let user: (Uuid,) = sqlx::query_as(
+    // Upsert so concurrent or repeated runs resolve to the same user
+    let (id, inserted): (Uuid, bool) = sqlx::query_as(
         r#"
         INSERT INTO users (firebase_uid, email, display_name, provider)
         VALUES ($1, $2, $3, $4)
-        RETURNING id
+        ON CONFLICT (firebase_uid) DO UPDATE SET display_name = EXCLUDED.display_name
+        RETURNING id, (xmax = 0) AS inserted
         "#
     )
     .bind(NATARIAN_FIREBASE_UID)
@@ -345,8 +368,12 @@ async fn get_or_create_natarian_user(pool: &PgPool) -> anyhow::Result<Uuid> {
     .fetch_one(pool)
     .await?;
 
-    println!("Created Natarian user: {}", user.0);
-    Ok(user.0)
+    if inserted {
+        println!("Created Natarian user: {}", id);
+    } else {
+        println!("Found existing Natarian user: {}", id);
+    }
+    Ok(id)
 }
 
 async fn get_existing_coordinates(pool: &PgPool) -> anyhow::Result<HashSet<(i32, i32)>> {
@@ -359,6 +386,26 @@ async fn get_existing_coordinates(pool: &PgPool) -> anyhow::Result<HashSet<(i32,
     Ok(rows.into_iter().collect())
 }
 
+async fn count_natarian_villages(pool: &PgPool, natarian_id: Uuid) -> anyhow::Result<usize> {
+    let count: (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM villages WHERE user_id = $1"
+    )
+    .bind(natarian_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count.0 as usize)
+}
+
+/// Whether an insert failed because another village already holds the coordinates
+fn is_coordinate_conflict(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<sqlx::Error>()
+        .and_then(|e| e.as_database_error())
+        .map(|e| e.is_unique_violation())
+        .unwrap_or(false)
+}
+
 async fn clear_natarian_villages(pool: &PgPool, natarian_id: Uuid) -> anyhow::Result<u64> {
     // Get all Natarian village IDs
     let village_ids: Vec<(Uuid,)> = sqlx::query_as(
@@ -403,7 +450,7 @@ async fn clear_natarian_villages(pool: &PgPool, natarian_id: Uuid) -> anyhow::Re
 }
 
 async fn create_village(
-    pool: &PgPool,
+    tx: &mut Transaction<'_, Postgres>,
     user_id: Uuid,
     name: &str,
     x: i32,
@@ -437,57 +484,96 @@ async fn create_village(
     .bind(warehouse)
     .bind(granary)
     .bind(population)
-    .fetch_one(pool)
+    .fetch_one(&mut **tx)
     .await?;
 
     Ok(village.0)
 }
 
 async fn create_buildings(
-    pool: &PgPool,
+    tx: &mut Transaction<'_, Postgres>,
     village_id: Uuid,
     tier: VillageTier,
 ) -> anyhow::Result<()> {
-    for (building_type, slot, level) in tier.building_config() {
-        sqlx::query(
-            r#"
-            INSERT INTO buildings (village_id, building_type, slot, level)
-            VALUES ($1, $2::building_type, $3, $4)
-            "#
-        )
-        .bind(village_id)
-        .bind(building_type.as_str())
-        .bind(slot)
-        .bind(level)
-        .execute(pool)
-        .await?;
-    }
+    let config = tier.building_config();
+    let building_types: Vec<&str> = config.iter().map(|(b, _, _)| b.as_str()).collect();
+    let slots: Vec<i32> = config.iter().map(|(_, slot, _)| *slot).collect();
+    let levels: Vec<i32> = config.iter().map(|(_, _, level)| *level).collect();
+
+    // One multi-row insert per village instead of one round-trip per building
+    sqlx::query(
+        r#"
+        INSERT INTO buildings (village_id, building_type, slot, level)
+        SELECT $1, t.building_type::building_type, t.slot, t.level
+        FROM UNNEST($2::TEXT[], $3::INT[], $4::INT[]) AS t(building_type, slot, level)
+        "#
+    )
+    .bind(village_id)
+    .bind(&building_types)
+    .bind(&slots)
+    .bind(&levels)
+    .execute(&mut **tx)
+    .await?;
 
     Ok(())
 }
 
 async fn create_troops(
-    pool: &PgPool,
+    tx: &mut Transaction<'_, Postgres>,
     village_id: Uuid,
     tier: VillageTier,
 ) -> anyhow::Result<()> {
-    for (troop_type, count) in tier.troop_config() {
-        sqlx::query(
-            r#"
-            INSERT INTO troops (village_id, troop_type, count, in_village)
-            VALUES ($1, $2::troop_type, $3, $3)
-            "#
-        )
-        .bind(village_id)
-        .bind(troop_type.as_str())
-        .bind(count)
-        .execute(pool)
-        .await?;
-    }
+    let config = tier.troop_config();
+    let troop_types: Vec<&str> = config.iter().map(|(t, _)| t.as_str()).collect();
+    let counts: Vec<i32> = config.iter().map(|(_, count)| *count).collect();
+
+    sqlx::query(
+        r#"
+        INSERT INTO troops (village_id, troop_type, count, in_village)
+        SELECT $1, t.troop_type::troop_type, t.count, t.count
+        FROM UNNEST($2::TEXT[], $3::INT[]) AS t(troop_type, count)
+        "#
+    )
+    .bind(village_id)
+    .bind(&troop_types)
+    .bind(&counts)
+    .execute(&mut **tx)
+    .await?;
 
     Ok(())
 }
 
+/// Create a village with its buildings and troops, all or nothing
+async fn create_natarian_village(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    x: i32,
+    y: i32,
+    tier: VillageTier,
+) -> anyhow::Result<Uuid> {
+    let mut tx = pool.begin().await?;
+
+    let village_id = create_village(&mut tx, user_id, name, x, y, tier).await?;
+    create_buildings(&mut tx, village_id, tier).await?;
+    create_troops(&mut tx, village_id, tier).await?;
+
+    tx.commit().await?;
+
+    Ok(village_id)
+}
+
+/// Parse the value following `flag`, if present
+fn parse_arg<T: std::str::FromStr>(args: &[String], flag: &str) -> anyhow::Result<Option<T>> {
+    match args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1)) {
+        Some(s) => s
+            .parse()
+            .map(Some)
+            .map_err(|_| anyhow::anyhow!("Invalid {} value: {}", flag, s)),
+        None => Ok(None),
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // Initialize tracing
@@ -504,11 +590,28 @@ async fn main() -> anyhow::Result<()> {
         .and_then(|i| args.get(i + 1))
         .and_then(|s| s.parse().ok())
         .unwrap_or(DEFAULT_VILLAGE_COUNT);
+    let target: Option<usize> = parse_arg(&args, "--target")?;
+    let seed: u64 = match parse_arg(&args, "--seed")? {
+        Some(seed) => seed,
+        None => rand::thread_rng().gen(),
+    };
+    let map = MapConfig {
+        map_size: parse_arg(&args, "--map-size")?.unwrap_or(DEFAULT_MAP_SIZE),
+        min_distance: parse_arg(&args, "--min-distance")?.unwrap_or(DEFAULT_MIN_DISTANCE),
+        center_radius: parse_arg(&args, "--center-radius")?.unwrap_or(DEFAULT_CENTER_RADIUS),
+    };
+    map.validate()?;
 
     println!("=== Tusk & Horn Map Generator ===");
-    println!("Map size: {}x{} (±{})", MAP_SIZE * 2, MAP_SIZE * 2, MAP_SIZE);
-    println!("Villages to generate: {}", village_count);
+    println!("Map size: {}x{} (±{})", map.map_size * 2, map.map_size * 2, map.map_size);
+    println!("Min distance between villages: {}", map.min_distance);
+    println!("Center exclusion: ±{}", map.center_radius);
+    match target {
+        Some(target) => println!("Target Natarian villages: {}", target),
+        None => println!("Villages to generate: {}", village_count),
+    }
     println!("Clear existing: {}", clear_existing);
+    println!("Seed: {} (re-run with --seed {} to reproduce)", seed, seed);
     println!();
 
     // Load environment
@@ -533,41 +636,62 @@ async fn main() -> anyhow::Result<()> {
         println!();
     }
 
+    // In target mode, only generate what's missing
+    let existing_natarian = count_natarian_villages(&pool, natarian_id).await?;
+    let village_count = match target {
+        Some(target) => {
+            let missing = target.saturating_sub(existing_natarian);
+            println!("Existing Natarian villages: {}", existing_natarian);
+            println!("Villages to generate: {}", missing);
+            missing
+        }
+        None => village_count,
+    };
+
     // Get existing coordinates
     let mut existing_coords = get_existing_coordinates(&pool).await?;
     println!("Existing villages on map: {}", existing_coords.len());
 
     // Generate villages
-    let mut rng = rand::thread_rng();
+    let mut rng = StdRng::seed_from_u64(seed);
     let mut created = 0;
+    let mut skipped_conflicts = 0;
+    let mut failed: Vec<(i32, i32, String)> = Vec::new();
     let mut tier_counts = [0usize; 4]; // [Elite, Veteran, Regular, Beginner]
 
     println!();
     println!("Generating villages...");
+    let started_at = Instant::now();
 
     for i in 0..village_count {
-        // Generate coordinates with minimum distance of 5 tiles
-        let coords = match generate_coordinates(&mut rng, &existing_coords, 5) {
+        // Generate coordinates at least min_distance tiles from other villages
+        let coords = match generate_coordinates(&mut rng, &existing_coords, &map) {
             Some(c) => c,
             None => {
                 println!("Warning: Could not find valid coordinates for village {}", i + 1);
+                skipped_conflicts += 1;
                 continue;
             }
         };
 
         let (x, y) = coords;
         let distance = calculate_distance(x, y);
-        let tier = VillageTier::from_distance(distance);
+        let tier = VillageTier::from_distance(distance, map.map_size);
         let name = generate_village_name(&mut rng);
 
-        // Create village
-        let village_id = create_village(&pool, natarian_id, &name, x, y, tier).await?;
-
-        // Create buildings
-        create_buildings(&pool, village_id, tier).await?;
-
-        // Create troops
-        create_troops(&pool, village_id, tier).await?;
+        // Create village with buildings and troops
+        if let Err(e) = create_natarian_village(&pool, natarian_id, &name, x, y, tier).await {
+            if is_coordinate_conflict(&e) {
+                // Someone settled here since we loaded the map
+                println!("Warning: Coordinates ({}, {}) were taken, skipping village {}", x, y, i + 1);
+                existing_coords.insert(coords);
+                skipped_conflicts += 1;
+            } else {
+                println!("Warning: Failed to create village {} at ({}, {}): {}", i + 1, x, y, e);
+                failed.push((x, y, e.to_string()));
+            }
+            continue;
+        }
 
         // Track stats
         existing_coords.insert(coords);
@@ -587,11 +711,25 @@ async fn main() -> anyhow::Result<()> {
 
     println!();
     println!("=== Generation Complete ===");
+    println!("Natarian villages before: {}", existing_natarian);
     println!("Total villages created: {}", created);
+    let elapsed = started_at.elapsed();
+    println!(
+        "Generation time: {:.2?} ({:.1} ms per village)",
+        elapsed,
+        if created > 0 { elapsed.as_secs_f64() * 1000.0 / created as f64 } else { 0.0 }
+    );
     println!("  - Elite (center): {}", tier_counts[0]);
     println!("  - Veteran: {}", tier_counts[1]);
     println!("  - Regular: {}", tier_counts[2]);
     println!("  - Beginner (edge): {}", tier_counts[3]);
+    println!("Skipped (coordinate conflicts): {}", skipped_conflicts);
+    if !failed.is_empty() {
+        println!("Failed villages: {}", failed.len());
+        for (x, y, error) in &failed {
+            println!("  - ({}, {}): {}", x, y, error);
+        }
+    }
     println!();
     println!("Total villages on map: {}", existing_coords.len());
 
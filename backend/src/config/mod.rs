@@ -1,5 +1,8 @@
 use anyhow::{Context, Result};
 use std::env;
+use std::net::IpAddr;
+
+use crate::models::trade::WashTradeFilter;
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -8,6 +11,56 @@ pub struct Config {
     pub redis: RedisConfig,
     pub jwt: JwtConfig,
     pub firebase: FirebaseConfig,
+    pub game: GameConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct GameConfig {
+    /// Whether heroes keep regenerating health while away on an adventure
+    pub hero_regen_on_adventure: bool,
+    /// Distance (in tiles) within which alliance members are warned about an attack
+    pub alliance_alert_radius: f64,
+    /// How far (in percent) a new order's price may deviate from the last trade price.
+    /// 0 disables the band and only the static price limits apply.
+    pub trade_price_band_percent: i32,
+    /// Whether expired sell orders (which refund no gold) also leave an inbox message
+    pub trade_expiry_message_on_sell: bool,
+    /// Days after which read, non-archived battle reports are pruned
+    pub battle_report_retention_days: i64,
+    /// Whether raids are allowed between alliances with a non-aggression pact
+    pub nap_allows_raids: bool,
+    /// Whether the invitee gets an inbox message when an invitation is cancelled
+    pub invitation_cancel_notify: bool,
+    /// Whether a village with no crop is blocked from selling, transferring resources or training
+    pub starvation_guard: bool,
+    /// Hours a new player's villages are immune to attacks (0 disables protection)
+    pub beginner_protection_hours: i64,
+    /// Whether beginner protection also blocks raids and scouting, not just attacks
+    pub beginner_protection_blocks_raids: bool,
+    /// Order creations/cancellations allowed per user within the window (0 disables the limit)
+    pub trade_rate_limit_actions: usize,
+    /// Length of the trade rate limit window in seconds
+    pub trade_rate_limit_window_secs: u64,
+    /// Lifetime in hours of orders created without an explicit expiry (capped at the max expiry)
+    pub trade_default_expiry_hours: i32,
+    /// Hours after death until a Travian Plus subscriber's hero revives on its own (0 disables)
+    pub hero_auto_revive_delay_hours: i64,
+    /// Health an auto-revived hero comes back with
+    pub hero_auto_revive_health: i32,
+    /// Whether trades between players on the same IP are left out of market prices
+    pub wash_trade_exclude_same_ip: bool,
+    /// Trades between the same two players within 24h beyond which the rest are left
+    /// out of market prices (0 disables)
+    pub wash_trade_max_pair_trades_per_day: i64,
+}
+
+impl GameConfig {
+    pub fn wash_trade_filter(&self) -> WashTradeFilter {
+        WashTradeFilter {
+            exclude_same_ip: self.wash_trade_exclude_same_ip,
+            max_pair_trades_per_day: self.wash_trade_max_pair_trades_per_day,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -19,6 +72,9 @@ pub struct FirebaseConfig {
 pub struct ServerConfig {
     pub port: u16,
     pub environment: String,
+    /// Reverse proxies allowed to report the client address in X-Forwarded-For.
+    /// Empty means the header is ignored and the connection's peer address is used.
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 #[derive(Debug, Clone)]
@@ -51,6 +107,13 @@ impl Config {
                     .parse()
                     .context("Invalid SERVER_PORT")?,
                 environment: env::var("ENVIRONMENT").unwrap_or_else(|_| "development".to_string()),
+                trusted_proxies: env::var("TRUSTED_PROXIES")
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|ip| !ip.is_empty())
+                    .map(|ip| ip.parse().with_context(|| format!("Invalid TRUSTED_PROXIES entry: {}", ip)))
+                    .collect::<Result<_>>()?,
             },
             database: DatabaseConfig {
                 host: env::var("DB_HOST").unwrap_or_else(|_| "localhost".to_string()),
@@ -80,6 +143,76 @@ impl Config {
                 project_id: env::var("FIREBASE_PROJECT_ID")
                     .context("FIREBASE_PROJECT_ID is required")?,
             },
+            game: GameConfig {
+                hero_regen_on_adventure: env::var("HERO_REGEN_ON_ADVENTURE")
+                    .unwrap_or_else(|_| "true".to_string())
+                    .parse()
+                    .context("Invalid HERO_REGEN_ON_ADVENTURE")?,
+                alliance_alert_radius: env::var("ALLIANCE_ALERT_RADIUS")
+                    .unwrap_or_else(|_| "20".to_string())
+                    .parse()
+                    .context("Invalid ALLIANCE_ALERT_RADIUS")?,
+                trade_price_band_percent: env::var("TRADE_PRICE_BAND_PERCENT")
+                    .unwrap_or_else(|_| "50".to_string())
+                    .parse()
+                    .context("Invalid TRADE_PRICE_BAND_PERCENT")?,
+                trade_expiry_message_on_sell: env::var("TRADE_EXPIRY_MESSAGE_ON_SELL")
+                    .unwrap_or_else(|_| "true".to_string())
+                    .parse()
+                    .context("Invalid TRADE_EXPIRY_MESSAGE_ON_SELL")?,
+                battle_report_retention_days: env::var("BATTLE_REPORT_RETENTION_DAYS")
+                    .unwrap_or_else(|_| "30".to_string())
+                    .parse()
+                    .context("Invalid BATTLE_REPORT_RETENTION_DAYS")?,
+                nap_allows_raids: env::var("NAP_ALLOWS_RAIDS")
+                    .unwrap_or_else(|_| "false".to_string())
+                    .parse()
+                    .context("Invalid NAP_ALLOWS_RAIDS")?,
+                invitation_cancel_notify: env::var("INVITATION_CANCEL_NOTIFY")
+                    .unwrap_or_else(|_| "true".to_string())
+                    .parse()
+                    .context("Invalid INVITATION_CANCEL_NOTIFY")?,
+                starvation_guard: env::var("STARVATION_GUARD")
+                    .unwrap_or_else(|_| "true".to_string())
+                    .parse()
+                    .context("Invalid STARVATION_GUARD")?,
+                beginner_protection_hours: env::var("BEGINNER_PROTECTION_HOURS")
+                    .unwrap_or_else(|_| "72".to_string())
+                    .parse()
+                    .context("Invalid BEGINNER_PROTECTION_HOURS")?,
+                beginner_protection_blocks_raids: env::var("BEGINNER_PROTECTION_BLOCKS_RAIDS")
+                    .unwrap_or_else(|_| "true".to_string())
+                    .parse()
+                    .context("Invalid BEGINNER_PROTECTION_BLOCKS_RAIDS")?,
+                trade_rate_limit_actions: env::var("TRADE_RATE_LIMIT_ACTIONS")
+                    .unwrap_or_else(|_| "20".to_string())
+                    .parse()
+                    .context("Invalid TRADE_RATE_LIMIT_ACTIONS")?,
+                trade_rate_limit_window_secs: env::var("TRADE_RATE_LIMIT_WINDOW_SECS")
+                    .unwrap_or_else(|_| "60".to_string())
+                    .parse()
+                    .context("Invalid TRADE_RATE_LIMIT_WINDOW_SECS")?,
+                trade_default_expiry_hours: env::var("TRADE_DEFAULT_EXPIRY_HOURS")
+                    .unwrap_or_else(|_| "168".to_string())
+                    .parse()
+                    .context("Invalid TRADE_DEFAULT_EXPIRY_HOURS")?,
+                hero_auto_revive_delay_hours: env::var("HERO_AUTO_REVIVE_DELAY_HOURS")
+                    .unwrap_or_else(|_| "12".to_string())
+                    .parse()
+                    .context("Invalid HERO_AUTO_REVIVE_DELAY_HOURS")?,
+                hero_auto_revive_health: env::var("HERO_AUTO_REVIVE_HEALTH")
+                    .unwrap_or_else(|_| "50".to_string())
+                    .parse()
+                    .context("Invalid HERO_AUTO_REVIVE_HEALTH")?,
+                wash_trade_exclude_same_ip: env::var("WASH_TRADE_EXCLUDE_SAME_IP")
+                    .unwrap_or_else(|_| "true".to_string())
+                    .parse()
+                    .context("Invalid WASH_TRADE_EXCLUDE_SAME_IP")?,
+                wash_trade_max_pair_trades_per_day: env::var("WASH_TRADE_MAX_PAIR_TRADES_PER_DAY")
+                    .unwrap_or_else(|_| "10".to_string())
+                    .parse()
+                    .context("Invalid WASH_TRADE_MAX_PAIR_TRADES_PER_DAY")?,
+            },
         })
     }
 }
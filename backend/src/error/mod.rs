@@ -3,9 +3,28 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
+use serde::Serialize;
 use serde_json::json;
 use thiserror::Error;
 
+/// A validation failure tied to one request field
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            field,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Authentication required")]
@@ -23,14 +42,33 @@ pub enum AppError {
     #[error("{0}")]
     Conflict(String),
 
+    #[error("{0}")]
+    TooManyRequests(String),
+
     #[error("Internal server error")]
     InternalError(#[from] anyhow::Error),
 
     #[error("Database error")]
     DatabaseError(#[from] sqlx::Error),
 
-    #[error("Validation error: {0}")]
-    ValidationError(String),
+    #[error("{}", join_messages(.0))]
+    ValidationError(Vec<FieldError>),
+}
+
+impl AppError {
+    /// Validation error for a single field
+    pub fn field(field: &'static str, code: &'static str, message: impl Into<String>) -> Self {
+        AppError::ValidationError(vec![FieldError::new(field, code, message)])
+    }
+}
+
+/// Plain-message fallback for clients that don't read the field list
+fn join_messages(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|e| e.message.as_str())
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 impl IntoResponse for AppError {
@@ -41,19 +79,23 @@ impl IntoResponse for AppError {
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
             AppError::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
-            AppError::ValidationError(msg) => (StatusCode::UNPROCESSABLE_ENTITY, msg.clone()),
+            AppError::TooManyRequests(msg) => (StatusCode::TOO_MANY_REQUESTS, msg.clone()),
+            AppError::ValidationError(_) => (StatusCode::UNPROCESSABLE_ENTITY, self.to_string()),
             AppError::InternalError(_) | AppError::DatabaseError(_) => {
                 tracing::error!("Internal error: {:?}", self);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
             }
         };
 
-        let body = Json(json!({
-            "error": {
-                "message": message,
-                "code": status.as_u16()
-            }
-        }));
+        let mut error = json!({
+            "message": message,
+            "code": status.as_u16()
+        });
+        if let AppError::ValidationError(fields) = &self {
+            error["fields"] = json!(fields);
+        }
+
+        let body = Json(json!({ "error": error }));
 
         (status, body).into_response()
     }
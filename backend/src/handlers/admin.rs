@@ -9,9 +9,12 @@ use uuid::Uuid;
 use crate::error::{AppError, AppResult};
 use crate::middleware::AuthenticatedUser;
 use crate::models::admin::{
-    AdminUserResponse, AdjustResourcesRequest, BanUserRequest, PlayerDetailResponse,
-    ServerStatsResponse, SetAdminRequest,
+    AdminUserResponse, AdminVillageFilter, AdminVillageListItem, AdjustResourcesRequest,
+    BanUserRequest, DeleteVillageRequest,
+    DeleteVillageResponse, EconomyResponse, FlagTradePairRequest, ForceCloseOrderRequest, PlayerDetailResponse,
+    ServerStatsResponse, SetAdminRequest, TradeFlaggedPair,
 };
+use crate::models::trade::CancelOrderResponse;
 use crate::repositories::user_repo::UserRepository;
 use crate::services::admin_service::AdminService;
 use crate::AppState;
@@ -130,6 +133,25 @@ pub async fn get_server_stats(
     Ok(Json(stats))
 }
 
+// GET /api/admin/economy - Server-wide gold and resource totals with 24h changes
+pub async fn get_economy(
+    State(state): State<AppState>,
+) -> AppResult<Json<EconomyResponse>> {
+    let economy = state.economy_cache.get(&state.db).await?;
+    Ok(Json(economy))
+}
+
+// GET /api/admin/villages - Browse all villages with filters
+pub async fn list_villages(
+    State(state): State<AppState>,
+    Query(paging): Query<ListUsersQuery>,
+    Query(filter): Query<AdminVillageFilter>,
+) -> AppResult<Json<Vec<AdminVillageListItem>>> {
+    let villages =
+        AdminService::list_villages(&state.db, &filter, paging.page, paging.per_page).await?;
+    Ok(Json(villages))
+}
+
 // POST /api/admin/villages/:id/resources - Adjust village resources
 pub async fn adjust_resources(
     State(state): State<AppState>,
@@ -162,3 +184,103 @@ pub async fn adjust_resources(
         "message": "Resources adjusted successfully"
     })))
 }
+
+// DELETE /api/admin/villages/:id - Delete a village and its dependent data
+pub async fn delete_village(
+    State(state): State<AppState>,
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Path(village_id): Path<Uuid>,
+    Json(body): Json<DeleteVillageRequest>,
+) -> AppResult<Json<DeleteVillageResponse>> {
+    let admin = UserRepository::find_by_firebase_uid(&state.db, &auth_user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let response = AdminService::delete_village(&state.db, admin.id, village_id, &body.reason).await?;
+
+    info!("Admin {} deleted village {}: {}", admin.id, village_id, body.reason);
+
+    Ok(Json(response))
+}
+
+// POST /api/admin/trade/orders/:id/cancel - Force-cancel any player's order
+pub async fn force_cancel_order(
+    State(state): State<AppState>,
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Path(order_id): Path<Uuid>,
+    Json(body): Json<ForceCloseOrderRequest>,
+) -> AppResult<Json<CancelOrderResponse>> {
+    let admin = UserRepository::find_by_firebase_uid(&state.db, &auth_user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let response = AdminService::force_cancel_order(&state.db, admin.id, order_id, &body.reason).await?;
+
+    info!("Admin {} force-cancelled trade order {}: {}", admin.id, order_id, body.reason);
+
+    Ok(Json(response))
+}
+
+// POST /api/admin/trade/orders/:id/expire - Force-expire any player's order
+pub async fn force_expire_order(
+    State(state): State<AppState>,
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Path(order_id): Path<Uuid>,
+    Json(body): Json<ForceCloseOrderRequest>,
+) -> AppResult<Json<CancelOrderResponse>> {
+    let admin = UserRepository::find_by_firebase_uid(&state.db, &auth_user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let response = AdminService::force_expire_order(&state.db, admin.id, order_id, &body.reason).await?;
+
+    info!("Admin {} force-expired trade order {}: {}", admin.id, order_id, body.reason);
+
+    Ok(Json(response))
+}
+
+// POST /api/admin/trade/flagged-pairs - Exclude a pair's trades from market prices
+pub async fn flag_trade_pair(
+    State(state): State<AppState>,
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Json(body): Json<FlagTradePairRequest>,
+) -> AppResult<Json<TradeFlaggedPair>> {
+    let admin = UserRepository::find_by_firebase_uid(&state.db, &auth_user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let pair = AdminService::flag_trade_pair(
+        &state.db,
+        admin.id,
+        body.user_a_id,
+        body.user_b_id,
+        body.reason.as_deref(),
+    )
+    .await?;
+
+    // Market summary must stop counting the pair right away
+    state.market_cache.invalidate().await;
+
+    info!("Admin {} flagged trade pair {} / {}", admin.id, body.user_a_id, body.user_b_id);
+
+    Ok(Json(pair))
+}
+
+// DELETE /api/admin/trade/flagged-pairs/:user_a/:user_b - Remove a pair flag
+pub async fn unflag_trade_pair(
+    State(state): State<AppState>,
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Path((user_a_id, user_b_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<serde_json::Value>> {
+    let admin = UserRepository::find_by_firebase_uid(&state.db, &auth_user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    AdminService::unflag_trade_pair(&state.db, admin.id, user_a_id, user_b_id).await?;
+
+    state.market_cache.invalidate().await;
+
+    info!("Admin {} unflagged trade pair {} / {}", admin.id, user_a_id, user_b_id);
+
+    Ok(Json(serde_json::json!({ "success": true })))
+}
@@ -9,8 +9,9 @@ use crate::error::AppResult;
 use crate::middleware::auth::AuthenticatedUser;
 use crate::models::alliance::{
     AllianceDiplomacy, AllianceInvitation, AllianceListItem, AllianceMemberResponse,
-    AllianceResponse, CreateAllianceRequest, InvitePlayerRequest, RespondInvitationRequest,
-    SetDiplomacyRequest, UpdateAllianceRequest, UpdateMemberRoleRequest,
+    AllianceResponse, AllianceStatsResponse, AllianceTreasury, AllianceTreasuryLog, CreateAllianceRequest,
+    InvitePlayerRequest, RespondInvitationRequest, SetDiplomacyRequest, TreasuryTransferRequest,
+    UpdateAllianceRequest, UpdateMemberRoleRequest,
 };
 use crate::repositories::user_repo::UserRepository;
 use crate::services::alliance_service::AllianceService;
@@ -28,6 +29,16 @@ fn default_limit() -> i32 {
     20
 }
 
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    #[serde(default = "default_stats_days")]
+    pub days: i64,
+}
+
+fn default_stats_days() -> i64 {
+    7
+}
+
 // ==================== Alliance CRUD ====================
 
 /// POST /api/alliances - Create new alliance
@@ -118,10 +129,25 @@ pub async fn list_members(
     State(state): State<AppState>,
     Path(alliance_id): Path<Uuid>,
 ) -> AppResult<Json<Vec<AllianceMemberResponse>>> {
-    let members = AllianceService::list_members(&state.db, alliance_id).await?;
+    let members = AllianceService::list_members(&state.db, &state.ws, alliance_id).await?;
     Ok(Json(members))
 }
 
+/// GET /api/alliances/:id/stats - Member contribution stats over a window (members only)
+pub async fn get_member_stats(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Path(alliance_id): Path<Uuid>,
+    Query(query): Query<StatsQuery>,
+) -> AppResult<Json<AllianceStatsResponse>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or_else(|| crate::error::AppError::Unauthorized)?;
+
+    let stats = AllianceService::get_member_stats(&state.db, db_user.id, alliance_id, query.days).await?;
+    Ok(Json(stats))
+}
+
 /// POST /api/alliances/:id/invite - Invite player
 pub async fn invite_player(
     State(state): State<AppState>,
@@ -219,6 +245,26 @@ pub async fn respond_invitation(
     Ok(Json(()))
 }
 
+/// POST /api/alliances/invitations/:id/cancel - Withdraw a pending invitation
+pub async fn cancel_invitation(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Path(invitation_id): Path<Uuid>,
+) -> AppResult<Json<()>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or_else(|| crate::error::AppError::Unauthorized)?;
+
+    AllianceService::cancel_invitation(
+        &state.db,
+        db_user.id,
+        invitation_id,
+        state.config.game.invitation_cancel_notify,
+    )
+    .await?;
+    Ok(Json(()))
+}
+
 // ==================== Diplomacy ====================
 
 /// GET /api/alliances/:id/diplomacy - List diplomacy relations
@@ -252,3 +298,57 @@ pub async fn set_diplomacy(
     .await?;
     Ok(Json(diplomacy))
 }
+
+// ==================== Treasury ====================
+
+/// POST /api/alliances/:id/treasury/deposit - Deposit gold/resources into the treasury
+pub async fn deposit_to_treasury(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Path(alliance_id): Path<Uuid>,
+    Json(request): Json<TreasuryTransferRequest>,
+) -> AppResult<Json<AllianceTreasury>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or_else(|| crate::error::AppError::Unauthorized)?;
+
+    let treasury = AllianceService::deposit(
+        &state.db,
+        db_user.id,
+        alliance_id,
+        request,
+        state.config.game.starvation_guard,
+    )
+    .await?;
+    Ok(Json(treasury))
+}
+
+/// POST /api/alliances/:id/treasury/withdraw - Withdraw gold/resources (leader/officers)
+pub async fn withdraw_from_treasury(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Path(alliance_id): Path<Uuid>,
+    Json(request): Json<TreasuryTransferRequest>,
+) -> AppResult<Json<AllianceTreasury>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or_else(|| crate::error::AppError::Unauthorized)?;
+
+    let treasury = AllianceService::withdraw(&state.db, db_user.id, alliance_id, request).await?;
+    Ok(Json(treasury))
+}
+
+/// GET /api/alliances/:id/treasury/log - Treasury deposit/withdrawal history
+pub async fn get_treasury_log(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Path(alliance_id): Path<Uuid>,
+    Query(query): Query<PaginationQuery>,
+) -> AppResult<Json<Vec<AllianceTreasuryLog>>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or_else(|| crate::error::AppError::Unauthorized)?;
+
+    let log = AllianceService::get_treasury_log(&state.db, db_user.id, alliance_id, query.limit, query.offset).await?;
+    Ok(Json(log))
+}
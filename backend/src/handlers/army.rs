@@ -7,7 +7,7 @@ use uuid::Uuid;
 
 use crate::error::{AppError, AppResult};
 use crate::middleware::AuthenticatedUser;
-use crate::models::army::{ArmyResponse, BattleReportResponse, ScoutReportResponse, SendArmyRequest};
+use crate::models::army::{ArchiveReportRequest, ArmyResponse, BattleReportResponse, ScoutReportResponse, SendArmyRequest};
 use crate::repositories::army_repo::ArmyRepository;
 use crate::repositories::user_repo::UserRepository;
 use crate::repositories::village_repo::VillageRepository;
@@ -33,7 +33,15 @@ pub async fn send_army(
         return Err(AppError::Forbidden("Access denied".into()));
     }
 
-    let response = ArmyService::send_army(&state.db, user.id, village_id, body).await?;
+    let response = ArmyService::send_army(
+        &state.db,
+        &state.ws,
+        user.id,
+        village_id,
+        body,
+        &state.config.game,
+    )
+    .await?;
 
     info!(
         "Army sent from village {} to ({}, {})",
@@ -153,6 +161,24 @@ pub async fn mark_report_read(
     })))
 }
 
+// POST /api/reports/:report_id/archive - Archive or unarchive a report
+pub async fn archive_report(
+    State(state): State<AppState>,
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Path(report_id): Path<Uuid>,
+    Json(body): Json<ArchiveReportRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let user = UserRepository::find_by_firebase_uid(&state.db, &auth_user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    ArmyService::set_report_archived(&state.db, report_id, user.id, body.archived).await?;
+
+    Ok(Json(serde_json::json!({
+        "archived": body.archived
+    })))
+}
+
 // GET /api/reports/unread-count - Get unread report count (battle + scout)
 pub async fn get_unread_count(
     State(state): State<AppState>,
@@ -2,14 +2,16 @@ use axum::{
     extract::{Path, State},
     Extension, Json,
 };
-use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use tracing::info;
 use uuid::Uuid;
 
 use crate::error::{AppError, AppResult};
 use crate::middleware::AuthenticatedUser;
-use crate::models::building::{BuildingCost, BuildingResponse, BuildingType, CreateBuilding};
+use crate::models::building::{
+    BuildingCost, BuildingResponse, BuildingType, UpgradePreviewResponse,
+};
+use crate::models::trade::Resources;
 use crate::repositories::building_repo::BuildingRepository;
 use crate::repositories::user_repo::UserRepository;
 use crate::repositories::village_repo::VillageRepository;
@@ -69,51 +71,14 @@ pub async fn build(
         return Err(AppError::Forbidden("Access denied".into()));
     }
 
-    // Check if slot is empty
-    if BuildingRepository::find_by_village_and_slot(&state.db, village_id, slot)
-        .await?
-        .is_some()
-    {
-        return Err(AppError::Conflict("Slot already occupied".to_string()));
-    }
-
     // Check prerequisites
     BuildingService::validate_can_build(&state.db, village_id, &body.building_type).await?;
 
     // Get cost for level 1
-    let cost = body.building_type.cost_at_level(1);
-
-    // Check resources
-    if village.wood < cost.wood
-        || village.clay < cost.clay
-        || village.iron < cost.iron
-        || village.crop < cost.crop
-    {
-        return Err(AppError::BadRequest("Not enough resources".to_string()));
-    }
+    let cost = BuildingService::cost_for_village(&state.db, village_id, &body.building_type, 1).await?;
 
-    // Deduct resources
-    VillageRepository::deduct_resources(
-        &state.db,
-        village_id,
-        cost.wood,
-        cost.clay,
-        cost.iron,
-        cost.crop,
-    )
-    .await?;
-
-    // Create building
-    let create = CreateBuilding {
-        village_id,
-        building_type: body.building_type.clone(),
-        slot,
-    };
-    let building = BuildingRepository::create(&state.db, create).await?;
-
-    // Start upgrade timer
-    let upgrade_ends_at = Utc::now() + chrono::Duration::seconds(cost.time_seconds as i64);
-    let building = BuildingRepository::start_upgrade(&state.db, building.id, upgrade_ends_at).await?;
+    // Check the slot, pay and start building under a village lock
+    let building = BuildingService::build(&state.db, village_id, &body.building_type, slot, &cost).await?;
 
     info!(
         "Building {:?} started at slot {} in village {}",
@@ -132,6 +97,40 @@ pub struct UpgradeResponse {
     pub cost: BuildingCost,
 }
 
+#[derive(Debug, Serialize)]
+pub struct CancelUpgradeResponse {
+    /// None when a building still under construction was removed
+    pub building: Option<BuildingResponse>,
+    pub refunded: Resources,
+}
+
+// GET /api/villages/:village_id/buildings/:slot/preview - Preview the next upgrade
+pub async fn preview_upgrade(
+    State(state): State<AppState>,
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Path((village_id, slot)): Path<(Uuid, i32)>,
+) -> AppResult<Json<UpgradePreviewResponse>> {
+    let user = UserRepository::find_by_firebase_uid(&state.db, &auth_user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let village = VillageRepository::find_by_id(&state.db, village_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Village not found".to_string()))?;
+
+    if village.user_id != user.id {
+        return Err(AppError::Forbidden("Access denied".into()));
+    }
+
+    let building = BuildingRepository::find_by_village_and_slot(&state.db, village_id, slot)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Building not found".to_string()))?;
+
+    let preview = BuildingService::preview_upgrade(&state.db, &village, &building).await?;
+
+    Ok(Json(preview))
+}
+
 // POST /api/villages/:village_id/buildings/:slot/upgrade - Upgrade building
 pub async fn upgrade(
     State(state): State<AppState>,
@@ -163,31 +162,11 @@ pub async fn upgrade(
         return Err(AppError::BadRequest("Building is at max level".to_string()));
     }
 
-    let cost = building.building_type.cost_at_level(next_level);
-
-    // Check resources
-    if village.wood < cost.wood
-        || village.clay < cost.clay
-        || village.iron < cost.iron
-        || village.crop < cost.crop
-    {
-        return Err(AppError::BadRequest("Not enough resources".to_string()));
-    }
+    let cost =
+        BuildingService::cost_for_village(&state.db, village_id, &building.building_type, next_level).await?;
 
-    // Deduct resources
-    VillageRepository::deduct_resources(
-        &state.db,
-        village_id,
-        cost.wood,
-        cost.clay,
-        cost.iron,
-        cost.crop,
-    )
-    .await?;
-
-    // Start upgrade
-    let upgrade_ends_at = Utc::now() + chrono::Duration::seconds(cost.time_seconds as i64);
-    let building = BuildingRepository::start_upgrade(&state.db, building.id, upgrade_ends_at).await?;
+    // Check, deduct and start under a village lock
+    let building = BuildingService::start_upgrade(&state.db, &building, &cost).await?;
 
     info!(
         "Upgrading {:?} to level {} in village {}",
@@ -229,7 +208,7 @@ pub async fn demolish(
         ));
     }
 
-    BuildingRepository::demolish(&state.db, building.id).await?;
+    let refunded = BuildingService::demolish(&state.db, &building).await?;
 
     info!(
         "Building {:?} demolished at slot {} in village {}",
@@ -237,10 +216,43 @@ pub async fn demolish(
     );
 
     Ok(Json(serde_json::json!({
-        "message": "Building demolished successfully"
+        "message": "Building demolished successfully",
+        "refunded": refunded
     })))
 }
 
+// POST /api/villages/:village_id/buildings/:slot/cancel - Cancel an in-progress upgrade
+pub async fn cancel_upgrade(
+    State(state): State<AppState>,
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Path((village_id, slot)): Path<(Uuid, i32)>,
+) -> AppResult<Json<CancelUpgradeResponse>> {
+    let user = UserRepository::find_by_firebase_uid(&state.db, &auth_user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let village = VillageRepository::find_by_id(&state.db, village_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Village not found".to_string()))?;
+
+    if village.user_id != user.id {
+        return Err(AppError::Forbidden("Access denied".into()));
+    }
+
+    let building = BuildingRepository::find_by_village_and_slot(&state.db, village_id, slot)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Building not found".to_string()))?;
+
+    let (building, refunded) = BuildingService::cancel_upgrade(&state.db, &building).await?;
+
+    info!("Upgrade cancelled at slot {} in village {}", slot, village_id);
+
+    Ok(Json(CancelUpgradeResponse {
+        building: building.map(Into::into),
+        refunded,
+    }))
+}
+
 // GET /api/villages/:village_id/buildings/queue - Get build queue
 pub async fn get_build_queue(
     State(state): State<AppState>,
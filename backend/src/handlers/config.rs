@@ -0,0 +1,34 @@
+use axum::{extract::State, Json};
+use chrono::Utc;
+
+use crate::models::config::{GameConfigResponse, TradeLimits};
+use crate::services::trade_service::{
+    TradeService, MAX_EXPIRY_HOURS, MAX_OPEN_ORDERS_PER_USER, MAX_ORDER_VALUE, MAX_PRICE,
+    MAX_QUANTITY, MIN_PRICE, MIN_QUANTITY,
+};
+use crate::AppState;
+
+// GET /api/config - Game constants and server time for client rendering
+pub async fn get_config(State(state): State<AppState>) -> Json<GameConfigResponse> {
+    let game = &state.config.game;
+
+    Json(GameConfigResponse {
+        server_time: Utc::now(),
+        trade: TradeLimits {
+            min_quantity: MIN_QUANTITY,
+            max_quantity: MAX_QUANTITY,
+            min_price: MIN_PRICE,
+            max_price: MAX_PRICE,
+            max_open_orders: MAX_OPEN_ORDERS_PER_USER,
+            max_expiry_hours: MAX_EXPIRY_HOURS,
+            max_order_value: MAX_ORDER_VALUE,
+            default_expiry_hours: TradeService::effective_expiry_hours(None, game.trade_default_expiry_hours),
+            price_band_percent: game.trade_price_band_percent,
+        },
+        alliance_alert_radius: game.alliance_alert_radius,
+        nap_allows_raids: game.nap_allows_raids,
+        hero_regen_on_adventure: game.hero_regen_on_adventure,
+        battle_report_retention_days: game.battle_report_retention_days,
+        beginner_protection_hours: game.beginner_protection_hours,
+    })
+}
@@ -59,8 +59,8 @@ pub async fn create_hero(
     Ok(Json(hero))
 }
 
-/// PUT /api/heroes/{id}/home - Change home village
-pub async fn change_home_village(
+/// PUT /api/heroes/{id}/home - Assign hero to a village
+pub async fn assign_hero(
     State(state): State<AppState>,
     Extension(user): Extension<AuthenticatedUser>,
     Path(hero_id): Path<Uuid>,
@@ -70,8 +70,7 @@ pub async fn change_home_village(
         .await?
         .ok_or(AppError::Unauthorized)?;
 
-    let hero =
-        HeroService::change_home_village(&state.db, db_user.id, hero_id, request.village_id).await?;
+    let hero = HeroService::assign_hero(&state.db, db_user.id, hero_id, request.village_id).await?;
     Ok(Json(hero))
 }
 
@@ -8,9 +8,11 @@ use uuid::Uuid;
 use crate::error::{AppError, AppResult};
 use crate::middleware::auth::AuthenticatedUser;
 use crate::models::message::{
-    AllianceMessageListItem, ConversationResponse, MessageListItem, MessageResponse,
-    ReplyMessageRequest, SendAllianceMessageRequest, SendMessageRequest,
+    AllianceMessageListItem, AllianceMessageResponse, ConversationResponse, DeleteMessagesRequest,
+    MessageListItem, MessageResponse, ReplyMessageRequest, SendAllianceMessageRequest,
+    SendMessageRequest,
 };
+use crate::models::pagination::{PageQuery, Paginated};
 use crate::repositories::user_repo::UserRepository;
 use crate::services::message_service::MessageService;
 use crate::AppState;
@@ -41,6 +43,7 @@ pub async fn send_message(
 
     let message = MessageService::send_private_message(
         &state.db,
+        &state.ws,
         db_user.id,
         request.recipient_id,
         request.subject,
@@ -81,6 +84,36 @@ pub async fn get_sent(
     Ok(Json(messages))
 }
 
+/// GET /api/messages/inbox/paged - Get inbox messages with pagination metadata
+pub async fn get_inbox_paged(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Query(query): Query<PageQuery>,
+) -> AppResult<Json<Paginated<MessageListItem>>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let page = MessageService::get_inbox_page(&state.db, db_user.id, &query).await?;
+
+    Ok(Json(page))
+}
+
+/// GET /api/messages/sent/paged - Get sent messages with pagination metadata
+pub async fn get_sent_paged(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Query(query): Query<PageQuery>,
+) -> AppResult<Json<Paginated<MessageListItem>>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let page = MessageService::get_sent_page(&state.db, db_user.id, &query).await?;
+
+    Ok(Json(page))
+}
+
 /// GET /api/messages/:id - Get a single message
 pub async fn get_message(
     State(state): State<AppState>,
@@ -113,6 +146,23 @@ pub async fn delete_message(
     })))
 }
 
+/// POST /api/messages/delete - Delete several messages at once
+pub async fn delete_messages(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Json(request): Json<DeleteMessagesRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let deleted = MessageService::delete_messages(&state.db, db_user.id, &request.message_ids).await?;
+
+    Ok(Json(serde_json::json!({
+        "deleted": deleted
+    })))
+}
+
 /// GET /api/messages/unread-count - Get unread message count
 pub async fn get_unread_count(
     State(state): State<AppState>,
@@ -193,6 +243,7 @@ pub async fn reply_to_conversation(
     // Send reply
     let message = MessageService::send_private_message(
         &state.db,
+        &state.ws,
         db_user.id,
         conversation.other_user_id,
         format!("Re: {}", conversation.last_message_subject.unwrap_or_default()),
@@ -220,6 +271,40 @@ pub async fn delete_conversation(
     })))
 }
 
+/// POST /api/conversations/:id/mute - Stop unread counts and pings for a conversation
+pub async fn mute_conversation(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Path(conversation_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    MessageService::mute_conversation(&state.db, db_user.id, conversation_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Conversation muted"
+    })))
+}
+
+/// POST /api/conversations/:id/unmute - Resume notifications for a conversation
+pub async fn unmute_conversation(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Path(conversation_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    MessageService::unmute_conversation(&state.db, db_user.id, conversation_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Conversation unmuted"
+    })))
+}
+
 // ==================== Alliance Messages ====================
 
 /// POST /api/alliance-messages - Send an alliance message
@@ -265,7 +350,7 @@ pub async fn get_alliance_message(
     State(state): State<AppState>,
     Extension(user): Extension<AuthenticatedUser>,
     Path(message_id): Path<Uuid>,
-) -> AppResult<Json<MessageResponse>> {
+) -> AppResult<Json<AllianceMessageResponse>> {
     let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
         .await?
         .ok_or(AppError::Unauthorized)?;
@@ -3,6 +3,7 @@ mod alliance;
 mod army;
 mod auth;
 mod building;
+mod config;
 mod hero;
 mod message;
 mod ranking;
@@ -21,6 +22,7 @@ pub fn routes(state: AppState) -> Router<AppState> {
     Router::new()
         .nest("/auth", auth_routes(state.clone()))
         .nest("/dashboard", dashboard_routes(state.clone()))
+        .nest("/overview", overview_routes(state.clone()))
         .nest("/villages", village_routes(state.clone()))
         .nest("/map", map_routes(state.clone()))
         .nest("/troops", troop_routes(state.clone()))
@@ -45,6 +47,8 @@ pub fn routes(state: AppState) -> Router<AppState> {
 fn public_routes() -> Router<AppState> {
     Router::new()
         .route("/troops/definitions", get(troop::get_definitions))
+        .route("/config", get(config::get_config))
+        .route("/players/{id}", get(ranking::get_player_profile))
 }
 
 fn auth_routes(state: AppState) -> Router<AppState> {
@@ -61,6 +65,8 @@ fn village_routes(state: AppState) -> Router<AppState> {
     Router::new()
         .route("/", get(village::list_villages))
         .route("/", post(village::create_village))
+        .route("/detail", get(village::get_villages_detail))
+        .route("/paged", get(village::list_villages_paged))
         .route("/{id}", get(village::get_village))
         .route("/{id}", put(village::update_village))
         // Building routes nested under village
@@ -68,6 +74,8 @@ fn village_routes(state: AppState) -> Router<AppState> {
         .route("/{village_id}/buildings/queue", get(building::get_build_queue))
         .route("/{village_id}/buildings/{slot}", post(building::build))
         .route("/{village_id}/buildings/{slot}/upgrade", post(building::upgrade))
+        .route("/{village_id}/buildings/{slot}/preview", get(building::preview_upgrade))
+        .route("/{village_id}/buildings/{slot}/cancel", post(building::cancel_upgrade))
         .route("/{village_id}/buildings/{slot}", delete(building::demolish))
         // Troop routes nested under village
         .route("/{village_id}/troops", get(troop::list_troops))
@@ -88,6 +96,13 @@ fn dashboard_routes(state: AppState) -> Router<AppState> {
         .route_layer(middleware::from_fn_with_state(state, auth_middleware))
 }
 
+fn overview_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/upkeep", get(village::get_upkeep_overview))
+        .route("/expansion", get(village::get_expansion_overview))
+        .route_layer(middleware::from_fn_with_state(state, auth_middleware))
+}
+
 fn map_routes(state: AppState) -> Router<AppState> {
     Router::new()
         .route("/", get(village::get_map))
@@ -107,6 +122,7 @@ fn report_routes(state: AppState) -> Router<AppState> {
         .route("/unread-count", get(army::get_unread_count))
         .route("/{report_id}", get(army::get_report))
         .route("/{report_id}/read", post(army::mark_report_read))
+        .route("/{report_id}/archive", post(army::archive_report))
         .route_layer(middleware::from_fn_with_state(state, auth_middleware))
 }
 
@@ -142,15 +158,21 @@ fn alliance_routes(state: AppState) -> Router<AppState> {
         .route("/{id}", delete(alliance::disband_alliance))
         // Members
         .route("/{id}/members", get(alliance::list_members))
+        .route("/{id}/stats", get(alliance::get_member_stats))
         .route("/{id}/invite", post(alliance::invite_player))
         .route("/{id}/members/{user_id}", delete(alliance::kick_member))
         .route("/{id}/members/{user_id}/role", put(alliance::update_member_role))
         // Invitations
         .route("/invitations", get(alliance::get_invitations))
         .route("/invitations/{invitation_id}/respond", post(alliance::respond_invitation))
+        .route("/invitations/{invitation_id}/cancel", post(alliance::cancel_invitation))
         // Diplomacy
         .route("/{id}/diplomacy", get(alliance::list_diplomacy))
         .route("/{id}/diplomacy", post(alliance::set_diplomacy))
+        // Treasury
+        .route("/{id}/treasury/deposit", post(alliance::deposit_to_treasury))
+        .route("/{id}/treasury/withdraw", post(alliance::withdraw_from_treasury))
+        .route("/{id}/treasury/log", get(alliance::get_treasury_log))
         .route_layer(middleware::from_fn_with_state(state, auth_middleware))
 }
 
@@ -159,7 +181,10 @@ fn message_routes(state: AppState) -> Router<AppState> {
         .route("/", post(message::send_message))
         .route("/inbox", get(message::get_inbox))
         .route("/sent", get(message::get_sent))
+        .route("/inbox/paged", get(message::get_inbox_paged))
+        .route("/sent/paged", get(message::get_sent_paged))
         .route("/unread-count", get(message::get_unread_count))
+        .route("/delete", post(message::delete_messages))
         .route("/{id}", get(message::get_message))
         .route("/{id}", delete(message::delete_message))
         .route_layer(middleware::from_fn_with_state(state, auth_middleware))
@@ -170,6 +195,8 @@ fn conversation_routes(state: AppState) -> Router<AppState> {
         .route("/", get(message::get_conversations))
         .route("/{id}/messages", get(message::get_conversation_messages))
         .route("/{id}/reply", post(message::reply_to_conversation))
+        .route("/{id}/mute", post(message::mute_conversation))
+        .route("/{id}/unmute", post(message::unmute_conversation))
         .route("/{id}", delete(message::delete_conversation))
         .route_layer(middleware::from_fn_with_state(state, auth_middleware))
 }
@@ -193,12 +220,20 @@ fn shop_routes(state: AppState) -> Router<AppState> {
         .route("/balance", get(shop::get_balance))
         .route("/checkout", post(shop::create_checkout))
         .route("/subscriptions/buy", post(shop::buy_subscription))
+        .route("/gift", post(shop::gift_gold))
         .route("/transactions", get(shop::get_transactions))
+        .route("/transactions/{id}", get(shop::get_transaction))
+        .route("/usage", get(shop::get_gold_usage))
         // Gold features
+        .route("/features", get(shop::list_features))
         .route("/features/finish-now", post(shop::use_finish_now))
+        .route("/features/finish-all", post(shop::use_finish_all))
+        .route("/features/finish-queue", post(shop::use_finish_now_queue))
+        .route("/finish-now/preview", get(shop::preview_finish_now))
         .route("/features/npc-merchant", post(shop::use_npc_merchant))
         .route("/features/production-bonus", post(shop::use_production_bonus))
         .route("/features/book-of-wisdom", post(shop::use_book_of_wisdom))
+        .route("/features/instant-train", post(shop::use_instant_train))
         .route_layer(middleware::from_fn_with_state(state, auth_middleware))
 }
 
@@ -208,7 +243,7 @@ fn hero_routes(state: AppState) -> Router<AppState> {
         .route("/", get(hero::list_heroes))
         .route("/", post(hero::create_hero))
         .route("/{id}", get(hero::get_hero))
-        .route("/{id}/home", put(hero::change_home_village))
+        .route("/{id}/home", put(hero::assign_hero))
         .route("/{id}/attributes", put(hero::assign_attributes))
         // Hero Slots
         .route("/slots/buy", post(hero::buy_hero_slot))
@@ -242,8 +277,16 @@ fn admin_routes(state: AppState) -> Router<AppState> {
         .route("/users/{id}/admin", put(admin::set_admin))
         // Server stats
         .route("/stats", get(admin::get_server_stats))
+        .route("/economy", get(admin::get_economy))
         // Resource management
+        .route("/villages", get(admin::list_villages))
         .route("/villages/{id}/resources", post(admin::adjust_resources))
+        .route("/villages/{id}", delete(admin::delete_village))
+        // Trade intervention
+        .route("/trade/orders/{id}/cancel", post(admin::force_cancel_order))
+        .route("/trade/orders/{id}/expire", post(admin::force_expire_order))
+        .route("/trade/flagged-pairs", post(admin::flag_trade_pair))
+        .route("/trade/flagged-pairs/{user_a}/{user_b}", delete(admin::unflag_trade_pair))
         // Apply both auth and admin middleware
         .route_layer(middleware::from_fn_with_state(state.clone(), admin_middleware))
         .route_layer(middleware::from_fn_with_state(state, auth_middleware))
@@ -255,6 +298,7 @@ fn ranking_routes() -> Router<AppState> {
         .route("/players/population", get(ranking::get_population_ranking))
         .route("/players/attackers", get(ranking::get_attack_ranking))
         .route("/players/defenders", get(ranking::get_defense_ranking))
+        .route("/players/{id}/rank", get(ranking::get_player_rank))
         // Hero rankings
         .route("/heroes", get(ranking::get_hero_ranking))
         // Alliance rankings
@@ -265,9 +309,11 @@ fn market_routes() -> Router<AppState> {
     Router::new()
         // Public market endpoints (no auth required)
         .route("/summary", get(trade::get_market_summary))
+        .route("/depth", get(trade::get_order_book))
         .route("/orders", get(trade::get_open_orders))
         .route("/orders/{id}", get(trade::get_order))
         .route("/transactions", get(trade::get_recent_transactions))
+        .route("/economy", get(trade::get_public_economy))
 }
 
 fn trade_routes(state: AppState) -> Router<AppState> {
@@ -275,8 +321,15 @@ fn trade_routes(state: AppState) -> Router<AppState> {
         // Order management (authenticated)
         .route("/orders", post(trade::create_order))
         .route("/orders", get(trade::get_my_orders))
+        .route("/orders/counts", get(trade::get_my_order_counts))
+        .route("/orders/cancel-all", post(trade::cancel_all_orders))
+        .route("/orders/{id}", get(trade::get_order_details))
+        .route("/orders/{id}", put(trade::update_order))
         .route("/orders/{id}/accept", post(trade::accept_order))
         .route("/orders/{id}/cancel", post(trade::cancel_order))
+        .route("/market/orders", get(trade::get_open_orders_for_member))
+        .route("/quote", get(trade::get_quote))
+        .route("/candles", get(trade::get_price_candles))
         .route("/history", get(trade::get_trade_history))
         .route_layer(middleware::from_fn_with_state(state, auth_middleware))
 }
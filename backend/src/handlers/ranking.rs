@@ -1,9 +1,10 @@
-use axum::{extract::{Query, State}, Json};
+use axum::{extract::{Path, Query, State}, Json};
+use uuid::Uuid;
 
 use crate::error::AppResult;
 use crate::models::ranking::{
     AllianceRanking, HeroRanking, PlayerAttackRanking, PlayerDefenseRanking,
-    PlayerPopulationRanking, RankingListResponse, RankingQuery,
+    PlayerPopulationRanking, PlayerProfile, RankingListResponse, RankingQuery,
 };
 use crate::services::ranking_service::RankingService;
 use crate::AppState;
@@ -13,7 +14,7 @@ pub async fn get_population_ranking(
     State(state): State<AppState>,
     Query(query): Query<RankingQuery>,
 ) -> AppResult<Json<RankingListResponse<PlayerPopulationRanking>>> {
-    let rankings = RankingService::get_population_ranking(&state.db, query.page, query.per_page).await?;
+    let rankings = RankingService::get_population_ranking(&state.db, query.page, query.per_page, query.q.as_deref()).await?;
     Ok(Json(rankings))
 }
 
@@ -22,7 +23,7 @@ pub async fn get_attack_ranking(
     State(state): State<AppState>,
     Query(query): Query<RankingQuery>,
 ) -> AppResult<Json<RankingListResponse<PlayerAttackRanking>>> {
-    let rankings = RankingService::get_attack_ranking(&state.db, query.page, query.per_page).await?;
+    let rankings = RankingService::get_attack_ranking(&state.db, query.page, query.per_page, query.q.as_deref()).await?;
     Ok(Json(rankings))
 }
 
@@ -31,7 +32,7 @@ pub async fn get_defense_ranking(
     State(state): State<AppState>,
     Query(query): Query<RankingQuery>,
 ) -> AppResult<Json<RankingListResponse<PlayerDefenseRanking>>> {
-    let rankings = RankingService::get_defense_ranking(&state.db, query.page, query.per_page).await?;
+    let rankings = RankingService::get_defense_ranking(&state.db, query.page, query.per_page, query.q.as_deref()).await?;
     Ok(Json(rankings))
 }
 
@@ -40,7 +41,7 @@ pub async fn get_hero_ranking(
     State(state): State<AppState>,
     Query(query): Query<RankingQuery>,
 ) -> AppResult<Json<RankingListResponse<HeroRanking>>> {
-    let rankings = RankingService::get_hero_ranking(&state.db, query.page, query.per_page).await?;
+    let rankings = RankingService::get_hero_ranking(&state.db, query.page, query.per_page, query.q.as_deref()).await?;
     Ok(Json(rankings))
 }
 
@@ -49,6 +50,27 @@ pub async fn get_alliance_ranking(
     State(state): State<AppState>,
     Query(query): Query<RankingQuery>,
 ) -> AppResult<Json<RankingListResponse<AllianceRanking>>> {
-    let rankings = RankingService::get_alliance_ranking(&state.db, query.page, query.per_page).await?;
+    let rankings = RankingService::get_alliance_ranking(&state.db, query.page, query.per_page, query.q.as_deref()).await?;
     Ok(Json(rankings))
 }
+
+// GET /api/rankings/players/:id/rank - A player's population rank, even outside the current page
+pub async fn get_player_rank(
+    State(state): State<AppState>,
+    Path(user_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let rank = RankingService::get_player_rank(&state.db, user_id).await?;
+    Ok(Json(serde_json::json!({
+        "user_id": user_id,
+        "rank": rank
+    })))
+}
+
+// GET /api/players/:id - Public profile of a player
+pub async fn get_player_profile(
+    State(state): State<AppState>,
+    Path(user_id): Path<Uuid>,
+) -> AppResult<Json<PlayerProfile>> {
+    let profile = RankingService::get_player_profile(&state.db, user_id).await?;
+    Ok(Json(profile))
+}
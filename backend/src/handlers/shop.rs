@@ -10,9 +10,11 @@ use uuid::Uuid;
 use crate::error::{AppError, AppResult};
 use crate::middleware::auth::AuthenticatedUser;
 use crate::models::shop::{
-    BuySubscriptionRequest, CheckoutResponse, GoldBalanceResponse, GoldPackage,
-    PurchaseGoldRequest, SubscriptionPrice, TransactionResponse, UseBookOfWisdomRequest,
-    UseFeatureResponse, UseFinishNowRequest, UseNpcMerchantRequest, UseProductionBonusRequest,
+    BuySubscriptionRequest, CheckoutResponse, FinishNowPreviewResponse, FinishQueueResponse, GiftGoldRequest, GiftGoldResponse,
+    GoldBalanceResponse,
+    GoldFeatureListing, GoldPackage, GoldUsageSummaryResponse, PurchaseGoldRequest, SubscriptionPrice,
+    TransactionResponse, UseBookOfWisdomRequest, UseFeatureResponse, UseFinishAllRequest, UseFinishNowRequest,
+    UseInstantTrainRequest, UseNpcMerchantRequest, UseProductionBonusRequest,
 };
 use crate::repositories::user_repo::UserRepository;
 use crate::services::shop_service::ShopService;
@@ -30,6 +32,20 @@ fn default_limit() -> i32 {
     20
 }
 
+#[derive(Debug, Deserialize)]
+pub struct GoldUsageQuery {
+    #[serde(default = "default_usage_days")]
+    pub days: i32,
+    #[serde(default = "default_limit")]
+    pub limit: i32,
+    #[serde(default)]
+    pub offset: i32,
+}
+
+fn default_usage_days() -> i32 {
+    30
+}
+
 // ==================== Gold Packages ====================
 
 /// GET /api/shop/packages - Get available gold packages
@@ -111,6 +127,19 @@ pub async fn get_subscription_prices(
     Ok(Json(prices))
 }
 
+/// GET /api/shop/features - List gold features with their current prices
+pub async fn list_features(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+) -> AppResult<Json<Vec<GoldFeatureListing>>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let features = ShopService::list_features(&state.db, db_user.id).await?;
+    Ok(Json(features))
+}
+
 /// POST /api/shop/subscriptions/buy - Buy Travian Plus with gold
 pub async fn buy_subscription(
     State(state): State<AppState>,
@@ -126,6 +155,28 @@ pub async fn buy_subscription(
     Ok(Json(result))
 }
 
+/// POST /api/shop/gift - Send gold to another player
+pub async fn gift_gold(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Json(request): Json<GiftGoldRequest>,
+) -> AppResult<Json<GiftGoldResponse>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let result = ShopService::gift_gold(
+        &state.db,
+        &state.ws,
+        db_user.id,
+        request.recipient_id,
+        request.amount,
+        request.message,
+    )
+    .await?;
+    Ok(Json(result))
+}
+
 // ==================== Gold Features ====================
 
 /// POST /api/shop/features/finish-now - Finish building/training instantly
@@ -148,6 +199,54 @@ pub async fn use_finish_now(
     Ok(Json(result))
 }
 
+/// POST /api/shop/features/finish-all - Finish every build in progress in a village
+pub async fn use_finish_all(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Json(request): Json<UseFinishAllRequest>,
+) -> AppResult<Json<UseFeatureResponse>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let result = ShopService::use_finish_all(&state.db, db_user.id, request.village_id).await?;
+    Ok(Json(result))
+}
+
+/// POST /api/shop/features/finish-queue - Finish a village's whole training queue
+pub async fn use_finish_now_queue(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Json(request): Json<UseFinishAllRequest>,
+) -> AppResult<Json<FinishQueueResponse>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let result = ShopService::use_finish_now_queue(&state.db, db_user.id, request.village_id).await?;
+    Ok(Json(result))
+}
+
+/// GET /api/shop/finish-now/preview - Preview the Finish Now price without spending gold
+pub async fn preview_finish_now(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Query(query): Query<UseFinishNowRequest>,
+) -> AppResult<Json<FinishNowPreviewResponse>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let preview = ShopService::preview_finish_now(
+        &state.db,
+        db_user.id,
+        &query.target_type,
+        query.target_id,
+    )
+    .await?;
+    Ok(Json(preview))
+}
+
 /// POST /api/shop/features/npc-merchant - Exchange resources
 pub async fn use_npc_merchant(
     State(state): State<AppState>,
@@ -205,6 +304,28 @@ pub async fn use_book_of_wisdom(
     Ok(Json(result))
 }
 
+/// POST /api/shop/features/instant-train - Train troops instantly for gold
+pub async fn use_instant_train(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Json(request): Json<UseInstantTrainRequest>,
+) -> AppResult<Json<UseFeatureResponse>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let result = ShopService::use_instant_train(
+        &state.db,
+        db_user.id,
+        request.village_id,
+        request.troop_type,
+        request.count,
+        state.config.game.starvation_guard,
+    )
+    .await?;
+    Ok(Json(result))
+}
+
 // ==================== Transactions ====================
 
 /// GET /api/shop/transactions - Get transaction history
@@ -221,3 +342,40 @@ pub async fn get_transactions(
         ShopService::get_transactions(&state.db, db_user.id, query.limit, query.offset).await?;
     Ok(Json(transactions))
 }
+
+/// GET /api/shop/transactions/{id} - Get a single transaction (receipt)
+pub async fn get_transaction(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Path(transaction_id): Path<Uuid>,
+) -> AppResult<Json<TransactionResponse>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let transaction = ShopService::get_transaction(&state.db, db_user.id, transaction_id).await?;
+    Ok(Json(transaction))
+}
+
+// ==================== Gold Usage ====================
+
+/// GET /api/shop/usage - Get gold spent per feature and usage history
+pub async fn get_gold_usage(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Query(query): Query<GoldUsageQuery>,
+) -> AppResult<Json<GoldUsageSummaryResponse>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let summary = ShopService::get_gold_usage_summary(
+        &state.db,
+        db_user.id,
+        query.days,
+        query.limit,
+        query.offset,
+    )
+    .await?;
+    Ok(Json(summary))
+}
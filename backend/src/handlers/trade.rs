@@ -1,19 +1,25 @@
+use std::net::{IpAddr, SocketAddr};
+
 use axum::{
-    extract::{Path, Query, State},
+    extract::{ConnectInfo, Path, Query, State},
+    http::HeaderMap,
     Extension, Json,
 };
-use chrono::Utc;
 use serde::Deserialize;
 use uuid::Uuid;
 
 use crate::error::{AppError, AppResult};
 use crate::middleware::auth::AuthenticatedUser;
+use crate::models::admin::PublicEconomyResponse;
 use crate::models::trade::{
-    AcceptOrderRequest, AcceptOrderResponse, CancelOrderResponse, CreateOrderRequest,
+    AcceptOrderRequest, AcceptOrderResponse, CancelAllOrdersQuery, CancelAllOrdersResponse, CancelOrderResponse,
+    CreateOrderRequest,
     CreateOrderResponse, GetOrdersQuery, GetOrdersResponse, MarketSummaryResponse,
-    MyOrdersResponse, TradeHistoryResponse, TradeOrder, TradeOrderStatus, TradeResourceType,
-    TradeTransaction,
+    MyOrdersResponse, OrderBookQuery, OrderBookResponse, OrderStatusCounts, PriceCandlesQuery, PriceCandlesResponse, TradeHistoryResponse, TradeOrderStatus,
+    TradeOrderWithDetails, TradeQuoteQuery, TradeQuoteResponse, TradeResourceType, TradeTransaction,
+    UpdateOrderRequest, UpdateOrderResponse,
 };
+use crate::repositories::alliance_repo::AllianceRepository;
 use crate::repositories::trade_repo::TradeRepository;
 use crate::repositories::user_repo::UserRepository;
 use crate::services::trade_service::TradeService;
@@ -49,18 +55,57 @@ pub struct UserOrdersQuery {
     pub status: Option<TradeOrderStatus>,
 }
 
+/// Client IP recorded with orders and trades as a wash trade signal.
+/// X-Forwarded-For is only read when the connection comes from a trusted proxy, and then
+/// the client is the last hop that isn't one of those proxies (earlier entries can be forged).
+fn client_ip(headers: &HeaderMap, peer: SocketAddr, trusted_proxies: &[IpAddr]) -> String {
+    let peer_ip = peer.ip();
+    if !trusted_proxies.contains(&peer_ip) {
+        return peer_ip.to_string();
+    }
+
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .rev()
+        .map(|hop| hop.trim().parse::<IpAddr>().ok())
+        .find(|hop| !matches!(hop, Some(ip) if trusted_proxies.contains(ip)))
+        .flatten()
+        .unwrap_or(peer_ip)
+        .to_string()
+}
+
 // ==================== Public Market Endpoints ====================
 
 /// GET /api/market/summary - Get market summary for all resources
 pub async fn get_market_summary(
     State(state): State<AppState>,
 ) -> AppResult<Json<MarketSummaryResponse>> {
-    let summaries = TradeService::get_market_summary(&state.db).await?;
+    let response = state
+        .market_cache
+        .get(&state.db, &state.config.game.wash_trade_filter())
+        .await?;
 
-    Ok(Json(MarketSummaryResponse {
-        summaries,
-        updated_at: Utc::now(),
-    }))
+    Ok(Json(response))
+}
+
+/// GET /api/market/depth - Order book aggregated by price level
+pub async fn get_order_book(
+    State(state): State<AppState>,
+    Query(query): Query<OrderBookQuery>,
+) -> AppResult<Json<OrderBookResponse>> {
+    let book = TradeService::get_order_book(&state.db, query).await?;
+    Ok(Json(book))
+}
+
+/// GET /api/market/economy - Server-wide resource and trade totals (no gold figures)
+pub async fn get_public_economy(
+    State(state): State<AppState>,
+) -> AppResult<Json<PublicEconomyResponse>> {
+    let economy = state.economy_cache.get(&state.db).await?;
+    Ok(Json(PublicEconomyResponse::from(&economy)))
 }
 
 /// GET /api/market/orders - Get open orders with optional filters
@@ -68,29 +113,62 @@ pub async fn get_open_orders(
     State(state): State<AppState>,
     Query(query): Query<GetOrdersQuery>,
 ) -> AppResult<Json<GetOrdersResponse>> {
+    open_orders_response(&state, query, None).await.map(Json)
+}
+
+/// GET /api/trade/market/orders - Get open orders including the viewer's alliance-only orders
+pub async fn get_open_orders_for_member(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Query(query): Query<GetOrdersQuery>,
+) -> AppResult<Json<GetOrdersResponse>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let alliance_id = AllianceRepository::get_user_alliance(&state.db, db_user.id)
+        .await?
+        .map(|m| m.alliance_id);
+
+    open_orders_response(&state, query, alliance_id).await.map(Json)
+}
+
+/// GET /api/trade/quote - Preview what hitting the book now would fill, without trading
+pub async fn get_quote(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Query(query): Query<TradeQuoteQuery>,
+) -> AppResult<Json<TradeQuoteResponse>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let quote = TradeService::quote(&state.db, db_user.id, query).await?;
+    Ok(Json(quote))
+}
+
+/// GET /api/trade/candles - OHLC price history of a resource for the market chart
+pub async fn get_price_candles(
+    State(state): State<AppState>,
+    Query(query): Query<PriceCandlesQuery>,
+) -> AppResult<Json<PriceCandlesResponse>> {
+    let candles =
+        TradeService::get_price_candles(&state.db, query, &state.config.game.wash_trade_filter()).await?;
+    Ok(Json(candles))
+}
+
+async fn open_orders_response(
+    state: &AppState,
+    query: GetOrdersQuery,
+    viewer_alliance_id: Option<Uuid>,
+) -> AppResult<GetOrdersResponse> {
     let page = query.page.unwrap_or(1).max(1);
     let limit = query.limit.unwrap_or(20).min(100).max(1);
     let offset = (page - 1) * limit;
 
-    let orders = TradeRepository::get_open_orders(
-        &state.db,
-        query.resource_type,
-        query.order_type,
-        query.min_price,
-        query.max_price,
-        limit,
-        offset,
-    )
-    .await?;
+    let orders = TradeRepository::get_open_orders(&state.db, &query, viewer_alliance_id, limit, offset).await?;
 
-    let total = TradeRepository::count_open_orders(
-        &state.db,
-        query.resource_type,
-        query.order_type,
-        query.min_price,
-        query.max_price,
-    )
-    .await?;
+    let total = TradeRepository::count_open_orders(&state.db, &query, viewer_alliance_id).await?;
 
     // TODO: Add village/user details for display
     // For now, return orders without additional details
@@ -105,21 +183,23 @@ pub async fn get_open_orders(
         })
         .collect();
 
-    Ok(Json(GetOrdersResponse {
+    Ok(GetOrdersResponse {
         orders: orders_with_details,
         total,
         page,
         limit,
-    }))
+    })
 }
 
 /// GET /api/market/orders/:id - Get order details
 pub async fn get_order(
     State(state): State<AppState>,
     Path(order_id): Path<Uuid>,
-) -> AppResult<Json<TradeOrder>> {
-    let order = TradeRepository::get_order_by_id(&state.db, order_id)
+) -> AppResult<Json<TradeOrderWithDetails>> {
+    // Alliance-only orders are not exposed on the public market
+    let order = TradeRepository::get_order_with_details(&state.db, order_id)
         .await?
+        .filter(|details| !details.order.alliance_only)
         .ok_or_else(|| AppError::NotFound("Order not found".into()))?;
 
     Ok(Json(order))
@@ -146,13 +226,23 @@ pub async fn get_recent_transactions(
 pub async fn create_order(
     State(state): State<AppState>,
     Extension(user): Extension<AuthenticatedUser>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(request): Json<CreateOrderRequest>,
 ) -> AppResult<Json<CreateOrderResponse>> {
     let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
         .await?
         .ok_or(AppError::Unauthorized)?;
 
-    let response = TradeService::create_order(&state.db, db_user.id, request).await?;
+    state.trade_rate_limiter.check(db_user.id).await?;
+
+    let ip = client_ip(&headers, peer, &state.config.server.trusted_proxies);
+    let response = TradeService::create_order(&state.db, db_user.id, request, &state.config.game, Some(&ip)).await?;
+
+    // The new order may have matched against the book right away
+    if !response.fills.is_empty() {
+        state.market_cache.invalidate().await;
+    }
 
     Ok(Json(response))
 }
@@ -161,6 +251,8 @@ pub async fn create_order(
 pub async fn accept_order(
     State(state): State<AppState>,
     Extension(user): Extension<AuthenticatedUser>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Path(order_id): Path<Uuid>,
     Json(request): Json<AcceptOrderRequest>,
 ) -> AppResult<Json<AcceptOrderResponse>> {
@@ -168,7 +260,36 @@ pub async fn accept_order(
         .await?
         .ok_or(AppError::Unauthorized)?;
 
-    let response = TradeService::accept_order(&state.db, db_user.id, order_id, request).await?;
+    let ip = client_ip(&headers, peer, &state.config.server.trusted_proxies);
+    let response = TradeService::accept_order(&state.db, db_user.id, order_id, request, &state.config.game, Some(&ip)).await?;
+    state.market_cache.invalidate().await;
+
+    Ok(Json(response))
+}
+
+/// PUT /api/trade/orders/:id - Change the price and/or quantity of an open order
+pub async fn update_order(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Path(order_id): Path<Uuid>,
+    Json(request): Json<UpdateOrderRequest>,
+) -> AppResult<Json<UpdateOrderResponse>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    state.trade_rate_limiter.check(db_user.id).await?;
+
+    let response = TradeService::update_order(
+        &state.db,
+        db_user.id,
+        order_id,
+        request.price_per_unit,
+        request.quantity,
+        &state.config.game,
+    )
+    .await?;
+    state.market_cache.invalidate().await;
 
     Ok(Json(response))
 }
@@ -183,11 +304,30 @@ pub async fn cancel_order(
         .await?
         .ok_or(AppError::Unauthorized)?;
 
+    state.trade_rate_limiter.check(db_user.id).await?;
+
     let response = TradeService::cancel_order(&state.db, db_user.id, order_id).await?;
 
     Ok(Json(response))
 }
 
+/// POST /api/trade/orders/cancel-all - Cancel all open orders, optionally for one resource
+pub async fn cancel_all_orders(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Query(query): Query<CancelAllOrdersQuery>,
+) -> AppResult<Json<CancelAllOrdersResponse>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    state.trade_rate_limiter.check(db_user.id).await?;
+
+    let response = TradeService::cancel_all_orders(&state.db, db_user.id, query.resource_type).await?;
+
+    Ok(Json(response))
+}
+
 /// GET /api/trade/orders - Get user's own orders
 pub async fn get_my_orders(
     State(state): State<AppState>,
@@ -203,6 +343,42 @@ pub async fn get_my_orders(
     Ok(Json(MyOrdersResponse { orders }))
 }
 
+/// GET /api/trade/orders/counts - Count user's own orders per status
+pub async fn get_my_order_counts(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+) -> AppResult<Json<OrderStatusCounts>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let counts = TradeRepository::count_user_orders_by_status(&state.db, db_user.id).await?;
+
+    Ok(Json(counts))
+}
+
+/// GET /api/trade/orders/:id - Get any order the user may see, with village and player details
+pub async fn get_order_details(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Path(order_id): Path<Uuid>,
+) -> AppResult<Json<TradeOrderWithDetails>> {
+    let db_user = UserRepository::find_by_firebase_uid(&state.db, &user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let details = TradeRepository::get_order_with_details(&state.db, order_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order not found".into()))?;
+
+    // Owners always see their own orders; others need alliance access for alliance-only ones
+    if details.order.user_id != db_user.id {
+        TradeService::validate_alliance_access(&state.db, &details.order, db_user.id).await?;
+    }
+
+    Ok(Json(details))
+}
+
 /// GET /api/trade/history - Get user's trade history
 pub async fn get_trade_history(
     State(state): State<AppState>,
@@ -225,3 +401,33 @@ pub async fn get_trade_history(
         total,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forwarded(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn forwarded_header_is_only_trusted_from_a_configured_proxy() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let from_proxy = SocketAddr::new(proxy, 443);
+        let direct: SocketAddr = "203.0.113.9:443".parse().unwrap();
+
+        // A client connecting directly can't pick its own address
+        assert_eq!(client_ip(&forwarded("198.51.100.7"), direct, &[proxy]), "203.0.113.9");
+        assert_eq!(client_ip(&forwarded("198.51.100.7"), from_proxy, &[]), "10.0.0.1");
+
+        // Behind the proxy, the hop it appended wins over whatever the client sent
+        assert_eq!(client_ip(&forwarded("1.2.3.4, 198.51.100.7"), from_proxy, &[proxy]), "198.51.100.7");
+        assert_eq!(client_ip(&forwarded("198.51.100.7, 10.0.0.1"), from_proxy, &[proxy]), "198.51.100.7");
+
+        // Without a usable hop, fall back to the peer
+        assert_eq!(client_ip(&HeaderMap::new(), from_proxy, &[proxy]), "10.0.0.1");
+        assert_eq!(client_ip(&forwarded("not-an-ip"), from_proxy, &[proxy]), "10.0.0.1");
+    }
+}
@@ -90,7 +90,14 @@ pub async fn train_troops(
         return Err(AppError::Forbidden("Access denied".into()));
     }
 
-    let response = TroopService::train_troops(&state.db, village_id, body.troop_type, body.count).await?;
+    let response = TroopService::train_troops(
+        &state.db,
+        village_id,
+        body.troop_type,
+        body.count,
+        state.config.game.starvation_guard,
+    )
+    .await?;
 
     info!(
         "Training {} {:?} in village {}",
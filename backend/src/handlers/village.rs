@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use axum::{
     extract::{Path, Query, State},
     Extension, Json,
@@ -8,8 +10,17 @@ use uuid::Uuid;
 
 use crate::error::{AppError, AppResult};
 use crate::middleware::AuthenticatedUser;
-use crate::models::village::{CreateVillage, ProductionRates, UpdateVillage, VillageResponse};
+use crate::models::alliance::MapRelation;
+use crate::models::army::ArmyTroops;
+use crate::models::hero::AssignedHeroResponse;
+use crate::models::pagination::{PageQuery, Paginated};
+use crate::models::village::{
+    CreateVillage, ExpansionOverviewResponse, ProductionRates, UpdateVillage, UpkeepOverviewResponse,
+    VillageResponse,
+};
+use crate::repositories::alliance_repo::AllianceRepository;
 use crate::repositories::building_repo::BuildingRepository;
+use crate::repositories::hero_repo::HeroRepository;
 use crate::repositories::troop_repo::TroopRepository;
 use crate::repositories::user_repo::UserRepository;
 use crate::repositories::village_repo::VillageRepository;
@@ -18,19 +29,70 @@ use crate::services::resource_service::ResourceService;
 use crate::services::village_service::VillageService;
 use crate::AppState;
 
+#[derive(Debug, Deserialize)]
+pub struct ListVillagesQuery {
+    /// capital (default), population, created_at or net_crop
+    #[serde(default = "default_village_sort")]
+    pub sort: String,
+    #[serde(default)]
+    pub desc: bool,
+    #[serde(default)]
+    pub capital_only: bool,
+    #[serde(default)]
+    pub under_attack: bool,
+}
+
+fn default_village_sort() -> String {
+    "capital".to_string()
+}
+
 // GET /api/villages - List current user's villages
 pub async fn list_villages(
     State(state): State<AppState>,
     Extension(auth_user): Extension<AuthenticatedUser>,
+    Query(query): Query<ListVillagesQuery>,
 ) -> AppResult<Json<Vec<VillageResponse>>> {
     // Get user from database
     let user = UserRepository::find_by_firebase_uid(&state.db, &auth_user.firebase_uid)
         .await?
         .ok_or(AppError::Unauthorized)?;
 
-    let villages = VillageRepository::find_by_user_id(&state.db, user.id).await?;
+    let villages = VillageService::list_villages(
+        &state.db,
+        user.id,
+        &query.sort,
+        query.desc,
+        query.capital_only,
+        query.under_attack,
+    )
+    .await?;
+
+    Ok(Json(villages))
+}
 
-    Ok(Json(villages.into_iter().map(|v| v.into()).collect()))
+// GET /api/villages/paged - List current user's villages with pagination metadata
+pub async fn list_villages_paged(
+    State(state): State<AppState>,
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Query(query): Query<ListVillagesQuery>,
+    Query(page): Query<PageQuery>,
+) -> AppResult<Json<Paginated<VillageResponse>>> {
+    let user = UserRepository::find_by_firebase_uid(&state.db, &auth_user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let villages = VillageService::list_villages_page(
+        &state.db,
+        user.id,
+        &query.sort,
+        query.desc,
+        query.capital_only,
+        query.under_attack,
+        &page,
+    )
+    .await?;
+
+    Ok(Json(villages))
 }
 
 // GET /api/villages/:id - Get village detail
@@ -57,17 +119,56 @@ pub async fn get_village(
 
     // Calculate production rates
     let production = ResourceService::calculate_production(&state.db, village_id).await?;
-    let production_rates = ProductionRates {
-        wood_per_hour: production.wood_per_hour,
-        clay_per_hour: production.clay_per_hour,
-        iron_per_hour: production.iron_per_hour,
-        crop_per_hour: production.crop_per_hour,
-        crop_consumption: production.crop_consumption,
-        net_crop_per_hour: production.net_crop_per_hour,
-    };
+    let production_rates = production.into();
+
+    let army_slots = ArmyService::get_army_slots(&state.db, village_id).await?;
+    let accounting = VillageService::resource_accounting(&state.db, &village).await?;
+    let assigned_hero = HeroRepository::find_stationed_by_villages(&state.db, &[village_id])
+        .await?
+        .iter()
+        .max_by_key(|h| h.production_bonus_percent())
+        .map(AssignedHeroResponse::from);
+
+    let protection_until = UserRepository::get_active_protection(&state.db, user.id).await?;
 
     let response: VillageResponse = village.into();
-    Ok(Json(response.with_production(production_rates)))
+    Ok(Json(
+        response
+            .with_production(production_rates)
+            .with_army_slots(army_slots)
+            .with_resource_accounting(accounting)
+            .with_assigned_hero(assigned_hero)
+            .with_protection_until(protection_until),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VillagesDetailQuery {
+    /// Comma-separated village ids
+    pub ids: String,
+}
+
+// GET /api/villages/detail?ids=a,b,c - Get detail for several villages at once
+pub async fn get_villages_detail(
+    State(state): State<AppState>,
+    Extension(auth_user): Extension<AuthenticatedUser>,
+    Query(query): Query<VillagesDetailQuery>,
+) -> AppResult<Json<Vec<VillageResponse>>> {
+    let user = UserRepository::find_by_firebase_uid(&state.db, &auth_user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let ids = query
+        .ids
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| Uuid::parse_str(s).map_err(|_| AppError::BadRequest(format!("Invalid village id: {}", s))))
+        .collect::<AppResult<Vec<Uuid>>>()?;
+
+    let villages = VillageService::get_villages_detail(&state.db, user.id, &ids).await?;
+
+    Ok(Json(villages))
 }
 
 #[derive(Debug, Deserialize)]
@@ -87,6 +188,8 @@ pub async fn create_village(
         .await?
         .ok_or(AppError::Unauthorized)?;
 
+    let name = VillageService::validate_name(&state.db, user.id, &body.name, None).await?;
+
     // Check if coordinates are available
     if !VillageRepository::is_coordinate_available(&state.db, body.x, body.y).await? {
         return Err(AppError::Conflict("Coordinates already occupied".to_string()));
@@ -98,14 +201,19 @@ pub async fn create_village(
 
     let create_village = CreateVillage {
         user_id: user.id,
-        name: body.name,
+        name,
         x: body.x,
         y: body.y,
         is_capital,
     };
 
     // Create village with initial buildings
-    let (village, buildings) = VillageService::create_village_with_buildings(&state.db, create_village).await?;
+    let (village, buildings) = VillageService::create_village_with_buildings(
+        &state.db,
+        create_village,
+        state.config.game.beginner_protection_hours,
+    )
+    .await?;
 
     info!(
         "Village created: {} at ({}, {}) for user {} with {} initial buildings",
@@ -118,6 +226,7 @@ pub async fn create_village(
 #[derive(Debug, Deserialize)]
 pub struct UpdateVillageRequest {
     pub name: Option<String>,
+    pub note: Option<String>,
 }
 
 // PUT /api/villages/:id - Update village
@@ -139,7 +248,14 @@ pub async fn update_village(
         return Err(AppError::Forbidden("Access denied".into()));
     }
 
-    let update = UpdateVillage { name: body.name };
+    let name = match body.name {
+        Some(name) => Some(VillageService::validate_name(&state.db, user.id, &name, Some(village_id)).await?),
+        None => None,
+    };
+
+    let note = body.note.as_deref().map(VillageService::sanitize_note).transpose()?;
+
+    let update = UpdateVillage { name, note };
     let updated = VillageRepository::update(&state.db, village_id, update).await?;
 
     Ok(Json(updated.into()))
@@ -173,6 +289,12 @@ pub struct MapVillageInfo {
     pub player_name: Option<String>,
     pub population: i32,
     pub is_own: bool,
+    /// Owner is under beginner protection until this time
+    pub protection_until: Option<chrono::DateTime<chrono::Utc>>,
+    pub alliance_id: Option<Uuid>,
+    pub alliance_tag: Option<String>,
+    /// Viewer's standing toward the owner, for color-coding the map
+    pub relation: MapRelation,
 }
 
 // GET /api/map - Get map tiles around coordinates
@@ -190,6 +312,24 @@ pub async fn get_map(
 
     let villages = VillageRepository::find_in_range(&state.db, query.x, query.y, range).await?;
 
+    // Look up the viewer's diplomacy toward every alliance in view at once
+    let viewer_alliance_id = AllianceRepository::get_user_alliance(&state.db, user.id)
+        .await?
+        .map(|m| m.alliance_id);
+    let diplomacy: HashMap<Uuid, MapRelation> = match viewer_alliance_id {
+        Some(alliance_id) => {
+            let mut targets: Vec<Uuid> = villages.iter().filter_map(|v| v.alliance_id).collect();
+            targets.sort();
+            targets.dedup();
+            AllianceRepository::get_diplomacy_toward(&state.db, alliance_id, &targets)
+                .await?
+                .into_iter()
+                .map(|d| (d.target_alliance_id, d.status.map_relation()))
+                .collect()
+        }
+        None => HashMap::new(),
+    };
+
     // Generate tiles for the range
     let mut tiles = Vec::new();
     for dy in -range..=range {
@@ -202,12 +342,29 @@ pub async fn get_map(
             tiles.push(MapTileResponse {
                 x,
                 y,
-                village: village.map(|v| MapVillageInfo {
-                    id: v.id,
-                    name: v.name.clone(),
-                    player_name: v.player_name.clone(),
-                    population: v.population,
-                    is_own: v.user_id == user.id,
+                village: village.map(|v| {
+                    // Players without an alliance are always neutral
+                    let relation = if v.user_id == user.id {
+                        MapRelation::Own
+                    } else if v.alliance_id.is_some() && v.alliance_id == viewer_alliance_id {
+                        MapRelation::Ally
+                    } else {
+                        v.alliance_id
+                            .and_then(|id| diplomacy.get(&id).copied())
+                            .unwrap_or(MapRelation::Neutral)
+                    };
+
+                    MapVillageInfo {
+                        id: v.id,
+                        name: v.name.clone(),
+                        player_name: v.player_name.clone(),
+                        population: v.population,
+                        is_own: v.user_id == user.id,
+                        protection_until: v.protection_until,
+                        alliance_id: v.alliance_id,
+                        alliance_tag: v.alliance_tag.clone(),
+                        relation,
+                    }
                 }),
             });
         }
@@ -351,6 +508,8 @@ pub struct BuildingQueueItem {
     pub slot: i32,
     pub level: i32,
     pub ends_at: chrono::DateTime<chrono::Utc>,
+    /// Seconds left as of the response's `server_time`
+    pub remaining_seconds: i64,
 }
 
 #[derive(Debug, Serialize)]
@@ -359,6 +518,8 @@ pub struct TroopQueueItem {
     pub troop_type: String,
     pub count: i32,
     pub ends_at: chrono::DateTime<chrono::Utc>,
+    /// Seconds left as of the response's `server_time`
+    pub remaining_seconds: i64,
 }
 
 #[derive(Debug, Serialize)]
@@ -370,6 +531,9 @@ pub struct IncomingArmy {
     pub to_village_name: String,
     pub mission: String,
     pub arrives_at: chrono::DateTime<chrono::Utc>,
+    /// Fuzzed troop counts, only shown when the defender's Rally Point is high enough
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_troops: Option<ArmyTroops>,
 }
 
 #[derive(Debug, Serialize)]
@@ -377,6 +541,41 @@ pub struct DashboardResponse {
     pub villages: Vec<DashboardVillage>,
     pub incoming_attacks: Vec<IncomingArmy>,
     pub unread_reports: i64,
+    /// Server clock when the response was built, so clients can correct for clock skew
+    pub server_time: chrono::DateTime<chrono::Utc>,
+}
+
+/// Seconds from `now` until `ends_at`, never negative
+fn remaining_seconds(ends_at: chrono::DateTime<chrono::Utc>, now: chrono::DateTime<chrono::Utc>) -> i64 {
+    (ends_at - now).num_seconds().max(0)
+}
+
+// GET /api/overview/upkeep - Crop production vs upkeep across all user's villages
+pub async fn get_upkeep_overview(
+    State(state): State<AppState>,
+    Extension(auth_user): Extension<AuthenticatedUser>,
+) -> AppResult<Json<UpkeepOverviewResponse>> {
+    let user = UserRepository::find_by_firebase_uid(&state.db, &auth_user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let overview = ResourceService::upkeep_overview(&state.db, user.id).await?;
+
+    Ok(Json(overview))
+}
+
+// GET /api/overview/expansion - Culture points and settlement slots across all user's villages
+pub async fn get_expansion_overview(
+    State(state): State<AppState>,
+    Extension(auth_user): Extension<AuthenticatedUser>,
+) -> AppResult<Json<ExpansionOverviewResponse>> {
+    let user = UserRepository::find_by_firebase_uid(&state.db, &auth_user.firebase_uid)
+        .await?
+        .ok_or(AppError::Unauthorized)?;
+
+    let overview = VillageService::expansion_overview(&state.db, user.id).await?;
+
+    Ok(Json(overview))
 }
 
 // GET /api/dashboard - Get dashboard overview for all user's villages
@@ -392,6 +591,8 @@ pub async fn get_dashboard(
     let villages = VillageRepository::find_by_user_id(&state.db, user.id).await?;
 
     let mut dashboard_villages = Vec::new();
+    // One timestamp for every countdown so they agree with `server_time`
+    let now = chrono::Utc::now();
 
     for village in villages {
         // Update resources
@@ -399,14 +600,7 @@ pub async fn get_dashboard(
 
         // Get production rates
         let production = ResourceService::calculate_production(&state.db, village.id).await.ok();
-        let production_rates = production.map(|p| ProductionRates {
-            wood_per_hour: p.wood_per_hour,
-            clay_per_hour: p.clay_per_hour,
-            iron_per_hour: p.iron_per_hour,
-            crop_per_hour: p.crop_per_hour,
-            crop_consumption: p.crop_consumption,
-            net_crop_per_hour: p.net_crop_per_hour,
-        });
+        let production_rates = production.map(ProductionRates::from);
 
         // Get building queue (buildings currently upgrading)
         let building_queue: Vec<BuildingQueueItem> = BuildingRepository::find_upgrading_by_village(&state.db, village.id)
@@ -419,6 +613,7 @@ pub async fn get_dashboard(
                     slot: b.slot,
                     level: b.level + 1, // Show target level
                     ends_at,
+                    remaining_seconds: remaining_seconds(ends_at, now),
                 })
             })
             .collect();
@@ -432,6 +627,7 @@ pub async fn get_dashboard(
                 troop_type: format!("{:?}", t.troop_type).to_lowercase(),
                 count: t.count,
                 ends_at: t.ends_at,
+                remaining_seconds: remaining_seconds(t.ends_at, now),
             })
             .collect();
 
@@ -458,10 +654,17 @@ pub async fn get_dashboard(
     let mut incoming_attacks = Vec::new();
     for village in &dashboard_villages {
         let armies = ArmyService::get_incoming_armies(&state.db, village.id).await.unwrap_or_default();
+        if armies.is_empty() {
+            continue;
+        }
+
+        // Rally Point level decides whether the defender can estimate incoming troops
+        let rally_point_level = ArmyService::get_rally_point_level(&state.db, village.id).await?;
+
         for army in armies {
-            // Only show hostile missions
-            let mission_str = format!("{:?}", army.mission).to_lowercase();
-            if mission_str == "attack" || mission_str == "raid" || mission_str == "conquer" {
+            // Only show hostile missions the defender can see coming
+            if army.mission.alerts_defender() {
+                let mission_str = format!("{:?}", army.mission).to_lowercase();
                 // Get attacker village info
                 let from_village = VillageRepository::find_by_id(&state.db, army.from_village_id)
                     .await
@@ -476,6 +679,7 @@ pub async fn get_dashboard(
                     to_village_name: village.name.clone(),
                     mission: mission_str,
                     arrives_at: army.arrives_at,
+                    estimated_troops: ArmyService::estimate_incoming_troops(&army, rally_point_level),
                 });
             }
         }
@@ -493,5 +697,6 @@ pub async fn get_dashboard(
         villages: dashboard_villages,
         incoming_attacks,
         unread_reports,
+        server_time: now,
     }))
 }
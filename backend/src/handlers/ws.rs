@@ -1,54 +1,58 @@
 use axum::{
     extract::{
-        ws::{Message, WebSocket, WebSocketUpgrade},
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
         Query, State,
     },
     response::Response,
 };
+use chrono::{DateTime, Utc};
 use futures_util::{SinkExt, StreamExt};
 use serde::Deserialize;
+use std::time::Duration;
+use tokio::time::{sleep_until, Instant};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+use crate::error::{AppError, AppResult};
 use crate::middleware::auth::FirebaseAuth;
 use crate::repositories::user_repo::UserRepository;
-use crate::services::ws_service::{WsEvent, WsManager};
+use crate::services::ws_service::WsEvent;
 use crate::AppState;
 
+/// Close code sent when the session token expires without being refreshed
+const CLOSE_TOKEN_EXPIRED: u16 = 4001;
+/// Close code sent when a refresh token is invalid or belongs to another user
+const CLOSE_TOKEN_INVALID: u16 = 4003;
+
 #[derive(Debug, Deserialize)]
 pub struct WsQuery {
     token: Option<String>,
 }
 
+/// Authenticated WebSocket session
+struct WsSession {
+    user_id: Uuid,
+    expires_at: DateTime<Utc>,
+}
+
 /// WebSocket upgrade handler
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     Query(query): Query<WsQuery>,
     State(state): State<AppState>,
-) -> Response {
-    // Authenticate user from token
-    let user_id = match authenticate_ws(&query, &state).await {
-        Ok(id) => id,
-        Err(e) => {
-            warn!("WebSocket auth failed: {}", e);
-            // Return upgrade anyway but will close immediately
-            return ws.on_upgrade(|socket| async move {
-                let _ = socket.close().await;
-            });
-        }
-    };
-
-    let ws_manager = state.ws.clone();
-    ws.on_upgrade(move |socket| handle_socket(socket, user_id, ws_manager))
+) -> AppResult<Response> {
+    // Authenticate user from token before upgrading
+    let token = query.token.as_deref().ok_or(AppError::Unauthorized)?;
+    let session = authenticate_ws(token, &state).await.map_err(|e| {
+        warn!("WebSocket auth failed: {}", e);
+        AppError::Unauthorized
+    })?;
+
+    Ok(ws.on_upgrade(move |socket| handle_socket(socket, session, state)))
 }
 
 /// Authenticate WebSocket connection using Firebase token
-async fn authenticate_ws(query: &WsQuery, state: &AppState) -> Result<Uuid, String> {
-    let token = query
-        .token
-        .as_ref()
-        .ok_or_else(|| "Missing token".to_string())?;
-
+async fn authenticate_ws(token: &str, state: &AppState) -> Result<WsSession, String> {
     let firebase_auth = FirebaseAuth::new(state.config.firebase.project_id.clone());
 
     let claims = firebase_auth
@@ -62,39 +66,71 @@ async fn authenticate_ws(query: &WsQuery, state: &AppState) -> Result<Uuid, Stri
         .map_err(|e| format!("Database error: {:?}", e))?
         .ok_or_else(|| "User not found".to_string())?;
 
-    Ok(user.id)
+    let expires_at = DateTime::from_timestamp(claims.exp, 0)
+        .ok_or_else(|| "Invalid token expiry".to_string())?;
+
+    Ok(WsSession {
+        user_id: user.id,
+        expires_at,
+    })
+}
+
+/// Convert a token expiry into a tokio deadline
+fn expiry_deadline(expires_at: DateTime<Utc>) -> Instant {
+    let remaining = (expires_at - Utc::now()).to_std().unwrap_or(Duration::ZERO);
+    Instant::now() + remaining
+}
+
+fn event_message(event: &WsEvent) -> Option<Message> {
+    serde_json::to_string(event).ok().map(Message::Text)
+}
+
+fn close_message(code: u16, reason: &'static str) -> Message {
+    Message::Close(Some(CloseFrame {
+        code,
+        reason: reason.into(),
+    }))
 }
 
 /// Handle WebSocket connection
-async fn handle_socket(socket: WebSocket, user_id: Uuid, ws_manager: WsManager) {
+async fn handle_socket(socket: WebSocket, session: WsSession, state: AppState) {
+    let user_id = session.user_id;
     let (mut sender, mut receiver) = socket.split();
 
     // Register this connection
-    let mut rx = ws_manager.register(user_id).await;
+    let (connection_id, mut rx) = state.ws.register(user_id).await;
 
     // Send connected event
-    let connected_event = WsEvent::Connected { user_id };
-    if let Ok(json) = serde_json::to_string(&connected_event) {
-        let _ = sender.send(Message::Text(json)).await;
+    let connected_event = WsEvent::Connected {
+        user_id,
+        expires_at: session.expires_at,
+    };
+    if let Some(msg) = event_message(&connected_event) {
+        let _ = sender.send(msg).await;
     }
 
-    // Spawn task to forward messages from manager to WebSocket
-    let send_task = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            if sender.send(msg).await.is_err() {
-                break;
+    // The connection is closed when the token expires unless the client
+    // sends a fresh token for the same user first
+    let expiry = sleep_until(expiry_deadline(session.expires_at));
+    tokio::pin!(expiry);
+
+    loop {
+        tokio::select! {
+            // Forward messages from manager to WebSocket
+            outgoing = rx.recv() => {
+                let Some(msg) = outgoing else { break };
+                if sender.send(msg).await.is_err() {
+                    break;
+                }
             }
-        }
-    });
-
-    // Handle incoming messages from client
-    let recv_task = tokio::spawn(async move {
-        while let Some(result) = receiver.next().await {
-            match result {
-                Ok(Message::Text(text)) => {
-                    debug!("Received from user {}: {}", user_id, text);
-                    // Handle client messages if needed (e.g., ping, subscribe to specific events)
-                    if let Ok(msg) = serde_json::from_str::<ClientMessage>(&text) {
+            // Handle incoming messages from client
+            incoming = receiver.next() => {
+                match incoming {
+                    Some(Ok(Message::Text(text))) => {
+                        debug!("Received from user {}: {}", user_id, text);
+                        let Ok(msg) = serde_json::from_str::<ClientMessage>(&text) else {
+                            continue;
+                        };
                         match msg {
                             ClientMessage::Ping => {
                                 debug!("Ping from user {}", user_id);
@@ -102,36 +138,59 @@ async fn handle_socket(socket: WebSocket, user_id: Uuid, ws_manager: WsManager)
                             ClientMessage::Subscribe { event_type } => {
                                 debug!("User {} subscribed to {}", user_id, event_type);
                             }
+                            ClientMessage::Auth { token } => {
+                                match authenticate_ws(&token, &state).await {
+                                    Ok(refreshed) if refreshed.user_id == user_id => {
+                                        expiry.as_mut().reset(expiry_deadline(refreshed.expires_at));
+                                        let event = WsEvent::SessionRefreshed {
+                                            expires_at: refreshed.expires_at,
+                                        };
+                                        if let Some(msg) = event_message(&event) {
+                                            let _ = sender.send(msg).await;
+                                        }
+                                    }
+                                    Ok(_) => {
+                                        warn!("WebSocket refresh with another user's token: user_id={}", user_id);
+                                        let _ = sender.send(close_message(CLOSE_TOKEN_INVALID, "Token mismatch")).await;
+                                        break;
+                                    }
+                                    Err(e) => {
+                                        warn!("WebSocket token refresh failed for user {}: {}", user_id, e);
+                                        let _ = sender.send(close_message(CLOSE_TOKEN_INVALID, "Invalid token")).await;
+                                        break;
+                                    }
+                                }
+                            }
                         }
                     }
+                    Some(Ok(Message::Close(_))) | None => {
+                        info!("WebSocket closed by client: user_id={}", user_id);
+                        break;
+                    }
+                    Some(Ok(Message::Ping(_))) => {
+                        debug!("Ping from user {}", user_id);
+                    }
+                    Some(Ok(Message::Pong(_))) | Some(Ok(Message::Binary(_))) => {}
+                    Some(Err(e)) => {
+                        error!("WebSocket error for user {}: {}", user_id, e);
+                        break;
+                    }
                 }
-                Ok(Message::Close(_)) => {
-                    info!("WebSocket closed by client: user_id={}", user_id);
-                    break;
-                }
-                Ok(Message::Ping(_)) => {
-                    debug!("Ping from user {}", user_id);
-                }
-                Ok(Message::Pong(_)) => {}
-                Ok(Message::Binary(_)) => {}
-                Err(e) => {
-                    error!("WebSocket error for user {}: {}", user_id, e);
-                    break;
+            }
+            // Token expired without a refresh
+            _ = &mut expiry => {
+                info!("WebSocket token expired: user_id={}", user_id);
+                if let Some(msg) = event_message(&WsEvent::SessionExpired) {
+                    let _ = sender.send(msg).await;
                 }
+                let _ = sender.send(close_message(CLOSE_TOKEN_EXPIRED, "Token expired")).await;
+                break;
             }
         }
-    });
-
-    // Wait for either task to finish
-    tokio::select! {
-        _ = send_task => {
-            debug!("Send task finished for user {}", user_id);
-        }
-        _ = recv_task => {
-            debug!("Recv task finished for user {}", user_id);
-        }
     }
 
+    state.ws.unregister(user_id, connection_id).await;
+
     info!("WebSocket connection closed: user_id={}", user_id);
 }
 
@@ -141,4 +200,6 @@ async fn handle_socket(socket: WebSocket, user_id: Uuid, ws_manager: WsManager)
 enum ClientMessage {
     Ping,
     Subscribe { event_type: String },
+    /// Re-authenticate with a fresh Firebase token before the current one expires
+    Auth { token: String },
 }
@@ -6,13 +6,19 @@ mod middleware;
 mod models;
 mod repositories;
 mod services;
+#[cfg(test)]
+mod test_support;
 
 use axum::{routing::get, Router};
 use std::net::SocketAddr;
+use std::time::Duration;
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 use tracing::info;
 
+use services::economy_cache::EconomyCache;
+use services::market_cache::MarketSummaryCache;
+use services::trade_rate_limiter::TradeRateLimiter;
 use services::ws_service::WsManager;
 
 #[tokio::main]
@@ -48,10 +54,16 @@ async fn main() -> anyhow::Result<()> {
         redis: redis_pool,
         config: config.clone(),
         ws: ws_manager.clone(),
+        market_cache: MarketSummaryCache::new(),
+        economy_cache: EconomyCache::new(),
+        trade_rate_limiter: TradeRateLimiter::new(
+            config.game.trade_rate_limit_actions,
+            Duration::from_secs(config.game.trade_rate_limit_window_secs),
+        ),
     };
 
     // Start background jobs with WebSocket manager for broadcasting
-    services::background_jobs::start_background_jobs(db_pool, ws_manager).await;
+    services::background_jobs::start_background_jobs(db_pool, ws_manager, config.game.clone()).await;
 
     // Build router
     let app = Router::new()
@@ -67,7 +79,8 @@ async fn main() -> anyhow::Result<()> {
     info!("Server listening on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    // Peer addresses are needed for client IPs (wash trade signals)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
@@ -82,4 +95,7 @@ pub struct AppState {
     pub redis: redis::aio::ConnectionManager,
     pub config: config::Config,
     pub ws: WsManager,
+    pub market_cache: MarketSummaryCache,
+    pub economy_cache: EconomyCache,
+    pub trade_rate_limiter: TradeRateLimiter,
 }
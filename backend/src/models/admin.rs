@@ -31,11 +31,38 @@ pub struct AdjustResourcesRequest {
     pub reason: String,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForceCloseOrderRequest {
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FlagTradePairRequest {
+    pub user_a_id: Uuid,
+    pub user_b_id: Uuid,
+    pub reason: Option<String>,
+}
+
+/// Two players whose trades with each other are left out of market prices
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct TradeFlaggedPair {
+    pub user_1_id: Uuid,
+    pub user_2_id: Uuid,
+    pub reason: Option<String>,
+    pub flagged_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct SetAdminRequest {
     pub is_admin: bool,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeleteVillageRequest {
+    pub reason: String,
+}
+
 // ==================== Response DTOs ====================
 
 #[derive(Debug, Clone, Serialize)]
@@ -62,6 +89,102 @@ pub struct ServerStatsResponse {
     pub total_villages: i64,
     pub total_alliances: i64,
     pub total_battles_today: i64,
+    /// Gold removed from the economy by market fees
+    pub total_trade_fees_collected: i64,
+    pub trade_fees_collected_24h: i64,
+    pub trade_fees_collected_7d: i64,
+}
+
+/// Server-wide economy totals at one point in time (live or a stored snapshot)
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct EconomyMetrics {
+    /// Gold held by players
+    pub gold_in_circulation: i64,
+    /// Gold escrowed by open buy orders
+    pub gold_in_escrow: i64,
+    pub total_wood: i64,
+    pub total_clay: i64,
+    pub total_iron: i64,
+    pub total_crop: i64,
+    pub trade_count_24h: i64,
+    pub trade_quantity_24h: i64,
+    pub trade_gold_24h: i64,
+    pub gold_purchased_24h: i64,
+    pub captured_at: DateTime<Utc>,
+}
+
+/// Change of each economy metric since an earlier snapshot
+#[derive(Debug, Clone, Serialize)]
+pub struct EconomyMetricsChange {
+    pub gold_in_circulation: i64,
+    pub gold_in_escrow: i64,
+    pub total_wood: i64,
+    pub total_clay: i64,
+    pub total_iron: i64,
+    pub total_crop: i64,
+    pub trade_count_24h: i64,
+    pub trade_quantity_24h: i64,
+    pub trade_gold_24h: i64,
+    pub gold_purchased_24h: i64,
+    /// When the snapshot being compared against was taken
+    pub since: DateTime<Utc>,
+}
+
+impl EconomyMetrics {
+    pub fn change_since(&self, earlier: &EconomyMetrics) -> EconomyMetricsChange {
+        EconomyMetricsChange {
+            gold_in_circulation: self.gold_in_circulation - earlier.gold_in_circulation,
+            gold_in_escrow: self.gold_in_escrow - earlier.gold_in_escrow,
+            total_wood: self.total_wood - earlier.total_wood,
+            total_clay: self.total_clay - earlier.total_clay,
+            total_iron: self.total_iron - earlier.total_iron,
+            total_crop: self.total_crop - earlier.total_crop,
+            trade_count_24h: self.trade_count_24h - earlier.trade_count_24h,
+            trade_quantity_24h: self.trade_quantity_24h - earlier.trade_quantity_24h,
+            trade_gold_24h: self.trade_gold_24h - earlier.trade_gold_24h,
+            gold_purchased_24h: self.gold_purchased_24h - earlier.gold_purchased_24h,
+            since: earlier.captured_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EconomyResponse {
+    pub metrics: EconomyMetrics,
+    /// None until a snapshot at least 24 hours old exists
+    pub change_24h: Option<EconomyMetricsChange>,
+}
+
+/// Subset of the economy metrics shown to players (no gold figures)
+#[derive(Debug, Clone, Serialize)]
+pub struct PublicEconomyResponse {
+    pub total_wood: i64,
+    pub total_clay: i64,
+    pub total_iron: i64,
+    pub total_crop: i64,
+    pub trade_count_24h: i64,
+    pub trade_quantity_24h: i64,
+    /// Change in daily trade count versus the day before
+    pub trade_count_change_24h: Option<i64>,
+    pub trade_quantity_change_24h: Option<i64>,
+    pub captured_at: DateTime<Utc>,
+}
+
+impl From<&EconomyResponse> for PublicEconomyResponse {
+    fn from(economy: &EconomyResponse) -> Self {
+        let metrics = &economy.metrics;
+        Self {
+            total_wood: metrics.total_wood,
+            total_clay: metrics.total_clay,
+            total_iron: metrics.total_iron,
+            total_crop: metrics.total_crop,
+            trade_count_24h: metrics.trade_count_24h,
+            trade_quantity_24h: metrics.trade_quantity_24h,
+            trade_count_change_24h: economy.change_24h.as_ref().map(|c| c.trade_count_24h),
+            trade_quantity_change_24h: economy.change_24h.as_ref().map(|c| c.trade_quantity_24h),
+            captured_at: metrics.captured_at,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -84,7 +207,7 @@ pub struct PlayerDetailResponse {
     pub alliance: Option<AdminAllianceInfoResponse>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, FromRow)]
 pub struct AdminVillageResponse {
     pub id: Uuid,
     pub name: String,
@@ -98,6 +221,87 @@ pub struct AdminVillageResponse {
     pub population: i32,
 }
 
+/// Sort order for the admin village browser
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminVillageSort {
+    #[default]
+    Newest,
+    PopulationDesc,
+    PopulationAsc,
+}
+
+impl AdminVillageSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AdminVillageSort::Newest => "newest",
+            AdminVillageSort::PopulationDesc => "population_desc",
+            AdminVillageSort::PopulationAsc => "population_asc",
+        }
+    }
+}
+
+/// Filters for the admin village browser; every filter is optional
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AdminVillageFilter {
+    /// Partial village name
+    pub name: Option<String>,
+    /// Partial owner display name or email
+    pub owner: Option<String>,
+    pub min_x: Option<i32>,
+    pub max_x: Option<i32>,
+    pub min_y: Option<i32>,
+    pub max_y: Option<i32>,
+    pub min_population: Option<i32>,
+    pub max_population: Option<i32>,
+    #[serde(default)]
+    pub sort: AdminVillageSort,
+}
+
+/// Village in the admin village browser, with its owner
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AdminVillageListItem {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub village: AdminVillageResponse,
+    pub owner_id: Uuid,
+    pub owner_name: Option<String>,
+    pub owner_email: Option<String>,
+    pub owner_banned_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Rows removed or updated while deleting a village
+#[derive(Debug, Clone, Serialize)]
+pub struct VillageCleanupCounts {
+    pub buildings: u64,
+    pub troops: u64,
+    pub troop_queue: u64,
+    pub armies_removed: u64,
+    pub armies_sent_home: u64,
+    /// Reports and trades kept for the other party, with the village cleared
+    pub battle_reports_detached: u64,
+    pub scout_reports_detached: u64,
+    pub trade_transactions_detached: u64,
+    pub heroes_rehomed: u64,
+    pub heroes_removed: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeleteVillageResponse {
+    pub village_id: Uuid,
+    pub name: String,
+    pub owner_id: Uuid,
+    pub cancelled_orders: usize,
+    /// Incoming trade deliveries sent on to the owner's other village
+    pub redirected_deliveries: u64,
+    /// Incoming trade deliveries called off because the owner has no other village
+    pub refunded_deliveries: u64,
+    /// Gold returned for cancelled buy orders and called-off deliveries
+    pub refunded_gold: i32,
+    pub removed: VillageCleanupCounts,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct AdminHeroResponse {
     pub id: Uuid,
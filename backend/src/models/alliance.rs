@@ -22,6 +22,7 @@ pub enum InvitationStatus {
     Accepted,
     Rejected,
     Expired,
+    Cancelled,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
@@ -34,6 +35,37 @@ pub enum DiplomacyStatus {
     Enemy,
 }
 
+impl DiplomacyStatus {
+    /// Map coloring for a village whose owner's alliance has this status with the viewer's
+    pub fn map_relation(self) -> MapRelation {
+        match self {
+            DiplomacyStatus::Neutral => MapRelation::Neutral,
+            DiplomacyStatus::Ally => MapRelation::Ally,
+            DiplomacyStatus::Nap => MapRelation::Nap,
+            DiplomacyStatus::Enemy => MapRelation::War,
+        }
+    }
+}
+
+/// How a village on the map relates to the viewer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MapRelation {
+    Own,
+    Ally,
+    Nap,
+    War,
+    Neutral,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "treasury_action", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum TreasuryAction {
+    Deposit,
+    Withdraw,
+}
+
 // ==================== Database Models ====================
 
 #[derive(Debug, Clone, FromRow)]
@@ -82,6 +114,30 @@ pub struct AllianceDiplomacy {
     pub updated_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Default, Serialize, FromRow)]
+pub struct AllianceTreasury {
+    pub gold: i32,
+    pub wood: i32,
+    pub clay: i32,
+    pub iron: i32,
+    pub crop: i32,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AllianceTreasuryLog {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub player_name: Option<String>,
+    pub village_id: Option<Uuid>,
+    pub action: TreasuryAction,
+    pub gold: i32,
+    pub wood: i32,
+    pub clay: i32,
+    pub iron: i32,
+    pub crop: i32,
+    pub created_at: DateTime<Utc>,
+}
+
 // ==================== Request DTOs ====================
 
 #[derive(Debug, Deserialize)]
@@ -119,6 +175,29 @@ pub struct SetDiplomacyRequest {
     pub status: DiplomacyStatus,
 }
 
+/// Deposit to / withdraw from the alliance treasury.
+/// `village_id` is the source (deposit) or destination (withdraw) of resources.
+#[derive(Debug, Deserialize)]
+pub struct TreasuryTransferRequest {
+    pub village_id: Option<Uuid>,
+    #[serde(default)]
+    pub gold: i32,
+    #[serde(default)]
+    pub wood: i32,
+    #[serde(default)]
+    pub clay: i32,
+    #[serde(default)]
+    pub iron: i32,
+    #[serde(default)]
+    pub crop: i32,
+}
+
+impl TreasuryTransferRequest {
+    pub fn has_resources(&self) -> bool {
+        self.wood > 0 || self.clay > 0 || self.iron > 0 || self.crop > 0
+    }
+}
+
 // ==================== Response DTOs ====================
 
 #[derive(Debug, Clone, Serialize)]
@@ -132,6 +211,8 @@ pub struct AllianceResponse {
     pub max_members: i32,
     pub member_count: i32,
     pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub treasury: Option<AllianceTreasury>,
 }
 
 #[derive(Debug, Clone, Serialize, FromRow)]
@@ -143,6 +224,39 @@ pub struct AllianceMemberResponse {
     pub villages_count: i32,
     pub population: i32,
     pub joined_at: DateTime<Utc>,
+    pub last_login_at: DateTime<Utc>,
+    /// Filled in from live WebSocket connections
+    #[sqlx(default)]
+    pub is_online: bool,
+}
+
+/// One member's contribution over the stats window
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AllianceMemberStats {
+    pub user_id: Uuid,
+    pub player_name: Option<String>,
+    pub role: AllianceRole,
+    /// Current population (no history is kept, so this is not windowed)
+    pub population: i64,
+    /// Villages founded or conquered within the window
+    pub villages_gained: i64,
+    pub attack_points: i64,
+    pub defense_points: i64,
+    /// Gold deposited into the treasury within the window
+    pub treasury_gold: i64,
+    /// Wood, clay, iron and crop deposited into the treasury within the window
+    pub treasury_resources: i64,
+    /// attack + defense points + everything deposited into the treasury
+    pub total_contribution: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AllianceStatsResponse {
+    pub alliance_id: Uuid,
+    pub days: i64,
+    pub since: DateTime<Utc>,
+    /// Sorted by total contribution, highest first
+    pub members: Vec<AllianceMemberStats>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -188,6 +302,7 @@ impl From<Alliance> for AllianceResponse {
             max_members: a.max_members,
             member_count: 0, // Will be populated by service
             created_at: a.created_at,
+            treasury: None,
         }
     }
 }
@@ -23,6 +23,11 @@ impl MissionType {
         matches!(self, MissionType::Raid | MissionType::Attack | MissionType::Conquer | MissionType::Scout)
     }
 
+    /// Hostile missions the defender sees coming (scouts arrive unnoticed)
+    pub fn alerts_defender(&self) -> bool {
+        matches!(self, MissionType::Raid | MissionType::Attack | MissionType::Conquer)
+    }
+
     pub fn is_support(&self) -> bool {
         matches!(self, MissionType::Support)
     }
@@ -83,7 +88,7 @@ pub struct BattleReport {
     pub id: Uuid,
     pub attacker_player_id: Uuid,
     pub defender_player_id: Option<Uuid>,
-    pub attacker_village_id: Uuid,
+    pub attacker_village_id: Option<Uuid>,
     pub defender_village_id: Option<Uuid>,
     pub mission: MissionType,
     pub attacker_troops: sqlx::types::Json<ArmyTroops>,
@@ -95,6 +100,9 @@ pub struct BattleReport {
     pub occurred_at: DateTime<Utc>,
     pub read_by_attacker: bool,
     pub read_by_defender: bool,
+    /// Reports archived by either side are kept by the retention cleanup job
+    pub attacker_archived: bool,
+    pub defender_archived: bool,
     pub created_at: DateTime<Utc>,
 }
 
@@ -104,7 +112,7 @@ pub struct ScoutReport {
     pub id: Uuid,
     pub attacker_player_id: Uuid,
     pub defender_player_id: Option<Uuid>,
-    pub attacker_village_id: Uuid,
+    pub attacker_village_id: Option<Uuid>,
     pub defender_village_id: Option<Uuid>,
     pub attacker_scouts: i32,
     pub defender_scouts: i32,
@@ -133,6 +141,11 @@ pub struct SendArmyRequest {
     pub hero_id: Option<Uuid>,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct ArchiveReportRequest {
+    pub archived: bool,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct ArmyResponse {
     pub id: Uuid,
@@ -179,7 +192,7 @@ pub struct BattleReportResponse {
     pub id: Uuid,
     pub attacker_player_id: Uuid,
     pub defender_player_id: Option<Uuid>,
-    pub attacker_village_id: Uuid,
+    pub attacker_village_id: Option<Uuid>,
     pub defender_village_id: Option<Uuid>,
     pub mission: MissionType,
     pub attacker_troops: ArmyTroops,
@@ -190,6 +203,7 @@ pub struct BattleReportResponse {
     pub winner: String,
     pub occurred_at: DateTime<Utc>,
     pub is_read: bool,
+    pub archived: bool,
 }
 
 impl BattleReport {
@@ -209,6 +223,7 @@ impl BattleReport {
             winner: self.winner.clone(),
             occurred_at: self.occurred_at,
             is_read: if is_attacker { self.read_by_attacker } else { self.read_by_defender },
+            archived: if is_attacker { self.attacker_archived } else { self.defender_archived },
         }
     }
 }
@@ -218,7 +233,7 @@ pub struct ScoutReportResponse {
     pub id: Uuid,
     pub attacker_player_id: Uuid,
     pub defender_player_id: Option<Uuid>,
-    pub attacker_village_id: Uuid,
+    pub attacker_village_id: Option<Uuid>,
     pub defender_village_id: Option<Uuid>,
     pub attacker_scouts: i32,
     pub defender_scouts: i32,
@@ -1,3 +1,5 @@
+use std::ops::RangeInclusive;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
@@ -25,6 +27,11 @@ pub enum BuildingType {
     Treasury,
     TradeOffice,
     Wall,
+    // Processing buildings (boost a resource field's production)
+    Sawmill,
+    Brickyard,
+    IronFoundry,
+    GrainMill,
     // Resource fields
     Woodcutter,
     ClayPit,
@@ -32,6 +39,14 @@ pub enum BuildingType {
     CropField,
 }
 
+/// Village layout: slots taken by village center buildings
+pub const VILLAGE_BUILDING_SLOTS: RangeInclusive<i32> = 1..=40;
+/// Village layout: slots taken by resource fields (created with the village)
+pub const RESOURCE_FIELD_SLOTS: RangeInclusive<i32> = 101..=118;
+
+/// Warehouse/granary capacity of a village before any storage building is finished
+pub const BASE_STORAGE_CAPACITY: i32 = 800;
+
 /// Prerequisite for building
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BuildingPrerequisite {
@@ -50,8 +65,41 @@ impl BuildingType {
         )
     }
 
+    pub fn is_storage(&self) -> bool {
+        matches!(self, BuildingType::Warehouse | BuildingType::Granary)
+    }
+
+    /// Whether this building type may be placed on the given slot
+    pub fn fits_slot(&self, slot: i32) -> bool {
+        if self.is_resource_field() {
+            RESOURCE_FIELD_SLOTS.contains(&slot)
+        } else {
+            VILLAGE_BUILDING_SLOTS.contains(&slot)
+        }
+    }
+
+    /// Resource field whose production this processing building boosts
+    pub fn boosted_resource_field(&self) -> Option<BuildingType> {
+        match self {
+            BuildingType::Sawmill => Some(BuildingType::Woodcutter),
+            BuildingType::Brickyard => Some(BuildingType::ClayPit),
+            BuildingType::IronFoundry => Some(BuildingType::IronMine),
+            BuildingType::GrainMill => Some(BuildingType::CropField),
+            _ => None,
+        }
+    }
+
+    /// Production bonus (percent) of a processing building: 5% per level, capped at max level
+    pub fn production_bonus_percent(&self, level: i32) -> i32 {
+        if self.boosted_resource_field().is_none() {
+            return 0;
+        }
+        level.clamp(0, self.max_level()) * 5
+    }
+
     pub fn max_level(&self) -> i32 {
         match self {
+            _ if self.boosted_resource_field().is_some() => 5,
             BuildingType::Wall => 20,
             BuildingType::Palace | BuildingType::Residence => 20,
             _ if self.is_resource_field() => 20,
@@ -126,6 +174,23 @@ impl BuildingType {
                 BuildingPrerequisite { building_type: BuildingType::MainBuilding, min_level: 10 },
             ],
 
+            // Processing buildings
+            BuildingType::Sawmill => vec![
+                BuildingPrerequisite { building_type: BuildingType::Woodcutter, min_level: 10 },
+                BuildingPrerequisite { building_type: BuildingType::MainBuilding, min_level: 5 },
+            ],
+            BuildingType::Brickyard => vec![
+                BuildingPrerequisite { building_type: BuildingType::ClayPit, min_level: 10 },
+                BuildingPrerequisite { building_type: BuildingType::MainBuilding, min_level: 5 },
+            ],
+            BuildingType::IronFoundry => vec![
+                BuildingPrerequisite { building_type: BuildingType::IronMine, min_level: 10 },
+                BuildingPrerequisite { building_type: BuildingType::MainBuilding, min_level: 5 },
+            ],
+            BuildingType::GrainMill => vec![
+                BuildingPrerequisite { building_type: BuildingType::CropField, min_level: 5 },
+            ],
+
             // Resource fields - no prerequisites
             BuildingType::Woodcutter => vec![],
             BuildingType::ClayPit => vec![],
@@ -171,6 +236,12 @@ impl BuildingType {
             BuildingType::Residence => 1,
             BuildingType::Palace => 1,
             BuildingType::Treasury => 4,
+
+            // Processing buildings
+            BuildingType::Sawmill => 4,
+            BuildingType::Brickyard => 3,
+            BuildingType::IronFoundry => 4,
+            BuildingType::GrainMill => 3,
         };
 
         // Population increases slightly with level
@@ -186,6 +257,7 @@ pub struct Building {
     pub slot: i32,
     pub level: i32,
     pub is_upgrading: bool,
+    pub upgrade_started_at: Option<DateTime<Utc>>,
     pub upgrade_ends_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -221,6 +293,29 @@ impl From<Building> for BuildingResponse {
     }
 }
 
+/// Next level of a building next to its current level, without committing to the upgrade
+#[derive(Debug, Clone, Serialize)]
+pub struct UpgradePreviewResponse {
+    pub building_type: BuildingType,
+    pub current_level: i32,
+    pub next_level: i32,
+    /// Cost of the next level; time already reduced by the Main Building
+    pub cost: BuildingCost,
+    /// Resource fields only
+    pub current_production_per_hour: Option<i32>,
+    pub next_production_per_hour: Option<i32>,
+    /// Warehouse and Granary only
+    pub current_storage_capacity: Option<i32>,
+    pub next_storage_capacity: Option<i32>,
+    /// Processing buildings only (Sawmill, Brickyard, ...)
+    pub current_bonus_percent: Option<i32>,
+    pub next_bonus_percent: Option<i32>,
+    pub population_delta: i32,
+    pub affordable: bool,
+    pub prerequisites_met: bool,
+    pub is_upgrading: bool,
+}
+
 // Building costs and production rates
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BuildingCost {
@@ -276,6 +371,35 @@ impl BuildingType {
                 crop: 70,
                 time_seconds: 400,
             },
+            // Processing buildings
+            BuildingType::Sawmill => BuildingCost {
+                wood: 520,
+                clay: 380,
+                iron: 290,
+                crop: 90,
+                time_seconds: 900,
+            },
+            BuildingType::Brickyard => BuildingCost {
+                wood: 440,
+                clay: 480,
+                iron: 320,
+                crop: 50,
+                time_seconds: 840,
+            },
+            BuildingType::IronFoundry => BuildingCost {
+                wood: 200,
+                clay: 450,
+                iron: 510,
+                crop: 120,
+                time_seconds: 1080,
+            },
+            BuildingType::GrainMill => BuildingCost {
+                wood: 500,
+                clay: 440,
+                iron: 380,
+                crop: 1240,
+                time_seconds: 720,
+            },
             // Resource fields
             BuildingType::Woodcutter => BuildingCost {
                 wood: 40,
@@ -340,14 +464,14 @@ impl BuildingType {
     /// Storage capacity for Warehouse/Granary at given level
     /// Based on Travian formula: base * 1.2^level
     pub fn storage_capacity(&self, level: i32) -> i32 {
-        if level == 0 {
-            return 800; // Base capacity
-        }
         let base = match self {
             BuildingType::Warehouse => 400,
             BuildingType::Granary => 400,
             _ => return 0,
         };
+        if level == 0 {
+            return BASE_STORAGE_CAPACITY;
+        }
         (base as f64 * (1.2_f64).powi(level)) as i32
     }
 }
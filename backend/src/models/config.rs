@@ -0,0 +1,30 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Trade order limits enforced when creating orders
+#[derive(Debug, Serialize)]
+pub struct TradeLimits {
+    pub min_quantity: i32,
+    pub max_quantity: i32,
+    pub min_price: i32,
+    pub max_price: i32,
+    pub max_open_orders: i64,
+    pub max_expiry_hours: i32,
+    pub max_order_value: i64,
+    /// Expiry applied to orders created without one
+    pub default_expiry_hours: i32,
+    /// Allowed deviation from the last trade price in percent (0 = disabled)
+    pub price_band_percent: i32,
+}
+
+/// Public game constants and the server clock
+#[derive(Debug, Serialize)]
+pub struct GameConfigResponse {
+    pub server_time: DateTime<Utc>,
+    pub trade: TradeLimits,
+    pub alliance_alert_radius: f64,
+    pub nap_allows_raids: bool,
+    pub hero_regen_on_adventure: bool,
+    pub battle_report_retention_days: i64,
+    pub beginner_protection_hours: i64,
+}
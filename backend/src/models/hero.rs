@@ -46,6 +46,13 @@ pub enum ItemSlot {
     Consumable,
 }
 
+impl ItemSlot {
+    /// Check if items of this slot can be equipped (consumables are used, not worn)
+    pub fn is_equippable(&self) -> bool {
+        !matches!(self, ItemSlot::Consumable)
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq)]
 #[sqlx(type_name = "adventure_difficulty", rename_all = "snake_case")]
 #[serde(rename_all = "snake_case")]
@@ -192,6 +199,19 @@ impl Hero {
         self.def_bonus as f64 * 0.2
     }
 
+    /// Production bonus percentage granted to the home village (0.2% per point, like off/def)
+    pub fn production_bonus_percent(&self) -> i32 {
+        (self.resources_bonus as f64 * 0.2) as i32
+    }
+
+    /// Heroes away from home (adventure, travelling army, battle) can't change village
+    pub fn can_be_reassigned(&self) -> bool {
+        !matches!(
+            self.status,
+            HeroStatus::Moving | HeroStatus::InAdventure | HeroStatus::InBattle
+        )
+    }
+
     /// Check if hero is available for actions
     pub fn is_available(&self) -> bool {
         self.status == HeroStatus::Idle && self.health > 0
@@ -246,6 +266,37 @@ pub struct ItemDefinition {
     pub created_at: DateTime<Utc>,
 }
 
+/// Combined stat bonuses from all items a hero has equipped
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EquipmentBonuses {
+    pub attack: i32,
+    pub defense: i32,
+    pub speed: Decimal,
+    pub health_regen: Decimal,
+    pub experience: i32, // % bonus
+    pub resource: i32,   // % bonus
+    pub carry: i32,
+}
+
+impl EquipmentBonuses {
+    /// Sum the bonuses of equipped item definitions
+    pub fn from_items<'a>(items: impl IntoIterator<Item = &'a ItemDefinition>) -> Self {
+        let mut bonuses = Self::default();
+
+        for item in items {
+            bonuses.attack += item.attack_bonus;
+            bonuses.defense += item.defense_bonus;
+            bonuses.speed += item.speed_bonus;
+            bonuses.health_regen += item.health_regen_bonus;
+            bonuses.experience += item.experience_bonus;
+            bonuses.resource += item.resource_bonus;
+            bonuses.carry += item.carry_bonus;
+        }
+
+        bonuses
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct HeroItem {
     pub id: Uuid,
@@ -411,6 +462,28 @@ pub struct AssignAttributesRequest {
     pub resources_bonus: i32,
 }
 
+/// Hero stationed in a village and the local bonuses it grants
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssignedHeroResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub level: i32,
+    pub production_bonus_percent: i32,
+    pub def_bonus_percent: f64,
+}
+
+impl From<&Hero> for AssignedHeroResponse {
+    fn from(h: &Hero) -> Self {
+        Self {
+            id: h.id,
+            name: h.name.clone(),
+            level: h.level,
+            production_bonus_percent: h.production_bonus_percent(),
+            def_bonus_percent: h.def_bonus_percent(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct ChangeHomeVillageRequest {
     pub village_id: Uuid,
@@ -520,6 +593,12 @@ pub struct HeroResponse {
     // Active passive bonuses (from hero definition)
     pub active_bonuses: Vec<PassiveBonus>,
 
+    // Equipped loadout (only included in hero detail)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub equipped: Option<EquippedItemsResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub equipment_bonuses: Option<EquipmentBonuses>,
+
     // Timestamps
     pub died_at: Option<DateTime<Utc>>,
     pub revive_at: Option<DateTime<Utc>>,
@@ -561,10 +640,18 @@ impl HeroResponse {
             def_bonus_percent: h.def_bonus_percent(),
             base_speed: h.base_speed,
             active_bonuses,
+            equipped: None,
+            equipment_bonuses: None,
             died_at: h.died_at,
             revive_at: h.revive_at,
         }
     }
+
+    pub fn with_equipment(mut self, equipped: EquippedItemsResponse, bonuses: EquipmentBonuses) -> Self {
+        self.equipped = Some(equipped);
+        self.equipment_bonuses = Some(bonuses);
+        self
+    }
 }
 
 impl From<Hero> for HeroResponse {
@@ -643,7 +730,7 @@ pub struct HeroItemResponse {
     pub obtained_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct EquippedItemsResponse {
     pub helmet: Option<HeroItemResponse>,
     pub weapon: Option<HeroItemResponse>,
@@ -655,6 +742,23 @@ pub struct EquippedItemsResponse {
     pub bandage: Option<HeroItemResponse>,
 }
 
+impl EquippedItemsResponse {
+    /// Put an item into its equipment slot (consumables have no slot)
+    pub fn place(&mut self, slot: ItemSlot, item: HeroItemResponse) {
+        match slot {
+            ItemSlot::Helmet => self.helmet = Some(item),
+            ItemSlot::Weapon => self.weapon = Some(item),
+            ItemSlot::ArmorLeft => self.armor_left = Some(item),
+            ItemSlot::ArmorRight => self.armor_right = Some(item),
+            ItemSlot::Boots => self.boots = Some(item),
+            ItemSlot::Horse => self.horse = Some(item),
+            ItemSlot::Bag => self.bag = Some(item),
+            ItemSlot::Bandage => self.bandage = Some(item),
+            ItemSlot::Consumable => {}
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct InventoryResponse {
     pub equipped: EquippedItemsResponse,
@@ -11,6 +11,8 @@ use uuid::Uuid;
 pub enum MessageType {
     Private,
     Alliance,
+    /// Notice from the game to one player, with no sender
+    System,
 }
 
 // ==================== Database Models ====================
@@ -19,15 +21,13 @@ pub enum MessageType {
 pub struct Message {
     pub id: Uuid,
     pub message_type: MessageType,
-    pub sender_id: Uuid,
+    /// None for system messages
+    pub sender_id: Option<Uuid>,
     pub recipient_id: Option<Uuid>,
     pub alliance_id: Option<Uuid>,
-    pub conversation_id: Option<Uuid>,
     pub subject: String,
     pub body: String,
     pub is_read: bool,
-    pub sender_deleted: bool,
-    pub recipient_deleted: bool,
     pub created_at: DateTime<Utc>,
 }
 
@@ -43,12 +43,19 @@ pub struct MessageRead {
 pub struct Conversation {
     pub id: Uuid,
     pub user_1_id: Uuid,
-    pub user_2_id: Uuid,
-    pub last_message_id: Option<Uuid>,
-    pub last_message_at: DateTime<Utc>,
-    pub user_1_deleted: bool,
-    pub user_2_deleted: bool,
-    pub created_at: DateTime<Utc>,
+    pub user_1_muted: bool,
+    pub user_2_muted: bool,
+}
+
+impl Conversation {
+    /// Whether the given participant has muted this conversation
+    pub fn is_muted_for(&self, user_id: Uuid) -> bool {
+        if self.user_1_id == user_id {
+            self.user_1_muted
+        } else {
+            self.user_2_muted
+        }
+    }
 }
 
 // ==================== Request DTOs ====================
@@ -71,13 +78,19 @@ pub struct ReplyMessageRequest {
     pub body: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct DeleteMessagesRequest {
+    pub message_ids: Vec<Uuid>,
+}
+
 // ==================== Response DTOs ====================
 
 #[derive(Debug, Clone, Serialize, FromRow)]
 pub struct MessageResponse {
     pub id: Uuid,
     pub message_type: MessageType,
-    pub sender_id: Uuid,
+    /// None for system messages
+    pub sender_id: Option<Uuid>,
     pub sender_name: String,
     pub recipient_id: Option<Uuid>,
     pub recipient_name: Option<String>,
@@ -87,6 +100,48 @@ pub struct MessageResponse {
     pub body: String,
     pub is_read: bool,
     pub created_at: DateTime<Utc>,
+    /// Coordinates and players mentioned in the body, resolved when the message is sent
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub entities: Vec<MessageEntity>,
+}
+
+/// Something a client can render as a link inside a message body.
+/// `text` is the exact snippet from the body, which is stored unchanged.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MessageEntity {
+    /// `(x|y)`; village fields are set when the tile is occupied
+    Coordinates {
+        text: String,
+        x: i32,
+        y: i32,
+        village_id: Option<Uuid>,
+        village_name: Option<String>,
+        player_name: Option<String>,
+    },
+    /// `@name` matching exactly one player's display name
+    Player {
+        text: String,
+        user_id: Uuid,
+        name: String,
+    },
+}
+
+/// Alliance message detail; read receipts are only filled in for leaders and officers
+#[derive(Debug, Clone, Serialize)]
+pub struct AllianceMessageResponse {
+    #[serde(flatten)]
+    pub message: MessageResponse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_receipts: Option<MessageReadReceipts>,
+}
+
+/// How many current alliance members have opened a message
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageReadReceipts {
+    pub read_count: i64,
+    pub member_count: i64,
 }
 
 #[derive(Debug, Clone, Serialize, FromRow)]
@@ -98,12 +153,14 @@ pub struct ConversationResponse {
     pub last_message_preview: Option<String>,
     pub last_message_at: DateTime<Utc>,
     pub unread_count: i64,
+    pub is_muted: bool,
 }
 
 #[derive(Debug, Clone, Serialize, FromRow)]
 pub struct MessageListItem {
     pub id: Uuid,
-    pub sender_id: Uuid,
+    /// None for system messages
+    pub sender_id: Option<Uuid>,
     pub sender_name: String,
     pub subject: String,
     pub is_read: bool,
@@ -135,6 +192,7 @@ impl From<Message> for MessageResponse {
             body: m.body,
             is_read: m.is_read,
             created_at: m.created_at,
+            entities: Vec::new(),
         }
     }
 }
@@ -2,8 +2,10 @@ pub mod admin;
 pub mod alliance;
 pub mod army;
 pub mod building;
+pub mod config;
 pub mod hero;
 pub mod message;
+pub mod pagination;
 pub mod ranking;
 pub mod shop;
 pub mod trade;
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+/// Standard envelope for paginated list endpoints
+#[derive(Debug, Clone, Serialize)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub page: i32,
+    pub limit: i32,
+    pub has_more: bool,
+}
+
+impl<T> Paginated<T> {
+    pub fn new(items: Vec<T>, total: i64, page: i32, limit: i32) -> Self {
+        Self {
+            has_more: (page as i64) * (limit as i64) < total,
+            items,
+            total,
+            page,
+            limit,
+        }
+    }
+}
+
+/// `?page=&limit=` for paginated endpoints (pages start at 1)
+#[derive(Debug, Clone, Deserialize)]
+pub struct PageQuery {
+    #[serde(default = "default_page")]
+    pub page: i32,
+    #[serde(default = "default_page_limit")]
+    pub limit: i32,
+}
+
+fn default_page() -> i32 {
+    1
+}
+
+fn default_page_limit() -> i32 {
+    20
+}
+
+impl PageQuery {
+    /// Page and limit clamped to sane values, plus the matching row offset
+    pub fn resolve(&self, max_limit: i32) -> (i32, i32, i32) {
+        let page = self.page.max(1);
+        let limit = self.limit.clamp(1, max_limit);
+        (page, limit, (page - 1) * limit)
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
@@ -59,6 +60,34 @@ pub struct AllianceRanking {
     pub total_population: i64,
 }
 
+// ==================== Player Profile ====================
+
+/// Publicly visible info about a player
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerProfile {
+    pub user_id: Uuid,
+    pub display_name: Option<String>,
+    pub alliance_id: Option<Uuid>,
+    pub alliance_tag: Option<String>,
+    pub population: i64,
+    pub village_count: i64,
+    pub population_rank: Option<i64>,
+    pub attack_points: i64,
+    pub defense_points: i64,
+    pub villages: Vec<PlayerProfileVillage>,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerProfileVillage {
+    pub id: Uuid,
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub population: i32,
+    pub is_capital: bool,
+}
+
 // ==================== Response Wrappers ====================
 
 #[derive(Debug, Clone, Serialize)]
@@ -77,6 +106,8 @@ pub struct RankingQuery {
     pub page: i64,
     #[serde(default = "default_per_page")]
     pub per_page: i64,
+    /// Filter by player, hero or alliance name (partial match)
+    pub q: Option<String>,
 }
 
 fn default_page() -> i64 {
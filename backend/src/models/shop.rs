@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
+use crate::models::troop::TroopType;
+
 // ==================== Enums ====================
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
@@ -24,6 +26,8 @@ pub enum TransactionType {
     GoldSpend,
     GoldRefund,
     GoldGift,
+    /// Gold deposited to or withdrawn from an alliance treasury
+    AllianceTreasury,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
@@ -45,6 +49,23 @@ pub enum GoldFeature {
     Ointment,
     PlusSubscription,
     HeroSlot,
+    InstantTrain,
+}
+
+impl GoldFeature {
+    pub fn all() -> Vec<GoldFeature> {
+        vec![
+            GoldFeature::FinishNow,
+            GoldFeature::NpcMerchant,
+            GoldFeature::ProductionBonus,
+            GoldFeature::BookOfWisdom,
+            GoldFeature::Artwork,
+            GoldFeature::Ointment,
+            GoldFeature::PlusSubscription,
+            GoldFeature::HeroSlot,
+            GoldFeature::InstantTrain,
+        ]
+    }
 }
 
 // ==================== Database Models ====================
@@ -72,11 +93,7 @@ pub struct Transaction {
     pub amount_cents: Option<i32>,
     pub currency: Option<String>,
     pub stripe_session_id: Option<String>,
-    pub stripe_payment_intent_id: Option<String>,
-    pub stripe_subscription_id: Option<String>,
-    pub gold_package_id: Option<Uuid>,
     pub description: Option<String>,
-    pub metadata: Option<serde_json::Value>,
     pub created_at: DateTime<Utc>,
     pub completed_at: Option<DateTime<Utc>>,
 }
@@ -93,6 +110,7 @@ pub struct UserSubscription {
     pub auto_renew: bool,
     pub is_active: bool,
     pub cancelled_at: Option<DateTime<Utc>>,
+    pub expiry_reminder_sent_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -147,6 +165,18 @@ pub struct UseFinishNowRequest {
     pub target_id: Uuid,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct UseFinishAllRequest {
+    pub village_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GiftGoldRequest {
+    pub recipient_id: Uuid,
+    pub amount: i32,
+    pub message: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct UseNpcMerchantRequest {
     pub village_id: Uuid,
@@ -167,6 +197,13 @@ pub struct UseBookOfWisdomRequest {
     pub village_id: Uuid,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct UseInstantTrainRequest {
+    pub village_id: Uuid,
+    pub troop_type: TroopType,
+    pub count: i32,
+}
+
 // ==================== Response DTOs ====================
 
 #[derive(Debug, Clone, Serialize)]
@@ -188,8 +225,12 @@ pub struct TransactionResponse {
     pub transaction_type: TransactionType,
     pub status: TransactionStatus,
     pub gold_amount: i32,
+    pub amount_cents: Option<i32>,
+    pub currency: Option<String>,
+    pub stripe_session_id: Option<String>,
     pub description: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -199,6 +240,63 @@ pub struct FeatureCostResponse {
     pub description: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct GoldUsageFeatureSummary {
+    pub feature: GoldFeature,
+    pub total_spent: i64,
+    pub usage_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GoldUsageSummaryResponse {
+    pub days: i32,
+    pub total_spent: i64,
+    pub usage_count: i64,
+    pub by_feature: Vec<GoldUsageFeatureSummary>,
+    pub history: Vec<GoldUsage>,
+}
+
+/// How the price of a gold feature is determined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeaturePricing {
+    /// Flat price per use
+    Fixed,
+    /// Price per day of effect
+    PerDay,
+    /// Price per started 5 minutes of remaining time
+    PerFiveMinutes,
+    /// Price of the user's next hero slot
+    PerSlot,
+}
+
+/// A gold feature as listed in the shop menu
+#[derive(Debug, Clone, Serialize)]
+pub struct GoldFeatureListing {
+    pub feature: GoldFeature,
+    pub description: Option<String>,
+    pub pricing: FeaturePricing,
+    pub gold_cost: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FinishNowPreviewResponse {
+    pub target_type: String,
+    pub target_id: Uuid,
+    pub remaining_seconds: i32,
+    pub gold_cost: i32,
+    pub gold_balance: i32,
+    pub can_afford: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GiftGoldResponse {
+    pub recipient_id: Uuid,
+    pub recipient_name: String,
+    pub amount: i32,
+    pub new_balance: i32,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct UseFeatureResponse {
     pub success: bool,
@@ -207,6 +305,16 @@ pub struct UseFeatureResponse {
     pub message: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct FinishQueueResponse {
+    pub success: bool,
+    pub gold_spent: i32,
+    pub new_balance: i32,
+    /// Training batches completed by this call
+    pub batches_finished: i32,
+    pub message: String,
+}
+
 impl From<Transaction> for TransactionResponse {
     fn from(t: Transaction) -> Self {
         Self {
@@ -214,8 +322,12 @@ impl From<Transaction> for TransactionResponse {
             transaction_type: t.transaction_type,
             status: t.status,
             gold_amount: t.gold_amount,
+            amount_cents: t.amount_cents,
+            currency: t.currency,
+            stripe_session_id: t.stripe_session_id,
             description: t.description,
             created_at: t.created_at,
+            completed_at: t.completed_at,
         }
     }
 }
@@ -34,6 +34,26 @@ pub enum TradeResourceType {
     Crop,
 }
 
+/// How a new order interacts with the book when it is placed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderExecution {
+    /// Match whatever crosses right now, then rest the remainder on the book
+    /// until accepted, cancelled or expired
+    #[default]
+    Resting,
+    /// Match whatever crosses right now and cancel the rest
+    ImmediateOrCancel,
+    /// Match the full quantity right now or do nothing
+    FillOrKill,
+}
+
+impl OrderExecution {
+    pub fn is_immediate(self) -> bool {
+        self != OrderExecution::Resting
+    }
+}
+
 impl TradeResourceType {
     pub fn all() -> Vec<TradeResourceType> {
         vec![
@@ -128,6 +148,11 @@ pub struct TradeOrder {
     pub updated_at: DateTime<Utc>,
     pub filled_at: Option<DateTime<Utc>>,
     pub cancelled_at: Option<DateTime<Utc>>,
+    /// Only members of the owner's alliance can see and fill this order
+    pub alliance_only: bool,
+    /// Where the order was placed from; a wash trade signal, never sent to clients
+    #[serde(skip)]
+    pub client_ip: Option<String>,
 }
 
 impl TradeOrder {
@@ -167,10 +192,27 @@ impl TradeOrder {
     }
 }
 
+/// A trade order to insert
+#[derive(Debug, Clone)]
+pub struct NewOrder<'a> {
+    pub user_id: Uuid,
+    pub village_id: Uuid,
+    pub order_type: TradeOrderType,
+    pub resource_type: TradeResourceType,
+    pub quantity: i32,
+    pub price_per_unit: i32,
+    pub expires_in_hours: Option<i32>,
+    pub alliance_only: bool,
+    pub client_ip: Option<&'a str>,
+    /// Records the acceptor's side of an accepted order; never listed as a player's order
+    pub is_taker_fill: bool,
+}
+
 /// Trade order with additional details for display
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct TradeOrderWithDetails {
     #[serde(flatten)]
+    #[sqlx(flatten)]
     pub order: TradeOrder,
     pub village_name: String,
     pub village_x: i32,
@@ -182,6 +224,26 @@ pub struct TradeOrderWithDetails {
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct TradeTransaction {
     pub id: Uuid,
+    /// The buyer's order. Trades recorded before takers got their own order rows
+    /// carry the resting order's id on both sides.
+    /// Orders and villages are None once their village has been deleted.
+    pub buy_order_id: Option<Uuid>,
+    pub sell_order_id: Option<Uuid>,
+    pub buyer_id: Uuid,
+    pub seller_id: Uuid,
+    pub buyer_village_id: Option<Uuid>,
+    pub seller_village_id: Option<Uuid>,
+    pub resource_type: TradeResourceType,
+    pub quantity: i32,
+    pub price_per_unit: i32,
+    pub total_gold: i64,
+    pub fee_gold: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A completed trade to record
+#[derive(Debug, Clone)]
+pub struct NewTransaction {
     pub buy_order_id: Uuid,
     pub sell_order_id: Uuid,
     pub buyer_id: Uuid,
@@ -191,8 +253,37 @@ pub struct TradeTransaction {
     pub resource_type: TradeResourceType,
     pub quantity: i32,
     pub price_per_unit: i32,
-    pub total_gold: i32,
-    pub created_at: DateTime<Utc>,
+    pub fee_gold: i32,
+}
+
+/// Resources from a trade travelling to the buyer's village with the seller's merchants
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ResourceDelivery {
+    pub id: Uuid,
+    pub trade_transaction_id: Uuid,
+    /// None once the seller's village has been deleted; the delivery still arrives
+    pub from_village_id: Option<Uuid>,
+    /// Only cleared on finished deliveries; one still on the road always has a destination
+    pub to_village_id: Uuid,
+    pub recipient_id: Uuid,
+    pub resource_type: TradeResourceType,
+    pub quantity: i32,
+    pub departed_at: DateTime<Utc>,
+    pub arrives_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// Merchants of a village: how many the Market provides and how many are still free
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MerchantAvailability {
+    pub market_level: i32,
+    pub total: i32,
+    /// Reserved by open sell orders or out on a delivery
+    pub busy: i32,
+    pub free: i32,
+    pub capacity_per_merchant: i32,
+    /// Resources the free merchants can carry in one shipment
+    pub free_capacity: i32,
 }
 
 /// Resource lock record (escrow)
@@ -221,6 +312,16 @@ impl ResourceLock {
     }
 }
 
+/// Heuristic for keeping likely wash trades (a player trading with their own
+/// second account) out of the public last price and volume
+#[derive(Debug, Clone, Copy)]
+pub struct WashTradeFilter {
+    /// Ignore trades where buyer and seller traded from the same IP
+    pub exclude_same_ip: bool,
+    /// Ignore trades between two players beyond this many in 24 hours (0 disables)
+    pub max_pair_trades_per_day: i64,
+}
+
 /// Market summary for a resource type
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MarketSummary {
@@ -233,6 +334,36 @@ pub struct MarketSummary {
     pub trade_count_24h: i32,
 }
 
+/// Raw price bucket as aggregated by the database; prices are None when nothing traded
+#[derive(Debug, Clone, FromRow)]
+pub struct PriceBucket {
+    pub bucket_start: DateTime<Utc>,
+    pub open: Option<i32>,
+    pub high: Option<i32>,
+    pub low: Option<i32>,
+    pub close: Option<i32>,
+    pub volume: i64,
+}
+
+/// OHLC candle for the market chart
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceCandle {
+    pub bucket_start: DateTime<Utc>,
+    pub open: i32,
+    pub high: i32,
+    pub low: i32,
+    pub close: i32,
+    pub volume: i64,
+}
+
+/// Open quantity resting at one price
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderBookLevel {
+    pub price_per_unit: i32,
+    pub quantity: i64,
+    pub order_count: i64,
+}
+
 // ==================== Request DTOs ====================
 
 #[derive(Debug, Clone, Deserialize)]
@@ -242,7 +373,26 @@ pub struct CreateOrderRequest {
     pub resource_type: TradeResourceType,
     pub quantity: i32,
     pub price_per_unit: i32,
-    pub expires_in_hours: Option<i32>, // None = no expiry
+    pub expires_in_hours: Option<i32>, // None = server default expiry
+    #[serde(default)]
+    pub alliance_only: bool,
+    #[serde(default)]
+    pub execution: OrderExecution,
+}
+
+impl CreateOrderRequest {
+    /// Total gold value of the order
+    pub fn total_cost(&self) -> i64 {
+        (self.quantity as i64) * (self.price_per_unit as i64)
+    }
+}
+
+/// New terms for an open order; omitted fields stay unchanged
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpdateOrderRequest {
+    pub price_per_unit: Option<i32>,
+    /// Total quantity including what has already been filled
+    pub quantity: Option<i32>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -257,10 +407,45 @@ pub struct GetOrdersQuery {
     pub order_type: Option<TradeOrderType>,
     pub min_price: Option<i32>,
     pub max_price: Option<i32>,
+    pub village_id: Option<Uuid>,
+    pub exclude_user_id: Option<Uuid>,
     pub page: Option<i32>,
     pub limit: Option<i32>,
 }
 
+/// What a taker would get by hitting the book right now
+#[derive(Debug, Clone, Deserialize)]
+pub struct TradeQuoteQuery {
+    pub resource: TradeResourceType,
+    /// The taker's side: `buy` fills against sell orders and vice versa
+    pub side: TradeOrderType,
+    pub quantity: i32,
+}
+
+/// Price history of a resource in fixed-size buckets
+#[derive(Debug, Clone, Deserialize)]
+pub struct PriceCandlesQuery {
+    pub resource: TradeResourceType,
+    /// Bucket size, defaults to an hour
+    pub interval_minutes: Option<i32>,
+    /// Number of buckets up to now, defaults to 100
+    pub limit: Option<i32>,
+}
+
+/// Aggregated order book of a resource
+#[derive(Debug, Clone, Deserialize)]
+pub struct OrderBookQuery {
+    pub resource: TradeResourceType,
+    /// Price levels per side, defaults to 20
+    pub levels: Option<i32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CancelAllOrdersQuery {
+    /// Only cancel orders for this resource
+    pub resource_type: Option<TradeResourceType>,
+}
+
 // ==================== Response DTOs ====================
 
 #[derive(Debug, Clone, Serialize)]
@@ -268,14 +453,30 @@ pub struct CreateOrderResponse {
     pub order: TradeOrder,
     pub locked_resources: Option<Resources>, // for sell orders
     pub locked_gold: Option<i32>,            // for buy orders
+    /// Trades executed against the book when the order was placed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fills: Vec<TradeTransaction>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateOrderResponse {
+    pub order: TradeOrder,
+    pub locked_resources: Option<Resources>, // for sell orders, after the update
+    pub locked_gold: Option<i32>,            // for buy orders, after the update
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct AcceptOrderResponse {
     pub transaction: TradeTransaction,
     pub order_status: TradeOrderStatus,
+    /// On their way to the buyer's village, see `arrives_at`
     pub resources_received: Option<Resources>,
+    /// The seller's proceeds, after the market fee
     pub gold_received: Option<i32>,
+    /// Market fee taken from the seller's proceeds
+    pub fee_paid: i32,
+    /// When the merchants reach the buyer's village
+    pub arrives_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -285,6 +486,60 @@ pub struct CancelOrderResponse {
     pub refunded_gold: Option<i32>,
 }
 
+/// An order bulk cancel could not close
+#[derive(Debug, Clone, Serialize)]
+pub struct CancelOrderFailure {
+    pub order_id: Uuid,
+    pub error: String,
+}
+
+/// Result of cancelling all of a user's open orders
+#[derive(Debug, Clone, Serialize)]
+pub struct CancelAllOrdersResponse {
+    pub cancelled: Vec<TradeOrder>,
+    pub refunded_resources: Resources,
+    pub refunded_gold: i64,
+    /// Orders left open because their cancel failed; the rest still went through
+    pub failed: Vec<CancelOrderFailure>,
+}
+
+/// Simulated fill of a taker order against the current book; nothing is executed
+#[derive(Debug, Clone, Serialize)]
+pub struct TradeQuoteResponse {
+    pub resource_type: TradeResourceType,
+    pub side: TradeOrderType,
+    pub quantity_requested: i32,
+    pub quantity_filled: i32,
+    pub quantity_unfilled: i32,
+    pub total_gold: i64,
+    /// None when nothing would fill
+    pub average_price: Option<f64>,
+    pub best_price: Option<i32>,
+    pub worst_price: Option<i32>,
+    pub orders_matched: i32,
+}
+
+/// Most recent candles, oldest first. Buckets without trades repeat the previous
+/// close with zero volume; buckets before the first trade ever are left out.
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceCandlesResponse {
+    pub resource_type: TradeResourceType,
+    pub interval_minutes: i32,
+    pub candles: Vec<PriceCandle>,
+}
+
+/// Public order book depth; alliance-only orders are left out
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderBookResponse {
+    pub resource_type: TradeResourceType,
+    /// Buy levels, highest price first
+    pub bids: Vec<OrderBookLevel>,
+    /// Sell levels, lowest price first
+    pub asks: Vec<OrderBookLevel>,
+    /// Best ask minus best bid
+    pub spread: Option<i32>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct MarketSummaryResponse {
     pub summaries: Vec<MarketSummary>,
@@ -310,3 +565,14 @@ pub struct TradeHistoryResponse {
 pub struct MyOrdersResponse {
     pub orders: Vec<TradeOrder>,
 }
+
+/// Number of the user's orders in each status
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct OrderStatusCounts {
+    pub open: i64,
+    pub partially_filled: i64,
+    pub filled: i64,
+    pub cancelled: i64,
+    pub expired: i64,
+    pub total: i64,
+}
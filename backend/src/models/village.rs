@@ -3,6 +3,9 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
+use crate::models::hero::AssignedHeroResponse;
+use crate::models::trade::Resources;
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Village {
     pub id: Uuid,
@@ -27,6 +30,8 @@ pub struct Village {
     pub resources_updated_at: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Owner's private note, never shown to other players
+    pub note: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,6 +46,8 @@ pub struct CreateVillage {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpdateVillage {
     pub name: Option<String>,
+    /// Some("") clears the note
+    pub note: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,6 +58,11 @@ pub struct ProductionRates {
     pub crop_per_hour: i32,
     pub crop_consumption: i32,
     pub net_crop_per_hour: i32,
+    /// Percent bonus applied by processing buildings (Sawmill, Brickyard, ...), a stationed hero and gold boosts
+    pub wood_bonus_percent: i32,
+    pub clay_bonus_percent: i32,
+    pub iron_bonus_percent: i32,
+    pub crop_bonus_percent: i32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -71,7 +83,36 @@ pub struct VillageResponse {
     pub loyalty: i32,
     pub created_at: DateTime<Utc>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub production: Option<ProductionRates>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub army_slots: Option<ArmySlots>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_accounting: Option<ResourceAccounting>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assigned_hero: Option<AssignedHeroResponse>,
+    /// End of the owner's beginner protection, if still active
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protection_until: Option<DateTime<Utc>>,
+}
+
+/// Breakdown of a village's resources into committed and free amounts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceAccounting {
+    /// Already paid for building upgrades still in progress
+    pub in_construction: Resources,
+    /// Still in the village balance but locked by open sell orders
+    pub locked_by_trade: Resources,
+    /// Balance minus trade locks, i.e. what can be spent right now
+    pub available: Resources,
+}
+
+/// Outgoing army capacity of a village (limited by Rally Point level)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArmySlots {
+    pub used: i32,
+    pub total: i32,
 }
 
 impl From<Village> for VillageResponse {
@@ -92,7 +133,12 @@ impl From<Village> for VillageResponse {
             culture_points: v.culture_points,
             loyalty: v.loyalty,
             created_at: v.created_at,
+            note: v.note,
             production: None,
+            army_slots: None,
+            resource_accounting: None,
+            assigned_hero: None,
+            protection_until: None,
         }
     }
 }
@@ -102,6 +148,78 @@ impl VillageResponse {
         self.production = Some(production);
         self
     }
+
+    pub fn with_army_slots(mut self, army_slots: ArmySlots) -> Self {
+        self.army_slots = Some(army_slots);
+        self
+    }
+
+    pub fn with_resource_accounting(mut self, accounting: ResourceAccounting) -> Self {
+        self.resource_accounting = Some(accounting);
+        self
+    }
+
+    pub fn with_assigned_hero(mut self, hero: Option<AssignedHeroResponse>) -> Self {
+        self.assigned_hero = hero;
+        self
+    }
+
+    pub fn with_protection_until(mut self, protection_until: Option<DateTime<Utc>>) -> Self {
+        self.protection_until = protection_until;
+        self
+    }
+}
+
+/// Crop balance of a single village
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VillageUpkeep {
+    pub village_id: Uuid,
+    pub name: String,
+    pub crop_per_hour: i32,
+    /// Crop eaten by the village population (buildings)
+    pub building_upkeep: i32,
+    /// Crop eaten by troops belonging to the village
+    pub troop_upkeep: i32,
+    pub net_crop_per_hour: i32,
+    pub is_net_negative: bool,
+}
+
+/// Empire-wide crop balance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpkeepOverviewResponse {
+    pub villages: Vec<VillageUpkeep>,
+    pub total_crop_per_hour: i32,
+    pub total_building_upkeep: i32,
+    pub total_troop_upkeep: i32,
+    pub total_net_crop_per_hour: i32,
+    pub net_negative_villages: i32,
+}
+
+/// Settlement slots of a single village, granted by its Residence or Palace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VillageExpansionSlots {
+    pub village_id: Uuid,
+    pub name: String,
+    pub residence_level: i32,
+    pub palace_level: i32,
+    pub total_slots: i32,
+    /// Settle missions currently under way from this village
+    pub used_slots: i32,
+    pub available_slots: i32,
+}
+
+/// Empire-wide expansion status
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpansionOverviewResponse {
+    pub culture_points: i64,
+    pub village_count: i32,
+    pub culture_points_for_next_village: i64,
+    pub culture_points_missing: i64,
+    pub villages: Vec<VillageExpansionSlots>,
+    /// Villages with a free settlement slot
+    pub eligible_village_ids: Vec<Uuid>,
+    /// Enough culture points and at least one eligible village
+    pub can_found_village: bool,
 }
 
 // For map display - lightweight version
@@ -114,4 +232,8 @@ pub struct VillageMapInfo {
     pub y: i32,
     pub population: i32,
     pub player_name: Option<String>,
+    /// Owner's beginner protection end, only while it is active
+    pub protection_until: Option<DateTime<Utc>>,
+    pub alliance_id: Option<Uuid>,
+    pub alliance_tag: Option<String>,
 }
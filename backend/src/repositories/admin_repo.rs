@@ -1,9 +1,11 @@
-use chrono::{Duration, Utc};
-use sqlx::PgPool;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::error::AppResult;
-use crate::models::admin::AdminLog;
+use crate::models::admin::{
+    AdminLog, AdminVillageFilter, AdminVillageListItem, EconomyMetrics, VillageCleanupCounts,
+};
 use crate::models::user::User;
 
 pub struct AdminRepository;
@@ -216,6 +218,129 @@ impl AdminRepository {
         Ok(count.0)
     }
 
+    /// Sum of market fees collected, optionally only since a given time
+    pub async fn sum_trade_fees(pool: &PgPool, since: Option<DateTime<Utc>>) -> AppResult<i64> {
+        let total: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COALESCE(SUM(fee_gold), 0)::BIGINT
+            FROM trade_transactions
+            WHERE $1::TIMESTAMPTZ IS NULL OR created_at >= $1
+            "#,
+        )
+        .bind(since)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(total.0)
+    }
+
+    // ==================== Economy ====================
+
+    /// Compute the economy totals from live tables.
+    /// Village and balance sums scan whole tables; activity sums only cover the last 24h.
+    pub async fn compute_economy_metrics(pool: &PgPool) -> AppResult<EconomyMetrics> {
+        let metrics = sqlx::query_as::<_, EconomyMetrics>(
+            r#"
+            SELECT
+                (SELECT COALESCE(SUM(gold_balance), 0)::BIGINT
+                    FROM users WHERE deleted_at IS NULL) AS gold_in_circulation,
+                (SELECT COALESCE(SUM((quantity - quantity_filled)::BIGINT * price_per_unit), 0)::BIGINT
+                    FROM trade_orders
+                    WHERE order_type = 'buy' AND status IN ('open', 'partially_filled')) AS gold_in_escrow,
+                v.total_wood, v.total_clay, v.total_iron, v.total_crop,
+                t.trade_count_24h, t.trade_quantity_24h, t.trade_gold_24h,
+                (SELECT COALESCE(SUM(gold_amount), 0)::BIGINT
+                    FROM transactions
+                    WHERE transaction_type = 'gold_purchase'
+                        AND status = 'completed'
+                        AND created_at > NOW() - INTERVAL '24 hours') AS gold_purchased_24h,
+                NOW() AS captured_at
+            FROM (
+                SELECT COALESCE(SUM(wood), 0)::BIGINT AS total_wood,
+                       COALESCE(SUM(clay), 0)::BIGINT AS total_clay,
+                       COALESCE(SUM(iron), 0)::BIGINT AS total_iron,
+                       COALESCE(SUM(crop), 0)::BIGINT AS total_crop
+                FROM villages
+            ) v,
+            (
+                SELECT COUNT(*) AS trade_count_24h,
+                       COALESCE(SUM(quantity), 0)::BIGINT AS trade_quantity_24h,
+                       COALESCE(SUM(total_gold), 0)::BIGINT AS trade_gold_24h
+                FROM trade_transactions
+                WHERE created_at > NOW() - INTERVAL '24 hours'
+            ) t
+            "#,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(metrics)
+    }
+
+    /// Store a snapshot unless one was taken within the last `min_interval_minutes`.
+    /// Returns whether a row was written.
+    pub async fn insert_economy_snapshot(
+        pool: &PgPool,
+        metrics: &EconomyMetrics,
+        min_interval_minutes: i64,
+    ) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO economy_snapshots (
+                gold_in_circulation, gold_in_escrow,
+                total_wood, total_clay, total_iron, total_crop,
+                trade_count_24h, trade_quantity_24h, trade_gold_24h, gold_purchased_24h,
+                captured_at
+            )
+            SELECT $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11
+            WHERE NOT EXISTS (
+                SELECT 1 FROM economy_snapshots
+                WHERE captured_at > $11 - make_interval(mins => $12::INT)
+            )
+            "#,
+        )
+        .bind(metrics.gold_in_circulation)
+        .bind(metrics.gold_in_escrow)
+        .bind(metrics.total_wood)
+        .bind(metrics.total_clay)
+        .bind(metrics.total_iron)
+        .bind(metrics.total_crop)
+        .bind(metrics.trade_count_24h)
+        .bind(metrics.trade_quantity_24h)
+        .bind(metrics.trade_gold_24h)
+        .bind(metrics.gold_purchased_24h)
+        .bind(metrics.captured_at)
+        .bind(min_interval_minutes as i32)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Latest snapshot taken at or before `at`
+    pub async fn get_economy_snapshot_before(
+        pool: &PgPool,
+        at: DateTime<Utc>,
+    ) -> AppResult<Option<EconomyMetrics>> {
+        let snapshot = sqlx::query_as::<_, EconomyMetrics>(
+            r#"
+            SELECT gold_in_circulation, gold_in_escrow,
+                   total_wood, total_clay, total_iron, total_crop,
+                   trade_count_24h, trade_quantity_24h, trade_gold_24h, gold_purchased_24h,
+                   captured_at
+            FROM economy_snapshots
+            WHERE captured_at <= $1
+            ORDER BY captured_at DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(at)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(snapshot)
+    }
+
     /// Get village count for a user
     pub async fn count_user_villages(pool: &PgPool, user_id: Uuid) -> AppResult<i64> {
         let count: (i64,) = sqlx::query_as(
@@ -228,6 +353,61 @@ impl AdminRepository {
         Ok(count.0)
     }
 
+    // ==================== Villages ====================
+
+    /// Browse all villages with their owners, filtered and sorted
+    pub async fn list_villages(
+        pool: &PgPool,
+        filter: &AdminVillageFilter,
+        limit: i64,
+        offset: i64,
+    ) -> AppResult<Vec<AdminVillageListItem>> {
+        let name_pattern = filter.name.as_ref().map(|n| format!("%{}%", n));
+        let owner_pattern = filter.owner.as_ref().map(|o| format!("%{}%", o));
+
+        let villages = sqlx::query_as::<_, AdminVillageListItem>(
+            r#"
+            SELECT v.id, v.name, v.x, v.y, v.is_capital,
+                   v.wood, v.clay, v.iron, v.crop, v.population,
+                   v.user_id AS owner_id,
+                   u.display_name AS owner_name,
+                   u.email AS owner_email,
+                   u.banned_at AS owner_banned_at,
+                   v.created_at
+            FROM villages v
+            JOIN users u ON u.id = v.user_id
+            WHERE ($1::TEXT IS NULL OR v.name ILIKE $1)
+              AND ($2::TEXT IS NULL OR u.display_name ILIKE $2 OR u.email ILIKE $2)
+              AND ($3::INT IS NULL OR v.x >= $3)
+              AND ($4::INT IS NULL OR v.x <= $4)
+              AND ($5::INT IS NULL OR v.y >= $5)
+              AND ($6::INT IS NULL OR v.y <= $6)
+              AND ($7::INT IS NULL OR v.population >= $7)
+              AND ($8::INT IS NULL OR v.population <= $8)
+            ORDER BY
+                CASE WHEN $9 = 'population_desc' THEN v.population END DESC,
+                CASE WHEN $9 = 'population_asc' THEN v.population END ASC,
+                v.created_at DESC
+            LIMIT $10 OFFSET $11
+            "#,
+        )
+        .bind(name_pattern)
+        .bind(owner_pattern)
+        .bind(filter.min_x)
+        .bind(filter.max_x)
+        .bind(filter.min_y)
+        .bind(filter.max_y)
+        .bind(filter.min_population)
+        .bind(filter.max_population)
+        .bind(filter.sort.as_str())
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(villages)
+    }
+
     // ==================== Admin Logs ====================
 
     /// Create admin log entry
@@ -311,4 +491,166 @@ impl AdminRepository {
 
         Ok(())
     }
+
+    // ==================== Village Deletion ====================
+
+    /// Remove a village and every row that references it, in foreign-key order.
+    /// Battle reports, scout reports and trades are kept with the village cleared.
+    /// Other players' armies headed to or stationed in the village are sent home,
+    /// and heroes based there move to `fallback_village_id` (or are removed without one).
+    pub async fn purge_village_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        village_id: Uuid,
+        fallback_village_id: Option<Uuid>,
+    ) -> AppResult<VillageCleanupCounts> {
+        // Foreign armies turn around: stationed ones travel the full way back,
+        // ones still on their way return over the distance covered so far
+        let armies_sent_home = sqlx::query(
+            r#"
+            UPDATE armies
+            SET arrives_at = CASE
+                    WHEN COALESCE(is_stationed, FALSE) THEN NOW() + (arrives_at - departed_at)
+                    WHEN COALESCE(is_returning, FALSE) THEN arrives_at
+                    ELSE NOW() + (NOW() - departed_at)
+                END,
+                is_stationed = FALSE,
+                is_returning = TRUE,
+                to_village_id = NULL
+            WHERE to_village_id = $1 AND from_village_id <> $1
+            "#,
+        )
+        .bind(village_id)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected();
+
+        // Heroes leave the village before its armies are dropped
+        sqlx::query("UPDATE heroes SET current_village_id = NULL WHERE current_village_id = $1")
+            .bind(village_id)
+            .execute(&mut **tx)
+            .await?;
+
+        let mut heroes_rehomed = 0;
+        if let Some(fallback_id) = fallback_village_id {
+            heroes_rehomed = sqlx::query(
+                r#"
+                UPDATE heroes
+                SET home_village_id = $2,
+                    status = CASE WHEN status IN ('dead', 'reviving') THEN status ELSE 'idle' END
+                WHERE home_village_id = $1
+                "#,
+            )
+            .bind(village_id)
+            .bind(fallback_id)
+            .execute(&mut **tx)
+            .await?
+            .rows_affected();
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE armies SET hero_id = NULL
+            WHERE hero_id IN (SELECT id FROM heroes WHERE home_village_id = $1)
+            "#,
+        )
+        .bind(village_id)
+        .execute(&mut **tx)
+        .await?;
+
+        let heroes_removed = sqlx::query("DELETE FROM heroes WHERE home_village_id = $1")
+            .bind(village_id)
+            .execute(&mut **tx)
+            .await?
+            .rows_affected();
+
+        // Armies sent from the village have nowhere to return to
+        let armies_removed = sqlx::query("DELETE FROM armies WHERE from_village_id = $1")
+            .bind(village_id)
+            .execute(&mut **tx)
+            .await?
+            .rows_affected();
+
+        // Reports and trades are other players' history too: they stay, without the village
+        let battle_reports_detached = sqlx::query(
+            r#"
+            UPDATE battle_reports
+            SET attacker_village_id = NULLIF(attacker_village_id, $1),
+                defender_village_id = NULLIF(defender_village_id, $1)
+            WHERE attacker_village_id = $1 OR defender_village_id = $1
+            "#,
+        )
+        .bind(village_id)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected();
+
+        let scout_reports_detached = sqlx::query(
+            r#"
+            UPDATE scout_reports
+            SET attacker_village_id = NULLIF(attacker_village_id, $1),
+                defender_village_id = NULLIF(defender_village_id, $1)
+            WHERE attacker_village_id = $1 OR defender_village_id = $1
+            "#,
+        )
+        .bind(village_id)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected();
+
+        let trade_transactions_detached = sqlx::query(
+            r#"
+            UPDATE trade_transactions
+            SET buyer_village_id = NULLIF(buyer_village_id, $1),
+                seller_village_id = NULLIF(seller_village_id, $1)
+            WHERE buyer_village_id = $1 OR seller_village_id = $1
+            "#,
+        )
+        .bind(village_id)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected();
+
+        // Orders and resource locks cascade, and trades let go of the orders;
+        // open orders are closed and refunded beforehand
+        sqlx::query("DELETE FROM trade_orders WHERE village_id = $1")
+            .bind(village_id)
+            .execute(&mut **tx)
+            .await?;
+
+        let troop_queue = sqlx::query("DELETE FROM troop_queue WHERE village_id = $1")
+            .bind(village_id)
+            .execute(&mut **tx)
+            .await?
+            .rows_affected();
+
+        let troops = sqlx::query("DELETE FROM troops WHERE village_id = $1")
+            .bind(village_id)
+            .execute(&mut **tx)
+            .await?
+            .rows_affected();
+
+        let buildings = sqlx::query("DELETE FROM buildings WHERE village_id = $1")
+            .bind(village_id)
+            .execute(&mut **tx)
+            .await?
+            .rows_affected();
+
+        sqlx::query("DELETE FROM villages WHERE id = $1")
+            .bind(village_id)
+            .execute(&mut **tx)
+            .await?;
+
+        Ok(VillageCleanupCounts {
+            buildings,
+            troops,
+            troop_queue,
+            armies_removed,
+            armies_sent_home,
+            battle_reports_detached,
+            scout_reports_detached,
+            trade_transactions_detached,
+            heroes_rehomed,
+            heroes_removed,
+        })
+    }
 }
@@ -1,10 +1,12 @@
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::error::AppResult;
 use crate::models::alliance::{
     Alliance, AllianceDiplomacy, AllianceInvitation, AllianceListItem, AllianceMember,
-    AllianceMemberResponse, AllianceRole, DiplomacyStatus, InvitationStatus,
+    AllianceMemberResponse, AllianceMemberStats, AllianceRole, AllianceTreasury, AllianceTreasuryLog, DiplomacyStatus,
+    InvitationStatus, TreasuryAction, TreasuryTransferRequest,
 };
 
 pub struct AllianceRepository;
@@ -226,12 +228,13 @@ impl AllianceRepository {
                 am.role,
                 COUNT(v.id)::INT as villages_count,
                 COALESCE(SUM(v.population), 0)::INT as population,
-                am.joined_at
+                am.joined_at,
+                u.last_login_at
             FROM alliance_members am
             JOIN users u ON am.user_id = u.id
             LEFT JOIN villages v ON am.user_id = v.user_id
             WHERE am.alliance_id = $1
-            GROUP BY am.id, am.user_id, u.display_name, am.role, am.joined_at
+            GROUP BY am.id, am.user_id, u.display_name, am.role, am.joined_at, u.last_login_at
             ORDER BY am.role, population DESC
             "#,
         )
@@ -242,6 +245,112 @@ impl AllianceRepository {
         Ok(members)
     }
 
+    /// Per-member contribution stats since `since`, highest total contribution first.
+    /// Battle points use the same JSONB loss sums as the rankings.
+    pub async fn get_member_stats(
+        pool: &PgPool,
+        alliance_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> AppResult<Vec<AllianceMemberStats>> {
+        let stats = sqlx::query_as::<_, AllianceMemberStats>(
+            r#"
+            WITH members AS (
+                SELECT am.user_id, am.role, u.display_name
+                FROM alliance_members am
+                JOIN users u ON am.user_id = u.id
+                WHERE am.alliance_id = $1
+            ),
+            villages AS (
+                SELECT
+                    v.user_id,
+                    SUM(v.population)::BIGINT as population,
+                    COUNT(*) FILTER (WHERE v.created_at >= $2) as villages_gained
+                FROM villages v
+                JOIN members m ON v.user_id = m.user_id
+                GROUP BY v.user_id
+            ),
+            battles AS (
+                SELECT
+                    m.user_id,
+                    COALESCE(SUM(
+                        (SELECT COALESCE(SUM((value::text)::int), 0)
+                         FROM jsonb_each(COALESCE(br.defender_losses, '{}'::jsonb)))
+                    ) FILTER (WHERE br.attacker_player_id = m.user_id), 0)::BIGINT as attack_points,
+                    COALESCE(SUM(
+                        (SELECT COALESCE(SUM((value::text)::int), 0)
+                         FROM jsonb_each(COALESCE(br.attacker_losses, '{}'::jsonb)))
+                    ) FILTER (WHERE br.defender_player_id = m.user_id), 0)::BIGINT as defense_points
+                FROM battle_reports br
+                JOIN members m ON m.user_id IN (br.attacker_player_id, br.defender_player_id)
+                WHERE br.created_at >= $2
+                GROUP BY m.user_id
+            ),
+            deposits AS (
+                SELECT
+                    l.user_id,
+                    SUM(l.gold)::BIGINT as gold,
+                    SUM(l.wood + l.clay + l.iron + l.crop)::BIGINT as resources
+                FROM alliance_treasury_log l
+                WHERE l.alliance_id = $1 AND l.action = 'deposit' AND l.created_at >= $2
+                GROUP BY l.user_id
+            )
+            SELECT
+                m.user_id,
+                m.display_name as player_name,
+                m.role,
+                COALESCE(v.population, 0) as population,
+                COALESCE(v.villages_gained, 0) as villages_gained,
+                COALESCE(b.attack_points, 0) as attack_points,
+                COALESCE(b.defense_points, 0) as defense_points,
+                COALESCE(d.gold, 0) as treasury_gold,
+                COALESCE(d.resources, 0) as treasury_resources,
+                (COALESCE(b.attack_points, 0) + COALESCE(b.defense_points, 0)
+                    + COALESCE(d.gold, 0) + COALESCE(d.resources, 0)) as total_contribution
+            FROM members m
+            LEFT JOIN villages v ON m.user_id = v.user_id
+            LEFT JOIN battles b ON m.user_id = b.user_id
+            LEFT JOIN deposits d ON m.user_id = d.user_id
+            ORDER BY total_contribution DESC, population DESC
+            "#,
+        )
+        .bind(alliance_id)
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(stats)
+    }
+
+    /// Members of an alliance owning at least one village within `radius` tiles of (x, y)
+    pub async fn find_members_near(
+        pool: &PgPool,
+        alliance_id: Uuid,
+        x: i32,
+        y: i32,
+        radius: f64,
+        exclude_user_id: Uuid,
+    ) -> AppResult<Vec<Uuid>> {
+        let user_ids: Vec<(Uuid,)> = sqlx::query_as(
+            r#"
+            SELECT DISTINCT am.user_id
+            FROM alliance_members am
+            JOIN villages v ON v.user_id = am.user_id
+            WHERE am.alliance_id = $1
+              AND am.user_id != $5
+              AND SQRT(POWER(v.x - $2, 2) + POWER(v.y - $3, 2)) <= $4
+            "#,
+        )
+        .bind(alliance_id)
+        .bind(x)
+        .bind(y)
+        .bind(radius)
+        .bind(exclude_user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(user_ids.into_iter().map(|(id,)| id).collect())
+    }
+
     pub async fn update_member_role(
         pool: &PgPool,
         alliance_id: Uuid,
@@ -337,6 +446,22 @@ impl AllianceRepository {
         Ok(())
     }
 
+    /// Mark a pending invitation as cancelled. Returns false if it was no longer pending.
+    pub async fn cancel_pending_invitation(pool: &PgPool, id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE alliance_invitations
+            SET status = 'cancelled', responded_at = NOW()
+            WHERE id = $1 AND status = 'pending'
+            "#,
+        )
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     pub async fn has_pending_invitation(
         pool: &PgPool,
         alliance_id: Uuid,
@@ -404,6 +529,27 @@ impl AllianceRepository {
         Ok(diplomacy)
     }
 
+    /// Diplomacy of one alliance toward several others, for batched lookups
+    pub async fn get_diplomacy_toward(
+        pool: &PgPool,
+        alliance_id: Uuid,
+        target_alliance_ids: &[Uuid],
+    ) -> AppResult<Vec<AllianceDiplomacy>> {
+        let diplomacy = sqlx::query_as::<_, AllianceDiplomacy>(
+            r#"
+            SELECT id, alliance_id, target_alliance_id, status, proposed_by, created_at, updated_at
+            FROM alliance_diplomacy
+            WHERE alliance_id = $1 AND target_alliance_id = ANY($2)
+            "#,
+        )
+        .bind(alliance_id)
+        .bind(target_alliance_ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(diplomacy)
+    }
+
     pub async fn list_diplomacy(pool: &PgPool, alliance_id: Uuid) -> AppResult<Vec<AllianceDiplomacy>> {
         let diplomacy = sqlx::query_as::<_, AllianceDiplomacy>(
             r#"
@@ -420,15 +566,143 @@ impl AllianceRepository {
         Ok(diplomacy)
     }
 
-    pub async fn remove_diplomacy(pool: &PgPool, alliance_id: Uuid, target_alliance_id: Uuid) -> AppResult<()> {
+    // ==================== Treasury ====================
+
+    pub async fn get_treasury(pool: &PgPool, alliance_id: Uuid) -> AppResult<AllianceTreasury> {
+        let treasury = sqlx::query_as::<_, AllianceTreasury>(
+            r#"
+            SELECT gold, wood, clay, iron, crop
+            FROM alliance_treasury
+            WHERE alliance_id = $1
+            "#,
+        )
+        .bind(alliance_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(treasury.unwrap_or_default())
+    }
+
+    pub async fn deposit_to_treasury_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        alliance_id: Uuid,
+        amounts: &TreasuryTransferRequest,
+    ) -> AppResult<AllianceTreasury> {
+        let treasury = sqlx::query_as::<_, AllianceTreasury>(
+            r#"
+            INSERT INTO alliance_treasury (alliance_id, gold, wood, clay, iron, crop)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (alliance_id) DO UPDATE
+            SET gold = alliance_treasury.gold + EXCLUDED.gold,
+                wood = alliance_treasury.wood + EXCLUDED.wood,
+                clay = alliance_treasury.clay + EXCLUDED.clay,
+                iron = alliance_treasury.iron + EXCLUDED.iron,
+                crop = alliance_treasury.crop + EXCLUDED.crop,
+                updated_at = NOW()
+            RETURNING gold, wood, clay, iron, crop
+            "#,
+        )
+        .bind(alliance_id)
+        .bind(amounts.gold)
+        .bind(amounts.wood)
+        .bind(amounts.clay)
+        .bind(amounts.iron)
+        .bind(amounts.crop)
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(treasury)
+    }
+
+    /// Withdraw from the treasury. Returns None if the treasury can't cover every amount.
+    pub async fn withdraw_from_treasury_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        alliance_id: Uuid,
+        amounts: &TreasuryTransferRequest,
+    ) -> AppResult<Option<AllianceTreasury>> {
+        let treasury = sqlx::query_as::<_, AllianceTreasury>(
+            r#"
+            UPDATE alliance_treasury
+            SET gold = gold - $2,
+                wood = wood - $3,
+                clay = clay - $4,
+                iron = iron - $5,
+                crop = crop - $6,
+                updated_at = NOW()
+            WHERE alliance_id = $1
+              AND gold >= $2
+              AND wood >= $3
+              AND clay >= $4
+              AND iron >= $5
+              AND crop >= $6
+            RETURNING gold, wood, clay, iron, crop
+            "#,
+        )
+        .bind(alliance_id)
+        .bind(amounts.gold)
+        .bind(amounts.wood)
+        .bind(amounts.clay)
+        .bind(amounts.iron)
+        .bind(amounts.crop)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(treasury)
+    }
+
+    pub async fn log_treasury_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        alliance_id: Uuid,
+        user_id: Uuid,
+        action: TreasuryAction,
+        amounts: &TreasuryTransferRequest,
+    ) -> AppResult<()> {
         sqlx::query(
-            "DELETE FROM alliance_diplomacy WHERE alliance_id = $1 AND target_alliance_id = $2",
+            r#"
+            INSERT INTO alliance_treasury_log (
+                alliance_id, user_id, village_id, action, gold, wood, clay, iron, crop
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
         )
         .bind(alliance_id)
-        .bind(target_alliance_id)
-        .execute(pool)
+        .bind(user_id)
+        .bind(amounts.village_id)
+        .bind(action)
+        .bind(amounts.gold)
+        .bind(amounts.wood)
+        .bind(amounts.clay)
+        .bind(amounts.iron)
+        .bind(amounts.crop)
+        .execute(&mut **tx)
         .await?;
 
         Ok(())
     }
+
+    pub async fn list_treasury_log(
+        pool: &PgPool,
+        alliance_id: Uuid,
+        limit: i32,
+        offset: i32,
+    ) -> AppResult<Vec<AllianceTreasuryLog>> {
+        let log = sqlx::query_as::<_, AllianceTreasuryLog>(
+            r#"
+            SELECT l.id, l.user_id, u.display_name as player_name, l.village_id, l.action,
+                   l.gold, l.wood, l.clay, l.iron, l.crop, l.created_at
+            FROM alliance_treasury_log l
+            LEFT JOIN users u ON l.user_id = u.id
+            WHERE l.alliance_id = $1
+            ORDER BY l.created_at DESC
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(alliance_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(log)
+    }
 }
@@ -1,5 +1,5 @@
 use chrono::{DateTime, Utc};
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
 use uuid::Uuid;
 
 use crate::error::AppResult;
@@ -63,6 +63,82 @@ impl ArmyRepository {
         Ok(armies)
     }
 
+    /// Count armies from a village that are still moving (outgoing or returning)
+    pub async fn count_outgoing_from_village(pool: &PgPool, village_id: Uuid) -> AppResult<i64> {
+        let count: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*)
+            FROM armies
+            WHERE from_village_id = $1 AND is_stationed = FALSE
+            "#,
+        )
+        .bind(village_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count.0)
+    }
+
+    /// Outgoing army count per village, for villages that have any
+    pub async fn count_outgoing_by_villages(
+        pool: &PgPool,
+        village_ids: &[Uuid],
+    ) -> AppResult<Vec<(Uuid, i64)>> {
+        let counts: Vec<(Uuid, i64)> = sqlx::query_as(
+            r#"
+            SELECT from_village_id, COUNT(*)
+            FROM armies
+            WHERE from_village_id = ANY($1) AND is_stationed = FALSE
+            GROUP BY from_village_id
+            "#,
+        )
+        .bind(village_ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(counts)
+    }
+
+    /// Settle missions under way per village, for villages that have any
+    pub async fn count_settle_missions_by_villages(
+        pool: &PgPool,
+        village_ids: &[Uuid],
+    ) -> AppResult<Vec<(Uuid, i64)>> {
+        let counts: Vec<(Uuid, i64)> = sqlx::query_as(
+            r#"
+            SELECT from_village_id, COUNT(*)
+            FROM armies
+            WHERE from_village_id = ANY($1) AND mission = 'settle' AND is_stationed = FALSE
+            GROUP BY from_village_id
+            "#,
+        )
+        .bind(village_ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(counts)
+    }
+
+    /// Ids of a player's villages with hostile armies on the way
+    pub async fn find_villages_under_attack(pool: &PgPool, user_id: Uuid) -> AppResult<Vec<Uuid>> {
+        let ids: Vec<(Uuid,)> = sqlx::query_as(
+            r#"
+            SELECT DISTINCT a.to_village_id
+            FROM armies a
+            JOIN villages v ON v.id = a.to_village_id
+            WHERE v.user_id = $1
+              AND a.is_returning = FALSE
+              AND a.is_stationed = FALSE
+              AND a.mission IN ('raid', 'attack', 'conquer')
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(ids.into_iter().map(|(id,)| id).collect())
+    }
+
     pub async fn find_incoming_to_village(pool: &PgPool, village_id: Uuid) -> AppResult<Vec<Army>> {
         let armies = sqlx::query_as::<_, Army>(
             r#"
@@ -322,7 +398,8 @@ impl ArmyRepository {
             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
             RETURNING id, attacker_player_id, defender_player_id, attacker_village_id, defender_village_id,
                       mission, attacker_troops, defender_troops, attacker_losses, defender_losses,
-                      resources_stolen, winner, occurred_at, read_by_attacker, read_by_defender, created_at
+                      resources_stolen, winner, occurred_at, read_by_attacker, read_by_defender,
+                      attacker_archived, defender_archived, created_at
             "#,
         )
         .bind(attacker_player_id)
@@ -348,7 +425,8 @@ impl ArmyRepository {
             r#"
             SELECT id, attacker_player_id, defender_player_id, attacker_village_id, defender_village_id,
                    mission, attacker_troops, defender_troops, attacker_losses, defender_losses,
-                   resources_stolen, winner, occurred_at, read_by_attacker, read_by_defender, created_at
+                   resources_stolen, winner, occurred_at, read_by_attacker, read_by_defender,
+                   attacker_archived, defender_archived, created_at
             FROM battle_reports
             WHERE attacker_player_id = $1 OR defender_player_id = $1
             ORDER BY occurred_at DESC
@@ -367,7 +445,8 @@ impl ArmyRepository {
             r#"
             SELECT id, attacker_player_id, defender_player_id, attacker_village_id, defender_village_id,
                    mission, attacker_troops, defender_troops, attacker_losses, defender_losses,
-                   resources_stolen, winner, occurred_at, read_by_attacker, read_by_defender, created_at
+                   resources_stolen, winner, occurred_at, read_by_attacker, read_by_defender,
+                   attacker_archived, defender_archived, created_at
             FROM battle_reports
             WHERE id = $1
             "#,
@@ -391,6 +470,47 @@ impl ArmyRepository {
         Ok(())
     }
 
+    pub async fn set_report_archived(pool: &PgPool, id: Uuid, is_attacker: bool, archived: bool) -> AppResult<()> {
+        let query = if is_attacker {
+            "UPDATE battle_reports SET attacker_archived = $2 WHERE id = $1"
+        } else {
+            "UPDATE battle_reports SET defender_archived = $2 WHERE id = $1"
+        };
+
+        sqlx::query(query).bind(id).bind(archived).execute(pool).await?;
+
+        Ok(())
+    }
+
+    /// Delete one batch of read battle reports neither side archived, older than the retention window.
+    /// Runs on the connection holding the cleanup job's advisory lock.
+    pub async fn delete_expired_reports_batch(
+        conn: &mut PgConnection,
+        retention_days: i64,
+        batch_size: i64,
+    ) -> AppResult<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM battle_reports
+            WHERE id IN (
+                SELECT id FROM battle_reports
+                WHERE attacker_archived = FALSE
+                  AND defender_archived = FALSE
+                  AND read_by_attacker = TRUE
+                  AND (defender_player_id IS NULL OR read_by_defender = TRUE)
+                  AND created_at < NOW() - make_interval(days => $1::int)
+                LIMIT $2
+            )
+            "#,
+        )
+        .bind(retention_days)
+        .bind(batch_size)
+        .execute(conn)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     pub async fn count_unread_reports(pool: &PgPool, player_id: Uuid) -> AppResult<i64> {
         let count: (i64,) = sqlx::query_as(
             r#"
@@ -1,5 +1,5 @@
 use chrono::{DateTime, Utc};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::error::AppResult;
@@ -12,7 +12,7 @@ impl BuildingRepository {
         let building = sqlx::query_as::<_, Building>(
             r#"
             SELECT id, village_id, building_type, slot, level,
-                   is_upgrading, upgrade_ends_at, created_at, updated_at
+                   is_upgrading, upgrade_started_at, upgrade_ends_at, created_at, updated_at
             FROM buildings
             WHERE id = $1
             "#,
@@ -28,7 +28,7 @@ impl BuildingRepository {
         let buildings = sqlx::query_as::<_, Building>(
             r#"
             SELECT id, village_id, building_type, slot, level,
-                   is_upgrading, upgrade_ends_at, created_at, updated_at
+                   is_upgrading, upgrade_started_at, upgrade_ends_at, created_at, updated_at
             FROM buildings
             WHERE village_id = $1
             ORDER BY slot ASC
@@ -41,6 +41,43 @@ impl BuildingRepository {
         Ok(buildings)
     }
 
+    pub async fn find_by_village_ids(pool: &PgPool, village_ids: &[Uuid]) -> AppResult<Vec<Building>> {
+        let buildings = sqlx::query_as::<_, Building>(
+            r#"
+            SELECT id, village_id, building_type, slot, level,
+                   is_upgrading, upgrade_started_at, upgrade_ends_at, created_at, updated_at
+            FROM buildings
+            WHERE village_id = ANY($1)
+            ORDER BY village_id, slot ASC
+            "#,
+        )
+        .bind(village_ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(buildings)
+    }
+
+    pub async fn find_by_village_id_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        village_id: Uuid,
+    ) -> AppResult<Vec<Building>> {
+        let buildings = sqlx::query_as::<_, Building>(
+            r#"
+            SELECT id, village_id, building_type, slot, level,
+                   is_upgrading, upgrade_started_at, upgrade_ends_at, created_at, updated_at
+            FROM buildings
+            WHERE village_id = $1
+            ORDER BY slot ASC
+            "#,
+        )
+        .bind(village_id)
+        .fetch_all(&mut **tx)
+        .await?;
+
+        Ok(buildings)
+    }
+
     pub async fn find_by_village_and_slot(
         pool: &PgPool,
         village_id: Uuid,
@@ -49,7 +86,7 @@ impl BuildingRepository {
         let building = sqlx::query_as::<_, Building>(
             r#"
             SELECT id, village_id, building_type, slot, level,
-                   is_upgrading, upgrade_ends_at, created_at, updated_at
+                   is_upgrading, upgrade_started_at, upgrade_ends_at, created_at, updated_at
             FROM buildings
             WHERE village_id = $1 AND slot = $2
             "#,
@@ -62,6 +99,27 @@ impl BuildingRepository {
         Ok(building)
     }
 
+    pub async fn find_by_village_and_slot_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        village_id: Uuid,
+        slot: i32,
+    ) -> AppResult<Option<Building>> {
+        let building = sqlx::query_as::<_, Building>(
+            r#"
+            SELECT id, village_id, building_type, slot, level,
+                   is_upgrading, upgrade_started_at, upgrade_ends_at, created_at, updated_at
+            FROM buildings
+            WHERE village_id = $1 AND slot = $2
+            "#,
+        )
+        .bind(village_id)
+        .bind(slot)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(building)
+    }
+
     pub async fn find_upgrading_by_village(
         pool: &PgPool,
         village_id: Uuid,
@@ -69,7 +127,7 @@ impl BuildingRepository {
         let buildings = sqlx::query_as::<_, Building>(
             r#"
             SELECT id, village_id, building_type, slot, level,
-                   is_upgrading, upgrade_ends_at, created_at, updated_at
+                   is_upgrading, upgrade_started_at, upgrade_ends_at, created_at, updated_at
             FROM buildings
             WHERE village_id = $1 AND is_upgrading = TRUE
             ORDER BY upgrade_ends_at ASC
@@ -82,95 +140,136 @@ impl BuildingRepository {
         Ok(buildings)
     }
 
-    pub async fn create(pool: &PgPool, input: CreateBuilding) -> AppResult<Building> {
+    /// Lock every in-progress upgrade of a village, soonest first
+    pub async fn find_upgrading_by_village_for_update_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        village_id: Uuid,
+    ) -> AppResult<Vec<Building>> {
+        let buildings = sqlx::query_as::<_, Building>(
+            r#"
+            SELECT id, village_id, building_type, slot, level,
+                   is_upgrading, upgrade_started_at, upgrade_ends_at, created_at, updated_at
+            FROM buildings
+            WHERE village_id = $1 AND is_upgrading = TRUE
+            ORDER BY upgrade_ends_at ASC
+            FOR UPDATE
+            "#,
+        )
+        .bind(village_id)
+        .fetch_all(&mut **tx)
+        .await?;
+
+        Ok(buildings)
+    }
+
+    /// Create a building at a given level within a transaction
+    pub async fn create_with_level_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        input: CreateBuilding,
+        level: i32,
+    ) -> AppResult<Building> {
         let building = sqlx::query_as::<_, Building>(
             r#"
             INSERT INTO buildings (village_id, building_type, slot, level)
-            VALUES ($1, $2, $3, 1)
+            VALUES ($1, $2, $3, $4)
             RETURNING id, village_id, building_type, slot, level,
-                      is_upgrading, upgrade_ends_at, created_at, updated_at
+                      is_upgrading, upgrade_started_at, upgrade_ends_at, created_at, updated_at
             "#,
         )
-        .bind(&input.village_id)
+        .bind(input.village_id)
         .bind(&input.building_type)
         .bind(input.slot)
-        .fetch_one(pool)
+        .bind(level)
+        .fetch_one(&mut **tx)
         .await?;
 
         Ok(building)
     }
 
-    pub async fn start_upgrade(
-        pool: &PgPool,
+    /// Start an upgrade within a transaction. Returns None if the building
+    /// is already upgrading.
+    pub async fn start_upgrade_tx(
+        tx: &mut Transaction<'_, Postgres>,
         id: Uuid,
         upgrade_ends_at: DateTime<Utc>,
-    ) -> AppResult<Building> {
+    ) -> AppResult<Option<Building>> {
         let building = sqlx::query_as::<_, Building>(
             r#"
             UPDATE buildings
             SET is_upgrading = TRUE,
+                upgrade_started_at = NOW(),
                 upgrade_ends_at = $2,
                 updated_at = NOW()
-            WHERE id = $1
+            WHERE id = $1 AND is_upgrading = FALSE
             RETURNING id, village_id, building_type, slot, level,
-                      is_upgrading, upgrade_ends_at, created_at, updated_at
+                      is_upgrading, upgrade_started_at, upgrade_ends_at, created_at, updated_at
             "#,
         )
         .bind(id)
         .bind(upgrade_ends_at)
-        .fetch_one(pool)
+        .fetch_optional(&mut **tx)
         .await?;
 
         Ok(building)
     }
 
-    pub async fn complete_upgrade(pool: &PgPool, id: Uuid) -> AppResult<Building> {
+    /// Finish an upgrade. Returns None if it was already completed.
+    pub async fn complete_upgrade_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        id: Uuid,
+    ) -> AppResult<Option<Building>> {
         let building = sqlx::query_as::<_, Building>(
             r#"
             UPDATE buildings
             SET level = level + 1,
                 is_upgrading = FALSE,
+                upgrade_started_at = NULL,
                 upgrade_ends_at = NULL,
                 updated_at = NOW()
-            WHERE id = $1
+            WHERE id = $1 AND is_upgrading = TRUE
             RETURNING id, village_id, building_type, slot, level,
-                      is_upgrading, upgrade_ends_at, created_at, updated_at
+                      is_upgrading, upgrade_started_at, upgrade_ends_at, created_at, updated_at
             "#,
         )
         .bind(id)
-        .fetch_one(pool)
+        .fetch_optional(&mut **tx)
         .await?;
 
         Ok(building)
     }
 
-    pub async fn cancel_upgrade(pool: &PgPool, id: Uuid) -> AppResult<Building> {
+    /// Stop an in-progress upgrade. Returns None if the upgrade already completed.
+    pub async fn cancel_upgrade_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        id: Uuid,
+    ) -> AppResult<Option<Building>> {
         let building = sqlx::query_as::<_, Building>(
             r#"
             UPDATE buildings
             SET is_upgrading = FALSE,
+                upgrade_started_at = NULL,
                 upgrade_ends_at = NULL,
                 updated_at = NOW()
-            WHERE id = $1
+            WHERE id = $1 AND is_upgrading = TRUE
             RETURNING id, village_id, building_type, slot, level,
-                      is_upgrading, upgrade_ends_at, created_at, updated_at
+                      is_upgrading, upgrade_started_at, upgrade_ends_at, created_at, updated_at
             "#,
         )
         .bind(id)
-        .fetch_one(pool)
+        .fetch_optional(&mut **tx)
         .await?;
 
         Ok(building)
     }
 
-    pub async fn demolish(pool: &PgPool, id: Uuid) -> AppResult<()> {
+    pub async fn demolish_tx(tx: &mut Transaction<'_, Postgres>, id: Uuid) -> AppResult<()> {
         sqlx::query(
             r#"
             DELETE FROM buildings WHERE id = $1
             "#,
         )
         .bind(id)
-        .execute(pool)
+        .execute(&mut **tx)
         .await?;
 
         Ok(())
@@ -180,7 +279,7 @@ impl BuildingRepository {
         let buildings = sqlx::query_as::<_, Building>(
             r#"
             SELECT id, village_id, building_type, slot, level,
-                   is_upgrading, upgrade_ends_at, created_at, updated_at
+                   is_upgrading, upgrade_started_at, upgrade_ends_at, created_at, updated_at
             FROM buildings
             WHERE is_upgrading = TRUE AND upgrade_ends_at <= NOW()
             "#,
@@ -191,18 +290,24 @@ impl BuildingRepository {
         Ok(buildings)
     }
 
-    pub async fn count_upgrading_by_village(pool: &PgPool, village_id: Uuid) -> AppResult<i64> {
-        let count: (i64,) = sqlx::query_as(
+    /// Highest level of a building type in a village (0 if not built)
+    pub async fn get_max_level_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        village_id: Uuid,
+        building_type: BuildingType,
+    ) -> AppResult<i32> {
+        let level: (Option<i32>,) = sqlx::query_as(
             r#"
-            SELECT COUNT(*) FROM buildings
-            WHERE village_id = $1 AND is_upgrading = TRUE
+            SELECT MAX(level) FROM buildings
+            WHERE village_id = $1 AND building_type = $2
             "#,
         )
         .bind(village_id)
-        .fetch_one(pool)
+        .bind(building_type)
+        .fetch_one(&mut **tx)
         .await?;
 
-        Ok(count.0)
+        Ok(level.0.unwrap_or(0))
     }
 
     pub async fn find_by_type(
@@ -213,7 +318,7 @@ impl BuildingRepository {
         let buildings = sqlx::query_as::<_, Building>(
             r#"
             SELECT id, village_id, building_type, slot, level,
-                   is_upgrading, upgrade_ends_at, created_at, updated_at
+                   is_upgrading, upgrade_started_at, upgrade_ends_at, created_at, updated_at
             FROM buildings
             WHERE village_id = $1 AND building_type = $2
             ORDER BY level DESC
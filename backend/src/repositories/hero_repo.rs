@@ -1,5 +1,5 @@
 use chrono::{DateTime, Utc};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::error::AppResult;
@@ -55,6 +55,26 @@ impl HeroRepository {
         Ok(hero)
     }
 
+    /// Get heroes stationed in the given villages (home there, alive and idle)
+    pub async fn find_stationed_by_villages(pool: &PgPool, village_ids: &[Uuid]) -> AppResult<Vec<Hero>> {
+        let heroes = sqlx::query_as::<_, Hero>(
+            r#"
+            SELECT id, user_id, slot_number, hero_definition_id, name, tribe, home_village_id, current_village_id,
+                   status, level, experience, experience_to_next, health, health_regen_rate,
+                   unassigned_points, fighting_strength, off_bonus, def_bonus, resources_bonus,
+                   base_attack, base_defense, base_speed, last_health_update, died_at, revive_at,
+                   created_at, updated_at
+            FROM heroes
+            WHERE home_village_id = ANY($1) AND status = 'idle' AND health > 0
+            "#,
+        )
+        .bind(village_ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(heroes)
+    }
+
     /// Get hero by user and slot
     pub async fn find_by_slot(pool: &PgPool, user_id: Uuid, slot: i32) -> AppResult<Option<Hero>> {
         let hero = sqlx::query_as::<_, Hero>(
@@ -385,6 +405,46 @@ impl HeroRepository {
         Ok(hero)
     }
 
+    /// Revive every hero that has been dead for at least `delay_hours` and whose owner
+    /// has an active Travian Plus subscription right now
+    pub async fn auto_revive_subscriber_heroes(
+        pool: &PgPool,
+        delay_hours: i64,
+        health: i32,
+    ) -> AppResult<Vec<Hero>> {
+        let heroes = sqlx::query_as::<_, Hero>(
+            r#"
+            UPDATE heroes h
+            SET health = $2,
+                status = 'idle',
+                died_at = NULL,
+                revive_at = NULL,
+                last_health_update = NOW(),
+                updated_at = NOW()
+            WHERE h.status = 'dead'
+                AND h.died_at <= NOW() - ($1 || ' hours')::INTERVAL
+                AND EXISTS (
+                    SELECT 1 FROM user_subscriptions s
+                    WHERE s.user_id = h.user_id
+                        AND s.subscription_type = 'travian_plus'
+                        AND s.is_active = TRUE
+                        AND s.expires_at > NOW()
+                )
+            RETURNING id, user_id, slot_number, hero_definition_id, name, tribe, home_village_id, current_village_id,
+                      status, level, experience, experience_to_next, health, health_regen_rate,
+                      unassigned_points, fighting_strength, off_bonus, def_bonus, resources_bonus,
+                      base_attack, base_defense, base_speed, last_health_update, died_at, revive_at,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(delay_hours.to_string())
+        .bind(health)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(heroes)
+    }
+
     // ==================== Hero Slots ====================
 
     /// Get hero slot prices
@@ -427,6 +487,26 @@ impl HeroRepository {
         Ok(result.0)
     }
 
+    /// Add one hero slot within a transaction (the user row is locked by the gold spend)
+    pub async fn add_user_slot_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+    ) -> AppResult<i32> {
+        let result: (i32,) = sqlx::query_as(
+            r#"
+            UPDATE users
+            SET hero_slots = hero_slots + 1
+            WHERE id = $1
+            RETURNING hero_slots
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(result.0)
+    }
+
     // ==================== Item Definitions ====================
 
     /// Get all item definitions
@@ -64,6 +64,29 @@ impl MessageRepository {
         Ok(message)
     }
 
+    /// Create system notices in bulk, one per player
+    pub async fn create_system_messages(
+        pool: &PgPool,
+        user_ids: &[Uuid],
+        subjects: &[String],
+        bodies: &[String],
+    ) -> AppResult<u64> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO messages (message_type, recipient_id, subject, body)
+            SELECT 'system', u.user_id, u.subject, u.body
+            FROM UNNEST($1::UUID[], $2::VARCHAR[], $3::TEXT[]) AS u(user_id, subject, body)
+            "#,
+        )
+        .bind(user_ids)
+        .bind(subjects)
+        .bind(bodies)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     /// Get message by ID with full details
     pub async fn get_message(pool: &PgPool, message_id: Uuid) -> AppResult<Option<MessageResponse>> {
         let message = sqlx::query_as::<_, MessageResponse>(
@@ -72,9 +95,11 @@ impl MessageRepository {
                 m.id,
                 m.message_type,
                 m.sender_id,
-                sender.display_name as sender_name,
+                CASE WHEN m.message_type = 'system' THEN 'System'
+                    ELSE COALESCE(CASE WHEN sender.deleted_at IS NULL THEN sender.display_name END, 'Unknown player')
+                END as sender_name,
                 m.recipient_id,
-                recipient.display_name as recipient_name,
+                CASE WHEN recipient.deleted_at IS NULL THEN recipient.display_name END as recipient_name,
                 m.alliance_id,
                 a.name as alliance_name,
                 m.subject,
@@ -82,7 +107,7 @@ impl MessageRepository {
                 m.is_read,
                 m.created_at
             FROM messages m
-            JOIN users sender ON sender.id = m.sender_id
+            LEFT JOIN users sender ON sender.id = m.sender_id
             LEFT JOIN users recipient ON recipient.id = m.recipient_id
             LEFT JOIN alliances a ON a.id = m.alliance_id
             WHERE m.id = $1
@@ -95,7 +120,7 @@ impl MessageRepository {
         Ok(message)
     }
 
-    /// Get inbox (received private messages)
+    /// Get inbox (received private and system messages)
     pub async fn get_inbox(
         pool: &PgPool,
         user_id: Uuid,
@@ -107,13 +132,15 @@ impl MessageRepository {
             SELECT
                 m.id,
                 m.sender_id,
-                sender.display_name as sender_name,
+                CASE WHEN m.message_type = 'system' THEN 'System'
+                    ELSE COALESCE(CASE WHEN sender.deleted_at IS NULL THEN sender.display_name END, 'Unknown player')
+                END as sender_name,
                 m.subject,
                 m.is_read,
                 m.created_at
             FROM messages m
-            JOIN users sender ON sender.id = m.sender_id
-            WHERE m.message_type = 'private'
+            LEFT JOIN users sender ON sender.id = m.sender_id
+            WHERE m.message_type IN ('private', 'system')
                 AND m.recipient_id = $1
                 AND m.recipient_deleted = FALSE
             ORDER BY m.created_at DESC
@@ -129,6 +156,42 @@ impl MessageRepository {
         Ok(messages)
     }
 
+    /// Count inbox messages (same filter as `get_inbox`)
+    pub async fn count_inbox(pool: &PgPool, user_id: Uuid) -> AppResult<i64> {
+        let count: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*)
+            FROM messages
+            WHERE message_type IN ('private', 'system')
+                AND recipient_id = $1
+                AND recipient_deleted = FALSE
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count.0)
+    }
+
+    /// Count sent messages (same filter as `get_sent`)
+    pub async fn count_sent(pool: &PgPool, user_id: Uuid) -> AppResult<i64> {
+        let count: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*)
+            FROM messages
+            WHERE message_type = 'private'
+                AND sender_id = $1
+                AND sender_deleted = FALSE
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count.0)
+    }
+
     /// Get sent messages
     pub async fn get_sent(
         pool: &PgPool,
@@ -141,7 +204,7 @@ impl MessageRepository {
             SELECT
                 m.id,
                 m.sender_id,
-                recipient.display_name as sender_name,
+                COALESCE(CASE WHEN recipient.deleted_at IS NULL THEN recipient.display_name END, 'Unknown player') as sender_name,
                 m.subject,
                 m.is_read,
                 m.created_at
@@ -176,12 +239,12 @@ impl MessageRepository {
             SELECT
                 m.id,
                 m.sender_id,
-                sender.display_name as sender_name,
+                COALESCE(CASE WHEN sender.deleted_at IS NULL THEN sender.display_name END, 'Unknown player') as sender_name,
                 m.subject,
                 CASE WHEN mr.id IS NOT NULL THEN TRUE ELSE FALSE END as is_read,
                 m.created_at
             FROM messages m
-            JOIN users sender ON sender.id = m.sender_id
+            LEFT JOIN users sender ON sender.id = m.sender_id
             LEFT JOIN message_reads mr ON mr.message_id = m.id AND mr.user_id = $2
             WHERE m.message_type = 'alliance'
                 AND m.alliance_id = $1
@@ -237,6 +300,28 @@ impl MessageRepository {
         Ok(())
     }
 
+    /// Count current alliance members who have read an alliance message
+    pub async fn count_alliance_message_reads(
+        pool: &PgPool,
+        message_id: Uuid,
+        alliance_id: Uuid,
+    ) -> AppResult<i64> {
+        let count: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*)
+            FROM message_reads mr
+            JOIN alliance_members am ON am.user_id = mr.user_id AND am.alliance_id = $2
+            WHERE mr.message_id = $1
+            "#,
+        )
+        .bind(message_id)
+        .bind(alliance_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count.0)
+    }
+
     /// Delete message for user (soft delete)
     pub async fn delete_for_user(
         pool: &PgPool,
@@ -261,16 +346,48 @@ impl MessageRepository {
         Ok(result.rows_affected() > 0)
     }
 
-    /// Get unread private message count
+    /// Soft delete several messages for a user in one statement.
+    /// Ids the user neither sent nor received, or already deleted, are skipped.
+    pub async fn delete_many_for_user(
+        pool: &PgPool,
+        user_id: Uuid,
+        message_ids: &[Uuid],
+    ) -> AppResult<u64> {
+        let result = sqlx::query(
+            r#"
+            UPDATE messages
+            SET
+                sender_deleted = CASE WHEN sender_id = $2 THEN TRUE ELSE sender_deleted END,
+                recipient_deleted = CASE WHEN recipient_id = $2 THEN TRUE ELSE recipient_deleted END
+            WHERE id = ANY($1)
+                AND ((sender_id = $2 AND sender_deleted = FALSE)
+                    OR (recipient_id = $2 AND recipient_deleted = FALSE))
+            "#,
+        )
+        .bind(message_ids)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Get unread private and system message count
     pub async fn get_unread_count(pool: &PgPool, user_id: Uuid) -> AppResult<i64> {
         let count: (i64,) = sqlx::query_as(
             r#"
             SELECT COUNT(*)
             FROM messages
-            WHERE message_type = 'private'
+            WHERE message_type IN ('private', 'system')
                 AND recipient_id = $1
                 AND recipient_deleted = FALSE
                 AND is_read = FALSE
+                AND NOT EXISTS (
+                    SELECT 1 FROM conversations c
+                    WHERE c.id = messages.conversation_id
+                        AND ((c.user_1_id = $1 AND c.user_1_muted)
+                            OR (c.user_2_id = $1 AND c.user_2_muted))
+                )
             "#,
         )
         .bind(user_id)
@@ -337,6 +454,29 @@ impl MessageRepository {
         Ok(conversation)
     }
 
+    /// Whether two users already have a conversation
+    pub async fn conversation_exists(pool: &PgPool, user_a: Uuid, user_b: Uuid) -> AppResult<bool> {
+        let (user_1, user_2) = if user_a < user_b {
+            (user_a, user_b)
+        } else {
+            (user_b, user_a)
+        };
+
+        let exists: (bool,) = sqlx::query_as(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM conversations WHERE user_1_id = $1 AND user_2_id = $2
+            )
+            "#,
+        )
+        .bind(user_1)
+        .bind(user_2)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(exists.0)
+    }
+
     /// Update conversation last message
     pub async fn update_conversation_last_message(
         pool: &PgPool,
@@ -370,7 +510,14 @@ impl MessageRepository {
             SELECT
                 c.id,
                 CASE WHEN c.user_1_id = $1 THEN c.user_2_id ELSE c.user_1_id END as other_user_id,
-                CASE WHEN c.user_1_id = $1 THEN u2.display_name ELSE u1.display_name END as other_user_name,
+                COALESCE(
+                    CASE WHEN c.user_1_id = $1 THEN
+                        CASE WHEN u2.deleted_at IS NULL THEN u2.display_name END
+                    ELSE
+                        CASE WHEN u1.deleted_at IS NULL THEN u1.display_name END
+                    END,
+                    'Unknown player'
+                ) as other_user_name,
                 m.subject as last_message_subject,
                 LEFT(m.body, 100) as last_message_preview,
                 c.last_message_at,
@@ -380,10 +527,11 @@ impl MessageRepository {
                     WHERE msg.conversation_id = c.id
                         AND msg.recipient_id = $1
                         AND msg.is_read = FALSE
-                ) as unread_count
+                ) as unread_count,
+                CASE WHEN c.user_1_id = $1 THEN c.user_1_muted ELSE c.user_2_muted END as is_muted
             FROM conversations c
-            JOIN users u1 ON u1.id = c.user_1_id
-            JOIN users u2 ON u2.id = c.user_2_id
+            LEFT JOIN users u1 ON u1.id = c.user_1_id
+            LEFT JOIN users u2 ON u2.id = c.user_2_id
             LEFT JOIN messages m ON m.id = c.last_message_id
             WHERE (c.user_1_id = $1 AND c.user_1_deleted = FALSE)
                OR (c.user_2_id = $1 AND c.user_2_deleted = FALSE)
@@ -414,9 +562,11 @@ impl MessageRepository {
                 m.id,
                 m.message_type,
                 m.sender_id,
-                sender.display_name as sender_name,
+                CASE WHEN m.message_type = 'system' THEN 'System'
+                    ELSE COALESCE(CASE WHEN sender.deleted_at IS NULL THEN sender.display_name END, 'Unknown player')
+                END as sender_name,
                 m.recipient_id,
-                recipient.display_name as recipient_name,
+                CASE WHEN recipient.deleted_at IS NULL THEN recipient.display_name END as recipient_name,
                 m.alliance_id,
                 NULL::VARCHAR as alliance_name,
                 m.subject,
@@ -424,7 +574,7 @@ impl MessageRepository {
                 m.is_read,
                 m.created_at
             FROM messages m
-            JOIN users sender ON sender.id = m.sender_id
+            LEFT JOIN users sender ON sender.id = m.sender_id
             LEFT JOIN users recipient ON recipient.id = m.recipient_id
             WHERE m.conversation_id = $1
                 AND (
@@ -468,6 +618,31 @@ impl MessageRepository {
         Ok(result.rows_affected() > 0)
     }
 
+    /// Mute or unmute a conversation for one of its participants
+    pub async fn set_conversation_muted(
+        pool: &PgPool,
+        conversation_id: Uuid,
+        user_id: Uuid,
+        muted: bool,
+    ) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE conversations
+            SET
+                user_1_muted = CASE WHEN user_1_id = $2 THEN $3 ELSE user_1_muted END,
+                user_2_muted = CASE WHEN user_2_id = $2 THEN $3 ELSE user_2_muted END
+            WHERE id = $1 AND (user_1_id = $2 OR user_2_id = $2)
+            "#,
+        )
+        .bind(conversation_id)
+        .bind(user_id)
+        .bind(muted)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     /// Check if user owns the message (sender or recipient)
     pub async fn user_can_access(pool: &PgPool, message_id: Uuid, user_id: Uuid) -> AppResult<bool> {
         let result: Option<(Uuid, MessageType, Option<Uuid>, Option<Uuid>)> = sqlx::query_as(
@@ -486,6 +661,7 @@ impl MessageRepository {
                 MessageType::Private => {
                     Ok(sender_id == Some(user_id) || recipient_id == Some(user_id))
                 }
+                MessageType::System => Ok(recipient_id == Some(user_id)),
                 MessageType::Alliance => {
                     // For alliance messages, check membership via service
                     Ok(true) // Will be validated in service
@@ -12,11 +12,13 @@ pub struct RankingRepository;
 impl RankingRepository {
     // ==================== Player Population Ranking ====================
 
-    /// Get players ranked by total population
+    /// Get players ranked by total population, optionally only those whose name matches `search`.
+    /// Ranks are computed before filtering so they stay global.
     pub async fn get_population_ranking(
         pool: &PgPool,
         limit: i64,
         offset: i64,
+        search: Option<&str>,
     ) -> AppResult<Vec<PlayerPopulationRanking>> {
         let rankings = sqlx::query_as::<_, PlayerPopulationRanking>(
             r#"
@@ -43,12 +45,14 @@ impl RankingRepository {
             )
             SELECT rank, user_id, display_name, alliance_tag, population, village_count
             FROM ranked
+            WHERE $3::TEXT IS NULL OR display_name ILIKE $3
             ORDER BY rank
             LIMIT $1 OFFSET $2
             "#,
         )
         .bind(limit)
         .bind(offset)
+        .bind(search.map(|q| format!("%{}%", q)))
         .fetch_all(pool)
         .await?;
 
@@ -73,11 +77,12 @@ impl RankingRepository {
 
     // ==================== Player Attack Ranking ====================
 
-    /// Get players ranked by attack points (troops killed as attacker)
+    /// Get players ranked by attack points (troops killed as attacker), optionally filtered by name
     pub async fn get_attack_ranking(
         pool: &PgPool,
         limit: i64,
         offset: i64,
+        search: Option<&str>,
     ) -> AppResult<Vec<PlayerAttackRanking>> {
         // Calculate attack points from battle reports
         // Attack points = sum of all troops killed (from defender_losses JSONB)
@@ -115,12 +120,14 @@ impl RankingRepository {
             )
             SELECT rank, user_id, display_name, alliance_tag, attack_points, battles_won
             FROM ranked
+            WHERE $3::TEXT IS NULL OR display_name ILIKE $3
             ORDER BY rank
             LIMIT $1 OFFSET $2
             "#,
         )
         .bind(limit)
         .bind(offset)
+        .bind(search.map(|q| format!("%{}%", q)))
         .fetch_all(pool)
         .await?;
 
@@ -144,11 +151,12 @@ impl RankingRepository {
 
     // ==================== Player Defense Ranking ====================
 
-    /// Get players ranked by defense points (troops killed as defender)
+    /// Get players ranked by defense points (troops killed as defender), optionally filtered by name
     pub async fn get_defense_ranking(
         pool: &PgPool,
         limit: i64,
         offset: i64,
+        search: Option<&str>,
     ) -> AppResult<Vec<PlayerDefenseRanking>> {
         let rankings = sqlx::query_as::<_, PlayerDefenseRanking>(
             r#"
@@ -184,12 +192,14 @@ impl RankingRepository {
             )
             SELECT rank, user_id, display_name, alliance_tag, defense_points, battles_defended
             FROM ranked
+            WHERE $3::TEXT IS NULL OR display_name ILIKE $3
             ORDER BY rank
             LIMIT $1 OFFSET $2
             "#,
         )
         .bind(limit)
         .bind(offset)
+        .bind(search.map(|q| format!("%{}%", q)))
         .fetch_all(pool)
         .await?;
 
@@ -213,11 +223,12 @@ impl RankingRepository {
 
     // ==================== Hero Ranking ====================
 
-    /// Get heroes ranked by level
+    /// Get heroes ranked by level, optionally filtered by hero or owner name
     pub async fn get_hero_ranking(
         pool: &PgPool,
         limit: i64,
         offset: i64,
+        search: Option<&str>,
     ) -> AppResult<Vec<HeroRanking>> {
         let rankings = sqlx::query_as::<_, HeroRanking>(
             r#"
@@ -237,12 +248,14 @@ impl RankingRepository {
             )
             SELECT rank, hero_id, hero_name, owner_id, owner_name, level, experience
             FROM ranked
+            WHERE $3::TEXT IS NULL OR hero_name ILIKE $3 OR owner_name ILIKE $3
             ORDER BY rank
             LIMIT $1 OFFSET $2
             "#,
         )
         .bind(limit)
         .bind(offset)
+        .bind(search.map(|q| format!("%{}%", q)))
         .fetch_all(pool)
         .await?;
 
@@ -268,11 +281,12 @@ impl RankingRepository {
 
     // ==================== Alliance Ranking ====================
 
-    /// Get alliances ranked by total population
+    /// Get alliances ranked by total population, optionally filtered by name or tag
     pub async fn get_alliance_ranking(
         pool: &PgPool,
         limit: i64,
         offset: i64,
+        search: Option<&str>,
     ) -> AppResult<Vec<AllianceRanking>> {
         let rankings = sqlx::query_as::<_, AllianceRanking>(
             r#"
@@ -300,12 +314,14 @@ impl RankingRepository {
             )
             SELECT rank, alliance_id, name, tag, member_count, total_population
             FROM ranked
+            WHERE $3::TEXT IS NULL OR name ILIKE $3 OR tag ILIKE $3
             ORDER BY rank
             LIMIT $1 OFFSET $2
             "#,
         )
         .bind(limit)
         .bind(offset)
+        .bind(search.map(|q| format!("%{}%", q)))
         .fetch_all(pool)
         .await?;
 
@@ -321,6 +337,32 @@ impl RankingRepository {
         Ok(count.0)
     }
 
+    // ==================== Player Points ====================
+
+    /// Get a player's attack and defense points, counted the same way as the rankings
+    pub async fn get_player_points(pool: &PgPool, user_id: Uuid) -> AppResult<(i64, i64)> {
+        let points: (i64, i64) = sqlx::query_as(
+            r#"
+            SELECT
+                COALESCE(SUM(
+                    (SELECT COALESCE(SUM((value::text)::int), 0)
+                     FROM jsonb_each(COALESCE(br.defender_losses, '{}'::jsonb)))
+                ) FILTER (WHERE br.attacker_player_id = $1), 0)::BIGINT as attack_points,
+                COALESCE(SUM(
+                    (SELECT COALESCE(SUM((value::text)::int), 0)
+                     FROM jsonb_each(COALESCE(br.attacker_losses, '{}'::jsonb)))
+                ) FILTER (WHERE br.defender_player_id = $1), 0)::BIGINT as defense_points
+            FROM battle_reports br
+            WHERE br.attacker_player_id = $1 OR br.defender_player_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(points)
+    }
+
     // ==================== Player Position ====================
 
     /// Get a specific player's rank by population
@@ -1,10 +1,11 @@
 use chrono::{DateTime, Duration, Utc};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction as DbTransaction};
 use uuid::Uuid;
 
 use crate::error::AppResult;
 use crate::models::shop::{
-    GoldFeature, GoldFeatureCost, GoldPackage, GoldUsage, SubscriptionPrice, SubscriptionType,
+    GoldFeature, GoldFeatureCost, GoldPackage, GoldUsage, GoldUsageFeatureSummary, SubscriptionPrice,
+    SubscriptionType,
     Transaction, TransactionStatus, TransactionType, UserSubscription,
 };
 
@@ -54,6 +55,24 @@ impl ShopRepository {
         Ok(result.0)
     }
 
+    /// Lock the user's row for a gold spend and return the current balance.
+    ///
+    /// NO KEY UPDATE serializes spends without blocking inserts elsewhere that
+    /// reference the user (transactions, gold_usage, subscriptions).
+    pub async fn lock_gold_balance_tx(
+        tx: &mut DbTransaction<'_, Postgres>,
+        user_id: Uuid,
+    ) -> AppResult<i32> {
+        let result: (i32,) = sqlx::query_as(
+            r#"SELECT gold_balance FROM users WHERE id = $1 FOR NO KEY UPDATE"#,
+        )
+        .bind(user_id)
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(result.0)
+    }
+
     /// Add gold to user's balance
     pub async fn add_gold(pool: &PgPool, user_id: Uuid, amount: i32) -> AppResult<i32> {
         let result: (i32,) = sqlx::query_as(
@@ -72,8 +91,34 @@ impl ShopRepository {
         Ok(result.0)
     }
 
-    /// Deduct gold from user's balance (returns new balance or error if insufficient)
-    pub async fn deduct_gold(pool: &PgPool, user_id: Uuid, amount: i32) -> AppResult<i32> {
+    /// Add gold to user's balance within a database transaction
+    pub async fn add_gold_tx(
+        tx: &mut DbTransaction<'_, Postgres>,
+        user_id: Uuid,
+        amount: i32,
+    ) -> AppResult<i32> {
+        let result: (i32,) = sqlx::query_as(
+            r#"
+            UPDATE users
+            SET gold_balance = gold_balance + $2
+            WHERE id = $1
+            RETURNING gold_balance
+            "#,
+        )
+        .bind(user_id)
+        .bind(amount)
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(result.0)
+    }
+
+    /// Deduct gold from the locked balance (returns new balance or error if insufficient)
+    pub async fn deduct_gold_tx(
+        tx: &mut DbTransaction<'_, Postgres>,
+        user_id: Uuid,
+        amount: i32,
+    ) -> AppResult<i32> {
         let result: (i32,) = sqlx::query_as(
             r#"
             UPDATE users
@@ -84,7 +129,7 @@ impl ShopRepository {
         )
         .bind(user_id)
         .bind(amount)
-        .fetch_one(pool)
+        .fetch_one(&mut **tx)
         .await?;
 
         Ok(result.0)
@@ -128,6 +173,31 @@ impl ShopRepository {
         Ok(tx)
     }
 
+    /// Create a new transaction within a database transaction
+    pub async fn create_transaction_tx(
+        tx: &mut DbTransaction<'_, Postgres>,
+        user_id: Uuid,
+        transaction_type: TransactionType,
+        gold_amount: i32,
+        description: Option<&str>,
+    ) -> AppResult<Transaction> {
+        let transaction = sqlx::query_as::<_, Transaction>(
+            r#"
+            INSERT INTO transactions (user_id, transaction_type, gold_amount, description)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+            "#,
+        )
+        .bind(user_id)
+        .bind(transaction_type)
+        .bind(gold_amount)
+        .bind(description)
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(transaction)
+    }
+
     /// Update transaction status
     pub async fn update_transaction_status(
         pool: &PgPool,
@@ -175,6 +245,86 @@ impl ShopRepository {
         Ok(tx)
     }
 
+    /// Record a Stripe webhook event as handled.
+    /// Returns false if the event id was seen before (a retried delivery).
+    pub async fn try_record_stripe_event(pool: &PgPool, event_id: &str, event_type: &str) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO stripe_events (event_id, event_type)
+            VALUES ($1, $2)
+            ON CONFLICT (event_id) DO NOTHING
+            "#,
+        )
+        .bind(event_id)
+        .bind(event_type)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Forget a Stripe event whose handling failed, so Stripe's retry runs it again
+    pub async fn forget_stripe_event(pool: &PgPool, event_id: &str) -> AppResult<()> {
+        sqlx::query(r#"DELETE FROM stripe_events WHERE event_id = $1"#)
+            .bind(event_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get transaction by Stripe payment intent ID
+    pub async fn get_transaction_by_payment_intent(
+        pool: &PgPool,
+        payment_intent_id: &str,
+    ) -> AppResult<Option<Transaction>> {
+        let tx = sqlx::query_as::<_, Transaction>(
+            r#"SELECT * FROM transactions WHERE stripe_payment_intent_id = $1"#,
+        )
+        .bind(payment_intent_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(tx)
+    }
+
+    /// Mark a completed transaction as refunded.
+    /// Returns false if it was not completed (e.g. already refunded).
+    pub async fn mark_transaction_refunded_tx(
+        tx: &mut DbTransaction<'_, Postgres>,
+        id: Uuid,
+    ) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE transactions
+            SET status = 'refunded'
+            WHERE id = $1 AND status = 'completed'
+            "#,
+        )
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Get a single transaction owned by the user
+    pub async fn get_transaction_by_id(
+        pool: &PgPool,
+        id: Uuid,
+        user_id: Uuid,
+    ) -> AppResult<Option<Transaction>> {
+        let tx = sqlx::query_as::<_, Transaction>(
+            r#"SELECT * FROM transactions WHERE id = $1 AND user_id = $2"#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(tx)
+    }
+
     /// Get user's transaction history
     pub async fn get_user_transactions(
         pool: &PgPool,
@@ -226,42 +376,114 @@ impl ShopRepository {
         Ok(sub)
     }
 
-    /// Create or extend subscription
-    pub async fn create_or_extend_subscription(
-        pool: &PgPool,
+    /// Get the active subscription within a transaction
+    pub async fn get_active_subscription_tx(
+        tx: &mut DbTransaction<'_, Postgres>,
+        user_id: Uuid,
+        subscription_type: SubscriptionType,
+    ) -> AppResult<Option<UserSubscription>> {
+        let sub = sqlx::query_as::<_, UserSubscription>(
+            r#"
+            SELECT * FROM user_subscriptions
+            WHERE user_id = $1
+                AND subscription_type = $2
+                AND is_active = TRUE
+                AND expires_at > NOW()
+            ORDER BY expires_at DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(user_id)
+        .bind(subscription_type)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(sub)
+    }
+
+    /// Create or extend subscription within a transaction
+    pub async fn create_or_extend_subscription_tx(
+        tx: &mut DbTransaction<'_, Postgres>,
         user_id: Uuid,
         subscription_type: SubscriptionType,
         duration_days: i32,
     ) -> AppResult<UserSubscription> {
         // Check if there's an existing active subscription
-        let existing = Self::get_active_subscription(pool, user_id, subscription_type).await?;
+        let existing = Self::get_active_subscription_tx(tx, user_id, subscription_type).await?;
 
-        let starts_at = Utc::now();
-        let expires_at = if let Some(existing) = existing {
+        let sub = if let Some(existing) = existing {
             // Extend from current expiry
-            existing.expires_at + Duration::days(duration_days as i64)
+            sqlx::query_as::<_, UserSubscription>(
+                r#"
+                UPDATE user_subscriptions
+                SET expires_at = $2, expiry_reminder_sent_at = NULL, updated_at = NOW()
+                WHERE id = $1
+                RETURNING *
+                "#,
+            )
+            .bind(existing.id)
+            .bind(existing.expires_at + Duration::days(duration_days as i64))
+            .fetch_one(&mut **tx)
+            .await?
         } else {
             // New subscription
-            starts_at + Duration::days(duration_days as i64)
+            let starts_at = Utc::now();
+            sqlx::query_as::<_, UserSubscription>(
+                r#"
+                INSERT INTO user_subscriptions (user_id, subscription_type, starts_at, expires_at)
+                VALUES ($1, $2, $3, $4)
+                RETURNING *
+                "#,
+            )
+            .bind(user_id)
+            .bind(subscription_type)
+            .bind(starts_at)
+            .bind(starts_at + Duration::days(duration_days as i64))
+            .fetch_one(&mut **tx)
+            .await?
         };
 
-        let sub = sqlx::query_as::<_, UserSubscription>(
+        Ok(sub)
+    }
+
+    /// Mark active subscriptions expiring within the given window as reminded,
+    /// returning the ones that hadn't been reminded yet
+    pub async fn mark_expiry_reminders(
+        pool: &PgPool,
+        within: Duration,
+    ) -> AppResult<Vec<UserSubscription>> {
+        let subs = sqlx::query_as::<_, UserSubscription>(
             r#"
-            INSERT INTO user_subscriptions (user_id, subscription_type, starts_at, expires_at)
-            VALUES ($1, $2, $3, $4)
-            ON CONFLICT (user_id, subscription_type) WHERE is_active = TRUE
-            DO UPDATE SET expires_at = $4, updated_at = NOW()
+            UPDATE user_subscriptions
+            SET expiry_reminder_sent_at = NOW(), updated_at = NOW()
+            WHERE is_active = TRUE
+                AND expires_at > NOW()
+                AND expires_at <= $1
+                AND expiry_reminder_sent_at IS NULL
             RETURNING *
             "#,
         )
-        .bind(user_id)
-        .bind(subscription_type)
-        .bind(starts_at)
-        .bind(expires_at)
-        .fetch_one(pool)
+        .bind(Utc::now() + within)
+        .fetch_all(pool)
         .await?;
 
-        Ok(sub)
+        Ok(subs)
+    }
+
+    /// Deactivate subscriptions whose expiry has passed
+    pub async fn deactivate_expired_subscriptions(pool: &PgPool) -> AppResult<Vec<UserSubscription>> {
+        let subs = sqlx::query_as::<_, UserSubscription>(
+            r#"
+            UPDATE user_subscriptions
+            SET is_active = FALSE, updated_at = NOW()
+            WHERE is_active = TRUE AND expires_at <= NOW()
+            RETURNING *
+            "#,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(subs)
     }
 
     /// Get subscription prices
@@ -285,6 +507,17 @@ impl ShopRepository {
 
     // ==================== Gold Usage ====================
 
+    /// Get all feature costs
+    pub async fn get_feature_costs(pool: &PgPool) -> AppResult<Vec<GoldFeatureCost>> {
+        let costs = sqlx::query_as::<_, GoldFeatureCost>(
+            r#"SELECT * FROM gold_feature_costs"#,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(costs)
+    }
+
     /// Get feature cost
     pub async fn get_feature_cost(
         pool: &PgPool,
@@ -300,9 +533,9 @@ impl ShopRepository {
         Ok(cost)
     }
 
-    /// Record gold usage
-    pub async fn record_gold_usage(
-        pool: &PgPool,
+    /// Record gold usage within a transaction
+    pub async fn record_gold_usage_tx(
+        tx: &mut DbTransaction<'_, Postgres>,
         user_id: Uuid,
         feature: GoldFeature,
         gold_spent: i32,
@@ -325,7 +558,7 @@ impl ShopRepository {
         .bind(target_id)
         .bind(effect_data)
         .bind(expires_at)
-        .fetch_one(pool)
+        .fetch_one(&mut **tx)
         .await?;
 
         Ok(usage)
@@ -406,33 +639,62 @@ impl ShopRepository {
         Ok(usage)
     }
 
-    /// Get production bonus multiplier for a village
-    pub async fn get_production_multiplier(
+    /// Get gold spent per feature since a point in time
+    pub async fn get_gold_usage_by_feature(
         pool: &PgPool,
         user_id: Uuid,
-        village_id: Uuid,
-        resource_type: &str,
-    ) -> AppResult<f64> {
-        let mut multiplier = 1.0;
+        since: DateTime<Utc>,
+    ) -> AppResult<Vec<GoldUsageFeatureSummary>> {
+        let summary = sqlx::query_as::<_, GoldUsageFeatureSummary>(
+            r#"
+            SELECT feature,
+                   COALESCE(SUM(gold_spent), 0)::BIGINT as total_spent,
+                   COUNT(*) as usage_count
+            FROM gold_usage
+            WHERE user_id = $1 AND created_at >= $2
+            GROUP BY feature
+            "#,
+        )
+        .bind(user_id)
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
 
-        // Check Travian Plus (+25%)
-        if Self::get_active_subscription(pool, user_id, SubscriptionType::TravianPlus)
-            .await?
-            .is_some()
-        {
-            multiplier += 0.25;
-        }
-
-        // Check specific production bonus (+25%)
-        if Self::has_active_production_bonus(pool, user_id, village_id, resource_type).await? {
-            multiplier += 0.25;
-        }
-
-        // Check Book of Wisdom (2x = +100%)
-        if Self::has_active_book_of_wisdom(pool, user_id, village_id).await? {
-            multiplier += 1.0;
-        }
-
-        Ok(multiplier)
+        Ok(summary)
+    }
+
+    /// Active production boosts of several villages as (village_id, source, resource_type) rows.
+    /// Sources are 'production_bonus' (for one resource), 'book_of_wisdom' and the owner's
+    /// 'travian_plus' subscription.
+    pub async fn get_active_production_effects(
+        pool: &PgPool,
+        village_ids: &[Uuid],
+    ) -> AppResult<Vec<(Uuid, String, Option<String>)>> {
+        let effects = sqlx::query_as(
+            r#"
+            SELECT v.id, g.feature::TEXT, g.effect_data->>'resource_type'
+            FROM villages v
+            JOIN gold_usage g ON g.target_id = v.id AND g.user_id = v.user_id
+            WHERE v.id = ANY($1)
+                AND g.feature IN ('production_bonus', 'book_of_wisdom')
+                AND g.expires_at > NOW()
+            UNION ALL
+            SELECT v.id, 'travian_plus', NULL
+            FROM villages v
+            WHERE v.id = ANY($1)
+                AND EXISTS (
+                    SELECT 1 FROM user_subscriptions s
+                    WHERE s.user_id = v.user_id
+                        AND s.subscription_type = 'travian_plus'
+                        AND s.is_active = TRUE
+                        AND s.expires_at > NOW()
+                )
+            "#,
+        )
+        .bind(village_ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(effects)
     }
 }
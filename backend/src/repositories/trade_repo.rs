@@ -1,52 +1,91 @@
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::error::AppResult;
+use crate::models::admin::TradeFlaggedPair;
 use crate::models::trade::{
-    ResourceLock, TradeOrder, TradeOrderStatus, TradeOrderType, TradeResourceType,
-    TradeTransaction,
+    GetOrdersQuery, NewOrder, NewTransaction, OrderBookLevel, OrderStatusCounts, PriceBucket, ResourceDelivery, ResourceLock, Resources, TradeOrder, TradeOrderStatus, TradeOrderType,
+    TradeOrderWithDetails, TradeResourceType, TradeTransaction, WashTradeFilter,
 };
 
+/// Fillable counter-orders a taker would cross, best price first (oldest first within a price).
+/// Expects `$1` resource, `$2` counter order type, `$3` limit price (NULL = any price),
+/// `$4` taker (own orders are skipped), `$5` taker's alliance (for alliance-only orders).
+const CROSSING_ORDERS: &str = r#"
+    SELECT * FROM trade_orders
+    WHERE resource_type = $1
+        AND order_type = $2
+        AND status IN ('open', 'partially_filled')
+        AND (expires_at IS NULL OR expires_at > NOW())
+        AND user_id != $4
+        AND ($3::INT IS NULL OR CASE WHEN order_type = 'sell'
+            THEN price_per_unit <= $3
+            ELSE price_per_unit >= $3
+        END)
+        AND (alliance_only = FALSE OR user_id IN (
+            SELECT user_id FROM alliance_members WHERE alliance_id = $5
+        ))
+    ORDER BY
+        CASE WHEN order_type = 'sell' THEN price_per_unit END ASC,
+        CASE WHEN order_type = 'buy' THEN price_per_unit END DESC,
+        priority_at ASC
+"#;
+
+/// Condition on `trade_transactions t` that drops likely wash trades.
+/// Expects `$2` = exclude same-IP trades, `$3` = max trades per player pair per 24h (0 = no limit).
+const WASH_TRADE_EXCLUSION: &str = r#"
+    NOT ($2 AND t.buyer_ip IS NOT NULL AND t.buyer_ip = t.seller_ip)
+    AND NOT EXISTS (
+        SELECT 1 FROM trade_flagged_pairs f
+        WHERE f.user_1_id = LEAST(t.buyer_id, t.seller_id)
+            AND f.user_2_id = GREATEST(t.buyer_id, t.seller_id)
+    )
+    AND ($3 = 0 OR (
+        SELECT COUNT(*) FROM trade_transactions p
+        WHERE LEAST(p.buyer_id, p.seller_id) = LEAST(t.buyer_id, t.seller_id)
+            AND GREATEST(p.buyer_id, p.seller_id) = GREATEST(t.buyer_id, t.seller_id)
+            AND p.created_at > t.created_at - INTERVAL '24 hours'
+            AND p.created_at <= t.created_at
+    ) <= $3)
+"#;
+
 pub struct TradeRepository;
 
 impl TradeRepository {
     // ==================== Trade Orders CRUD ====================
 
-    /// Create a new trade order
-    pub async fn create_order(
-        pool: &PgPool,
-        user_id: Uuid,
-        village_id: Uuid,
-        order_type: TradeOrderType,
-        resource_type: TradeResourceType,
-        quantity: i32,
-        price_per_unit: i32,
-        expires_in_hours: Option<i32>,
+    /// Create a new trade order within a transaction
+    pub async fn create_order_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        order: NewOrder<'_>,
     ) -> AppResult<TradeOrder> {
-        let expires_at = expires_in_hours.map(|hours| Utc::now() + Duration::hours(hours as i64));
+        let expires_at = order.expires_in_hours.map(|hours| Utc::now() + Duration::hours(hours as i64));
 
-        let order = sqlx::query_as::<_, TradeOrder>(
+        let created = sqlx::query_as::<_, TradeOrder>(
             r#"
             INSERT INTO trade_orders (
                 user_id, village_id, order_type, resource_type,
-                quantity, price_per_unit, expires_at
+                quantity, price_per_unit, expires_at, alliance_only, client_ip, is_taker_fill
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
             RETURNING *
             "#,
         )
-        .bind(user_id)
-        .bind(village_id)
-        .bind(order_type)
-        .bind(resource_type)
-        .bind(quantity)
-        .bind(price_per_unit)
+        .bind(order.user_id)
+        .bind(order.village_id)
+        .bind(order.order_type)
+        .bind(order.resource_type)
+        .bind(order.quantity)
+        .bind(order.price_per_unit)
         .bind(expires_at)
-        .fetch_one(pool)
+        .bind(order.alliance_only)
+        .bind(order.client_ip)
+        .bind(order.is_taker_fill)
+        .fetch_one(&mut **tx)
         .await?;
 
-        Ok(order)
+        Ok(created)
     }
 
     /// Get order by ID
@@ -61,6 +100,33 @@ impl TradeRepository {
         Ok(order)
     }
 
+    /// Get order by ID with the owning village and player for display.
+    /// If the village or player is gone (or the player deleted their account) the
+    /// order is still returned, with blank details.
+    pub async fn get_order_with_details(
+        pool: &PgPool,
+        id: Uuid,
+    ) -> AppResult<Option<TradeOrderWithDetails>> {
+        let order = sqlx::query_as::<_, TradeOrderWithDetails>(
+            r#"
+            SELECT o.*,
+                   COALESCE(v.name, '') AS village_name,
+                   COALESCE(v.x, 0) AS village_x,
+                   COALESCE(v.y, 0) AS village_y,
+                   CASE WHEN u.deleted_at IS NULL THEN u.display_name END AS user_display_name
+            FROM trade_orders o
+            LEFT JOIN villages v ON v.id = o.village_id
+            LEFT JOIN users u ON u.id = o.user_id
+            WHERE o.id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(order)
+    }
+
     /// Get order by ID with row lock (FOR UPDATE) - for use within transaction
     pub async fn get_order_for_update(
         tx: &mut Transaction<'_, Postgres>,
@@ -192,6 +258,36 @@ impl TradeRepository {
         Ok(order)
     }
 
+    /// Change an order's quantity and price within a transaction.
+    /// `reset_priority` moves the order to the back of the queue at its price.
+    pub async fn update_order_terms_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        id: Uuid,
+        quantity: i32,
+        price_per_unit: i32,
+        reset_priority: bool,
+    ) -> AppResult<TradeOrder> {
+        let order = sqlx::query_as::<_, TradeOrder>(
+            r#"
+            UPDATE trade_orders
+            SET quantity = $2,
+                price_per_unit = $3,
+                priority_at = CASE WHEN $4 THEN NOW() ELSE priority_at END,
+                updated_at = NOW()
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(quantity)
+        .bind(price_per_unit)
+        .bind(reset_priority)
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(order)
+    }
+
     /// Update order filled quantity and status within a transaction
     pub async fn update_order_filled_tx(
         tx: &mut Transaction<'_, Postgres>,
@@ -366,6 +462,188 @@ impl TradeRepository {
         Ok(lock)
     }
 
+    /// Shrink an active lock after part of it was handed over.
+    /// The caller releases the lock instead when nothing would be left.
+    pub async fn reduce_resource_lock_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        lock_type: &str,
+        reference_id: Uuid,
+        amount: &Resources,
+    ) -> AppResult<Option<ResourceLock>> {
+        let lock = sqlx::query_as::<_, ResourceLock>(
+            r#"
+            UPDATE resource_locks
+            SET wood = GREATEST(wood - $3, 0),
+                clay = GREATEST(clay - $4, 0),
+                iron = GREATEST(iron - $5, 0),
+                crop = GREATEST(crop - $6, 0)
+            WHERE lock_type = $1 AND reference_id = $2 AND released_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(lock_type)
+        .bind(reference_id)
+        .bind(amount.wood)
+        .bind(amount.clay)
+        .bind(amount.iron)
+        .bind(amount.crop)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(lock)
+    }
+
+    /// Grow an active lock, e.g. when its order's quantity is raised
+    pub async fn increase_resource_lock_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        lock_type: &str,
+        reference_id: Uuid,
+        amount: &Resources,
+    ) -> AppResult<Option<ResourceLock>> {
+        let lock = sqlx::query_as::<_, ResourceLock>(
+            r#"
+            UPDATE resource_locks
+            SET wood = wood + $3,
+                clay = clay + $4,
+                iron = iron + $5,
+                crop = crop + $6
+            WHERE lock_type = $1 AND reference_id = $2 AND released_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(lock_type)
+        .bind(reference_id)
+        .bind(amount.wood)
+        .bind(amount.clay)
+        .bind(amount.iron)
+        .bind(amount.crop)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(lock)
+    }
+
+    // ==================== Resource Deliveries ====================
+
+    /// Send a trade's resources on their way to the buyer
+    pub async fn create_delivery_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        transaction: &TradeTransaction,
+        arrives_at: DateTime<Utc>,
+    ) -> AppResult<ResourceDelivery> {
+        let delivery = sqlx::query_as::<_, ResourceDelivery>(
+            r#"
+            INSERT INTO resource_deliveries (
+                trade_transaction_id, from_village_id, to_village_id, recipient_id,
+                resource_type, quantity, arrives_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+        )
+        .bind(transaction.id)
+        .bind(transaction.seller_village_id)
+        .bind(transaction.buyer_village_id)
+        .bind(transaction.buyer_id)
+        .bind(transaction.resource_type)
+        .bind(transaction.quantity)
+        .bind(arrives_at)
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(delivery)
+    }
+
+    /// Mark up to `limit` arrived deliveries as delivered and return them.
+    /// Rows another worker is already handling are skipped.
+    pub async fn take_arrived_deliveries_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        limit: i32,
+    ) -> AppResult<Vec<ResourceDelivery>> {
+        let deliveries = sqlx::query_as::<_, ResourceDelivery>(
+            r#"
+            UPDATE resource_deliveries
+            SET delivered_at = NOW()
+            WHERE id IN (
+                SELECT id FROM resource_deliveries
+                WHERE delivered_at IS NULL AND arrives_at <= NOW()
+                ORDER BY arrives_at ASC
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&mut **tx)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    /// Deliveries from a village that are still on the road
+    pub async fn get_outgoing_deliveries(
+        pool: &PgPool,
+        village_id: Uuid,
+    ) -> AppResult<Vec<ResourceDelivery>> {
+        let deliveries = sqlx::query_as::<_, ResourceDelivery>(
+            r#"
+            SELECT * FROM resource_deliveries
+            WHERE from_village_id = $1 AND delivered_at IS NULL
+            "#,
+        )
+        .bind(village_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    /// Send deliveries still on their way to `village_id` to `new_village_id` instead
+    pub async fn redirect_incoming_deliveries_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        village_id: Uuid,
+        new_village_id: Uuid,
+    ) -> AppResult<u64> {
+        let redirected = sqlx::query(
+            r#"
+            UPDATE resource_deliveries
+            SET to_village_id = $2
+            WHERE to_village_id = $1 AND delivered_at IS NULL
+            "#,
+        )
+        .bind(village_id)
+        .bind(new_village_id)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected();
+
+        Ok(redirected)
+    }
+
+    /// Call off deliveries still on their way to `village_id`.
+    /// Returns each one's recipient with the gold they paid for it.
+    pub async fn cancel_incoming_deliveries_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        village_id: Uuid,
+    ) -> AppResult<Vec<(Uuid, i64)>> {
+        let cancelled = sqlx::query_as::<_, (Uuid, i64)>(
+            r#"
+            DELETE FROM resource_deliveries d
+            USING trade_transactions t
+            WHERE t.id = d.trade_transaction_id
+                AND d.to_village_id = $1
+                AND d.delivered_at IS NULL
+            RETURNING d.recipient_id, t.total_gold
+            "#,
+        )
+        .bind(village_id)
+        .fetch_all(&mut **tx)
+        .await?;
+
+        Ok(cancelled)
+    }
+
     /// Get total locked resources for a village
     pub async fn get_village_locked_resources(
         pool: &PgPool,
@@ -424,13 +702,12 @@ impl TradeRepository {
 
     // ==================== Query Functions ====================
 
-    /// Get open orders with optional filters
+    /// Get open orders with optional filters.
+    /// Alliance-only orders are included only when they belong to a member of `viewer_alliance_id`.
     pub async fn get_open_orders(
         pool: &PgPool,
-        resource_type: Option<TradeResourceType>,
-        order_type: Option<TradeOrderType>,
-        min_price: Option<i32>,
-        max_price: Option<i32>,
+        filter: &GetOrdersQuery,
+        viewer_alliance_id: Option<Uuid>,
         limit: i32,
         offset: i32,
     ) -> AppResult<Vec<TradeOrder>> {
@@ -443,19 +720,27 @@ impl TradeRepository {
                 AND ($2::trade_order_type IS NULL OR order_type = $2)
                 AND ($3::INT IS NULL OR price_per_unit >= $3)
                 AND ($4::INT IS NULL OR price_per_unit <= $4)
+                AND ($5::UUID IS NULL OR village_id = $5)
+                AND ($6::UUID IS NULL OR user_id != $6)
+                AND (alliance_only = FALSE OR user_id IN (
+                    SELECT user_id FROM alliance_members WHERE alliance_id = $9
+                ))
             ORDER BY
                 CASE WHEN order_type = 'sell' THEN price_per_unit END ASC,
                 CASE WHEN order_type = 'buy' THEN price_per_unit END DESC,
-                created_at ASC
-            LIMIT $5 OFFSET $6
+                priority_at ASC
+            LIMIT $7 OFFSET $8
             "#,
         )
-        .bind(resource_type)
-        .bind(order_type)
-        .bind(min_price)
-        .bind(max_price)
+        .bind(filter.resource_type)
+        .bind(filter.order_type)
+        .bind(filter.min_price)
+        .bind(filter.max_price)
+        .bind(filter.village_id)
+        .bind(filter.exclude_user_id)
         .bind(limit)
         .bind(offset)
+        .bind(viewer_alliance_id)
         .fetch_all(pool)
         .await?;
 
@@ -465,10 +750,8 @@ impl TradeRepository {
     /// Count open orders with optional filters
     pub async fn count_open_orders(
         pool: &PgPool,
-        resource_type: Option<TradeResourceType>,
-        order_type: Option<TradeOrderType>,
-        min_price: Option<i32>,
-        max_price: Option<i32>,
+        filter: &GetOrdersQuery,
+        viewer_alliance_id: Option<Uuid>,
     ) -> AppResult<i64> {
         let result: (i64,) = sqlx::query_as(
             r#"
@@ -479,19 +762,27 @@ impl TradeRepository {
                 AND ($2::trade_order_type IS NULL OR order_type = $2)
                 AND ($3::INT IS NULL OR price_per_unit >= $3)
                 AND ($4::INT IS NULL OR price_per_unit <= $4)
+                AND ($5::UUID IS NULL OR village_id = $5)
+                AND ($6::UUID IS NULL OR user_id != $6)
+                AND (alliance_only = FALSE OR user_id IN (
+                    SELECT user_id FROM alliance_members WHERE alliance_id = $7
+                ))
             "#,
         )
-        .bind(resource_type)
-        .bind(order_type)
-        .bind(min_price)
-        .bind(max_price)
+        .bind(filter.resource_type)
+        .bind(filter.order_type)
+        .bind(filter.min_price)
+        .bind(filter.max_price)
+        .bind(filter.village_id)
+        .bind(filter.exclude_user_id)
+        .bind(viewer_alliance_id)
         .fetch_one(pool)
         .await?;
 
         Ok(result.0)
     }
 
-    /// Get user's orders with optional status filter
+    /// Get user's orders with optional status filter, leaving out taker fill records
     pub async fn get_user_orders(
         pool: &PgPool,
         user_id: Uuid,
@@ -501,6 +792,7 @@ impl TradeRepository {
             r#"
             SELECT * FROM trade_orders
             WHERE user_id = $1
+                AND is_taker_fill = FALSE
                 AND ($2::trade_order_status IS NULL OR status = $2)
             ORDER BY created_at DESC
             "#,
@@ -513,6 +805,95 @@ impl TradeRepository {
         Ok(orders)
     }
 
+    /// Count user's orders per status in a single pass; absent statuses count as zero
+    pub async fn count_user_orders_by_status(
+        pool: &PgPool,
+        user_id: Uuid,
+    ) -> AppResult<OrderStatusCounts> {
+        let counts = sqlx::query_as::<_, OrderStatusCounts>(
+            r#"
+            SELECT
+                COUNT(*) FILTER (WHERE status = 'open') AS open,
+                COUNT(*) FILTER (WHERE status = 'partially_filled') AS partially_filled,
+                COUNT(*) FILTER (WHERE status = 'filled') AS filled,
+                COUNT(*) FILTER (WHERE status = 'cancelled') AS cancelled,
+                COUNT(*) FILTER (WHERE status = 'expired') AS expired,
+                COUNT(*) AS total
+            FROM trade_orders
+            WHERE user_id = $1 AND is_taker_fill = FALSE
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(counts)
+    }
+
+    /// Lock a village's still-open orders within a transaction
+    pub async fn get_open_village_orders_for_update(
+        tx: &mut Transaction<'_, Postgres>,
+        village_id: Uuid,
+    ) -> AppResult<Vec<TradeOrder>> {
+        let orders = sqlx::query_as::<_, TradeOrder>(
+            r#"
+            SELECT * FROM trade_orders
+            WHERE village_id = $1 AND status IN ('open', 'partially_filled')
+            FOR UPDATE
+            "#,
+        )
+        .bind(village_id)
+        .fetch_all(&mut **tx)
+        .await?;
+
+        Ok(orders)
+    }
+
+    /// Lock the fillable counter-orders an incoming order at `limit_price` would cross,
+    /// best price first (oldest first within a price).
+    /// Alliance-only orders are included only when they belong to a member of `viewer_alliance_id`.
+    pub async fn get_crossing_orders_for_update(
+        tx: &mut Transaction<'_, Postgres>,
+        resource_type: TradeResourceType,
+        counter_type: TradeOrderType,
+        limit_price: i32,
+        exclude_user_id: Uuid,
+        viewer_alliance_id: Option<Uuid>,
+    ) -> AppResult<Vec<TradeOrder>> {
+        let orders = sqlx::query_as::<_, TradeOrder>(&format!("{} FOR UPDATE", CROSSING_ORDERS))
+            .bind(resource_type)
+            .bind(counter_type)
+            .bind(limit_price)
+            .bind(exclude_user_id)
+            .bind(viewer_alliance_id)
+            .fetch_all(&mut **tx)
+            .await?;
+
+        Ok(orders)
+    }
+
+    /// Read-only snapshot of the counter-orders a taker could fill, for quotes.
+    /// Same ordering and visibility as `get_crossing_orders_for_update`.
+    pub async fn get_crossing_orders(
+        pool: &PgPool,
+        resource_type: TradeResourceType,
+        counter_type: TradeOrderType,
+        limit_price: Option<i32>,
+        exclude_user_id: Uuid,
+        viewer_alliance_id: Option<Uuid>,
+    ) -> AppResult<Vec<TradeOrder>> {
+        let orders = sqlx::query_as::<_, TradeOrder>(CROSSING_ORDERS)
+            .bind(resource_type)
+            .bind(counter_type)
+            .bind(limit_price)
+            .bind(exclude_user_id)
+            .bind(viewer_alliance_id)
+            .fetch_all(pool)
+            .await?;
+
+        Ok(orders)
+    }
+
     /// Get orders for a specific village
     pub async fn get_village_orders(
         pool: &PgPool,
@@ -521,7 +902,7 @@ impl TradeRepository {
         let orders = sqlx::query_as::<_, TradeOrder>(
             r#"
             SELECT * FROM trade_orders
-            WHERE village_id = $1
+            WHERE village_id = $1 AND is_taker_fill = FALSE
             ORDER BY created_at DESC
             "#,
         )
@@ -548,50 +929,54 @@ impl TradeRepository {
         Ok(result.0)
     }
 
-    /// Get best buy price for a resource (highest buy offer)
-    pub async fn get_best_buy_price(
+    /// Public order book aggregated by price, up to `levels` prices per side.
+    /// Returns (bids highest first, asks lowest first); only fillable, non-alliance orders count.
+    pub async fn get_order_book(
         pool: &PgPool,
         resource_type: TradeResourceType,
-    ) -> AppResult<Option<i32>> {
-        let result: Option<(i32,)> = sqlx::query_as(
+        levels: i32,
+    ) -> AppResult<(Vec<OrderBookLevel>, Vec<OrderBookLevel>)> {
+        let rows: Vec<(TradeOrderType, i32, i64, i64)> = sqlx::query_as(
             r#"
-            SELECT price_per_unit FROM trade_orders
-            WHERE resource_type = $1
-                AND order_type = 'buy'
-                AND status = 'open'
-                AND (expires_at IS NULL OR expires_at > NOW())
-            ORDER BY price_per_unit DESC
-            LIMIT 1
+            SELECT order_type, price_per_unit, quantity, order_count FROM (
+                SELECT order_type, price_per_unit,
+                       SUM(quantity - quantity_filled)::BIGINT AS quantity,
+                       COUNT(*) AS order_count,
+                       ROW_NUMBER() OVER (
+                           PARTITION BY order_type
+                           ORDER BY CASE WHEN order_type = 'buy' THEN -price_per_unit ELSE price_per_unit END
+                       ) AS level
+                FROM trade_orders
+                WHERE resource_type = $1
+                    AND status IN ('open', 'partially_filled')
+                    AND alliance_only = FALSE
+                    AND (expires_at IS NULL OR expires_at > NOW())
+                GROUP BY order_type, price_per_unit
+            ) book
+            WHERE level <= $2
+            ORDER BY level ASC
             "#,
         )
         .bind(resource_type)
-        .fetch_optional(pool)
+        .bind(levels)
+        .fetch_all(pool)
         .await?;
 
-        Ok(result.map(|r| r.0))
-    }
-
-    /// Get best sell price for a resource (lowest sell offer)
-    pub async fn get_best_sell_price(
-        pool: &PgPool,
-        resource_type: TradeResourceType,
-    ) -> AppResult<Option<i32>> {
-        let result: Option<(i32,)> = sqlx::query_as(
-            r#"
-            SELECT price_per_unit FROM trade_orders
-            WHERE resource_type = $1
-                AND order_type = 'sell'
-                AND status = 'open'
-                AND (expires_at IS NULL OR expires_at > NOW())
-            ORDER BY price_per_unit ASC
-            LIMIT 1
-            "#,
-        )
-        .bind(resource_type)
-        .fetch_optional(pool)
-        .await?;
+        let mut bids = Vec::new();
+        let mut asks = Vec::new();
+        for (order_type, price_per_unit, quantity, order_count) in rows {
+            let level = OrderBookLevel {
+                price_per_unit,
+                quantity,
+                order_count,
+            };
+            match order_type {
+                TradeOrderType::Buy => bids.push(level),
+                TradeOrderType::Sell => asks.push(level),
+            }
+        }
 
-        Ok(result.map(|r| r.0))
+        Ok((bids, asks))
     }
 
     /// Get expired orders that need to be processed
@@ -648,7 +1033,7 @@ impl TradeRepository {
         quantity: i32,
         price_per_unit: i32,
     ) -> AppResult<TradeTransaction> {
-        let total_gold = quantity * price_per_unit;
+        let total_gold = (quantity as i64) * (price_per_unit as i64);
 
         let tx = sqlx::query_as::<_, TradeTransaction>(
             r#"
@@ -680,39 +1065,32 @@ impl TradeRepository {
     /// Create a trade transaction within a database transaction
     pub async fn create_transaction_tx(
         tx: &mut Transaction<'_, Postgres>,
-        buy_order_id: Uuid,
-        sell_order_id: Uuid,
-        buyer_id: Uuid,
-        seller_id: Uuid,
-        buyer_village_id: Uuid,
-        seller_village_id: Uuid,
-        resource_type: TradeResourceType,
-        quantity: i32,
-        price_per_unit: i32,
+        trade: NewTransaction,
     ) -> AppResult<TradeTransaction> {
-        let total_gold = quantity * price_per_unit;
+        let total_gold = (trade.quantity as i64) * (trade.price_per_unit as i64);
 
         let trade_tx = sqlx::query_as::<_, TradeTransaction>(
             r#"
             INSERT INTO trade_transactions (
                 buy_order_id, sell_order_id, buyer_id, seller_id,
                 buyer_village_id, seller_village_id, resource_type,
-                quantity, price_per_unit, total_gold
+                quantity, price_per_unit, total_gold, fee_gold
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
             RETURNING *
             "#,
         )
-        .bind(buy_order_id)
-        .bind(sell_order_id)
-        .bind(buyer_id)
-        .bind(seller_id)
-        .bind(buyer_village_id)
-        .bind(seller_village_id)
-        .bind(resource_type)
-        .bind(quantity)
-        .bind(price_per_unit)
+        .bind(trade.buy_order_id)
+        .bind(trade.sell_order_id)
+        .bind(trade.buyer_id)
+        .bind(trade.seller_id)
+        .bind(trade.buyer_village_id)
+        .bind(trade.seller_village_id)
+        .bind(trade.resource_type)
+        .bind(trade.quantity)
+        .bind(trade.price_per_unit)
         .bind(total_gold)
+        .bind(trade.fee_gold)
         .fetch_one(&mut **tx)
         .await?;
 
@@ -773,49 +1151,209 @@ impl TradeRepository {
         Ok(tx)
     }
 
-    /// Get last trade price for a resource type
+    /// Get last trade price for a resource type, skipping likely wash trades
     pub async fn get_last_trade_price(
         pool: &PgPool,
         resource_type: TradeResourceType,
+        filter: &WashTradeFilter,
     ) -> AppResult<Option<i32>> {
-        let result: Option<(i32,)> = sqlx::query_as(
+        let result: Option<(i32,)> = sqlx::query_as(&format!(
             r#"
-            SELECT price_per_unit FROM trade_transactions
-            WHERE resource_type = $1
-            ORDER BY created_at DESC
+            SELECT t.price_per_unit FROM trade_transactions t
+            WHERE t.resource_type = $1
+                AND {}
+            ORDER BY t.created_at DESC
             LIMIT 1
             "#,
-        )
+            WASH_TRADE_EXCLUSION
+        ))
         .bind(resource_type)
+        .bind(filter.exclude_same_ip)
+        .bind(filter.max_pair_trades_per_day)
         .fetch_optional(pool)
         .await?;
 
         Ok(result.map(|r| r.0))
     }
 
-    /// Get 24h trading volume for a resource type
+    /// Last trade price strictly before `before`, skipping likely wash trades
+    pub async fn get_last_trade_price_before(
+        pool: &PgPool,
+        resource_type: TradeResourceType,
+        before: DateTime<Utc>,
+        filter: &WashTradeFilter,
+    ) -> AppResult<Option<i32>> {
+        let result: Option<(i32,)> = sqlx::query_as(&format!(
+            r#"
+            SELECT t.price_per_unit FROM trade_transactions t
+            WHERE t.resource_type = $1
+                AND t.created_at < $4
+                AND {}
+            ORDER BY t.created_at DESC
+            LIMIT 1
+            "#,
+            WASH_TRADE_EXCLUSION
+        ))
+        .bind(resource_type)
+        .bind(filter.exclude_same_ip)
+        .bind(filter.max_pair_trades_per_day)
+        .bind(before)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(result.map(|r| r.0))
+    }
+
+    /// The last `limit` buckets of `interval_minutes` up to now, oldest first.
+    /// Buckets are aligned to multiples of the interval since the Unix epoch (UTC);
+    /// every bucket is returned, with no prices where nothing traded.
+    pub async fn get_price_candles(
+        pool: &PgPool,
+        resource_type: TradeResourceType,
+        interval_minutes: i32,
+        limit: i32,
+        filter: &WashTradeFilter,
+    ) -> AppResult<Vec<PriceBucket>> {
+        let buckets = sqlx::query_as::<_, PriceBucket>(&format!(
+            r#"
+            WITH buckets AS (
+                SELECT generate_series(
+                    to_timestamp(floor(extract(epoch FROM NOW()) / $4::INT) * $4::INT)
+                        - ($5::INT - 1) * $4::INT * INTERVAL '1 second',
+                    to_timestamp(floor(extract(epoch FROM NOW()) / $4::INT) * $4::INT),
+                    $4::INT * INTERVAL '1 second'
+                ) AS bucket_start
+            ),
+            trades AS (
+                SELECT
+                    to_timestamp(floor(extract(epoch FROM t.created_at) / $4::INT) * $4::INT) AS bucket_start,
+                    (ARRAY_AGG(t.price_per_unit ORDER BY t.created_at ASC, t.id ASC))[1] AS open,
+                    MAX(t.price_per_unit) AS high,
+                    MIN(t.price_per_unit) AS low,
+                    (ARRAY_AGG(t.price_per_unit ORDER BY t.created_at DESC, t.id DESC))[1] AS close,
+                    SUM(t.quantity)::BIGINT AS volume
+                FROM trade_transactions t
+                WHERE t.resource_type = $1
+                    AND t.created_at >= (SELECT MIN(bucket_start) FROM buckets)
+                    AND {}
+                GROUP BY 1
+            )
+            SELECT b.bucket_start, tr.open, tr.high, tr.low, tr.close,
+                   COALESCE(tr.volume, 0) AS volume
+            FROM buckets b
+            LEFT JOIN trades tr ON tr.bucket_start = b.bucket_start
+            ORDER BY b.bucket_start ASC
+            "#,
+            WASH_TRADE_EXCLUSION
+        ))
+        .bind(resource_type)
+        .bind(filter.exclude_same_ip)
+        .bind(filter.max_pair_trades_per_day)
+        .bind(interval_minutes * 60)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(buckets)
+    }
+
+    /// Get 24h trading volume for a resource type, skipping likely wash trades
     pub async fn get_24h_volume(
         pool: &PgPool,
         resource_type: TradeResourceType,
+        filter: &WashTradeFilter,
     ) -> AppResult<(i64, i64)> {
         // Returns (total_quantity, trade_count)
-        let result: (Option<i64>, i64) = sqlx::query_as(
+        let result: (Option<i64>, i64) = sqlx::query_as(&format!(
             r#"
             SELECT
-                COALESCE(SUM(quantity), 0),
+                COALESCE(SUM(t.quantity), 0),
                 COUNT(*)
-            FROM trade_transactions
-            WHERE resource_type = $1
-                AND created_at > NOW() - INTERVAL '24 hours'
+            FROM trade_transactions t
+            WHERE t.resource_type = $1
+                AND t.created_at > NOW() - INTERVAL '24 hours'
+                AND {}
             "#,
-        )
+            WASH_TRADE_EXCLUSION
+        ))
         .bind(resource_type)
+        .bind(filter.exclude_same_ip)
+        .bind(filter.max_pair_trades_per_day)
         .fetch_one(pool)
         .await?;
 
         Ok((result.0.unwrap_or(0), result.1))
     }
 
+    /// Store both parties' client IPs on a trade, for wash trade detection
+    pub async fn record_transaction_ips_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        transaction_id: Uuid,
+        buyer_ip: Option<&str>,
+        seller_ip: Option<&str>,
+    ) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE trade_transactions
+            SET buyer_ip = $2, seller_ip = $3
+            WHERE id = $1
+            "#,
+        )
+        .bind(transaction_id)
+        .bind(buyer_ip)
+        .bind(seller_ip)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    // ==================== Flagged Pairs ====================
+
+    /// Flag two players so their trades with each other stay out of market prices
+    pub async fn flag_pair(
+        pool: &PgPool,
+        user_a_id: Uuid,
+        user_b_id: Uuid,
+        reason: Option<&str>,
+        flagged_by: Uuid,
+    ) -> AppResult<TradeFlaggedPair> {
+        let pair = sqlx::query_as::<_, TradeFlaggedPair>(
+            r#"
+            INSERT INTO trade_flagged_pairs (user_1_id, user_2_id, reason, flagged_by)
+            VALUES (LEAST($1::UUID, $2::UUID), GREATEST($1::UUID, $2::UUID), $3, $4)
+            ON CONFLICT (user_1_id, user_2_id) DO UPDATE
+            SET reason = EXCLUDED.reason, flagged_by = EXCLUDED.flagged_by
+            RETURNING user_1_id, user_2_id, reason, flagged_by, created_at
+            "#,
+        )
+        .bind(user_a_id)
+        .bind(user_b_id)
+        .bind(reason)
+        .bind(flagged_by)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(pair)
+    }
+
+    /// Remove a flagged pair. Returns false when the pair wasn't flagged.
+    pub async fn unflag_pair(pool: &PgPool, user_a_id: Uuid, user_b_id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM trade_flagged_pairs
+            WHERE user_1_id = LEAST($1::UUID, $2::UUID)
+                AND user_2_id = GREATEST($1::UUID, $2::UUID)
+            "#,
+        )
+        .bind(user_a_id)
+        .bind(user_b_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     /// Get recent transactions (for market activity display)
     pub async fn get_recent_transactions(
         pool: &PgPool,
@@ -1,5 +1,5 @@
 use chrono::{DateTime, Utc};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::error::AppResult;
@@ -65,33 +65,13 @@ impl TroopRepository {
         Ok(troops)
     }
 
-    pub async fn find_by_village_and_type(
-        pool: &PgPool,
-        village_id: Uuid,
-        troop_type: TroopType,
-    ) -> AppResult<Option<Troop>> {
-        let troop = sqlx::query_as::<_, Troop>(
-            r#"
-            SELECT id, village_id, troop_type, count, in_village, created_at, updated_at
-            FROM troops
-            WHERE village_id = $1 AND troop_type = $2
-            "#,
-        )
-        .bind(village_id)
-        .bind(&troop_type)
-        .fetch_optional(pool)
-        .await?;
-
-        Ok(troop)
-    }
-
-    pub async fn add_troops(
-        pool: &PgPool,
+    /// Add troops inside the caller's transaction
+    pub async fn add_troops_tx(
+        tx: &mut Transaction<'_, Postgres>,
         village_id: Uuid,
         troop_type: TroopType,
         count: i32,
     ) -> AppResult<Troop> {
-        // Upsert: create or update troop count
         let troop = sqlx::query_as::<_, Troop>(
             r#"
             INSERT INTO troops (village_id, troop_type, count, in_village)
@@ -107,7 +87,7 @@ impl TroopRepository {
         .bind(village_id)
         .bind(&troop_type)
         .bind(count)
-        .fetch_one(pool)
+        .fetch_one(&mut **tx)
         .await?;
 
         Ok(troop)
@@ -207,8 +187,8 @@ impl TroopRepository {
         Ok(queue)
     }
 
-    pub async fn add_to_queue(
-        pool: &PgPool,
+    pub async fn add_to_queue_tx(
+        tx: &mut Transaction<'_, Postgres>,
         village_id: Uuid,
         troop_type: TroopType,
         count: i32,
@@ -229,7 +209,7 @@ impl TroopRepository {
         .bind(each_duration_seconds)
         .bind(started_at)
         .bind(ends_at)
-        .fetch_one(pool)
+        .fetch_one(&mut **tx)
         .await?;
 
         Ok(queue_entry)
@@ -261,20 +241,63 @@ impl TroopRepository {
         Ok(queue)
     }
 
-    /// Complete training instantly (for Finish Now feature)
-    pub async fn complete_training(pool: &PgPool, queue_id: Uuid) -> AppResult<()> {
-        // Get queue entry
-        let queue = Self::find_queue_by_id(pool, queue_id)
-            .await?
-            .ok_or_else(|| crate::error::AppError::NotFound("Queue entry not found".into()))?;
+    /// Finish a queue entry: remove it and add its troops to the village.
+    /// Returns None if the entry was already completed.
+    pub async fn complete_training(pool: &PgPool, queue_id: Uuid) -> AppResult<Option<TroopQueue>> {
+        let mut tx = pool.begin().await?;
+        let queue = Self::complete_training_tx(&mut tx, queue_id).await?;
+        tx.commit().await?;
 
-        // Add troops to village
-        Self::add_troops(pool, queue.village_id, queue.troop_type.clone(), queue.count).await?;
+        Ok(queue)
+    }
 
-        // Remove from queue
-        Self::remove_from_queue(pool, queue_id).await?;
+    /// Finish a queue entry inside the caller's transaction. Deleting the entry first
+    /// means only one of the completion job and Finish Now can add its troops.
+    pub async fn complete_training_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        queue_id: Uuid,
+    ) -> AppResult<Option<TroopQueue>> {
+        let queue = sqlx::query_as::<_, TroopQueue>(
+            r#"
+            DELETE FROM troop_queue
+            WHERE id = $1
+            RETURNING id, village_id, troop_type, count, each_duration_seconds, started_at, ends_at, created_at
+            "#,
+        )
+        .bind(queue_id)
+        .fetch_optional(&mut **tx)
+        .await?;
 
-        Ok(())
+        if let Some(queue) = &queue {
+            Self::add_troops_tx(tx, queue.village_id, queue.troop_type, queue.count).await?;
+        }
+
+        Ok(queue)
+    }
+
+    /// Finish a village's whole training queue inside the caller's transaction,
+    /// adding the troops of every entry this call deleted. Returned in finishing order.
+    pub async fn complete_village_training_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        village_id: Uuid,
+    ) -> AppResult<Vec<TroopQueue>> {
+        let mut queue = sqlx::query_as::<_, TroopQueue>(
+            r#"
+            DELETE FROM troop_queue
+            WHERE village_id = $1
+            RETURNING id, village_id, troop_type, count, each_duration_seconds, started_at, ends_at, created_at
+            "#,
+        )
+        .bind(village_id)
+        .fetch_all(&mut **tx)
+        .await?;
+        queue.sort_by_key(|entry| entry.ends_at);
+
+        for entry in &queue {
+            Self::add_troops_tx(tx, entry.village_id, entry.troop_type, entry.count).await?;
+        }
+
+        Ok(queue)
     }
 
     pub async fn find_completed_training(pool: &PgPool) -> AppResult<Vec<TroopQueue>> {
@@ -292,17 +315,20 @@ impl TroopRepository {
         Ok(completed)
     }
 
-    pub async fn get_last_queue_end_time(pool: &PgPool, village_id: Uuid) -> AppResult<Option<DateTime<Utc>>> {
-        let result: Option<(DateTime<Utc>,)> = sqlx::query_as(
+    pub async fn get_last_queue_end_time_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        village_id: Uuid,
+    ) -> AppResult<Option<DateTime<Utc>>> {
+        let result: (Option<DateTime<Utc>>,) = sqlx::query_as(
             r#"
             SELECT MAX(ends_at) FROM troop_queue WHERE village_id = $1
             "#,
         )
         .bind(village_id)
-        .fetch_optional(pool)
+        .fetch_one(&mut **tx)
         .await?;
 
-        Ok(result.map(|r| r.0))
+        Ok(result.0)
     }
 
     // ==================== Crop Consumption ====================
@@ -1,4 +1,5 @@
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::error::AppResult;
@@ -24,6 +25,26 @@ impl UserRepository {
         Ok(user)
     }
 
+    /// Active players whose display name matches one of `names`, ignoring case.
+    /// Display names aren't unique, so a name may come back more than once.
+    pub async fn find_by_display_names(pool: &PgPool, names: &[String]) -> AppResult<Vec<User>> {
+        let lowered: Vec<String> = names.iter().map(|n| n.to_lowercase()).collect();
+        let users = sqlx::query_as::<_, User>(
+            r#"
+            SELECT id, firebase_uid, email, display_name, photo_url, provider,
+                   created_at, updated_at, last_login_at, deleted_at,
+                   is_admin, banned_at, banned_reason
+            FROM users
+            WHERE LOWER(display_name) = ANY($1) AND deleted_at IS NULL
+            "#,
+        )
+        .bind(lowered)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(users)
+    }
+
     pub async fn find_by_firebase_uid(pool: &PgPool, firebase_uid: &str) -> AppResult<Option<User>> {
         let user = sqlx::query_as::<_, User>(
             r#"
@@ -143,4 +164,58 @@ impl UserRepository {
 
         Ok(())
     }
+
+    // ==================== Beginner Protection ====================
+
+    /// Start beginner protection. Only applies once per account.
+    pub async fn start_protection_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+        until: DateTime<Utc>,
+    ) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET protection_until = $2
+            WHERE id = $1 AND protection_until IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(until)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the end of a user's beginner protection, if still active
+    pub async fn get_active_protection(pool: &PgPool, user_id: Uuid) -> AppResult<Option<DateTime<Utc>>> {
+        let result: Option<(Option<DateTime<Utc>>,)> = sqlx::query_as(
+            r#"
+            SELECT protection_until FROM users
+            WHERE id = $1 AND protection_until > NOW()
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(result.and_then(|(until,)| until))
+    }
+
+    /// End beginner protection now (the player went on the offensive)
+    pub async fn end_protection(pool: &PgPool, user_id: Uuid) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET protection_until = NOW()
+            WHERE id = $1 AND protection_until > NOW()
+            "#,
+        )
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
 }
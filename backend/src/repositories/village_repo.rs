@@ -1,4 +1,4 @@
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::error::AppResult;
@@ -14,7 +14,7 @@ impl VillageRepository {
                    wood, clay, iron, crop,
                    warehouse_capacity, granary_capacity,
                    population, culture_points, loyalty,
-                   resources_updated_at, created_at, updated_at
+                   resources_updated_at, created_at, updated_at, note
             FROM villages
             WHERE id = $1
             "#,
@@ -26,6 +26,45 @@ impl VillageRepository {
         Ok(village)
     }
 
+    /// Map position of a village - for use within transaction
+    pub async fn find_coordinates_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        id: Uuid,
+    ) -> AppResult<Option<(i32, i32)>> {
+        let coordinates = sqlx::query_as::<_, (i32, i32)>(
+            r#"SELECT x, y FROM villages WHERE id = $1"#,
+        )
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(coordinates)
+    }
+
+    /// Get a village with row lock (FOR UPDATE) - for use within transaction
+    pub async fn find_by_id_for_update(
+        tx: &mut Transaction<'_, Postgres>,
+        id: Uuid,
+    ) -> AppResult<Option<Village>> {
+        let village = sqlx::query_as::<_, Village>(
+            r#"
+            SELECT id, user_id, name, x, y, is_capital,
+                   wood, clay, iron, crop,
+                   warehouse_capacity, granary_capacity,
+                   population, culture_points, loyalty,
+                   resources_updated_at, created_at, updated_at, note
+            FROM villages
+            WHERE id = $1
+            FOR UPDATE
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(village)
+    }
+
     pub async fn find_by_user_id(pool: &PgPool, user_id: Uuid) -> AppResult<Vec<Village>> {
         let villages = sqlx::query_as::<_, Village>(
             r#"
@@ -33,7 +72,7 @@ impl VillageRepository {
                    wood, clay, iron, crop,
                    warehouse_capacity, granary_capacity,
                    population, culture_points, loyalty,
-                   resources_updated_at, created_at, updated_at
+                   resources_updated_at, created_at, updated_at, note
             FROM villages
             WHERE user_id = $1
             ORDER BY is_capital DESC, created_at ASC
@@ -53,7 +92,7 @@ impl VillageRepository {
                    wood, clay, iron, crop,
                    warehouse_capacity, granary_capacity,
                    population, culture_points, loyalty,
-                   resources_updated_at, created_at, updated_at
+                   resources_updated_at, created_at, updated_at, note
             FROM villages
             WHERE x = $1 AND y = $2
             "#,
@@ -75,9 +114,13 @@ impl VillageRepository {
         let villages = sqlx::query_as::<_, VillageMapInfo>(
             r#"
             SELECT v.id, v.user_id, v.name, v.x, v.y, v.population,
-                   u.display_name as player_name
+                   u.display_name as player_name,
+                   CASE WHEN u.protection_until > NOW() THEN u.protection_until END as protection_until,
+                   am.alliance_id, a.tag as alliance_tag
             FROM villages v
             LEFT JOIN users u ON v.user_id = u.id
+            LEFT JOIN alliance_members am ON am.user_id = v.user_id
+            LEFT JOIN alliances a ON a.id = am.alliance_id
             WHERE v.x BETWEEN $1 AND $2
               AND v.y BETWEEN $3 AND $4
             "#,
@@ -92,7 +135,38 @@ impl VillageRepository {
         Ok(villages)
     }
 
-    pub async fn create(pool: &PgPool, input: CreateVillage) -> AppResult<Village> {
+    /// Villages on any of the given tiles
+    pub async fn find_at_coordinates(
+        pool: &PgPool,
+        coordinates: &[(i32, i32)],
+    ) -> AppResult<Vec<VillageMapInfo>> {
+        let (xs, ys): (Vec<i32>, Vec<i32>) = coordinates.iter().copied().unzip();
+        let villages = sqlx::query_as::<_, VillageMapInfo>(
+            r#"
+            SELECT v.id, v.user_id, v.name, v.x, v.y, v.population,
+                   u.display_name as player_name,
+                   CASE WHEN u.protection_until > NOW() THEN u.protection_until END as protection_until,
+                   am.alliance_id, a.tag as alliance_tag
+            FROM villages v
+            LEFT JOIN users u ON v.user_id = u.id
+            LEFT JOIN alliance_members am ON am.user_id = v.user_id
+            LEFT JOIN alliances a ON a.id = am.alliance_id
+            WHERE (v.x, v.y) IN (SELECT * FROM UNNEST($1::INT[], $2::INT[]))
+            "#,
+        )
+        .bind(xs)
+        .bind(ys)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(villages)
+    }
+
+    /// Create a village within a transaction
+    pub async fn create_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        input: CreateVillage,
+    ) -> AppResult<Village> {
         let village = sqlx::query_as::<_, Village>(
             r#"
             INSERT INTO villages (user_id, name, x, y, is_capital)
@@ -101,7 +175,7 @@ impl VillageRepository {
                       wood, clay, iron, crop,
                       warehouse_capacity, granary_capacity,
                       population, culture_points, loyalty,
-                      resources_updated_at, created_at, updated_at
+                      resources_updated_at, created_at, updated_at, note
             "#,
         )
         .bind(&input.user_id)
@@ -109,7 +183,7 @@ impl VillageRepository {
         .bind(input.x)
         .bind(input.y)
         .bind(input.is_capital)
-        .fetch_one(pool)
+        .fetch_one(&mut **tx)
         .await?;
 
         Ok(village)
@@ -120,56 +194,131 @@ impl VillageRepository {
             r#"
             UPDATE villages
             SET name = COALESCE($2, name),
+                note = CASE WHEN $3::TEXT IS NULL THEN note ELSE NULLIF($3, '') END,
                 updated_at = NOW()
             WHERE id = $1
             RETURNING id, user_id, name, x, y, is_capital,
                       wood, clay, iron, crop,
                       warehouse_capacity, granary_capacity,
                       population, culture_points, loyalty,
-                      resources_updated_at, created_at, updated_at
+                      resources_updated_at, created_at, updated_at, note
             "#,
         )
         .bind(id)
         .bind(&input.name)
+        .bind(&input.note)
         .fetch_one(pool)
         .await?;
 
         Ok(village)
     }
 
-    pub async fn update_resources(
+    /// Find the given villages that belong to a user
+    pub async fn find_by_ids_for_user(
+        pool: &PgPool,
+        user_id: Uuid,
+        ids: &[Uuid],
+    ) -> AppResult<Vec<Village>> {
+        let villages = sqlx::query_as::<_, Village>(
+            r#"
+            SELECT id, user_id, name, x, y, is_capital,
+                   wood, clay, iron, crop,
+                   warehouse_capacity, granary_capacity,
+                   population, culture_points, loyalty,
+                   resources_updated_at, created_at, updated_at, note
+            FROM villages
+            WHERE user_id = $1 AND id = ANY($2)
+            ORDER BY created_at ASC
+            "#,
+        )
+        .bind(user_id)
+        .bind(ids)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(villages)
+    }
+
+    /// Apply production since `resources_updated_at` in a single statement.
+    /// Elapsed time is measured in SQL, so concurrent calls serialize on the row
+    /// and the later one only credits what accrued after the earlier one.
+    /// Returns None if nothing has elapsed since the last update.
+    pub async fn accrue_resources(
         pool: &PgPool,
         id: Uuid,
-        wood: i32,
-        clay: i32,
-        iron: i32,
-        crop: i32,
-    ) -> AppResult<Village> {
+        wood_per_hour: i32,
+        clay_per_hour: i32,
+        iron_per_hour: i32,
+        crop_per_hour: i32,
+    ) -> AppResult<Option<Village>> {
         let village = sqlx::query_as::<_, Village>(
             r#"
             UPDATE villages
-            SET wood = $2, clay = $3, iron = $4, crop = $5,
+            SET wood = LEAST(GREATEST(wood + TRUNC($2 * EXTRACT(EPOCH FROM (NOW() - resources_updated_at)) / 3600.0)::INT, 0), warehouse_capacity),
+                clay = LEAST(GREATEST(clay + TRUNC($3 * EXTRACT(EPOCH FROM (NOW() - resources_updated_at)) / 3600.0)::INT, 0), warehouse_capacity),
+                iron = LEAST(GREATEST(iron + TRUNC($4 * EXTRACT(EPOCH FROM (NOW() - resources_updated_at)) / 3600.0)::INT, 0), warehouse_capacity),
+                crop = LEAST(GREATEST(crop + TRUNC($5 * EXTRACT(EPOCH FROM (NOW() - resources_updated_at)) / 3600.0)::INT, 0), granary_capacity),
                 resources_updated_at = NOW(),
                 updated_at = NOW()
-            WHERE id = $1
+            WHERE id = $1 AND resources_updated_at < NOW()
             RETURNING id, user_id, name, x, y, is_capital,
                       wood, clay, iron, crop,
                       warehouse_capacity, granary_capacity,
                       population, culture_points, loyalty,
-                      resources_updated_at, created_at, updated_at
+                      resources_updated_at, created_at, updated_at, note
             "#,
         )
         .bind(id)
-        .bind(wood)
-        .bind(clay)
-        .bind(iron)
-        .bind(crop)
-        .fetch_one(pool)
+        .bind(wood_per_hour)
+        .bind(clay_per_hour)
+        .bind(iron_per_hour)
+        .bind(crop_per_hour)
+        .fetch_optional(pool)
         .await?;
 
         Ok(village)
     }
 
+    /// Batch version of `accrue_resources`: one rate per village, same index as `ids`.
+    /// Villages where nothing has elapsed are left out of the result.
+    pub async fn accrue_resources_batch(
+        pool: &PgPool,
+        ids: &[Uuid],
+        wood_per_hour: &[i32],
+        clay_per_hour: &[i32],
+        iron_per_hour: &[i32],
+        crop_per_hour: &[i32],
+    ) -> AppResult<Vec<Village>> {
+        let villages = sqlx::query_as::<_, Village>(
+            r#"
+            UPDATE villages v
+            SET wood = LEAST(GREATEST(v.wood + TRUNC(r.wood * EXTRACT(EPOCH FROM (NOW() - v.resources_updated_at)) / 3600.0)::INT, 0), v.warehouse_capacity),
+                clay = LEAST(GREATEST(v.clay + TRUNC(r.clay * EXTRACT(EPOCH FROM (NOW() - v.resources_updated_at)) / 3600.0)::INT, 0), v.warehouse_capacity),
+                iron = LEAST(GREATEST(v.iron + TRUNC(r.iron * EXTRACT(EPOCH FROM (NOW() - v.resources_updated_at)) / 3600.0)::INT, 0), v.warehouse_capacity),
+                crop = LEAST(GREATEST(v.crop + TRUNC(r.crop * EXTRACT(EPOCH FROM (NOW() - v.resources_updated_at)) / 3600.0)::INT, 0), v.granary_capacity),
+                resources_updated_at = NOW(),
+                updated_at = NOW()
+            FROM UNNEST($1::UUID[], $2::INT[], $3::INT[], $4::INT[], $5::INT[])
+                AS r(id, wood, clay, iron, crop)
+            WHERE v.id = r.id AND v.resources_updated_at < NOW()
+            RETURNING v.id, v.user_id, v.name, v.x, v.y, v.is_capital,
+                      v.wood, v.clay, v.iron, v.crop,
+                      v.warehouse_capacity, v.granary_capacity,
+                      v.population, v.culture_points, v.loyalty,
+                      v.resources_updated_at, v.created_at, v.updated_at, v.note
+            "#,
+        )
+        .bind(ids)
+        .bind(wood_per_hour)
+        .bind(clay_per_hour)
+        .bind(iron_per_hour)
+        .bind(crop_per_hour)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(villages)
+    }
+
     pub async fn deduct_resources(
         pool: &PgPool,
         id: Uuid,
@@ -195,7 +344,7 @@ impl VillageRepository {
                       wood, clay, iron, crop,
                       warehouse_capacity, granary_capacity,
                       population, culture_points, loyalty,
-                      resources_updated_at, created_at, updated_at
+                      resources_updated_at, created_at, updated_at, note
             "#,
         )
         .bind(id)
@@ -209,8 +358,8 @@ impl VillageRepository {
         Ok(village)
     }
 
-    pub async fn update_storage_capacity(
-        pool: &PgPool,
+    pub async fn update_storage_capacity_tx(
+        tx: &mut Transaction<'_, Postgres>,
         id: Uuid,
         warehouse_capacity: i32,
         granary_capacity: i32,
@@ -226,19 +375,23 @@ impl VillageRepository {
                       wood, clay, iron, crop,
                       warehouse_capacity, granary_capacity,
                       population, culture_points, loyalty,
-                      resources_updated_at, created_at, updated_at
+                      resources_updated_at, created_at, updated_at, note
             "#,
         )
         .bind(id)
         .bind(warehouse_capacity)
         .bind(granary_capacity)
-        .fetch_one(pool)
+        .fetch_one(&mut **tx)
         .await?;
 
         Ok(village)
     }
 
-    pub async fn update_population(pool: &PgPool, id: Uuid, population: i32) -> AppResult<Village> {
+    pub async fn update_population_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        id: Uuid,
+        population: i32,
+    ) -> AppResult<Village> {
         let village = sqlx::query_as::<_, Village>(
             r#"
             UPDATE villages
@@ -249,12 +402,12 @@ impl VillageRepository {
                       wood, clay, iron, crop,
                       warehouse_capacity, granary_capacity,
                       population, culture_points, loyalty,
-                      resources_updated_at, created_at, updated_at
+                      resources_updated_at, created_at, updated_at, note
             "#,
         )
         .bind(id)
         .bind(population)
-        .fetch_one(pool)
+        .fetch_one(&mut **tx)
         .await?;
 
         Ok(village)
@@ -273,6 +426,31 @@ impl VillageRepository {
         Ok(count.0)
     }
 
+    /// Check if the player already has another village with this name (case-insensitive)
+    pub async fn is_name_taken_by_user(
+        pool: &PgPool,
+        user_id: Uuid,
+        name: &str,
+        exclude_village_id: Option<Uuid>,
+    ) -> AppResult<bool> {
+        let exists: (bool,) = sqlx::query_as(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM villages
+                WHERE user_id = $1 AND LOWER(name) = LOWER($2)
+                  AND ($3::UUID IS NULL OR id != $3)
+            )
+            "#,
+        )
+        .bind(user_id)
+        .bind(name)
+        .bind(exclude_village_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(exists.0)
+    }
+
     pub async fn is_coordinate_available(pool: &PgPool, x: i32, y: i32) -> AppResult<bool> {
         let exists: (bool,) = sqlx::query_as(
             r#"
@@ -308,7 +486,7 @@ impl VillageRepository {
                       wood, clay, iron, crop,
                       warehouse_capacity, granary_capacity,
                       population, culture_points, loyalty,
-                      resources_updated_at, created_at, updated_at
+                      resources_updated_at, created_at, updated_at, note
             "#,
         )
         .bind(id)
@@ -322,6 +500,105 @@ impl VillageRepository {
         Ok(village)
     }
 
+    /// Deduct resources within a transaction, keeping `reserved` amounts (e.g. locked by
+    /// open sell orders) untouched. Returns false if the village can't cover them.
+    pub async fn deduct_resources_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        id: Uuid,
+        wood: i32,
+        clay: i32,
+        iron: i32,
+        crop: i32,
+        reserved: (i64, i64, i64, i64),
+    ) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE villages
+            SET wood = wood - $2,
+                clay = clay - $3,
+                iron = iron - $4,
+                crop = crop - $5,
+                updated_at = NOW()
+            WHERE id = $1
+              AND wood - $6 >= $2
+              AND clay - $7 >= $3
+              AND iron - $8 >= $4
+              AND crop - $9 >= $5
+            "#,
+        )
+        .bind(id)
+        .bind(wood)
+        .bind(clay)
+        .bind(iron)
+        .bind(crop)
+        .bind(reserved.0)
+        .bind(reserved.1)
+        .bind(reserved.2)
+        .bind(reserved.3)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Add resources within a transaction, capped at storage capacity
+    pub async fn add_resources_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        id: Uuid,
+        wood: i32,
+        clay: i32,
+        iron: i32,
+        crop: i32,
+    ) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE villages
+            SET wood = LEAST(wood + $2, warehouse_capacity),
+                clay = LEAST(clay + $3, warehouse_capacity),
+                iron = LEAST(iron + $4, warehouse_capacity),
+                crop = LEAST(crop + $5, granary_capacity),
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(wood)
+        .bind(clay)
+        .bind(iron)
+        .bind(crop)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Overwrite a village's resources within a transaction
+    pub async fn set_resources_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        id: Uuid,
+        wood: i32,
+        clay: i32,
+        iron: i32,
+        crop: i32,
+    ) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE villages
+            SET wood = $2, clay = $3, iron = $4, crop = $5, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(wood)
+        .bind(clay)
+        .bind(iron)
+        .bind(crop)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
     // ==================== Conquer-related ====================
 
     pub async fn update_loyalty(pool: &PgPool, id: Uuid, loyalty: i32) -> AppResult<Village> {
@@ -335,7 +612,7 @@ impl VillageRepository {
                       wood, clay, iron, crop,
                       warehouse_capacity, granary_capacity,
                       population, culture_points, loyalty,
-                      resources_updated_at, created_at, updated_at
+                      resources_updated_at, created_at, updated_at, note
             "#,
         )
         .bind(id)
@@ -358,7 +635,7 @@ impl VillageRepository {
                       wood, clay, iron, crop,
                       warehouse_capacity, granary_capacity,
                       population, culture_points, loyalty,
-                      resources_updated_at, created_at, updated_at
+                      resources_updated_at, created_at, updated_at, note
             "#,
         )
         .bind(id)
@@ -377,9 +654,13 @@ impl VillageRepository {
         let villages = sqlx::query_as::<_, VillageMapInfo>(
             r#"
             SELECT v.id, v.user_id, v.name, v.x, v.y, v.population,
-                   u.display_name as player_name
+                   u.display_name as player_name,
+                   CASE WHEN u.protection_until > NOW() THEN u.protection_until END as protection_until,
+                   am.alliance_id, a.tag as alliance_tag
             FROM villages v
             LEFT JOIN users u ON v.user_id = u.id
+            LEFT JOIN alliance_members am ON am.user_id = v.user_id
+            LEFT JOIN alliances a ON a.id = am.alliance_id
             WHERE v.name ILIKE $1
             ORDER BY v.population DESC
             LIMIT $2
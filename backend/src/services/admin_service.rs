@@ -1,17 +1,27 @@
+use chrono::{Duration, Utc};
 use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::error::{AppError, AppResult};
 use crate::models::admin::{
-    AdminHeroResponse, AdminUserResponse, AdminVillageResponse,
-    PlayerDetailResponse, ServerStatsResponse, AdminAllianceInfoResponse,
+    AdminHeroResponse, AdminUserResponse, AdminVillageFilter, AdminVillageListItem,
+    AdminVillageResponse, DeleteVillageResponse, PlayerDetailResponse, ServerStatsResponse,
+    AdminAllianceInfoResponse, EconomyResponse, TradeFlaggedPair,
 };
+use crate::models::shop::TransactionType;
+use crate::models::trade::{CancelOrderResponse, TradeOrderStatus};
 use crate::repositories::admin_repo::AdminRepository;
+use crate::repositories::trade_repo::TradeRepository;
 use crate::repositories::village_repo::VillageRepository;
 use crate::repositories::hero_repo::HeroRepository;
+use crate::repositories::shop_repo::ShopRepository;
+use crate::services::trade_service::TradeService;
 
 pub struct AdminService;
 
+/// Page size cap for the admin village browser
+const MAX_VILLAGES_PER_PAGE: i64 = 100;
+
 impl AdminService {
     // ==================== User Management ====================
 
@@ -303,6 +313,13 @@ impl AdminService {
         let total_alliances = AdminRepository::count_alliances(pool).await?;
         let total_battles_today = AdminRepository::count_battles_today(pool).await?;
 
+        let now = Utc::now();
+        let total_trade_fees_collected = AdminRepository::sum_trade_fees(pool, None).await?;
+        let trade_fees_collected_24h =
+            AdminRepository::sum_trade_fees(pool, Some(now - Duration::hours(24))).await?;
+        let trade_fees_collected_7d =
+            AdminRepository::sum_trade_fees(pool, Some(now - Duration::days(7))).await?;
+
         Ok(ServerStatsResponse {
             total_users,
             active_users_24h,
@@ -310,9 +327,47 @@ impl AdminService {
             total_villages,
             total_alliances,
             total_battles_today,
+            total_trade_fees_collected,
+            trade_fees_collected_24h,
+            trade_fees_collected_7d,
         })
     }
 
+    // ==================== Economy ====================
+
+    /// Live economy totals with their change over the last 24 hours
+    pub async fn get_economy(pool: &PgPool) -> AppResult<EconomyResponse> {
+        let metrics = AdminRepository::compute_economy_metrics(pool).await?;
+        let change_24h =
+            AdminRepository::get_economy_snapshot_before(pool, metrics.captured_at - Duration::hours(24))
+                .await?
+                .map(|earlier| metrics.change_since(&earlier));
+
+        Ok(EconomyResponse { metrics, change_24h })
+    }
+
+    /// Persist the current totals for later 24h comparisons.
+    /// Skipped if another snapshot was taken within `min_interval_minutes`.
+    pub async fn record_economy_snapshot(pool: &PgPool, min_interval_minutes: i64) -> AppResult<bool> {
+        let metrics = AdminRepository::compute_economy_metrics(pool).await?;
+        AdminRepository::insert_economy_snapshot(pool, &metrics, min_interval_minutes).await
+    }
+
+    // ==================== Village Browser ====================
+
+    /// Browse all villages (read-only, not logged)
+    pub async fn list_villages(
+        pool: &PgPool,
+        filter: &AdminVillageFilter,
+        page: i64,
+        per_page: i64,
+    ) -> AppResult<Vec<AdminVillageListItem>> {
+        let per_page = per_page.clamp(1, MAX_VILLAGES_PER_PAGE);
+        let offset = (page.max(1) - 1) * per_page;
+
+        AdminRepository::list_villages(pool, filter, per_page, offset).await
+    }
+
     // ==================== Resource Management ====================
 
     /// Adjust village resources (emergency fix)
@@ -355,4 +410,340 @@ impl AdminService {
 
         Ok(())
     }
+
+    /// Delete a village with everything that references it.
+    /// Open trade orders are closed and refunded and incoming deliveries redirected or
+    /// refunded first, then dependent rows are removed
+    /// in foreign-key order; everything happens in one transaction.
+    pub async fn delete_village(
+        pool: &PgPool,
+        admin_id: Uuid,
+        village_id: Uuid,
+        reason: &str,
+    ) -> AppResult<DeleteVillageResponse> {
+        if reason.trim().is_empty() {
+            return Err(AppError::BadRequest("A reason is required".into()));
+        }
+
+        let village = VillageRepository::find_by_id(pool, village_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Village not found".into()))?;
+
+        // Heroes based here move to the owner's capital, or another village if this was it
+        let fallback_village_id = VillageRepository::find_by_user_id(pool, village.user_id)
+            .await?
+            .into_iter()
+            .filter(|v| v.id != village_id)
+            .max_by_key(|v| v.is_capital)
+            .map(|v| v.id);
+
+        let mut tx = pool.begin().await?;
+
+        // Phase 1: close open orders so buyers get their escrowed gold back
+        let open_orders = TradeRepository::get_open_village_orders_for_update(&mut tx, village_id).await?;
+        let mut refunded_gold = 0;
+        for order in &open_orders {
+            let closed =
+                TradeService::close_order_with_refund_tx(&mut tx, order, TradeOrderStatus::Cancelled).await?;
+            refunded_gold += closed.refunded_gold.unwrap_or(0);
+        }
+
+        // Phase 2: traded goods on their way here are already paid for, so they go to
+        // the owner's other village, or the buyer gets their gold back
+        let mut redirected_deliveries = 0;
+        let mut refunded_deliveries = 0;
+        match fallback_village_id {
+            Some(fallback_id) => {
+                redirected_deliveries =
+                    TradeRepository::redirect_incoming_deliveries_tx(&mut tx, village_id, fallback_id).await?;
+            }
+            None => {
+                for (recipient_id, gold) in TradeRepository::cancel_incoming_deliveries_tx(&mut tx, village_id).await? {
+                    let gold = gold as i32;
+                    ShopRepository::add_gold_tx(&mut tx, recipient_id, gold).await?;
+                    ShopRepository::create_transaction_tx(
+                        &mut tx,
+                        recipient_id,
+                        TransactionType::GoldRefund,
+                        gold,
+                        Some("Refund for a trade delivery to a deleted village"),
+                    )
+                    .await?;
+                    refunded_gold += gold;
+                    refunded_deliveries += 1;
+                }
+            }
+        }
+
+        // Phase 3: remove the village and its dependencies
+        let removed = AdminRepository::purge_village_tx(&mut tx, village_id, fallback_village_id).await?;
+
+        tx.commit().await?;
+
+        let response = DeleteVillageResponse {
+            village_id,
+            name: village.name,
+            owner_id: village.user_id,
+            cancelled_orders: open_orders.len(),
+            redirected_deliveries,
+            refunded_deliveries,
+            refunded_gold,
+            removed,
+        };
+
+        // Log action
+        AdminRepository::create_log(
+            pool,
+            admin_id,
+            "delete_village",
+            "village",
+            Some(village_id),
+            Some(serde_json::json!({
+                "reason": reason,
+                "summary": response,
+            })),
+        )
+        .await?;
+
+        Ok(response)
+    }
+
+    // ==================== Trade Intervention ====================
+
+    /// Cancel any player's open order, refunding the owner
+    pub async fn force_cancel_order(
+        pool: &PgPool,
+        admin_id: Uuid,
+        order_id: Uuid,
+        reason: &str,
+    ) -> AppResult<CancelOrderResponse> {
+        Self::force_close_order(pool, admin_id, order_id, TradeOrderStatus::Cancelled, reason).await
+    }
+
+    /// Expire any player's open order, refunding the owner
+    pub async fn force_expire_order(
+        pool: &PgPool,
+        admin_id: Uuid,
+        order_id: Uuid,
+        reason: &str,
+    ) -> AppResult<CancelOrderResponse> {
+        Self::force_close_order(pool, admin_id, order_id, TradeOrderStatus::Expired, reason).await
+    }
+
+    async fn force_close_order(
+        pool: &PgPool,
+        admin_id: Uuid,
+        order_id: Uuid,
+        status: TradeOrderStatus,
+        reason: &str,
+    ) -> AppResult<CancelOrderResponse> {
+        if reason.trim().is_empty() {
+            return Err(AppError::BadRequest("A reason is required".into()));
+        }
+
+        // Snapshot the order as it was before the intervention
+        let snapshot = TradeRepository::get_order_by_id(pool, order_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Order not found".into()))?;
+
+        let response = TradeService::force_close_order(pool, order_id, status).await?;
+
+        // Log action
+        AdminRepository::create_log(
+            pool,
+            admin_id,
+            if status == TradeOrderStatus::Cancelled { "force_cancel_order" } else { "force_expire_order" },
+            "trade_order",
+            Some(order_id),
+            Some(serde_json::json!({
+                "reason": reason,
+                "order": snapshot,
+                "refunded_resources": response.refunded_resources,
+                "refunded_gold": response.refunded_gold,
+            })),
+        )
+        .await?;
+
+        Ok(response)
+    }
+
+    /// Leave trades between two players out of market prices and volume
+    pub async fn flag_trade_pair(
+        pool: &PgPool,
+        admin_id: Uuid,
+        user_a_id: Uuid,
+        user_b_id: Uuid,
+        reason: Option<&str>,
+    ) -> AppResult<TradeFlaggedPair> {
+        if user_a_id == user_b_id {
+            return Err(AppError::BadRequest("A pair needs two different players".into()));
+        }
+
+        let pair = TradeRepository::flag_pair(pool, user_a_id, user_b_id, reason, admin_id).await?;
+
+        // Log action
+        AdminRepository::create_log(
+            pool,
+            admin_id,
+            "flag_trade_pair",
+            "user",
+            Some(user_a_id),
+            Some(serde_json::json!({
+                "other_user_id": user_b_id,
+                "reason": reason,
+            })),
+        )
+        .await?;
+
+        Ok(pair)
+    }
+
+    /// Count trades between two players towards market prices again
+    pub async fn unflag_trade_pair(
+        pool: &PgPool,
+        admin_id: Uuid,
+        user_a_id: Uuid,
+        user_b_id: Uuid,
+    ) -> AppResult<()> {
+        if !TradeRepository::unflag_pair(pool, user_a_id, user_b_id).await? {
+            return Err(AppError::NotFound("Trade pair is not flagged".into()));
+        }
+
+        // Log action
+        AdminRepository::create_log(
+            pool,
+            admin_id,
+            "unflag_trade_pair",
+            "user",
+            Some(user_a_id),
+            Some(serde_json::json!({ "other_user_id": user_b_id })),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::building::BuildingType;
+    use crate::models::trade::{CreateOrderRequest, OrderExecution, TradeOrderType, TradeResourceType};
+    use crate::test_support;
+
+    /// Sell 100 wood at 10 from a village at (0|0) to one at (10|0).
+    /// Returns the seller and their village, then the buyer and theirs.
+    async fn village_with_a_trade(pool: &PgPool) -> (Uuid, Uuid, Uuid, Uuid) {
+        let game = test_support::game_config();
+        let seller_id = test_support::create_user(pool, 0).await;
+        let seller_village = test_support::create_village(pool, seller_id, 0, 0).await;
+        test_support::add_building(pool, seller_village.id, BuildingType::Market, 5, 5).await;
+        let buyer_id = test_support::create_user(pool, 10_000).await;
+        let buyer_village = test_support::create_village(pool, buyer_id, 10, 0).await;
+
+        for (user_id, village_id, order_type) in [
+            (seller_id, seller_village.id, TradeOrderType::Sell),
+            (buyer_id, buyer_village.id, TradeOrderType::Buy),
+        ] {
+            let request = CreateOrderRequest {
+                village_id,
+                order_type,
+                resource_type: TradeResourceType::Wood,
+                quantity: 100,
+                price_per_unit: 10,
+                expires_in_hours: None,
+                alliance_only: false,
+                execution: OrderExecution::Resting,
+            };
+            TradeService::create_order(pool, user_id, request, &game, None).await.unwrap();
+        }
+
+        (seller_id, seller_village.id, buyer_id, buyer_village.id)
+    }
+
+    #[sqlx::test]
+    async fn deleting_a_village_keeps_the_other_players_history(pool: PgPool) {
+        let admin_id = test_support::create_user(&pool, 0).await;
+        let (seller_id, village_id, buyer_id, buyer_village_id) = village_with_a_trade(&pool).await;
+        sqlx::query(
+            r#"
+            INSERT INTO battle_reports (attacker_player_id, defender_player_id, attacker_village_id,
+                                        defender_village_id, mission, winner, occurred_at)
+            VALUES ($1, $2, $3, $4, 'raid', 'attacker', NOW())
+            "#,
+        )
+        .bind(buyer_id)
+        .bind(seller_id)
+        .bind(buyer_village_id)
+        .bind(village_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let response = AdminService::delete_village(&pool, admin_id, village_id, "cleanup").await.unwrap();
+        assert_eq!(response.removed.trade_transactions_detached, 1);
+        assert_eq!(response.removed.battle_reports_detached, 1);
+
+        // The buyer's trade and their report on the raid are still there
+        let (seller_village, buyer_village, sell_order): (Option<Uuid>, Option<Uuid>, Option<Uuid>) =
+            sqlx::query_as("SELECT seller_village_id, buyer_village_id, sell_order_id FROM trade_transactions")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!((seller_village, buyer_village, sell_order), (None, Some(buyer_village_id), None));
+
+        let (attacker_village, defender_village): (Option<Uuid>, Option<Uuid>) =
+            sqlx::query_as("SELECT attacker_village_id, defender_village_id FROM battle_reports")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!((attacker_village, defender_village), (Some(buyer_village_id), None));
+    }
+
+    async fn pending_delivery(pool: &PgPool) -> (Option<Uuid>, Uuid) {
+        sqlx::query_as("SELECT from_village_id, to_village_id FROM resource_deliveries WHERE delivered_at IS NULL")
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn deliveries_from_a_deleted_village_stay_on_the_road(pool: PgPool) {
+        let admin_id = test_support::create_user(&pool, 0).await;
+        let (_, village_id, _, buyer_village_id) = village_with_a_trade(&pool).await;
+
+        AdminService::delete_village(&pool, admin_id, village_id, "cleanup").await.unwrap();
+
+        assert_eq!(pending_delivery(&pool).await, (None, buyer_village_id));
+    }
+
+    #[sqlx::test]
+    async fn deliveries_to_a_deleted_village_are_redirected_or_refunded(pool: PgPool) {
+        let admin_id = test_support::create_user(&pool, 0).await;
+
+        // With another village the goods go there
+        let (_, seller_village_id, buyer_id, buyer_village_id) = village_with_a_trade(&pool).await;
+        let other_village = test_support::create_village(&pool, buyer_id, 20, 0).await;
+        let response = AdminService::delete_village(&pool, admin_id, buyer_village_id, "cleanup").await.unwrap();
+        assert_eq!(response.redirected_deliveries, 1);
+        assert_eq!(pending_delivery(&pool).await, (Some(seller_village_id), other_village.id));
+
+        // Without one the buyer gets back the 1,000 gold they paid
+        AdminService::delete_village(&pool, admin_id, other_village.id, "cleanup").await.unwrap();
+        let (open_deliveries,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM resource_deliveries WHERE delivered_at IS NULL")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(open_deliveries, 0);
+        assert_eq!(test_support::gold_balance(&pool, buyer_id).await, 10_000);
+        let (refunded,): (i32,) = sqlx::query_as(
+            "SELECT gold_amount FROM transactions WHERE user_id = $1 AND transaction_type = 'gold_refund'",
+        )
+        .bind(buyer_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(refunded, 1_000);
+    }
 }
@@ -1,12 +1,30 @@
+use chrono::{Duration, Utc};
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::error::{AppError, AppResult};
+use crate::error::{AppError, AppResult, FieldError};
 use crate::models::alliance::{
     Alliance, AllianceDiplomacy, AllianceInvitation, AllianceListItem, AllianceMemberResponse,
-    AllianceResponse, AllianceRole, CreateAllianceRequest, DiplomacyStatus, InvitationStatus,
+    AllianceResponse, AllianceRole, AllianceStatsResponse, AllianceTreasury, AllianceTreasuryLog,
+    CreateAllianceRequest,
+    DiplomacyStatus, InvitationStatus, TreasuryAction, TreasuryTransferRequest,
 };
+use crate::models::shop::TransactionType;
+use crate::models::village::Village;
 use crate::repositories::alliance_repo::AllianceRepository;
+use crate::repositories::message_repo::MessageRepository;
+use crate::repositories::shop_repo::ShopRepository;
+use crate::repositories::trade_repo::TradeRepository;
+use crate::repositories::village_repo::VillageRepository;
+use crate::services::resource_service::ResourceService;
+use crate::services::village_service::VillageService;
+use crate::services::ws_service::WsManager;
+
+/// Members who haven't logged in for this many days count as inactive
+const INACTIVE_MEMBER_DAYS: i64 = 7;
+
+/// Longest window for alliance member stats
+const MAX_STATS_DAYS: i64 = 90;
 
 pub struct AllianceService;
 
@@ -19,14 +37,20 @@ impl AllianceService {
         user_id: Uuid,
         request: CreateAllianceRequest,
     ) -> AppResult<AllianceResponse> {
+        let mut errors = Vec::new();
+
         // Validate tag length (2-4 characters)
         if request.tag.len() < 2 || request.tag.len() > 4 {
-            return Err(AppError::BadRequest("Tag must be 2-4 characters".into()));
+            errors.push(FieldError::new("tag", "invalid_length", "Tag must be 2-4 characters"));
         }
 
         // Validate name length
         if request.name.len() < 3 || request.name.len() > 50 {
-            return Err(AppError::BadRequest("Name must be 3-50 characters".into()));
+            errors.push(FieldError::new("name", "invalid_length", "Name must be 3-50 characters"));
+        }
+
+        if !errors.is_empty() {
+            return Err(AppError::ValidationError(errors));
         }
 
         // Check if user is already in an alliance
@@ -36,7 +60,7 @@ impl AllianceService {
 
         // Check if tag is already taken
         if let Some(_) = AllianceRepository::find_by_tag(pool, &request.tag.to_uppercase()).await? {
-            return Err(AppError::BadRequest("This tag is already taken".into()));
+            return Err(AppError::field("tag", "taken", "This tag is already taken"));
         }
 
         // Create the alliance
@@ -65,9 +89,11 @@ impl AllianceService {
             .ok_or_else(|| AppError::NotFound("Alliance not found".into()))?;
 
         let member_count = AllianceRepository::get_member_count(pool, alliance_id).await?;
+        let treasury = AllianceRepository::get_treasury(pool, alliance_id).await?;
 
         let mut response: AllianceResponse = alliance.into();
         response.member_count = member_count;
+        response.treasury = Some(treasury);
 
         Ok(response)
     }
@@ -125,9 +151,45 @@ impl AllianceService {
 
     // ==================== Member Management ====================
 
-    /// List alliance members
-    pub async fn list_members(pool: &PgPool, alliance_id: Uuid) -> AppResult<Vec<AllianceMemberResponse>> {
-        AllianceRepository::list_members(pool, alliance_id).await
+    /// List alliance members with online status; inactive members are sorted last
+    pub async fn list_members(
+        pool: &PgPool,
+        ws_manager: &WsManager,
+        alliance_id: Uuid,
+    ) -> AppResult<Vec<AllianceMemberResponse>> {
+        let mut members = AllianceRepository::list_members(pool, alliance_id).await?;
+
+        for member in members.iter_mut() {
+            member.is_online = ws_manager.is_online(member.user_id).await;
+        }
+
+        let inactive_since = Utc::now() - Duration::days(INACTIVE_MEMBER_DAYS);
+        members.sort_by_key(|m| !m.is_online && m.last_login_at < inactive_since);
+
+        Ok(members)
+    }
+
+    /// Contribution stats for every member over the last `days` days (members only)
+    pub async fn get_member_stats(
+        pool: &PgPool,
+        user_id: Uuid,
+        alliance_id: Uuid,
+        days: i64,
+    ) -> AppResult<AllianceStatsResponse> {
+        AllianceRepository::get_member(pool, alliance_id, user_id)
+            .await?
+            .ok_or_else(|| AppError::Forbidden("You are not a member of this alliance".into()))?;
+
+        let days = days.clamp(1, MAX_STATS_DAYS);
+        let since = Utc::now() - Duration::days(days);
+        let members = AllianceRepository::get_member_stats(pool, alliance_id, since).await?;
+
+        Ok(AllianceStatsResponse {
+            alliance_id,
+            days,
+            since,
+            members,
+        })
     }
 
     /// Invite player to alliance
@@ -207,6 +269,43 @@ impl AllianceService {
         Ok(())
     }
 
+    /// Cancel a pending invitation on behalf of the inviting alliance
+    pub async fn cancel_invitation(
+        pool: &PgPool,
+        user_id: Uuid,
+        invitation_id: Uuid,
+        notify_invitee: bool,
+    ) -> AppResult<()> {
+        let invitation = AllianceRepository::get_invitation(pool, invitation_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Invitation not found".into()))?;
+
+        // Check permission (leader or officer of the inviting alliance)
+        Self::check_permission(pool, invitation.alliance_id, user_id, &[AllianceRole::Leader, AllianceRole::Officer]).await?;
+
+        if invitation.status != InvitationStatus::Pending
+            || !AllianceRepository::cancel_pending_invitation(pool, invitation_id).await?
+        {
+            return Err(AppError::BadRequest("Invitation is no longer pending".into()));
+        }
+
+        if notify_invitee {
+            let alliance = AllianceRepository::find_by_id(pool, invitation.alliance_id)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Alliance not found".into()))?;
+
+            MessageRepository::create_system_messages(
+                pool,
+                &[invitation.invitee_id],
+                &["Alliance invitation withdrawn".to_string()],
+                &[format!("Your invitation to join [{}] {} has been withdrawn.", alliance.tag, alliance.name)],
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
     /// Get pending invitations for user
     pub async fn get_pending_invitations(pool: &PgPool, user_id: Uuid) -> AppResult<Vec<AllianceInvitation>> {
         AllianceRepository::get_pending_invitations_for_user(pool, user_id).await
@@ -329,8 +428,216 @@ impl AllianceService {
         AllianceRepository::list_diplomacy(pool, alliance_id).await
     }
 
+    // ==================== Treasury ====================
+
+    /// Deposit gold and/or village resources into the treasury (any member)
+    pub async fn deposit(
+        pool: &PgPool,
+        user_id: Uuid,
+        alliance_id: Uuid,
+        request: TreasuryTransferRequest,
+        starvation_guard: bool,
+    ) -> AppResult<AllianceTreasury> {
+        AllianceRepository::get_member(pool, alliance_id, user_id)
+            .await?
+            .ok_or_else(|| AppError::Forbidden("You are not a member of this alliance".into()))?;
+
+        Self::validate_transfer(pool, user_id, &request).await?;
+
+        // Bring stored resources up to date before taking from them
+        if let (Some(village_id), true) = (request.village_id, request.has_resources()) {
+            let village = ResourceService::update_village_resources(pool, village_id).await?;
+            if starvation_guard {
+                VillageService::ensure_not_starving(&village)?;
+            }
+        }
+
+        let mut tx = pool.begin().await?;
+
+        if request.gold > 0 {
+            let result = sqlx::query(
+                r#"
+                UPDATE users
+                SET gold_balance = gold_balance - $2
+                WHERE id = $1 AND gold_balance >= $2
+                "#,
+            )
+            .bind(user_id)
+            .bind(request.gold)
+            .execute(&mut *tx)
+            .await?;
+
+            if result.rows_affected() == 0 {
+                return Err(AppError::BadRequest("Insufficient gold balance".into()));
+            }
+
+            ShopRepository::create_transaction_tx(
+                &mut tx,
+                user_id,
+                TransactionType::AllianceTreasury,
+                -request.gold,
+                Some("Alliance treasury deposit"),
+            )
+            .await?;
+        }
+
+        if let (Some(village_id), true) = (request.village_id, request.has_resources()) {
+            // Resources locked by open sell orders can't be deposited
+            let locked = TradeRepository::get_village_locked_resources_tx(&mut tx, village_id).await?;
+
+            let deducted = VillageRepository::deduct_resources_tx(
+                &mut tx,
+                village_id,
+                request.wood,
+                request.clay,
+                request.iron,
+                request.crop,
+                locked,
+            )
+            .await?;
+
+            if !deducted {
+                return Err(AppError::BadRequest("Insufficient resources in village".into()));
+            }
+        }
+
+        let treasury = AllianceRepository::deposit_to_treasury_tx(&mut tx, alliance_id, &request).await?;
+        AllianceRepository::log_treasury_tx(&mut tx, alliance_id, user_id, TreasuryAction::Deposit, &request).await?;
+
+        tx.commit().await?;
+
+        Ok(treasury)
+    }
+
+    /// Withdraw gold and/or resources from the treasury (leader/officers only)
+    pub async fn withdraw(
+        pool: &PgPool,
+        user_id: Uuid,
+        alliance_id: Uuid,
+        request: TreasuryTransferRequest,
+    ) -> AppResult<AllianceTreasury> {
+        Self::check_permission(pool, alliance_id, user_id, &[AllianceRole::Leader, AllianceRole::Officer]).await?;
+
+        Self::validate_transfer(pool, user_id, &request).await?;
+
+        if let (Some(village_id), true) = (request.village_id, request.has_resources()) {
+            ResourceService::update_village_resources(pool, village_id).await?;
+        }
+
+        let mut tx = pool.begin().await?;
+
+        // Anything past the village's free storage would be lost, so it stays in the treasury
+        if let (Some(village_id), true) = (request.village_id, request.has_resources()) {
+            let village = VillageRepository::find_by_id_for_update(&mut tx, village_id)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Village not found".into()))?;
+            if !Self::fits_in_storage(&village, &request) {
+                return Err(AppError::BadRequest("Not enough free storage in the village".into()));
+            }
+        }
+
+        let treasury = AllianceRepository::withdraw_from_treasury_tx(&mut tx, alliance_id, &request)
+            .await?
+            .ok_or_else(|| AppError::BadRequest("Insufficient treasury funds".into()))?;
+
+        if request.gold > 0 {
+            sqlx::query(
+                r#"
+                UPDATE users
+                SET gold_balance = gold_balance + $2
+                WHERE id = $1
+                "#,
+            )
+            .bind(user_id)
+            .bind(request.gold)
+            .execute(&mut *tx)
+            .await?;
+
+            ShopRepository::create_transaction_tx(
+                &mut tx,
+                user_id,
+                TransactionType::AllianceTreasury,
+                request.gold,
+                Some("Alliance treasury withdrawal"),
+            )
+            .await?;
+        }
+
+        if let (Some(village_id), true) = (request.village_id, request.has_resources()) {
+            VillageRepository::add_resources_tx(
+                &mut tx,
+                village_id,
+                request.wood,
+                request.clay,
+                request.iron,
+                request.crop,
+            )
+            .await?;
+        }
+
+        AllianceRepository::log_treasury_tx(&mut tx, alliance_id, user_id, TreasuryAction::Withdraw, &request).await?;
+
+        tx.commit().await?;
+
+        Ok(treasury)
+    }
+
+    /// Treasury deposit/withdrawal history (members only)
+    pub async fn get_treasury_log(
+        pool: &PgPool,
+        user_id: Uuid,
+        alliance_id: Uuid,
+        limit: i32,
+        offset: i32,
+    ) -> AppResult<Vec<AllianceTreasuryLog>> {
+        AllianceRepository::get_member(pool, alliance_id, user_id)
+            .await?
+            .ok_or_else(|| AppError::Forbidden("You are not a member of this alliance".into()))?;
+
+        AllianceRepository::list_treasury_log(pool, alliance_id, limit, offset).await
+    }
+
     // ==================== Helpers ====================
 
+    /// Whether the resources of `request` fit in the village's free warehouse and granary space
+    fn fits_in_storage(village: &Village, request: &TreasuryTransferRequest) -> bool {
+        let fits = |stored: i32, amount: i32, capacity: i32| stored as i64 + amount as i64 <= capacity as i64;
+        fits(village.wood, request.wood, village.warehouse_capacity)
+            && fits(village.clay, request.clay, village.warehouse_capacity)
+            && fits(village.iron, request.iron, village.warehouse_capacity)
+            && fits(village.crop, request.crop, village.granary_capacity)
+    }
+
+    async fn validate_transfer(
+        pool: &PgPool,
+        user_id: Uuid,
+        request: &TreasuryTransferRequest,
+    ) -> AppResult<()> {
+        let amounts = [request.gold, request.wood, request.clay, request.iron, request.crop];
+        if amounts.iter().any(|&a| a < 0) {
+            return Err(AppError::BadRequest("Amounts cannot be negative".into()));
+        }
+        if amounts.iter().all(|&a| a == 0) {
+            return Err(AppError::BadRequest("Nothing to transfer".into()));
+        }
+
+        if request.has_resources() {
+            let village_id = request
+                .village_id
+                .ok_or_else(|| AppError::BadRequest("village_id is required to transfer resources".into()))?;
+
+            let village = VillageRepository::find_by_id(pool, village_id)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Village not found".into()))?;
+
+            if village.user_id != user_id {
+                return Err(AppError::Forbidden("You don't own this village".into()));
+            }
+        }
+
+        Ok(())
+    }
+
     async fn check_permission(
         pool: &PgPool,
         alliance_id: Uuid,
@@ -348,3 +655,54 @@ impl AllianceService {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn transfer(village_id: Uuid, gold: i32, wood: i32) -> TreasuryTransferRequest {
+        TreasuryTransferRequest { village_id: Some(village_id), gold, wood, clay: 0, iron: 0, crop: 0 }
+    }
+
+    #[sqlx::test]
+    async fn treasury_transfers_respect_storage_and_reach_the_gold_ledger(pool: PgPool) {
+        let leader_id = test_support::create_user(&pool, 100).await;
+        let village = test_support::create_village(&pool, leader_id, 0, 0).await;
+        let request = CreateAllianceRequest { name: "Buffalo Riders".into(), tag: "BUF".into(), description: None };
+        let alliance = AllianceService::create_alliance(&pool, leader_id, request).await.unwrap();
+
+        AllianceService::deposit(&pool, leader_id, alliance.id, transfer(village.id, 40, 400), true).await.unwrap();
+
+        // With 500 wood stored the 800 warehouse has room for 300, so taking all 400 back is refused
+        sqlx::query("UPDATE villages SET wood = 500 WHERE id = $1")
+            .bind(village.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let result = AllianceService::withdraw(&pool, leader_id, alliance.id, transfer(village.id, 30, 400)).await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+        assert_eq!(test_support::gold_balance(&pool, leader_id).await, 60);
+
+        let treasury = AllianceService::withdraw(&pool, leader_id, alliance.id, transfer(village.id, 30, 300))
+            .await
+            .unwrap();
+        assert_eq!((treasury.gold, treasury.wood), (10, 100));
+        assert_eq!(test_support::village(&pool, village.id).await.wood, 800);
+
+        let ledger: Vec<(i32, Option<String>)> = sqlx::query_as(
+            "SELECT gold_amount, description FROM transactions WHERE user_id = $1 AND transaction_type = 'alliance_treasury' ORDER BY created_at",
+        )
+        .bind(leader_id)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            ledger,
+            vec![
+                (-40, Some("Alliance treasury deposit".to_string())),
+                (30, Some("Alliance treasury withdrawal".to_string())),
+            ]
+        );
+    }
+}
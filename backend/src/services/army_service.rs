@@ -1,21 +1,34 @@
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
+use std::sync::OnceLock;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use sqlx::PgPool;
 use tracing::{error, info};
 use uuid::Uuid;
 
+use crate::config::GameConfig;
 use crate::error::{AppError, AppResult};
 use crate::models::army::{
     Army, ArmyResponse, ArmyTroops, BattleReport, CarriedResources, MissionType, ScoutReport,
     SendArmyRequest,
 };
-use crate::models::hero::{HeroDefinition, HeroStatus};
+use crate::models::alliance::DiplomacyStatus;
+use crate::models::hero::{EquipmentBonuses, Hero, HeroDefinition, HeroStatus};
+use crate::models::building::BuildingType;
 use crate::models::troop::TroopDefinition;
-use crate::models::village::Village;
+use crate::models::village::{ArmySlots, Village};
+use crate::repositories::alliance_repo::AllianceRepository;
 use crate::repositories::army_repo::ArmyRepository;
+use crate::repositories::building_repo::BuildingRepository;
 use crate::repositories::hero_repo::HeroRepository;
+use crate::repositories::message_repo::MessageRepository;
 use crate::repositories::troop_repo::TroopRepository;
+use crate::repositories::user_repo::UserRepository;
 use crate::repositories::village_repo::VillageRepository;
-use crate::services::ws_service::{ArmyArrivedData, WsEvent, WsManager};
+use crate::services::hero_service::HeroService;
+use crate::services::ws_service::{
+    AllianceAttackAlertData, ArmyArrivedData, AttackIncomingData, AttackLandedData, WsEvent,
+    WsManager,
+};
 
 /// Internal struct for battle calculation results
 struct BattleResult {
@@ -26,6 +39,55 @@ struct BattleResult {
     defender_losses: ArmyTroops,
 }
 
+/// Minimum Rally Point level for defenders to see an estimate of incoming troops
+pub const INCOMING_ESTIMATE_MIN_RALLY_POINT_LEVEL: i32 = 5;
+
+/// Firebase UID of the system user that owns the generated Natarian villages
+pub const NATARIAN_FIREBASE_UID: &str = "natarian-npc-system";
+
+/// Natarian user id, resolved once it exists
+static NATARIAN_USER_ID: OnceLock<Uuid> = OnceLock::new();
+
+/// Difficulty tier of a Natarian village, inferred from the population it was generated with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NatarianTier {
+    Beginner,
+    Regular,
+    Veteran,
+    Elite,
+}
+
+impl NatarianTier {
+    fn from_population(population: i32) -> Self {
+        match population {
+            p if p >= 500 => NatarianTier::Elite,
+            p if p >= 300 => NatarianTier::Veteran,
+            p if p >= 150 => NatarianTier::Regular,
+            _ => NatarianTier::Beginner,
+        }
+    }
+
+    /// Extra loot on top of what was taken from the village (percent)
+    fn loot_bonus_percent(&self) -> i32 {
+        match self {
+            NatarianTier::Beginner => 10,
+            NatarianTier::Regular => 25,
+            NatarianTier::Veteran => 50,
+            NatarianTier::Elite => 100,
+        }
+    }
+
+    /// Experience for the attacking hero on a victory
+    fn hero_experience(&self) -> i32 {
+        match self {
+            NatarianTier::Beginner => 10,
+            NatarianTier::Regular => 25,
+            NatarianTier::Veteran => 60,
+            NatarianTier::Elite => 150,
+        }
+    }
+}
+
 /// Combat bonuses from hero passive abilities
 #[derive(Debug, Default)]
 struct CombatBonuses {
@@ -46,6 +108,9 @@ struct CombatBonuses {
 
     // Speed bonuses (for travel time)
     pub army_speed: i32,
+
+    // Flat attack the hero adds to the army (hero strength + equipped items)
+    pub hero_attack: i32,
 }
 
 impl CombatBonuses {
@@ -74,6 +139,12 @@ impl CombatBonuses {
         bonuses
     }
 
+    /// Add the hero's own fighting strength and equipped item bonuses
+    pub fn with_equipment(mut self, hero: &Hero, equipment: &EquipmentBonuses) -> Self {
+        self.hero_attack = hero.total_attack() + equipment.attack;
+        self
+    }
+
     /// Calculate attack multiplier for a specific troop type
     pub fn attack_multiplier(&self, troop_type: &crate::models::troop::TroopType) -> f64 {
         use crate::models::troop::TroopType;
@@ -123,9 +194,11 @@ impl ArmyService {
     /// Send an army from a village to target coordinates
     pub async fn send_army(
         pool: &PgPool,
+        ws_manager: &WsManager,
         player_id: Uuid,
         from_village_id: Uuid,
         request: SendArmyRequest,
+        game: &GameConfig,
     ) -> AppResult<ArmyResponse> {
         // Validate mission type
         if !matches!(
@@ -184,6 +257,15 @@ impl ArmyService {
             return Err(AppError::BadRequest("Must send at least one troop".into()));
         }
 
+        // Rally Point level limits how many armies can be on the move at once
+        let army_slots = Self::get_army_slots(pool, from_village_id).await?;
+        if army_slots.used >= army_slots.total {
+            return Err(AppError::BadRequest(format!(
+                "Rally Point can only handle {} armies at once. Upgrade it to send more.",
+                army_slots.total
+            )));
+        }
+
         // Get target village (if exists)
         let target_village = VillageRepository::find_by_coordinates(pool, request.to_x, request.to_y).await?;
 
@@ -194,6 +276,21 @@ impl ArmyService {
             }
         }
 
+        // Alliance diplomacy can forbid hostile missions against the target's owner
+        if let Some(ref target) = target_village {
+            if request.mission.is_hostile() {
+                Self::validate_diplomacy(pool, player_id, target.user_id, request.mission, game.nap_allows_raids)
+                    .await?;
+                Self::validate_beginner_protection(
+                    pool,
+                    target.user_id,
+                    request.mission,
+                    game.beginner_protection_blocks_raids,
+                )
+                .await?;
+            }
+        }
+
         // Support mission requires a target village
         if request.mission == MissionType::Support && target_village.is_none() {
             return Err(AppError::BadRequest("Support mission requires a target village".into()));
@@ -276,9 +373,165 @@ impl ArmyService {
             from_village_id, request.to_x, request.to_y, total_troops, arrives_at
         );
 
+        // Going on the offensive ends the attacker's own beginner protection
+        if matches!(request.mission, MissionType::Raid | MissionType::Attack | MissionType::Conquer) {
+            UserRepository::end_protection(pool, player_id).await?;
+        }
+
+        // Warn the defender and their nearby alliance members (scouts stay unnoticed)
+        if request.mission.alerts_defender() {
+            if let Some(ref target) = target_village {
+                if let Err(e) = Self::notify_defender(pool, ws_manager, &army, target).await {
+                    error!("Failed to notify defender of incoming attack on village {}: {:?}", target.id, e);
+                }
+                if let Err(e) =
+                    Self::alert_alliance_members(pool, ws_manager, target, arrives_at, game.alliance_alert_radius).await
+                {
+                    error!("Failed to send alliance attack alert for village {}: {:?}", target.id, e);
+                }
+            }
+        }
+
         Ok(army.into())
     }
 
+    /// Tell the defender right away that an attack is on its way
+    async fn notify_defender(
+        pool: &PgPool,
+        ws_manager: &WsManager,
+        army: &Army,
+        target: &Village,
+    ) -> AppResult<()> {
+        if !ws_manager.is_online(target.user_id).await {
+            return Ok(());
+        }
+
+        // Same estimate the dashboard shows, gated by the defender's Rally Point
+        let rally_point_level = Self::get_rally_point_level(pool, target.id).await?;
+
+        let event = WsEvent::AttackIncoming(AttackIncomingData {
+            army_id: army.id,
+            target_village_id: target.id,
+            target_village_name: target.name.clone(),
+            mission: format!("{:?}", army.mission).to_lowercase(),
+            arrival_time: army.arrives_at,
+            estimated_troops: Self::estimate_incoming_troops(army, rally_point_level),
+        });
+        ws_manager.send_to_user(target.user_id, &event).await;
+
+        Ok(())
+    }
+
+    /// Notify alliance members with a village near an attacked village.
+    /// Only the target and arrival time are shared, never the attacker.
+    async fn alert_alliance_members(
+        pool: &PgPool,
+        ws_manager: &WsManager,
+        target: &Village,
+        arrives_at: DateTime<Utc>,
+        radius: f64,
+    ) -> AppResult<()> {
+        let Some(membership) = AllianceRepository::get_user_alliance(pool, target.user_id).await? else {
+            return Ok(());
+        };
+
+        let defender_name = UserRepository::find_by_id(pool, target.user_id)
+            .await?
+            .and_then(|u| u.display_name)
+            .unwrap_or_else(|| "Unknown".to_string());
+
+        let nearby = AllianceRepository::find_members_near(
+            pool,
+            membership.alliance_id,
+            target.x,
+            target.y,
+            radius,
+            target.user_id,
+        )
+        .await?;
+
+        if nearby.is_empty() {
+            return Ok(());
+        }
+
+        // Persisted alert in the inbox of every nearby member, for those who are offline
+        let subject = format!("Attack alert: {} ({}|{})", target.name, target.x, target.y);
+        let body = format!(
+            "{}'s village {} at ({}|{}) is under attack. Hostile forces arrive at {}.",
+            defender_name,
+            target.name,
+            target.x,
+            target.y,
+            arrives_at.format("%Y-%m-%d %H:%M:%S UTC")
+        );
+        MessageRepository::create_system_messages(
+            pool,
+            &nearby,
+            &vec![subject; nearby.len()],
+            &vec![body; nearby.len()],
+        )
+        .await?;
+
+        let mut online = Vec::with_capacity(nearby.len());
+        for user_id in nearby {
+            if ws_manager.is_online(user_id).await {
+                online.push(user_id);
+            }
+        }
+
+        if online.is_empty() {
+            return Ok(());
+        }
+
+        let event = WsEvent::AllianceAttackAlert(AllianceAttackAlertData {
+            target_village_id: target.id,
+            target_village_name: target.name.clone(),
+            x: target.x,
+            y: target.y,
+            defender_id: target.user_id,
+            defender_name,
+            arrival_time: arrives_at,
+        });
+        ws_manager.send_to_users(&online, &event).await;
+
+        info!(
+            "Alliance attack alert for village {} sent to {} nearby members",
+            target.id,
+            online.len()
+        );
+
+        Ok(())
+    }
+
+    /// Maximum number of armies a village can have on the move for a Rally Point level
+    pub fn max_outgoing_armies(rally_point_level: i32) -> i32 {
+        // 5 base + 5 per Rally Point level
+        5 + rally_point_level.max(0) * 5
+    }
+
+    /// Get the Rally Point level of a village (0 if it has none)
+    pub async fn get_rally_point_level(pool: &PgPool, village_id: Uuid) -> AppResult<i32> {
+        let level = BuildingRepository::find_by_type(pool, village_id, BuildingType::RallyPoint)
+            .await?
+            .iter()
+            .map(|b| b.level)
+            .max()
+            .unwrap_or(0);
+
+        Ok(level)
+    }
+
+    /// Get used/total outgoing army slots for a village
+    pub async fn get_army_slots(pool: &PgPool, village_id: Uuid) -> AppResult<ArmySlots> {
+        let rally_point_level = Self::get_rally_point_level(pool, village_id).await?;
+        let used = ArmyRepository::count_outgoing_from_village(pool, village_id).await?;
+
+        Ok(ArmySlots {
+            used: used as i32,
+            total: Self::max_outgoing_armies(rally_point_level),
+        })
+    }
+
     /// Process all armies that have arrived at their destination
     pub async fn process_arrived_armies(pool: &PgPool) -> AppResult<i32> {
         let arrived = ArmyRepository::find_arrived(pool).await?;
@@ -386,6 +639,17 @@ impl ArmyService {
                         if let Some(target_id) = target_owner_id {
                             if home_owner_id != Some(target_id) {
                                 ws_manager.send_to_user(target_id, &event).await;
+
+                                if army.mission.alerts_defender() {
+                                    let landed = WsEvent::AttackLanded(AttackLandedData {
+                                        army_id: army.id,
+                                        target_village_id: army
+                                            .to_village_id
+                                            .unwrap_or(army.from_village_id),
+                                        mission: format!("{:?}", army.mission).to_lowercase(),
+                                    });
+                                    ws_manager.send_to_user(target_id, &landed).await;
+                                }
                             }
                         }
                     }
@@ -439,30 +703,16 @@ impl ArmyService {
         let mut total_defender_troops = village_troops.clone();
         for stationed in &stationed_armies {
             for (troop_type, count) in stationed.troops.0.iter() {
-                *total_defender_troops.entry(*troop_type).or_insert(0) += count;
+                let total = total_defender_troops.entry(*troop_type).or_insert(0);
+                *total = total.saturating_add(*count);
             }
         }
 
         // Get attacker's hero bonuses
-        let attacker_bonuses = if let Some(hero_id) = army.hero_id {
-            if let Some(hero) = HeroRepository::find_by_id(pool, hero_id).await? {
-                if let Some(def_id) = hero.hero_definition_id {
-                    let definition = HeroRepository::get_definition_by_id(pool, def_id).await?;
-                    CombatBonuses::from_hero_definition(definition.as_ref())
-                } else {
-                    CombatBonuses::default()
-                }
-            } else {
-                CombatBonuses::default()
-            }
-        } else {
-            CombatBonuses::default()
-        };
+        let attacker_bonuses = Self::get_hero_bonuses(pool, army.hero_id).await?;
 
-        // Get defender's hero bonuses (from stationed heroes)
-        // For now, use default (no defender hero bonuses from stationed armies)
-        // TODO: In the future, consider heroes stationed at villages for defense
-        let defender_bonuses = CombatBonuses::default();
+        // Get defender's hero bonuses (from a hero stationed in the village)
+        let defender_bonuses = Self::get_defender_hero_bonuses(pool, target.id).await?;
 
         // Calculate battle with combined defense and hero bonuses
         let battle = Self::calculate_battle(
@@ -475,9 +725,9 @@ impl ArmyService {
         );
 
         // Apply losses to village's own troops
-        let village_loss_ratio = if total_defender_troops.values().sum::<i32>() > 0 {
-            village_troops.values().sum::<i32>() as f64
-                / total_defender_troops.values().sum::<i32>() as f64
+        let village_loss_ratio = if Self::troop_total(&total_defender_troops) > 0 {
+            Self::troop_total(&village_troops) as f64
+                / Self::troop_total(&total_defender_troops) as f64
         } else {
             1.0
         };
@@ -529,7 +779,7 @@ impl ArmyService {
         }
 
         // Calculate stolen resources if attacker won
-        let stolen_resources = if battle.attacker_wins {
+        let mut stolen_resources = if battle.attacker_wins {
             Self::calculate_stolen_resources(&target, &battle.attacker_survivors, &definitions, army.mission)
         } else {
             CarriedResources::default()
@@ -548,10 +798,25 @@ impl ArmyService {
             .await?;
         }
 
+        // Beating a Natarian village pays a tier-scaled reward on top of the plunder
+        if battle.attacker_wins && Self::is_natarian(pool, target.user_id).await? {
+            let tier = NatarianTier::from_population(target.population);
+            stolen_resources = Self::apply_loot_bonus(&stolen_resources, tier.loot_bonus_percent());
+
+            if let Some(hero_id) = army.hero_id {
+                HeroRepository::add_experience(pool, hero_id, tier.hero_experience()).await?;
+            }
+
+            info!(
+                "Army {} defeated a {:?} Natarian village, loot bonus {}%",
+                army.id, tier, tier.loot_bonus_percent()
+            );
+        }
+
         // Create battle report (show total defender troops including support)
         let winner = if battle.attacker_wins {
             "attacker"
-        } else if battle.defender_survivors.values().sum::<i32>() > 0 {
+        } else if Self::troop_total(&battle.defender_survivors) > 0 {
             "defender"
         } else {
             "draw"
@@ -577,8 +842,8 @@ impl ArmyService {
         info!(
             "Battle at ({}, {}): {} wins! Attacker lost {:?}, Defender lost {:?} (including {} support armies)",
             army.to_x, army.to_y, winner,
-            battle.attacker_losses.values().sum::<i32>(),
-            battle.defender_losses.values().sum::<i32>(),
+            Self::troop_total(&battle.attacker_losses),
+            Self::troop_total(&battle.defender_losses),
             stationed_armies.len()
         );
 
@@ -850,27 +1115,15 @@ impl ArmyService {
         let mut total_defender_troops = village_troops.clone();
         for stationed in &stationed_armies {
             for (troop_type, count) in stationed.troops.0.iter() {
-                *total_defender_troops.entry(*troop_type).or_insert(0) += count;
+                let total = total_defender_troops.entry(*troop_type).or_insert(0);
+                *total = total.saturating_add(*count);
             }
         }
 
         // Get attacker's hero bonuses
-        let attacker_bonuses = if let Some(hero_id) = army.hero_id {
-            if let Some(hero) = HeroRepository::find_by_id(pool, hero_id).await? {
-                if let Some(def_id) = hero.hero_definition_id {
-                    let definition = HeroRepository::get_definition_by_id(pool, def_id).await?;
-                    CombatBonuses::from_hero_definition(definition.as_ref())
-                } else {
-                    CombatBonuses::default()
-                }
-            } else {
-                CombatBonuses::default()
-            }
-        } else {
-            CombatBonuses::default()
-        };
+        let attacker_bonuses = Self::get_hero_bonuses(pool, army.hero_id).await?;
 
-        let defender_bonuses = CombatBonuses::default();
+        let defender_bonuses = Self::get_defender_hero_bonuses(pool, target.id).await?;
 
         // Calculate battle (similar to Attack mission) with hero bonuses
         let battle = Self::calculate_battle(
@@ -969,7 +1222,7 @@ impl ArmyService {
         // Create battle report
         let winner = if battle.attacker_wins {
             "attacker"
-        } else if battle.defender_survivors.values().sum::<i32>() > 0 {
+        } else if Self::troop_total(&battle.defender_survivors) > 0 {
             "defender"
         } else {
             "draw"
@@ -1123,7 +1376,7 @@ impl ArmyService {
     }
 
     /// Calculate Euclidean distance between two points
-    fn calculate_distance(from_x: i32, from_y: i32, to_x: i32, to_y: i32) -> f64 {
+    pub fn calculate_distance(from_x: i32, from_y: i32, to_x: i32, to_y: i32) -> f64 {
         let dx = (to_x - from_x) as f64;
         let dy = (to_y - from_y) as f64;
         (dx * dx + dy * dy).sqrt()
@@ -1156,6 +1409,124 @@ impl ArmyService {
         Duration::seconds(seconds.max(60))
     }
 
+    /// Reject hostile missions against a player under beginner protection.
+    /// Attacks and conquests are always blocked; raids and scouting only when configured.
+    async fn validate_beginner_protection(
+        pool: &PgPool,
+        defender_id: Uuid,
+        mission: MissionType,
+        blocks_raids: bool,
+    ) -> AppResult<()> {
+        let blocked = match mission {
+            MissionType::Attack | MissionType::Conquer => true,
+            MissionType::Raid | MissionType::Scout => blocks_raids,
+            _ => false,
+        };
+        if !blocked {
+            return Ok(());
+        }
+
+        if let Some(until) = UserRepository::get_active_protection(pool, defender_id).await? {
+            return Err(AppError::BadRequest(format!(
+                "Target is under beginner protection until {}",
+                until.to_rfc3339()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Reject hostile missions between alliances at peace.
+    /// Allies can't send any hostile mission to each other; under a NAP attacks and
+    /// conquests are blocked, raids only when the server disallows them, and scouting is allowed.
+    /// A stance declared by either alliance counts.
+    async fn validate_diplomacy(
+        pool: &PgPool,
+        attacker_id: Uuid,
+        defender_id: Uuid,
+        mission: MissionType,
+        nap_allows_raids: bool,
+    ) -> AppResult<()> {
+        let attacker_alliance = AllianceRepository::get_user_alliance(pool, attacker_id).await?;
+        let defender_alliance = AllianceRepository::get_user_alliance(pool, defender_id).await?;
+        let (Some(attacker_alliance), Some(defender_alliance)) = (attacker_alliance, defender_alliance) else {
+            return Ok(());
+        };
+        if attacker_alliance.alliance_id == defender_alliance.alliance_id {
+            return Ok(());
+        }
+
+        let (ours, theirs) = (attacker_alliance.alliance_id, defender_alliance.alliance_id);
+        let ours_to_theirs = AllianceRepository::get_diplomacy(pool, ours, theirs).await?;
+        let theirs_to_ours = AllianceRepository::get_diplomacy(pool, theirs, ours).await?;
+        let statuses: Vec<DiplomacyStatus> =
+            ours_to_theirs.into_iter().chain(theirs_to_ours).map(|d| d.status).collect();
+
+        if statuses.contains(&DiplomacyStatus::Ally) {
+            return Err(AppError::BadRequest(format!(
+                "Cannot send a {:?} mission: your alliances are allied",
+                mission
+            )));
+        }
+
+        if statuses.contains(&DiplomacyStatus::Nap) {
+            let blocked = match mission {
+                MissionType::Attack | MissionType::Conquer => true,
+                MissionType::Raid => !nap_allows_raids,
+                _ => false,
+            };
+            if blocked {
+                return Err(AppError::BadRequest(format!(
+                    "Cannot send a {:?} mission: your alliances have a non-aggression pact",
+                    mission
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Load combat bonuses for a hero: passive abilities plus equipped items
+    async fn get_hero_bonuses(pool: &PgPool, hero_id: Option<Uuid>) -> AppResult<CombatBonuses> {
+        let Some(hero_id) = hero_id else {
+            return Ok(CombatBonuses::default());
+        };
+
+        let Some(hero) = HeroRepository::find_by_id(pool, hero_id).await? else {
+            return Ok(CombatBonuses::default());
+        };
+
+        let definition = match hero.hero_definition_id {
+            Some(def_id) => HeroRepository::get_definition_by_id(pool, def_id).await?,
+            None => None,
+        };
+        let equipment = HeroService::get_equipment_bonuses(pool, hero_id).await?;
+
+        Ok(CombatBonuses::from_hero_definition(definition.as_ref()).with_equipment(&hero, &equipment))
+    }
+
+    /// Load defense bonuses from the strongest defensive hero stationed in a village:
+    /// passive abilities plus the hero's def bonus attribute
+    async fn get_defender_hero_bonuses(pool: &PgPool, village_id: Uuid) -> AppResult<CombatBonuses> {
+        let Some(hero) = HeroRepository::find_stationed_by_villages(pool, &[village_id])
+            .await?
+            .into_iter()
+            .max_by_key(|h| h.def_bonus)
+        else {
+            return Ok(CombatBonuses::default());
+        };
+
+        let definition = match hero.hero_definition_id {
+            Some(def_id) => HeroRepository::get_definition_by_id(pool, def_id).await?,
+            None => None,
+        };
+
+        let mut bonuses = CombatBonuses::from_hero_definition(definition.as_ref());
+        bonuses.defense_bonus += hero.def_bonus_percent() as i32;
+
+        Ok(bonuses)
+    }
+
     /// Calculate battle using Travian-style formula with hero bonuses
     fn calculate_battle(
         attacker_troops: &ArmyTroops,
@@ -1191,8 +1562,8 @@ impl ArmyService {
         );
 
         // Apply last_stand bonus if attacker is outnumbered
-        let total_attacker_count: i32 = attacker_troops.values().sum();
-        let total_defender_count: i32 = defender_troops.values().sum();
+        let total_attacker_count = Self::troop_total(attacker_troops);
+        let total_defender_count = Self::troop_total(defender_troops);
         let attack_power = if total_attacker_count < total_defender_count && attacker_bonuses.last_stand > 0 {
             let last_stand_multiplier = 1.0 + (attacker_bonuses.last_stand as f64 / 100.0);
             attack_power * last_stand_multiplier
@@ -1313,7 +1684,8 @@ impl ArmyService {
                         base_attack * multiplier
                     })
             })
-            .sum()
+            .sum::<f64>()
+            + bonuses.hero_attack as f64
     }
 
     /// Calculate total defense power with hero bonuses applied
@@ -1338,6 +1710,11 @@ impl ArmyService {
             .sum()
     }
 
+    /// Number of troops in a stack, widened so large combined defenses can't overflow
+    fn troop_total(troops: &ArmyTroops) -> i64 {
+        troops.values().map(|&count| count as i64).sum()
+    }
+
     /// Apply loss ratio to troops
     fn apply_losses(troops: &ArmyTroops, loss_ratio: f64) -> ArmyTroops {
         troops
@@ -1417,6 +1794,27 @@ impl ArmyService {
         }
     }
 
+    /// Whether a user is the Natarian system player. The id is cached after the first hit.
+    async fn is_natarian(pool: &PgPool, user_id: Uuid) -> AppResult<bool> {
+        if let Some(id) = NATARIAN_USER_ID.get() {
+            return Ok(*id == user_id);
+        }
+
+        // Not cached until the map generator has created the Natarian user
+        let natarian = UserRepository::find_by_firebase_uid(pool, NATARIAN_FIREBASE_UID).await?;
+        Ok(natarian.is_some_and(|u| *NATARIAN_USER_ID.get_or_init(|| u.id) == user_id))
+    }
+
+    fn apply_loot_bonus(loot: &CarriedResources, bonus_percent: i32) -> CarriedResources {
+        let scale = |amount: i32| amount + amount * bonus_percent / 100;
+        CarriedResources {
+            wood: scale(loot.wood),
+            clay: scale(loot.clay),
+            iron: scale(loot.iron),
+            crop: scale(loot.crop),
+        }
+    }
+
     /// Get armies sent from a village
     pub async fn get_outgoing_armies(
         pool: &PgPool,
@@ -1431,6 +1829,38 @@ impl ArmyService {
         ArmyRepository::find_incoming_to_village(pool, village_id).await
     }
 
+    /// Estimate the troops of an incoming army as seen by the defender.
+    /// Returns None if the defender's Rally Point is too low to scout incoming armies.
+    /// Counts are fuzzed by a margin that shrinks as the Rally Point is upgraded
+    /// (±30% at level 5, ±20% at level 10, ±10% at level 15+). The fuzz is seeded
+    /// from the army id so repeated views of the same army show the same estimate.
+    pub fn estimate_incoming_troops(army: &Army, rally_point_level: i32) -> Option<ArmyTroops> {
+        if rally_point_level < INCOMING_ESTIMATE_MIN_RALLY_POINT_LEVEL {
+            return None;
+        }
+
+        let margin = match rally_point_level {
+            15.. => 0.10,
+            10..=14 => 0.20,
+            _ => 0.30,
+        };
+
+        let mut rng = StdRng::seed_from_u64(army.id.as_u128() as u64);
+
+        Some(
+            army.troops
+                .0
+                .iter()
+                .filter(|(_, count)| **count > 0)
+                .map(|(troop_type, count)| {
+                    let factor = rng.gen_range((1.0 - margin)..=(1.0 + margin));
+                    let estimate = (*count as f64 * factor).round() as i32;
+                    (*troop_type, estimate.max(1))
+                })
+                .collect(),
+        )
+    }
+
     /// Get battle reports for a player
     pub async fn get_reports(pool: &PgPool, player_id: Uuid) -> AppResult<Vec<BattleReport>> {
         ArmyRepository::find_reports_by_player(pool, player_id).await
@@ -1461,6 +1891,27 @@ impl ArmyService {
         ArmyRepository::mark_report_read(pool, report_id, is_attacker).await
     }
 
+    /// Archive or unarchive the player's copy of a battle report so it survives the retention cleanup
+    pub async fn set_report_archived(
+        pool: &PgPool,
+        report_id: Uuid,
+        player_id: Uuid,
+        archived: bool,
+    ) -> AppResult<()> {
+        let report = ArmyRepository::find_report_by_id(pool, report_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Report not found".into()))?;
+
+        let is_attacker = report.attacker_player_id == player_id;
+        let is_defender = report.defender_player_id == Some(player_id);
+
+        if !is_attacker && !is_defender {
+            return Err(AppError::Forbidden("Access denied".into()));
+        }
+
+        ArmyRepository::set_report_archived(pool, report_id, is_attacker, archived).await
+    }
+
     // ==================== Scout Reports ====================
 
     /// Get scout reports for a player
@@ -1566,3 +2017,49 @@ impl ArmyService {
         Ok(updated.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    #[sqlx::test]
+    async fn archiving_a_report_only_affects_the_players_own_copy(pool: PgPool) {
+        let attacker_id = test_support::create_user(&pool, 0).await;
+        let defender_id = test_support::create_user(&pool, 0).await;
+        let attacker_village = test_support::create_village(&pool, attacker_id, 0, 0).await;
+        let defender_village = test_support::create_village(&pool, defender_id, 10, 0).await;
+
+        let troops = ArmyTroops::new();
+        let report = ArmyRepository::create_battle_report(
+            &pool,
+            attacker_id,
+            Some(defender_id),
+            attacker_village.id,
+            Some(defender_village.id),
+            MissionType::Raid,
+            &troops,
+            &troops,
+            &troops,
+            &troops,
+            &CarriedResources::default(),
+            "attacker",
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+
+        ArmyService::set_report_archived(&pool, report.id, attacker_id, true).await.unwrap();
+
+        let report = ArmyService::get_report(&pool, report.id).await.unwrap().unwrap();
+        assert!(report.to_response(true).archived);
+        assert!(!report.to_response(false).archived);
+
+        ArmyService::set_report_archived(&pool, report.id, defender_id, true).await.unwrap();
+        ArmyService::set_report_archived(&pool, report.id, attacker_id, false).await.unwrap();
+
+        let report = ArmyService::get_report(&pool, report.id).await.unwrap().unwrap();
+        assert!(!report.to_response(true).archived);
+        assert!(report.to_response(false).archived);
+    }
+}
@@ -3,17 +3,23 @@ use std::time::Duration;
 use tokio::time::interval;
 use tracing::{error, info};
 
+use crate::config::GameConfig;
+use crate::repositories::army_repo::ArmyRepository;
 use crate::repositories::building_repo::BuildingRepository;
+use crate::repositories::message_repo::MessageRepository;
+use crate::repositories::shop_repo::ShopRepository;
 use crate::repositories::troop_repo::TroopRepository;
 use crate::repositories::village_repo::VillageRepository;
+use crate::services::admin_service::AdminService;
 use crate::services::army_service::ArmyService;
 use crate::services::building_service::BuildingService;
+use crate::services::hero_service::HeroService;
 use crate::services::resource_service::ResourceService;
 use crate::services::trade_service::TradeService;
-use crate::services::ws_service::{BuildingCompleteData, TradeOrderExpiredData, TroopTrainingCompleteData, TroopsStarvedData, WsEvent, WsManager};
+use crate::services::ws_service::{BuildingCompleteData, ResourcesDeliveredData, SubscriptionExpiryData, TradeOrderExpiredData, TroopTrainingCompleteData, TroopsStarvedData, WsEvent, WsManager};
 
 /// Start all background jobs
-pub async fn start_background_jobs(pool: PgPool, ws_manager: WsManager) {
+pub async fn start_background_jobs(pool: PgPool, ws_manager: WsManager, game_config: GameConfig) {
     // Spawn building completion job
     let pool_clone = pool.clone();
     let ws_clone = ws_manager.clone();
@@ -52,8 +58,58 @@ pub async fn start_background_jobs(pool: PgPool, ws_manager: WsManager) {
     // Spawn trade order expiry job
     let pool_clone = pool.clone();
     let ws_clone = ws_manager.clone();
+    let message_on_sell = game_config.trade_expiry_message_on_sell;
     tokio::spawn(async move {
-        run_trade_expiry_job(pool_clone, ws_clone).await;
+        run_trade_expiry_job(pool_clone, ws_clone, message_on_sell).await;
+    });
+
+    // Spawn merchant delivery job
+    let pool_clone = pool.clone();
+    let ws_clone = ws_manager.clone();
+    tokio::spawn(async move {
+        run_resource_delivery_job(pool_clone, ws_clone).await;
+    });
+
+    // Spawn subscription expiry job
+    let pool_clone = pool.clone();
+    let ws_clone = ws_manager.clone();
+    tokio::spawn(async move {
+        run_subscription_expiry_job(pool_clone, ws_clone).await;
+    });
+
+    // Spawn hero health regeneration job
+    let pool_clone = pool.clone();
+    tokio::spawn(async move {
+        run_hero_health_regen_job(pool_clone, game_config.hero_regen_on_adventure).await;
+    });
+
+    // Spawn hero adventure completion job
+    let pool_clone = pool.clone();
+    tokio::spawn(async move {
+        run_hero_adventure_job(pool_clone).await;
+    });
+
+    // Spawn hero auto-revive job (Travian Plus perk)
+    if game_config.hero_auto_revive_delay_hours > 0 {
+        let pool_clone = pool.clone();
+        let delay_hours = game_config.hero_auto_revive_delay_hours;
+        let health = game_config.hero_auto_revive_health;
+        tokio::spawn(async move {
+            run_hero_auto_revive_job(pool_clone, delay_hours, health).await;
+        });
+    }
+
+    // Spawn battle report retention cleanup job
+    let pool_clone = pool.clone();
+    let retention_days = game_config.battle_report_retention_days;
+    tokio::spawn(async move {
+        run_battle_report_cleanup_job(pool_clone, retention_days).await;
+    });
+
+    // Spawn economy snapshot job
+    let pool_clone = pool.clone();
+    tokio::spawn(async move {
+        run_economy_snapshot_job(pool_clone).await;
     });
 
     info!("Background jobs started");
@@ -184,15 +240,9 @@ async fn complete_troop_training(pool: &PgPool, ws_manager: &WsManager) -> anyho
     let mut count = 0;
 
     for entry in completed {
-        // Add troops to village
-        match TroopRepository::add_troops(pool, entry.village_id, entry.troop_type, entry.count).await {
-            Ok(_) => {
-                // Remove from queue
-                if let Err(e) = TroopRepository::remove_from_queue(pool, entry.id).await {
-                    error!("Failed to remove training queue entry {}: {:?}", entry.id, e);
-                    continue;
-                }
-
+        // Claim the entry and add its troops; None means Finish Now got there first
+        match TroopRepository::complete_training(pool, entry.id).await {
+            Ok(Some(_)) => {
                 info!(
                     "Troop training complete: {} x {:?} in village {}",
                     entry.count, entry.troop_type, entry.village_id
@@ -210,8 +260,9 @@ async fn complete_troop_training(pool: &PgPool, ws_manager: &WsManager) -> anyho
 
                 count += 1;
             }
+            Ok(None) => {}
             Err(e) => {
-                error!("Failed to add troops for queue entry {}: {:?}", entry.id, e);
+                error!("Failed to complete training queue entry {}: {:?}", entry.id, e);
             }
         }
     }
@@ -316,13 +367,13 @@ async fn process_starvation(pool: &PgPool, ws_manager: &WsManager) -> anyhow::Re
 }
 
 /// Process expired trade orders every 30 seconds
-async fn run_trade_expiry_job(pool: PgPool, ws_manager: WsManager) {
+async fn run_trade_expiry_job(pool: PgPool, ws_manager: WsManager, message_on_sell: bool) {
     let mut ticker = interval(Duration::from_secs(30));
 
     loop {
         ticker.tick().await;
 
-        match process_expired_trade_orders(&pool, &ws_manager).await {
+        match process_expired_trade_orders(&pool, &ws_manager, message_on_sell).await {
             Ok(count) => {
                 if count > 0 {
                     info!("Expired {} trade orders", count);
@@ -336,7 +387,11 @@ async fn run_trade_expiry_job(pool: PgPool, ws_manager: WsManager) {
 }
 
 /// Process expired trade orders and refund resources/gold
-async fn process_expired_trade_orders(pool: &PgPool, ws_manager: &WsManager) -> anyhow::Result<i32> {
+async fn process_expired_trade_orders(
+    pool: &PgPool,
+    ws_manager: &WsManager,
+    message_on_sell: bool,
+) -> anyhow::Result<i32> {
     let results = TradeService::process_expired_orders(pool, 100).await?;
 
     if results.is_empty() {
@@ -345,6 +400,37 @@ async fn process_expired_trade_orders(pool: &PgPool, ws_manager: &WsManager) ->
 
     let count = results.len() as i32;
 
+    // Persist an inbox message so offline players learn about the expiry too
+    let mut user_ids = Vec::new();
+    let mut subjects = Vec::new();
+    let mut bodies = Vec::new();
+    for result in &results {
+        if result.refunded_gold.is_none() && !message_on_sell {
+            continue;
+        }
+
+        let resource = format!("{:?}", result.order.resource_type).to_lowercase();
+        let remaining = result.order.quantity_remaining();
+        user_ids.push(result.user_id);
+        subjects.push(format!("Trade order expired: {:?} {}", result.order.order_type, resource));
+        bodies.push(match result.refunded_gold {
+            Some(gold) => format!(
+                "Your buy order for {} {} expired with {} units unfilled. {} gold has been refunded.",
+                result.order.quantity, resource, remaining, gold
+            ),
+            None => format!(
+                "Your sell order for {} {} expired with {} units unsold. The resources have been released back to your village.",
+                result.order.quantity, resource, remaining
+            ),
+        });
+    }
+
+    if !user_ids.is_empty() {
+        if let Err(e) = MessageRepository::create_system_messages(pool, &user_ids, &subjects, &bodies).await {
+            error!("Failed to create trade expiry messages: {:?}", e);
+        }
+    }
+
     // Send notifications to users
     for result in results {
         let event = WsEvent::TradeOrderExpired(TradeOrderExpiredData {
@@ -369,3 +455,238 @@ async fn process_expired_trade_orders(pool: &PgPool, ws_manager: &WsManager) ->
 
     Ok(count)
 }
+
+/// Unload arrived merchants every 10 seconds
+async fn run_resource_delivery_job(pool: PgPool, ws_manager: WsManager) {
+    let mut ticker = interval(Duration::from_secs(10));
+
+    loop {
+        ticker.tick().await;
+
+        match TradeService::process_arrived_deliveries(&pool, 100).await {
+            Ok(deliveries) => {
+                for delivery in deliveries {
+                    info!(
+                        "Delivered {} {:?} to village {}",
+                        delivery.quantity, delivery.resource_type, delivery.to_village_id
+                    );
+
+                    let event = WsEvent::ResourcesDelivered(ResourcesDeliveredData {
+                        delivery_id: delivery.id,
+                        village_id: delivery.to_village_id,
+                        resource_type: format!("{:?}", delivery.resource_type),
+                        quantity: delivery.quantity,
+                    });
+                    ws_manager.send_to_user(delivery.recipient_id, &event).await;
+                }
+            }
+            Err(e) => {
+                error!("Error processing resource deliveries: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Regenerate hero health every 60 seconds
+async fn run_hero_health_regen_job(pool: PgPool, regen_on_adventure: bool) {
+    let mut ticker = interval(Duration::from_secs(60));
+
+    loop {
+        ticker.tick().await;
+
+        match HeroService::process_health_regen(&pool, regen_on_adventure).await {
+            Ok(count) => {
+                if count > 0 {
+                    info!("Regenerated health for {} heroes", count);
+                }
+            }
+            Err(e) => {
+                error!("Error regenerating hero health: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Resolve finished hero adventures every 30 seconds
+async fn run_hero_adventure_job(pool: PgPool) {
+    let mut ticker = interval(Duration::from_secs(30));
+
+    loop {
+        ticker.tick().await;
+
+        match HeroService::process_completed_adventures(&pool).await {
+            Ok(count) => {
+                if count > 0 {
+                    info!("Completed {} hero adventures", count);
+                }
+            }
+            Err(e) => {
+                error!("Error completing hero adventures: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Auto-revive subscribers' dead heroes every 5 minutes
+async fn run_hero_auto_revive_job(pool: PgPool, delay_hours: i64, health: i32) {
+    let mut ticker = interval(Duration::from_secs(300));
+
+    loop {
+        ticker.tick().await;
+
+        match HeroService::process_auto_revive(&pool, delay_hours, health).await {
+            Ok(heroes) => {
+                for hero in &heroes {
+                    info!(
+                        "Auto-revived hero {} ({}) for user {} at {} health",
+                        hero.id, hero.name, hero.user_id, hero.health
+                    );
+                }
+            }
+            Err(e) => {
+                error!("Error auto-reviving heroes: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Minimum gap between economy snapshots, so several instances don't double up
+const ECONOMY_SNAPSHOT_MIN_INTERVAL_MINUTES: i64 = 50;
+
+/// Snapshot server-wide economy totals every hour
+async fn run_economy_snapshot_job(pool: PgPool) {
+    let mut ticker = interval(Duration::from_secs(60 * 60));
+
+    loop {
+        ticker.tick().await;
+
+        match AdminService::record_economy_snapshot(&pool, ECONOMY_SNAPSHOT_MIN_INTERVAL_MINUTES).await {
+            Ok(true) => {
+                info!("Recorded economy snapshot");
+            }
+            Ok(false) => {}
+            Err(e) => {
+                error!("Error recording economy snapshot: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Advisory lock key so only one instance prunes battle reports at a time
+const BATTLE_REPORT_CLEANUP_LOCK_KEY: i64 = 0x4252_5054; // "BRPT"
+/// Reports deleted per statement, keeping each delete's locks short
+const BATTLE_REPORT_CLEANUP_BATCH_SIZE: i64 = 1000;
+
+/// Prune old read battle reports once a day
+async fn run_battle_report_cleanup_job(pool: PgPool, retention_days: i64) {
+    let mut ticker = interval(Duration::from_secs(24 * 60 * 60));
+
+    loop {
+        ticker.tick().await;
+
+        match prune_battle_reports(&pool, retention_days).await {
+            Ok(Some(count)) => {
+                info!("Pruned {} battle reports older than {} days", count, retention_days);
+            }
+            Ok(None) => {
+                info!("Battle report cleanup skipped, another instance holds the lock");
+            }
+            Err(e) => {
+                error!("Error pruning battle reports: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Delete expired battle reports in batches while holding a session advisory lock.
+/// Returns None when another instance is already running the cleanup.
+async fn prune_battle_reports(pool: &PgPool, retention_days: i64) -> anyhow::Result<Option<u64>> {
+    let mut conn = pool.acquire().await?;
+
+    let (locked,): (bool,) = sqlx::query_as("SELECT pg_try_advisory_lock($1)")
+        .bind(BATTLE_REPORT_CLEANUP_LOCK_KEY)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    if !locked {
+        return Ok(None);
+    }
+
+    let mut total = 0u64;
+    let result = loop {
+        match ArmyRepository::delete_expired_reports_batch(
+            &mut conn,
+            retention_days,
+            BATTLE_REPORT_CLEANUP_BATCH_SIZE,
+        )
+        .await
+        {
+            Ok(deleted) => {
+                total += deleted;
+                if deleted < BATTLE_REPORT_CLEANUP_BATCH_SIZE as u64 {
+                    break Ok(Some(total));
+                }
+            }
+            Err(e) => break Err(e.into()),
+        }
+    };
+
+    sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(BATTLE_REPORT_CLEANUP_LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
+
+    result
+}
+
+/// Remind and expire subscriptions every 5 minutes
+async fn run_subscription_expiry_job(pool: PgPool, ws_manager: WsManager) {
+    let mut ticker = interval(Duration::from_secs(300));
+
+    loop {
+        ticker.tick().await;
+
+        match process_subscription_expiry(&pool, &ws_manager).await {
+            Ok(count) => {
+                if count > 0 {
+                    info!("Expired {} subscriptions", count);
+                }
+            }
+            Err(e) => {
+                error!("Error processing subscription expiry: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Send reminders for subscriptions expiring within 24h and deactivate expired ones
+async fn process_subscription_expiry(pool: &PgPool, ws_manager: &WsManager) -> anyhow::Result<i32> {
+    let expiring = ShopRepository::mark_expiry_reminders(pool, chrono::Duration::hours(24)).await?;
+
+    for sub in expiring {
+        let event = WsEvent::SubscriptionExpiring(SubscriptionExpiryData {
+            subscription_type: sub.subscription_type,
+            expires_at: sub.expires_at,
+        });
+        ws_manager.send_to_user(sub.user_id, &event).await;
+    }
+
+    // Deactivate so code paths that only check is_active don't see a stale subscription
+    let expired = ShopRepository::deactivate_expired_subscriptions(pool).await?;
+    let count = expired.len() as i32;
+
+    for sub in expired {
+        info!(
+            "Subscription {} ({:?}) expired for user {}",
+            sub.id, sub.subscription_type, sub.user_id
+        );
+
+        let event = WsEvent::SubscriptionExpired(SubscriptionExpiryData {
+            subscription_type: sub.subscription_type,
+            expires_at: sub.expires_at,
+        });
+        ws_manager.send_to_user(sub.user_id, &event).await;
+    }
+
+    Ok(count)
+}
@@ -1,8 +1,14 @@
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::error::{AppError, AppResult};
-use crate::models::building::{Building, BuildingType};
+use crate::models::building::{
+    Building, BuildingCost, BuildingType, CreateBuilding, UpgradePreviewResponse, BASE_STORAGE_CAPACITY,
+    RESOURCE_FIELD_SLOTS, VILLAGE_BUILDING_SLOTS,
+};
+use crate::models::trade::Resources;
+use crate::models::village::Village;
 use crate::repositories::building_repo::BuildingRepository;
 use crate::repositories::village_repo::VillageRepository;
 
@@ -51,6 +57,57 @@ impl BuildingService {
         Ok(missing)
     }
 
+    /// Check a building type may be placed at a slot of the village layout
+    pub fn validate_slot_placement(building_type: &BuildingType, slot: i32) -> AppResult<()> {
+        if !VILLAGE_BUILDING_SLOTS.contains(&slot) && !RESOURCE_FIELD_SLOTS.contains(&slot) {
+            return Err(AppError::BadRequest(format!("Invalid slot {}", slot)));
+        }
+
+        if !building_type.fits_slot(slot) {
+            let message = if building_type.is_resource_field() {
+                "Resource fields can only be built on resource slots"
+            } else {
+                "Resource slots are reserved for resource fields"
+            };
+            return Err(AppError::BadRequest(message.into()));
+        }
+
+        Ok(())
+    }
+
+    /// Validate a new building's slot against the village layout and existing buildings
+    pub async fn validate_slot_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        village_id: Uuid,
+        building_type: &BuildingType,
+        slot: i32,
+    ) -> AppResult<()> {
+        Self::validate_slot_placement(building_type, slot)?;
+
+        if BuildingRepository::find_by_village_and_slot_tx(tx, village_id, slot)
+            .await?
+            .is_some()
+        {
+            return Err(AppError::Conflict("Slot already occupied".into()));
+        }
+
+        Ok(())
+    }
+
+    /// Translate a unique violation on (village_id, slot) into a Conflict error,
+    /// for when two builds race for the same slot
+    pub fn map_slot_conflict(err: AppError) -> AppError {
+        match &err {
+            AppError::DatabaseError(sqlx::Error::Database(db_err))
+                if db_err.is_unique_violation()
+                    && db_err.constraint() == Some("buildings_village_id_slot_key") =>
+            {
+                AppError::Conflict("Slot already occupied".into())
+            }
+            _ => err,
+        }
+    }
+
     /// Validate building can be built (returns error if prerequisites not met)
     pub async fn validate_can_build(
         pool: &PgPool,
@@ -71,33 +128,315 @@ impl BuildingService {
         Ok(())
     }
 
+    /// Describe a building's next level without changing anything.
+    /// Uses the same formulas and cost lookup as the real upgrade.
+    pub async fn preview_upgrade(
+        pool: &PgPool,
+        village: &Village,
+        building: &Building,
+    ) -> AppResult<UpgradePreviewResponse> {
+        let building_type = &building.building_type;
+        let current_level = building.level;
+        let next_level = current_level + 1;
+
+        if next_level > building_type.max_level() {
+            return Err(AppError::BadRequest("Building is at max level".to_string()));
+        }
+
+        let cost = Self::cost_for_village(pool, village.id, building_type, next_level).await?;
+        let affordable = village.wood >= cost.wood
+            && village.clay >= cost.clay
+            && village.iron >= cost.iron
+            && village.crop >= cost.crop;
+        let prerequisites_met = Self::check_prerequisites(pool, village.id, building_type)
+            .await?
+            .is_empty();
+
+        let production = |level| building_type.is_resource_field().then(|| building_type.production_per_hour(level));
+        let storage = |level| {
+            building_type
+                .is_storage()
+                .then(|| building_type.storage_capacity(level))
+        };
+        let bonus = |level| {
+            building_type
+                .boosted_resource_field()
+                .map(|_| building_type.production_bonus_percent(level))
+        };
+
+        Ok(UpgradePreviewResponse {
+            building_type: building_type.clone(),
+            current_level,
+            next_level,
+            current_production_per_hour: production(current_level),
+            next_production_per_hour: production(next_level),
+            current_storage_capacity: storage(current_level),
+            next_storage_capacity: storage(next_level),
+            current_bonus_percent: bonus(current_level),
+            next_bonus_percent: bonus(next_level),
+            population_delta: building_type.population_at_level(next_level)
+                - building_type.population_at_level(current_level),
+            cost,
+            affordable,
+            prerequisites_met,
+            is_upgrading: building.is_upgrading,
+        })
+    }
+
+    /// Build speed factor for a Main Building level (5% faster per level)
+    pub fn build_speed_factor(main_building_level: i32) -> f64 {
+        1.0 + main_building_level.max(0) as f64 * 0.05
+    }
+
+    /// Get the Main Building level of a village (0 if it has none)
+    pub async fn get_main_building_level(pool: &PgPool, village_id: Uuid) -> AppResult<i32> {
+        let level = BuildingRepository::find_by_type(pool, village_id, BuildingType::MainBuilding)
+            .await?
+            .iter()
+            .map(|b| b.level)
+            .max()
+            .unwrap_or(0);
+
+        Ok(level)
+    }
+
+    /// Get the cost of building `building_type` at `level` in a village,
+    /// with the build time reduced by the village's Main Building
+    pub async fn cost_for_village(
+        pool: &PgPool,
+        village_id: Uuid,
+        building_type: &BuildingType,
+        level: i32,
+    ) -> AppResult<BuildingCost> {
+        let mut cost = building_type.cost_at_level(level);
+        let main_building_level = Self::get_main_building_level(pool, village_id).await?;
+        let factor = Self::build_speed_factor(main_building_level);
+        cost.time_seconds = ((cost.time_seconds as f64 / factor).round() as i32).max(1);
+
+        Ok(cost)
+    }
+
+    /// Pay for and start the next level of a building in one transaction.
+    /// The village row is locked so concurrent upgrades in the same village
+    /// can't both pass the affordability check.
+    pub async fn start_upgrade(pool: &PgPool, building: &Building, cost: &BuildingCost) -> AppResult<Building> {
+        let mut tx = pool.begin().await?;
+
+        let village = VillageRepository::find_by_id_for_update(&mut tx, building.village_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Village not found".into()))?;
+
+        Self::pay_cost_tx(&mut tx, &village, cost).await?;
+
+        let upgrade_ends_at = Utc::now() + chrono::Duration::seconds(cost.time_seconds as i64);
+        let building = BuildingRepository::start_upgrade_tx(&mut tx, building.id, upgrade_ends_at)
+            .await?
+            .ok_or_else(|| AppError::Conflict("Building is already upgrading".into()))?;
+
+        tx.commit().await?;
+
+        Ok(building)
+    }
+
+    /// Place a new building and start building it. The slot check, the payment and the
+    /// insert share one transaction under the village lock, so losing a race for the
+    /// slot costs nothing.
+    pub async fn build(
+        pool: &PgPool,
+        village_id: Uuid,
+        building_type: &BuildingType,
+        slot: i32,
+        cost: &BuildingCost,
+    ) -> AppResult<Building> {
+        let mut tx = pool.begin().await?;
+
+        let village = VillageRepository::find_by_id_for_update(&mut tx, village_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Village not found".into()))?;
+
+        Self::validate_slot_tx(&mut tx, village_id, building_type, slot).await?;
+        Self::pay_cost_tx(&mut tx, &village, cost).await?;
+
+        let create = CreateBuilding {
+            village_id,
+            building_type: building_type.clone(),
+            slot,
+        };
+        let building = BuildingRepository::create_with_level_tx(&mut tx, create, 1)
+            .await
+            .map_err(Self::map_slot_conflict)?;
+
+        let upgrade_ends_at = Utc::now() + chrono::Duration::seconds(cost.time_seconds as i64);
+        let building = BuildingRepository::start_upgrade_tx(&mut tx, building.id, upgrade_ends_at)
+            .await?
+            .ok_or_else(|| AppError::Conflict("Building is already upgrading".into()))?;
+
+        tx.commit().await?;
+
+        Ok(building)
+    }
+
+    /// Deduct a cost from a village locked FOR UPDATE in `tx`
+    async fn pay_cost_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        village: &Village,
+        cost: &BuildingCost,
+    ) -> AppResult<()> {
+        if village.wood < cost.wood
+            || village.clay < cost.clay
+            || village.iron < cost.iron
+            || village.crop < cost.crop
+        {
+            return Err(AppError::BadRequest("Not enough resources".into()));
+        }
+
+        if !VillageRepository::deduct_resources_tx(
+            tx,
+            village.id,
+            cost.wood,
+            cost.clay,
+            cost.iron,
+            cost.crop,
+            (0, 0, 0, 0),
+        )
+        .await?
+        {
+            return Err(AppError::BadRequest("Not enough resources".into()));
+        }
+
+        Ok(())
+    }
+
     /// Complete a building upgrade and handle side effects
     pub async fn complete_upgrade(pool: &PgPool, building_id: Uuid) -> AppResult<Building> {
-        // Complete the upgrade
-        let building = BuildingRepository::complete_upgrade(pool, building_id).await?;
+        let mut tx = pool.begin().await?;
+        let building = Self::complete_upgrade_tx(&mut tx, building_id).await?;
+        tx.commit().await?;
 
-        // Handle side effects based on building type
-        match building.building_type {
-            BuildingType::Warehouse | BuildingType::Granary => {
-                Self::update_village_storage(pool, building.village_id).await?;
-            }
-            _ => {}
-        }
+        Ok(building)
+    }
 
-        // Always update population after any building upgrade
-        Self::update_village_population(pool, building.village_id).await?;
+    /// Complete a building upgrade inside the caller's transaction
+    pub async fn complete_upgrade_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        building_id: Uuid,
+    ) -> AppResult<Building> {
+        // Complete the upgrade and bring population and storage in line with the new level
+        let building = BuildingRepository::complete_upgrade_tx(tx, building_id)
+            .await?
+            .ok_or_else(|| AppError::Conflict("Upgrade has already completed".into()))?;
+        Self::update_village_population_tx(tx, building.village_id).await?;
+        if building.building_type.is_storage() {
+            Self::update_village_storage_tx(tx, building.village_id).await?;
+        }
 
         Ok(building)
     }
 
-    /// Recalculate and update village storage capacity based on all Warehouse/Granary buildings
-    pub async fn update_village_storage(pool: &PgPool, village_id: Uuid) -> AppResult<()> {
-        let buildings = BuildingRepository::find_by_village_id(pool, village_id).await?;
+    /// Demolish a building and drop its population from the village.
+    /// An upgrade still in progress is refunded for its unfinished share.
+    pub async fn demolish(pool: &PgPool, building: &Building) -> AppResult<Option<Resources>> {
+        let refund = match building.upgrade_ends_at.filter(|_| building.is_upgrading) {
+            Some(ends_at) => Some(Self::upgrade_refund(pool, building, ends_at).await?),
+            None => None,
+        };
+
+        let mut tx = pool.begin().await?;
+
+        BuildingRepository::demolish_tx(&mut tx, building.id).await?;
+        Self::update_village_population_tx(&mut tx, building.village_id).await?;
+        if building.building_type.is_storage() {
+            Self::update_village_storage_tx(&mut tx, building.village_id).await?;
+        }
+        if let Some(refund) = &refund {
+            Self::refund_tx(&mut tx, building.village_id, refund).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(refund)
+    }
+
+    /// Cancel an in-progress upgrade and refund the unfinished share of its cost.
+    /// A new building that was still under construction (level 0) is removed.
+    /// Returns the building if it still exists, and the refund.
+    pub async fn cancel_upgrade(pool: &PgPool, building: &Building) -> AppResult<(Option<Building>, Resources)> {
+        let ends_at = building
+            .upgrade_ends_at
+            .filter(|_| building.is_upgrading)
+            .ok_or_else(|| AppError::BadRequest("Building is not upgrading".into()))?;
+
+        let refund = Self::upgrade_refund(pool, building, ends_at).await?;
+
+        let mut tx = pool.begin().await?;
+
+        // The completion job may have finished the upgrade in the meantime
+        let cancelled = BuildingRepository::cancel_upgrade_tx(&mut tx, building.id)
+            .await?
+            .ok_or_else(|| AppError::Conflict("Upgrade has already completed".into()))?;
+
+        let remaining = if cancelled.level == 0 {
+            BuildingRepository::demolish_tx(&mut tx, cancelled.id).await?;
+            Self::update_village_population_tx(&mut tx, cancelled.village_id).await?;
+            None
+        } else {
+            Some(cancelled)
+        };
+
+        Self::refund_tx(&mut tx, building.village_id, &refund).await?;
+
+        tx.commit().await?;
+
+        Ok((remaining, refund))
+    }
+
+    /// Unfinished share of an in-progress upgrade's cost
+    async fn upgrade_refund(pool: &PgPool, building: &Building, ends_at: DateTime<Utc>) -> AppResult<Resources> {
+        let cost = Self::cost_for_village(pool, building.village_id, &building.building_type, building.level + 1).await?;
+        // Upgrades started before the start time was recorded fall back to the current build time
+        let started_at = building
+            .upgrade_started_at
+            .unwrap_or_else(|| ends_at - chrono::Duration::seconds(cost.time_seconds as i64));
+
+        Ok(Self::unspent_share(&cost, started_at, ends_at, Utc::now()))
+    }
+
+    /// Scale a cost by the share of its upgrade window still ahead at `now`:
+    /// cancelling right after starting refunds almost everything, near the end almost nothing
+    pub fn unspent_share(
+        cost: &BuildingCost,
+        started_at: DateTime<Utc>,
+        ends_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Resources {
+        let total_seconds = (ends_at - started_at).num_seconds().max(1);
+        let remaining_seconds = (ends_at - now).num_seconds();
+        let fraction = (remaining_seconds as f64 / total_seconds as f64).clamp(0.0, 1.0);
+
+        Resources {
+            wood: (cost.wood as f64 * fraction) as i32,
+            clay: (cost.clay as f64 * fraction) as i32,
+            iron: (cost.iron as f64 * fraction) as i32,
+            crop: (cost.crop as f64 * fraction) as i32,
+        }
+    }
+
+    /// Credit a refund to a village, clamped to its storage
+    async fn refund_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        village_id: Uuid,
+        refund: &Resources,
+    ) -> AppResult<()> {
+        VillageRepository::add_resources_tx(tx, village_id, refund.wood, refund.clay, refund.iron, refund.crop).await
+    }
 
-        let mut warehouse_capacity = 800; // Base capacity
-        let mut granary_capacity = 800; // Base capacity
+    /// Warehouse and granary capacity for a village's buildings: the base capacity
+    /// plus every Warehouse/Granary that has finished at least level 1
+    pub fn storage_capacities(buildings: &[Building]) -> (i32, i32) {
+        let mut warehouse_capacity = BASE_STORAGE_CAPACITY;
+        let mut granary_capacity = BASE_STORAGE_CAPACITY;
 
-        for building in buildings {
+        for building in buildings.iter().filter(|b| b.level > 0) {
             match building.building_type {
                 BuildingType::Warehouse => {
                     warehouse_capacity += building.building_type.storage_capacity(building.level);
@@ -109,23 +448,268 @@ impl BuildingService {
             }
         }
 
-        VillageRepository::update_storage_capacity(pool, village_id, warehouse_capacity, granary_capacity)
+        (warehouse_capacity, granary_capacity)
+    }
+
+    /// Recalculate and update village storage capacity based on all Warehouse/Granary buildings
+    pub async fn update_village_storage_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        village_id: Uuid,
+    ) -> AppResult<()> {
+        let buildings = BuildingRepository::find_by_village_id_tx(tx, village_id).await?;
+        let (warehouse_capacity, granary_capacity) = Self::storage_capacities(&buildings);
+
+        VillageRepository::update_storage_capacity_tx(tx, village_id, warehouse_capacity, granary_capacity)
             .await?;
 
         Ok(())
     }
 
     /// Recalculate and update village population based on all buildings
-    pub async fn update_village_population(pool: &PgPool, village_id: Uuid) -> AppResult<()> {
-        let buildings = BuildingRepository::find_by_village_id(pool, village_id).await?;
+    pub async fn update_village_population_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        village_id: Uuid,
+    ) -> AppResult<()> {
+        let buildings = BuildingRepository::find_by_village_id_tx(tx, village_id).await?;
 
         let population: i32 = buildings
             .iter()
             .map(|b| b.building_type.population_at_level(b.level))
             .sum();
 
-        VillageRepository::update_population(pool, village_id, population).await?;
+        VillageRepository::update_population_tx(tx, village_id, population).await?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn build_speed_factor_grows_with_main_building() {
+        assert_eq!(BuildingService::build_speed_factor(0), 1.0);
+        assert_eq!(BuildingService::build_speed_factor(10), 1.5);
+        assert_eq!(BuildingService::build_speed_factor(-3), 1.0);
+        assert!(BuildingService::build_speed_factor(20) > BuildingService::build_speed_factor(1));
+    }
+
+    /// A village with a Main Building at `main_building_level` and a level 0 woodcutter
+    async fn village_with_main_building(pool: &PgPool, main_building_level: i32, x: i32) -> Building {
+        let user_id = test_support::create_user(pool, 0).await;
+        let village = test_support::create_village(pool, user_id, x, 0).await;
+        test_support::add_building(pool, village.id, BuildingType::MainBuilding, 1, main_building_level).await;
+        let field_id = test_support::add_building(pool, village.id, BuildingType::Woodcutter, 101, 0).await;
+
+        BuildingRepository::find_by_id(pool, field_id).await.unwrap().unwrap()
+    }
+
+    #[sqlx::test]
+    async fn higher_main_building_finishes_upgrade_sooner(pool: PgPool) {
+        let slow = village_with_main_building(&pool, 1, 0).await;
+        let fast = village_with_main_building(&pool, 10, 5).await;
+
+        let mut ends_at = Vec::new();
+        for field in [&slow, &fast] {
+            let cost = BuildingService::cost_for_village(&pool, field.village_id, &field.building_type, 1)
+                .await
+                .unwrap();
+            let upgrading = BuildingService::start_upgrade(&pool, field, &cost).await.unwrap();
+            ends_at.push(upgrading.upgrade_ends_at.unwrap());
+        }
+
+        assert!(ends_at[1] < ends_at[0]);
+    }
+
+    #[sqlx::test]
+    async fn demolishing_level_10_barracks_drops_its_population(pool: PgPool) {
+        let user_id = test_support::create_user(&pool, 0).await;
+        let village = test_support::create_village(&pool, user_id, 0, 0).await;
+        test_support::add_building(&pool, village.id, BuildingType::MainBuilding, 1, 5).await;
+        let barracks_id = test_support::add_building(&pool, village.id, BuildingType::Barracks, 2, 9).await;
+        sqlx::query("UPDATE buildings SET is_upgrading = TRUE, upgrade_ends_at = NOW() WHERE id = $1")
+            .bind(barracks_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Finishing level 10 brings the village population in line with its buildings
+        let barracks = BuildingService::complete_upgrade(&pool, barracks_id).await.unwrap();
+        assert_eq!(barracks.level, 10);
+        let before = test_support::village(&pool, village.id).await.population;
+        assert_eq!(
+            before,
+            BuildingType::MainBuilding.population_at_level(5) + BuildingType::Barracks.population_at_level(10)
+        );
+
+        BuildingService::demolish(&pool, &barracks).await.unwrap();
+
+        let after = test_support::village(&pool, village.id).await.population;
+        assert_eq!(before - after, BuildingType::Barracks.population_at_level(10));
+    }
+
+    #[test]
+    fn unspent_share_follows_upgrade_progress() {
+        let cost = BuildingCost { wood: 1000, clay: 800, iron: 600, crop: 400, time_seconds: 1000 };
+        let now = Utc::now();
+        let seconds = chrono::Duration::seconds;
+
+        let just_started = BuildingService::unspent_share(&cost, now - seconds(10), now + seconds(990), now);
+        assert_eq!(just_started.wood, 990);
+        assert_eq!(just_started.crop, 396);
+
+        let nearly_done = BuildingService::unspent_share(&cost, now - seconds(990), now + seconds(10), now);
+        assert_eq!(nearly_done.wood, 10);
+        assert_eq!(nearly_done.crop, 4);
+
+        // The window comes from the upgrade itself, not the cost's current build time
+        let slower_upgrade = BuildingService::unspent_share(&cost, now - seconds(1000), now + seconds(1000), now);
+        assert_eq!(slower_upgrade.wood, 500);
+
+        let finished = BuildingService::unspent_share(&cost, now - seconds(1000), now - seconds(1), now);
+        assert_eq!(finished.wood, 0);
+    }
+
+    #[sqlx::test]
+    async fn concurrent_upgrades_never_overdraw_the_village(pool: PgPool) {
+        let user_id = test_support::create_user(&pool, 0).await;
+        let village = test_support::create_village(&pool, user_id, 0, 0).await;
+        let first_id = test_support::add_building(&pool, village.id, BuildingType::Woodcutter, 101, 1).await;
+        let second_id = test_support::add_building(&pool, village.id, BuildingType::Woodcutter, 102, 1).await;
+        let first = BuildingRepository::find_by_id(&pool, first_id).await.unwrap().unwrap();
+        let second = BuildingRepository::find_by_id(&pool, second_id).await.unwrap().unwrap();
+
+        // Enough for one upgrade and a half
+        let cost = BuildingService::cost_for_village(&pool, village.id, &BuildingType::Woodcutter, 2).await.unwrap();
+        sqlx::query("UPDATE villages SET wood = $2, clay = $3, iron = $4, crop = $5 WHERE id = $1")
+            .bind(village.id)
+            .bind(cost.wood * 3 / 2)
+            .bind(cost.clay * 3 / 2)
+            .bind(cost.iron * 3 / 2)
+            .bind(cost.crop * 3 / 2)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let (a, b) = tokio::join!(
+            BuildingService::start_upgrade(&pool, &first, &cost),
+            BuildingService::start_upgrade(&pool, &second, &cost),
+        );
+        assert_eq!(a.is_ok() as u8 + b.is_ok() as u8, 1);
+
+        let after = test_support::village(&pool, village.id).await;
+        assert_eq!(after.wood, cost.wood * 3 / 2 - cost.wood);
+        assert!(after.clay >= 0 && after.iron >= 0 && after.crop >= 0);
+    }
+
+    #[test]
+    fn slot_placement_follows_the_village_layout() {
+        // Resource fields only on resource slots, everything else in the village center
+        assert!(BuildingService::validate_slot_placement(&BuildingType::Woodcutter, 101).is_ok());
+        assert!(BuildingService::validate_slot_placement(&BuildingType::Barracks, 19).is_ok());
+        assert!(BuildingService::validate_slot_placement(&BuildingType::Woodcutter, 19).is_err());
+        assert!(BuildingService::validate_slot_placement(&BuildingType::Barracks, 101).is_err());
+
+        for slot in [0, 41, 100, 119, -1] {
+            match BuildingService::validate_slot_placement(&BuildingType::Barracks, slot) {
+                Err(AppError::BadRequest(message)) => assert_eq!(message, format!("Invalid slot {}", slot)),
+                other => panic!("slot {} should be invalid, got {:?}", slot, other),
+            }
+        }
+    }
+
+    #[sqlx::test]
+    async fn building_on_an_occupied_slot_costs_nothing(pool: PgPool) {
+        let user_id = test_support::create_user(&pool, 0).await;
+        let village = test_support::create_village(&pool, user_id, 0, 0).await;
+        test_support::add_building(&pool, village.id, BuildingType::Warehouse, 10, 1).await;
+
+        let cost = BuildingService::cost_for_village(&pool, village.id, &BuildingType::Granary, 1).await.unwrap();
+        let result = BuildingService::build(&pool, village.id, &BuildingType::Granary, 10, &cost).await;
+        assert!(matches!(result, Err(AppError::Conflict(_))));
+        assert_eq!(test_support::village(&pool, village.id).await.wood, village.wood);
+    }
+
+    #[sqlx::test]
+    async fn racing_builds_for_one_slot_charge_only_the_winner(pool: PgPool) {
+        let user_id = test_support::create_user(&pool, 0).await;
+        let village = test_support::create_village(&pool, user_id, 0, 0).await;
+        let cost = BuildingService::cost_for_village(&pool, village.id, &BuildingType::Warehouse, 1).await.unwrap();
+
+        let (a, b) = tokio::join!(
+            BuildingService::build(&pool, village.id, &BuildingType::Warehouse, 10, &cost),
+            BuildingService::build(&pool, village.id, &BuildingType::Granary, 10, &cost),
+        );
+        assert_eq!(a.is_ok() as u8 + b.is_ok() as u8, 1);
+        assert!(matches!(a.and(b), Err(AppError::Conflict(_))));
+
+        let after = test_support::village(&pool, village.id).await;
+        assert_eq!(after.wood, village.wood - cost.wood);
+        assert_eq!(after.crop, village.crop - cost.crop);
+    }
+
+    fn building(building_type: BuildingType, slot: i32, level: i32) -> Building {
+        let now = Utc::now();
+        Building {
+            id: Uuid::new_v4(),
+            village_id: Uuid::nil(),
+            building_type,
+            slot,
+            level,
+            is_upgrading: false,
+            upgrade_started_at: None,
+            upgrade_ends_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn storage_capacities_add_up_every_storage_building() {
+        let buildings = [
+            building(BuildingType::Warehouse, 10, 3),
+            building(BuildingType::Warehouse, 11, 5),
+            building(BuildingType::Granary, 12, 2),
+            // Still under construction, and not a storage building
+            building(BuildingType::Granary, 13, 0),
+            building(BuildingType::Barracks, 19, 4),
+        ];
+
+        let (warehouse, granary) = BuildingService::storage_capacities(&buildings);
+        assert_eq!(
+            warehouse,
+            BASE_STORAGE_CAPACITY
+                + BuildingType::Warehouse.storage_capacity(3)
+                + BuildingType::Warehouse.storage_capacity(5)
+        );
+        assert_eq!(granary, BASE_STORAGE_CAPACITY + BuildingType::Granary.storage_capacity(2));
+
+        assert_eq!(BuildingService::storage_capacities(&[]), (BASE_STORAGE_CAPACITY, BASE_STORAGE_CAPACITY));
+    }
+
+    #[sqlx::test]
+    async fn completing_a_second_warehouse_adds_to_capacity(pool: PgPool) {
+        let user_id = test_support::create_user(&pool, 0).await;
+        let village = test_support::create_village(&pool, user_id, 0, 0).await;
+        test_support::add_building(&pool, village.id, BuildingType::Warehouse, 10, 4).await;
+        let second_id = test_support::add_building(&pool, village.id, BuildingType::Warehouse, 11, 1).await;
+        sqlx::query("UPDATE buildings SET is_upgrading = TRUE, upgrade_ends_at = NOW() WHERE id = $1")
+            .bind(second_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        BuildingService::complete_upgrade(&pool, second_id).await.unwrap();
+
+        let after = test_support::village(&pool, village.id).await;
+        assert_eq!(
+            after.warehouse_capacity,
+            BASE_STORAGE_CAPACITY
+                + BuildingType::Warehouse.storage_capacity(4)
+                + BuildingType::Warehouse.storage_capacity(2)
+        );
+        assert_eq!(after.granary_capacity, BASE_STORAGE_CAPACITY);
+    }
+}
@@ -0,0 +1,37 @@
+use sqlx::PgPool;
+use std::time::Duration;
+
+use crate::error::AppResult;
+use crate::models::admin::EconomyResponse;
+use crate::services::admin_service::AdminService;
+use crate::services::ttl_cache::TtlCache;
+
+/// How long computed economy metrics are served before they are rebuilt
+const ECONOMY_METRICS_TTL: Duration = Duration::from_secs(60);
+
+/// In-memory cache for the server-wide economy metrics.
+/// They sum over every village and player, so they are rebuilt at most once per TTL
+/// and shared by the admin and public endpoints.
+#[derive(Clone)]
+pub struct EconomyCache {
+    cache: TtlCache<EconomyResponse>,
+}
+
+impl EconomyCache {
+    pub fn new() -> Self {
+        Self {
+            cache: TtlCache::new(ECONOMY_METRICS_TTL),
+        }
+    }
+
+    /// Return the cached metrics, recomputing them when missing or stale
+    pub async fn get(&self, pool: &PgPool) -> AppResult<EconomyResponse> {
+        self.cache.get_or_refresh(|| AdminService::get_economy(pool)).await
+    }
+}
+
+impl Default for EconomyCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -6,7 +6,7 @@ use uuid::Uuid;
 use crate::error::{AppError, AppResult};
 use crate::models::hero::{
     AdventureDifficulty, AssignAttributesRequest, AvailableAdventureResponse, CreateHeroRequest,
-    EquippedItemsResponse, Hero, HeroAdventureResponse, HeroDefinition, HeroDefinitionResponse,
+    EquipmentBonuses, EquippedItemsResponse, Hero, HeroAdventure, HeroAdventureResponse, HeroDefinition, HeroDefinitionResponse,
     HeroItemResponse, HeroListResponse, HeroResponse, HeroSlotPurchaseResponse, HeroStatus,
     InventoryResponse, ItemDefinitionResponse, ItemRarity, ItemSlot, ReviveInfoResponse,
     ReviveResourceCost,
@@ -14,6 +14,10 @@ use crate::models::hero::{
 use crate::repositories::hero_repo::HeroRepository;
 use crate::repositories::shop_repo::ShopRepository;
 use crate::repositories::village_repo::VillageRepository;
+use crate::services::shop_service::ShopService;
+
+/// Item defense points that absorb 1 health of adventure damage
+const ADVENTURE_DEFENSE_PER_HEALTH: i32 = 10;
 
 pub struct HeroService;
 
@@ -62,7 +66,34 @@ impl HeroService {
         }
 
         let definition = Self::get_hero_definition(pool, &hero).await?;
-        Ok(HeroResponse::from_hero(hero, definition))
+
+        // Include the equipped loadout and its combined bonuses
+        let items = HeroRepository::get_equipped_items(pool, hero_id).await?;
+        let bonuses = EquipmentBonuses::from_items(items.iter().map(|(_, def)| def));
+        let mut equipped = EquippedItemsResponse::default();
+        for (hero_item, item_def) in items {
+            if let Some(slot) = hero_item.equipped_slot {
+                equipped.place(
+                    slot,
+                    HeroItemResponse {
+                        id: hero_item.id,
+                        item: item_def.into(),
+                        is_equipped: true,
+                        equipped_slot: hero_item.equipped_slot,
+                        quantity: hero_item.quantity,
+                        obtained_at: hero_item.obtained_at,
+                    },
+                );
+            }
+        }
+
+        Ok(HeroResponse::from_hero(hero, definition).with_equipment(equipped, bonuses))
+    }
+
+    /// Get combined bonuses from a hero's equipped items
+    pub async fn get_equipment_bonuses(pool: &PgPool, hero_id: Uuid) -> AppResult<EquipmentBonuses> {
+        let items = HeroRepository::get_equipped_items(pool, hero_id).await?;
+        Ok(EquipmentBonuses::from_items(items.iter().map(|(_, def)| def)))
     }
 
     /// Helper to get hero definition if the hero has one
@@ -143,8 +174,9 @@ impl HeroService {
         Ok(HeroResponse::from_hero(hero, hero_definition))
     }
 
-    /// Change hero's home village
-    pub async fn change_home_village(
+    /// Assign a hero to a village. While idle there, the hero grants the village
+    /// its production and defense bonuses.
+    pub async fn assign_hero(
         pool: &PgPool,
         user_id: Uuid,
         hero_id: Uuid,
@@ -158,6 +190,12 @@ impl HeroService {
             return Err(AppError::Forbidden("Access denied".into()));
         }
 
+        if !hero.can_be_reassigned() {
+            return Err(AppError::BadRequest(
+                "Hero can't be reassigned while on an adventure or travelling".into(),
+            ));
+        }
+
         // Verify village ownership
         let village = VillageRepository::find_by_id(pool, village_id)
             .await?
@@ -225,6 +263,9 @@ impl HeroService {
 
     /// Buy additional hero slot with gold
     pub async fn buy_hero_slot(pool: &PgPool, user_id: Uuid) -> AppResult<HeroSlotPurchaseResponse> {
+        // Hold the player's gold lock until commit
+        let (mut tx, balance) = ShopService::begin_gold_spend(pool, user_id).await?;
+
         let current_slots = HeroRepository::get_user_slots(pool, user_id).await?;
         let next_slot = current_slots + 1;
 
@@ -237,35 +278,26 @@ impl HeroService {
             .await?
             .ok_or_else(|| AppError::BadRequest("Invalid slot".into()))?;
 
-        // Check gold balance
-        let balance = ShopRepository::get_gold_balance(pool, user_id).await?;
-        if balance < price.gold_cost {
-            return Err(AppError::BadRequest("Insufficient gold".into()));
-        }
-
         // Deduct gold
-        let new_balance = ShopRepository::deduct_gold(pool, user_id, price.gold_cost).await?;
+        let new_balance =
+            ShopService::spend_gold_tx(&mut tx, user_id, balance, price.gold_cost).await?;
 
         // Add slot
-        let total_slots = HeroRepository::add_user_slot(pool, user_id).await?;
+        let total_slots = HeroRepository::add_user_slot_tx(&mut tx, user_id).await?;
 
         // Record transaction
-        ShopRepository::create_transaction(
-            pool,
+        ShopRepository::create_transaction_tx(
+            &mut tx,
             user_id,
             crate::models::shop::TransactionType::GoldSpend,
             -price.gold_cost,
-            None,
-            None,
-            None,
-            None,
             Some(&format!("Hero Slot #{}", next_slot)),
         )
         .await?;
 
         // Record gold usage
-        ShopRepository::record_gold_usage(
-            pool,
+        ShopRepository::record_gold_usage_tx(
+            &mut tx,
             user_id,
             crate::models::shop::GoldFeature::HeroSlot,
             price.gold_cost,
@@ -276,6 +308,8 @@ impl HeroService {
         )
         .await?;
 
+        tx.commit().await?;
+
         Ok(HeroSlotPurchaseResponse {
             success: true,
             new_slot_number: next_slot,
@@ -304,16 +338,7 @@ impl HeroService {
         let items = HeroRepository::get_hero_items(pool, hero_id).await?;
 
         // Separate equipped and unequipped items
-        let mut equipped = EquippedItemsResponse {
-            helmet: None,
-            weapon: None,
-            armor_left: None,
-            armor_right: None,
-            boots: None,
-            horse: None,
-            bag: None,
-            bandage: None,
-        };
+        let mut equipped = EquippedItemsResponse::default();
 
         let mut inventory_items: Vec<HeroItemResponse> = Vec::new();
         let mut extra_slots = 0;
@@ -329,21 +354,11 @@ impl HeroService {
             };
 
             if hero_item.is_equipped {
-                if let Some(slot) = &hero_item.equipped_slot {
-                    match slot {
-                        ItemSlot::Helmet => equipped.helmet = Some(item_response.clone()),
-                        ItemSlot::Weapon => equipped.weapon = Some(item_response.clone()),
-                        ItemSlot::ArmorLeft => equipped.armor_left = Some(item_response.clone()),
-                        ItemSlot::ArmorRight => equipped.armor_right = Some(item_response.clone()),
-                        ItemSlot::Boots => equipped.boots = Some(item_response.clone()),
-                        ItemSlot::Horse => equipped.horse = Some(item_response.clone()),
-                        ItemSlot::Bag => {
-                            extra_slots += item_def.extra_inventory_slots;
-                            equipped.bag = Some(item_response.clone());
-                        }
-                        ItemSlot::Bandage => equipped.bandage = Some(item_response.clone()),
-                        ItemSlot::Consumable => {}
+                if let Some(slot) = hero_item.equipped_slot {
+                    if slot == ItemSlot::Bag {
+                        extra_slots += item_def.extra_inventory_slots;
                     }
+                    equipped.place(slot, item_response);
                 }
             } else {
                 inventory_items.push(item_response);
@@ -399,7 +414,7 @@ impl HeroService {
         }
 
         // Consumables cannot be equipped
-        if item_def.is_consumable {
+        if item_def.is_consumable || !item_def.slot.is_equippable() {
             return Err(AppError::BadRequest("Consumables cannot be equipped".into()));
         }
 
@@ -434,6 +449,10 @@ impl HeroService {
             return Err(AppError::Forbidden("Access denied".into()));
         }
 
+        if !slot.is_equippable() {
+            return Err(AppError::BadRequest("Invalid equipment slot".into()));
+        }
+
         HeroRepository::unequip_slot(pool, hero_id, slot).await?;
         Ok(())
     }
@@ -738,7 +757,7 @@ impl HeroService {
         let mut count = 0;
 
         for adventure in completed {
-            if let Err(e) = Self::complete_adventure(pool, adventure.id).await {
+            if let Err(e) = Self::complete_adventure(pool, &adventure).await {
                 tracing::error!("Failed to complete adventure {}: {}", adventure.id, e);
             } else {
                 count += 1;
@@ -748,12 +767,14 @@ impl HeroService {
         Ok(count)
     }
 
-    /// Complete a single adventure
-    async fn complete_adventure(pool: &PgPool, adventure_id: Uuid) -> AppResult<()> {
-        let adventure = HeroRepository::get_active_adventure(pool, adventure_id)
-            .await?
-            .ok_or_else(|| AppError::NotFound("Adventure not found".into()))?;
+    /// Health lost on an adventure after armor. Item defense is flat points (like item
+    /// attack in battle), and every ADVENTURE_DEFENSE_PER_HEALTH points absorb 1 health.
+    fn adventure_health_damage(base_damage: i32, item_defense: i32) -> i32 {
+        (base_damage - item_defense.max(0) / ADVENTURE_DEFENSE_PER_HEALTH).max(0)
+    }
 
+    /// Complete a single adventure
+    async fn complete_adventure(pool: &PgPool, adventure: &HeroAdventure) -> AppResult<()> {
         // Pre-generate all random values (scope RNG so it's dropped before await)
         struct RewardParams {
             base_exp: i32,
@@ -843,23 +864,28 @@ impl HeroService {
             None
         };
 
+        // Apply equipped item bonuses: experience bonus (%) and armor absorbing damage taken
+        let bonuses = Self::get_equipment_bonuses(pool, adventure.hero_id).await?;
+        let experience = params.base_exp * (100 + bonuses.experience) / 100;
+        let health_damage = Self::adventure_health_damage(params.health_damage, bonuses.defense);
+
         // Complete adventure
         HeroRepository::complete_adventure(
             pool,
-            adventure_id,
-            params.base_exp,
+            adventure.id,
+            experience,
             params.base_silver,
             Some(params.resources),
             item_id,
-            params.health_damage,
+            health_damage,
         )
         .await?;
 
         // Add experience to hero
-        HeroRepository::add_experience(pool, adventure.hero_id, params.base_exp).await?;
+        HeroRepository::add_experience(pool, adventure.hero_id, experience).await?;
 
         // Damage hero
-        HeroRepository::damage_hero(pool, adventure.hero_id, params.health_damage).await?;
+        HeroRepository::damage_hero(pool, adventure.hero_id, health_damage).await?;
 
         // Update hero status back to idle (if not dead)
         let hero = HeroRepository::find_by_id(pool, adventure.hero_id).await?;
@@ -936,19 +962,17 @@ impl HeroService {
         }
 
         if use_gold {
+            // Hold the player's gold lock until commit; revive info re-checks the hero under it
+            let (mut tx, balance) = ShopService::begin_gold_spend(pool, user_id).await?;
             let revive_info = Self::get_revive_info(pool, user_id, hero_id).await?;
 
-            // Check gold balance
-            let balance = ShopRepository::get_gold_balance(pool, user_id).await?;
-            if balance < revive_info.gold_cost_instant {
-                return Err(AppError::BadRequest("Insufficient gold".into()));
-            }
-
             // Deduct gold
-            ShopRepository::deduct_gold(pool, user_id, revive_info.gold_cost_instant).await?;
+            ShopService::spend_gold_tx(&mut tx, user_id, balance, revive_info.gold_cost_instant)
+                .await?;
 
             // Revive with 50% health
             let hero = HeroRepository::revive_hero(pool, hero_id, 50).await?;
+            tx.commit().await?;
             Ok(hero.into())
         } else {
             // Natural revive - check if time has passed
@@ -966,19 +990,55 @@ impl HeroService {
     // ==================== Health Regeneration ====================
 
     /// Process health regeneration for all heroes (called by background job)
-    pub async fn process_health_regen(pool: &PgPool) -> AppResult<i32> {
-        // Get all heroes that need health regen (health < 100 and not dead)
+    ///
+    /// Travian Plus perk: bring back dead heroes after `delay_hours` at `health`,
+    /// without the player paying for a revive
+    pub async fn process_auto_revive(pool: &PgPool, delay_hours: i64, health: i32) -> AppResult<Vec<Hero>> {
+        HeroRepository::auto_revive_subscriber_heroes(pool, delay_hours, health.clamp(1, 100)).await
+    }
+
+    /// Heroes regenerate `health_regen_rate` plus the regen bonus of their equipped
+    /// items per hour. Dead heroes never regenerate; heroes on an adventure only
+    /// regenerate when `regen_on_adventure` is set.
+    pub async fn process_health_regen(pool: &PgPool, regen_on_adventure: bool) -> AppResult<i32> {
+        // Heroes that don't heal while away shouldn't catch up on that time when they return
+        if !regen_on_adventure {
+            sqlx::query(
+                r#"
+                UPDATE heroes
+                SET last_health_update = NOW()
+                WHERE status = 'in_adventure'
+                "#,
+            )
+            .execute(pool)
+            .await?;
+        }
+
+        // Only touch heroes that gained at least 1 HP, so partial hours keep accumulating
         let result = sqlx::query(
             r#"
-            UPDATE heroes
-            SET health = LEAST(100, health + (
-                EXTRACT(EPOCH FROM (NOW() - last_health_update)) / 3600.0 * health_regen_rate
-            )::INTEGER),
-            last_health_update = NOW(),
-            updated_at = NOW()
-            WHERE health < 100 AND health > 0 AND status != 'dead'
+            WITH regen AS (
+                SELECT h.id,
+                       FLOOR(
+                           EXTRACT(EPOCH FROM (NOW() - h.last_health_update)) / 3600.0
+                           * (h.health_regen_rate + COALESCE(SUM(idef.health_regen_bonus), 0))
+                       )::INTEGER AS amount
+                FROM heroes h
+                LEFT JOIN hero_items hi ON hi.hero_id = h.id AND hi.is_equipped = TRUE
+                LEFT JOIN item_definitions idef ON idef.id = hi.item_definition_id
+                WHERE h.health < 100 AND h.health > 0 AND h.status != 'dead'
+                  AND ($1 OR h.status != 'in_adventure')
+                GROUP BY h.id
+            )
+            UPDATE heroes h
+            SET health = LEAST(100, h.health + regen.amount),
+                last_health_update = NOW(),
+                updated_at = NOW()
+            FROM regen
+            WHERE h.id = regen.id AND regen.amount > 0
             "#,
         )
+        .bind(regen_on_adventure)
         .execute(pool)
         .await?;
 
@@ -0,0 +1,79 @@
+use chrono::Utc;
+use sqlx::PgPool;
+use std::time::Duration;
+
+use crate::error::AppResult;
+use crate::models::trade::{MarketSummaryResponse, WashTradeFilter};
+use crate::services::trade_service::TradeService;
+use crate::services::ttl_cache::TtlCache;
+
+/// How long a computed market summary is served before it is rebuilt
+const MARKET_SUMMARY_TTL: Duration = Duration::from_secs(5);
+
+/// In-memory cache for the global market summary.
+/// The summary takes several queries per resource, so it is rebuilt at most
+/// once per TTL and dropped whenever a trade executes.
+#[derive(Clone)]
+pub struct MarketSummaryCache {
+    cache: TtlCache<MarketSummaryResponse>,
+}
+
+impl MarketSummaryCache {
+    pub fn new() -> Self {
+        Self::with_ttl(MARKET_SUMMARY_TTL)
+    }
+
+    fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            cache: TtlCache::new(ttl),
+        }
+    }
+
+    /// Return the cached summary, rebuilding it from the database when missing or stale
+    pub async fn get(&self, pool: &PgPool, filter: &WashTradeFilter) -> AppResult<MarketSummaryResponse> {
+        self.cache
+            .get_or_refresh(|| async {
+                let summaries = TradeService::get_market_summary(pool, filter).await?;
+                Ok(MarketSummaryResponse {
+                    summaries,
+                    updated_at: Utc::now(),
+                })
+            })
+            .await
+    }
+
+    /// Drop the cached summary so the next request sees fresh prices
+    pub async fn invalidate(&self) {
+        self.cache.invalidate().await;
+    }
+}
+
+impl Default for MarketSummaryCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    #[sqlx::test]
+    async fn summary_is_rebuilt_once_the_ttl_has_passed(pool: PgPool) {
+        let filter = test_support::game_config().wash_trade_filter();
+        let cache = MarketSummaryCache::with_ttl(Duration::from_millis(200));
+
+        let first = cache.get(&pool, &filter).await.unwrap();
+        let cached = cache.get(&pool, &filter).await.unwrap();
+        assert_eq!(cached.updated_at, first.updated_at);
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        let rebuilt = cache.get(&pool, &filter).await.unwrap();
+        assert!(rebuilt.updated_at > first.updated_at);
+
+        cache.invalidate().await;
+        let after_invalidate = cache.get(&pool, &filter).await.unwrap();
+        assert!(after_invalidate.updated_at > rebuilt.updated_at);
+    }
+}
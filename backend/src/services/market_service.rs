@@ -0,0 +1,132 @@
+use chrono::{Duration, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::models::building::BuildingType;
+use crate::models::trade::{
+    MerchantAvailability, ResourceDelivery, TradeOrderStatus, TradeOrderType, TradeTransaction,
+};
+use crate::repositories::building_repo::BuildingRepository;
+use crate::repositories::trade_repo::TradeRepository;
+use crate::repositories::village_repo::VillageRepository;
+use crate::services::army_service::ArmyService;
+
+/// Merchant speed of a village without a Market (fields per hour)
+const MERCHANT_BASE_SPEED: f64 = 16.0;
+
+/// Extra merchant speed per Market level (percent)
+const MERCHANT_SPEED_PERCENT_PER_LEVEL: f64 = 5.0;
+
+/// Merchants granted by each Market level
+const MERCHANTS_PER_MARKET_LEVEL: i32 = 1;
+
+/// Resources a single merchant can carry
+const MERCHANT_CAPACITY: i32 = 500;
+
+/// Shortest delivery, so even neighbours don't trade instantly
+const MIN_DELIVERY_SECONDS: i64 = 60;
+
+pub struct MarketService;
+
+impl MarketService {
+    // ==================== Merchants ====================
+
+    /// Merchant speed (fields per hour) for a village with the given Market level
+    pub fn merchant_speed(market_level: i32) -> f64 {
+        MERCHANT_BASE_SPEED * (1.0 + MERCHANT_SPEED_PERCENT_PER_LEVEL * market_level.max(0) as f64 / 100.0)
+    }
+
+    /// Time merchants need to cover `distance` fields
+    pub fn delivery_time(distance: f64, market_level: i32) -> Duration {
+        let hours = distance / Self::merchant_speed(market_level);
+        let seconds = (hours * 3600.0).ceil() as i64;
+        Duration::seconds(seconds.max(MIN_DELIVERY_SECONDS))
+    }
+
+    /// Merchants needed to carry `quantity` resources
+    pub fn merchants_needed(quantity: i32) -> i32 {
+        (quantity.max(0) + MERCHANT_CAPACITY - 1) / MERCHANT_CAPACITY
+    }
+
+    /// Merchants of a village, minus those reserved by its open sell orders
+    /// and those still out on deliveries
+    pub async fn available_merchants(pool: &PgPool, village_id: Uuid) -> AppResult<MerchantAvailability> {
+        let market_level = BuildingRepository::find_by_type(pool, village_id, BuildingType::Market)
+            .await?
+            .first()
+            .map(|b| b.level)
+            .unwrap_or(0);
+        let total = market_level * MERCHANTS_PER_MARKET_LEVEL;
+
+        let reserved: i32 = TradeRepository::get_village_orders(pool, village_id)
+            .await?
+            .iter()
+            .filter(|o| {
+                o.order_type == TradeOrderType::Sell
+                    && matches!(o.status, TradeOrderStatus::Open | TradeOrderStatus::PartiallyFilled)
+            })
+            .map(|o| Self::merchants_needed(o.quantity_remaining()))
+            .sum();
+        let travelling: i32 = TradeRepository::get_outgoing_deliveries(pool, village_id)
+            .await?
+            .iter()
+            .map(|d| Self::merchants_needed(d.quantity))
+            .sum();
+
+        let busy = reserved + travelling;
+        let free = (total - busy).max(0);
+
+        Ok(MerchantAvailability {
+            market_level,
+            total,
+            busy,
+            free,
+            capacity_per_merchant: MERCHANT_CAPACITY,
+            free_capacity: free * MERCHANT_CAPACITY,
+        })
+    }
+
+    /// Check the village has enough free merchants to ship `quantity` resources
+    pub async fn validate_merchant_capacity(pool: &PgPool, village_id: Uuid, quantity: i32) -> AppResult<()> {
+        let merchants = Self::available_merchants(pool, village_id).await?;
+
+        if merchants.free_capacity < quantity {
+            return Err(AppError::BadRequest(format!(
+                "Not enough merchants. Available capacity: {}, Required: {}",
+                merchants.free_capacity, quantity
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Send a trade's resources from the seller's village to the buyer's.
+    /// Travel time depends on the distance and the seller's Market level.
+    pub async fn schedule_delivery_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        transaction: &TradeTransaction,
+    ) -> AppResult<ResourceDelivery> {
+        let seller_village_id = transaction
+            .seller_village_id
+            .ok_or_else(|| AppError::NotFound("Seller village not found".into()))?;
+        let buyer_village_id = transaction
+            .buyer_village_id
+            .ok_or_else(|| AppError::NotFound("Buyer village not found".into()))?;
+
+        let (from_x, from_y) = VillageRepository::find_coordinates_tx(tx, seller_village_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Seller village not found".into()))?;
+        let (to_x, to_y) = VillageRepository::find_coordinates_tx(tx, buyer_village_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Buyer village not found".into()))?;
+
+        let market_level =
+            BuildingRepository::get_max_level_tx(tx, seller_village_id, BuildingType::Market).await?;
+
+        let distance = ArmyService::calculate_distance(from_x, from_y, to_x, to_y);
+        let arrives_at = Utc::now() + Self::delivery_time(distance, market_level);
+
+        TradeRepository::create_delivery_tx(tx, transaction, arrives_at).await
+    }
+}
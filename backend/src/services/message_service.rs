@@ -1,12 +1,37 @@
+use chrono::{Duration, Utc};
 use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::error::{AppError, AppResult};
+use crate::models::alliance::AllianceRole;
 use crate::models::message::{
-    AllianceMessageListItem, ConversationResponse, MessageListItem, MessageResponse,
+    AllianceMessageListItem, AllianceMessageResponse, ConversationResponse, MessageListItem,
+    MessageEntity, MessageReadReceipts, MessageResponse,
 };
+use crate::models::pagination::{PageQuery, Paginated};
 use crate::repositories::alliance_repo::AllianceRepository;
 use crate::repositories::message_repo::MessageRepository;
+use crate::repositories::user_repo::UserRepository;
+use crate::repositories::village_repo::VillageRepository;
+use crate::services::ws_service::{NewMessageData, WsEvent, WsManager};
+
+/// Accounts younger than this may only message known players
+const NEW_ACCOUNT_MIN_AGE_HOURS: i64 = 72;
+/// Maximum messages per inbox/sent page
+const MAX_MESSAGES_PER_PAGE: i32 = 50;
+/// Maximum number of messages deleted in one bulk request
+const MAX_BULK_DELETE: usize = 100;
+/// Maximum coordinates/players resolved per message
+const MAX_MESSAGE_ENTITIES: usize = 20;
+/// Longest coordinate component accepted in `(x|y)`, in digits
+const MAX_COORDINATE_DIGITS: usize = 4;
+
+/// A mention found in a message body, before it is looked up
+#[derive(Debug, PartialEq)]
+enum Mention {
+    Coordinates { text: String, x: i32, y: i32 },
+    Player { text: String, name: String },
+}
 
 pub struct MessageService;
 
@@ -16,6 +41,7 @@ impl MessageService {
     /// Send a private message to another player
     pub async fn send_private_message(
         pool: &PgPool,
+        ws_manager: &WsManager,
         sender_id: Uuid,
         recipient_id: Uuid,
         subject: String,
@@ -41,6 +67,8 @@ impl MessageService {
             ));
         }
 
+        Self::check_new_account_messaging(pool, sender_id, recipient_id).await?;
+
         // Get or create conversation
         let conversation =
             MessageRepository::get_or_create_conversation(pool, sender_id, recipient_id).await?;
@@ -61,13 +89,66 @@ impl MessageService {
             .await?;
 
         // Return full message with user names
-        let response = MessageRepository::get_message(pool, message.id)
+        let mut response = MessageRepository::get_message(pool, message.id)
             .await?
             .ok_or_else(|| AppError::InternalError(anyhow::anyhow!("Failed to fetch created message")))?;
+        response.entities = Self::resolve_entities(pool, &body).await?;
+
+        // Still notify muted conversations, the client decides whether to ping
+        let event = WsEvent::NewMessage(NewMessageData {
+            message_id: response.id,
+            conversation_id: conversation.id,
+            sender_id,
+            sender_name: response.sender_name.clone(),
+            subject: response.subject.clone(),
+            muted: conversation.is_muted_for(recipient_id),
+        });
+        ws_manager.send_to_user(recipient_id, &event).await;
 
         Ok(response)
     }
 
+    /// New accounts (too young, or without any population) may only message players
+    /// they already have a conversation with, or members of their own alliance
+    async fn check_new_account_messaging(
+        pool: &PgPool,
+        sender_id: Uuid,
+        recipient_id: Uuid,
+    ) -> AppResult<()> {
+        let sender = UserRepository::find_by_id(pool, sender_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("User not found".into()))?;
+
+        let population: i32 = VillageRepository::find_by_user_id(pool, sender_id)
+            .await?
+            .iter()
+            .map(|v| v.population)
+            .sum();
+
+        let is_new_account = Utc::now() - sender.created_at < Duration::hours(NEW_ACCOUNT_MIN_AGE_HOURS);
+        if !is_new_account && population > 0 {
+            return Ok(());
+        }
+
+        if MessageRepository::conversation_exists(pool, sender_id, recipient_id).await? {
+            return Ok(());
+        }
+
+        let sender_alliance = AllianceRepository::get_user_alliance(pool, sender_id).await?;
+        let recipient_alliance = AllianceRepository::get_user_alliance(pool, recipient_id).await?;
+        if let (Some(a), Some(b)) = (sender_alliance, recipient_alliance) {
+            if a.alliance_id == b.alliance_id {
+                return Ok(());
+            }
+        }
+
+        Err(AppError::BadRequest(format!(
+            "New accounts can only message alliance members or players they already have a conversation with. \
+             This restriction lifts once your account is {} hours old and has a populated village",
+            NEW_ACCOUNT_MIN_AGE_HOURS
+        )))
+    }
+
     /// Get inbox messages
     pub async fn get_inbox(
         pool: &PgPool,
@@ -90,6 +171,32 @@ impl MessageService {
         MessageRepository::get_sent(pool, user_id, limit, offset).await
     }
 
+    /// Inbox page with total count
+    pub async fn get_inbox_page(
+        pool: &PgPool,
+        user_id: Uuid,
+        query: &PageQuery,
+    ) -> AppResult<Paginated<MessageListItem>> {
+        let (page, limit, offset) = query.resolve(MAX_MESSAGES_PER_PAGE);
+        let items = MessageRepository::get_inbox(pool, user_id, limit, offset).await?;
+        let total = MessageRepository::count_inbox(pool, user_id).await?;
+
+        Ok(Paginated::new(items, total, page, limit))
+    }
+
+    /// Sent messages page with total count
+    pub async fn get_sent_page(
+        pool: &PgPool,
+        user_id: Uuid,
+        query: &PageQuery,
+    ) -> AppResult<Paginated<MessageListItem>> {
+        let (page, limit, offset) = query.resolve(MAX_MESSAGES_PER_PAGE);
+        let items = MessageRepository::get_sent(pool, user_id, limit, offset).await?;
+        let total = MessageRepository::count_sent(pool, user_id).await?;
+
+        Ok(Paginated::new(items, total, page, limit))
+    }
+
     /// Get a single message
     pub async fn get_message(
         pool: &PgPool,
@@ -121,6 +228,21 @@ impl MessageService {
         Ok(())
     }
 
+    /// Delete several messages for the current user, returning how many were deleted
+    pub async fn delete_messages(pool: &PgPool, user_id: Uuid, message_ids: &[Uuid]) -> AppResult<u64> {
+        if message_ids.is_empty() {
+            return Ok(0);
+        }
+        if message_ids.len() > MAX_BULK_DELETE {
+            return Err(AppError::BadRequest(format!(
+                "At most {} messages can be deleted at once",
+                MAX_BULK_DELETE
+            )));
+        }
+
+        MessageRepository::delete_many_for_user(pool, user_id, message_ids).await
+    }
+
     /// Get unread message count
     pub async fn get_unread_count(pool: &PgPool, user_id: Uuid) -> AppResult<i64> {
         MessageRepository::get_unread_count(pool, user_id).await
@@ -164,6 +286,37 @@ impl MessageService {
         Ok(())
     }
 
+    /// Mute a conversation for the current user
+    pub async fn mute_conversation(
+        pool: &PgPool,
+        user_id: Uuid,
+        conversation_id: Uuid,
+    ) -> AppResult<()> {
+        Self::set_conversation_muted(pool, user_id, conversation_id, true).await
+    }
+
+    /// Unmute a conversation for the current user
+    pub async fn unmute_conversation(
+        pool: &PgPool,
+        user_id: Uuid,
+        conversation_id: Uuid,
+    ) -> AppResult<()> {
+        Self::set_conversation_muted(pool, user_id, conversation_id, false).await
+    }
+
+    async fn set_conversation_muted(
+        pool: &PgPool,
+        user_id: Uuid,
+        conversation_id: Uuid,
+        muted: bool,
+    ) -> AppResult<()> {
+        // Only participants match the update
+        if !MessageRepository::set_conversation_muted(pool, conversation_id, user_id, muted).await? {
+            return Err(AppError::NotFound("Conversation not found".into()));
+        }
+        Ok(())
+    }
+
     // ==================== Alliance Messages ====================
 
     /// Send an alliance message
@@ -202,9 +355,10 @@ impl MessageService {
         .await?;
 
         // Return full message with user names
-        let response = MessageRepository::get_message(pool, message.id)
+        let mut response = MessageRepository::get_message(pool, message.id)
             .await?
             .ok_or_else(|| AppError::InternalError(anyhow::anyhow!("Failed to fetch created message")))?;
+        response.entities = Self::resolve_entities(pool, &body).await?;
 
         Ok(response)
     }
@@ -231,7 +385,7 @@ impl MessageService {
         pool: &PgPool,
         user_id: Uuid,
         message_id: Uuid,
-    ) -> AppResult<MessageResponse> {
+    ) -> AppResult<AllianceMessageResponse> {
         // Check if user is in an alliance
         let member = AllianceRepository::get_user_alliance(pool, user_id)
             .await?
@@ -249,7 +403,17 @@ impl MessageService {
         // Mark as read for this user
         MessageRepository::mark_alliance_message_read(pool, message_id, user_id).await?;
 
-        Ok(message)
+        // Read receipts are for leadership only
+        let read_receipts = if matches!(member.role, AllianceRole::Leader | AllianceRole::Officer) {
+            let read_count =
+                MessageRepository::count_alliance_message_reads(pool, message_id, member.alliance_id).await?;
+            let member_count = AllianceRepository::get_member_count(pool, member.alliance_id).await? as i64;
+            Some(MessageReadReceipts { read_count, member_count })
+        } else {
+            None
+        };
+
+        Ok(AllianceMessageResponse { message, read_receipts })
     }
 
     /// Get unread alliance message count
@@ -268,4 +432,248 @@ impl MessageService {
         let alliance_count = Self::get_unread_alliance_count(pool, user_id).await?;
         Ok(private_count + alliance_count)
     }
+
+    // ==================== Mentions ====================
+
+    /// Look up the coordinates and players mentioned in a message body.
+    /// Unknown players are dropped; coordinates are kept even on empty tiles.
+    async fn resolve_entities(pool: &PgPool, body: &str) -> AppResult<Vec<MessageEntity>> {
+        let mentions = Self::parse_mentions(body);
+        if mentions.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let coordinates: Vec<(i32, i32)> = mentions
+            .iter()
+            .filter_map(|m| match m {
+                Mention::Coordinates { x, y, .. } => Some((*x, *y)),
+                Mention::Player { .. } => None,
+            })
+            .collect();
+        let villages = if coordinates.is_empty() {
+            Vec::new()
+        } else {
+            VillageRepository::find_at_coordinates(pool, &coordinates).await?
+        };
+
+        let names: Vec<String> = mentions
+            .iter()
+            .filter_map(|m| match m {
+                Mention::Player { name, .. } => Some(name.clone()),
+                Mention::Coordinates { .. } => None,
+            })
+            .collect();
+        let users = if names.is_empty() {
+            Vec::new()
+        } else {
+            UserRepository::find_by_display_names(pool, &names).await?
+        };
+
+        let entities = mentions
+            .into_iter()
+            .filter_map(|mention| match mention {
+                Mention::Coordinates { text, x, y } => {
+                    let village = villages.iter().find(|v| v.x == x && v.y == y);
+                    Some(MessageEntity::Coordinates {
+                        text,
+                        x,
+                        y,
+                        village_id: village.map(|v| v.id),
+                        village_name: village.map(|v| v.name.clone()),
+                        player_name: village.and_then(|v| v.player_name.clone()),
+                    })
+                }
+                Mention::Player { text, name } => {
+                    // Skip names shared by several players rather than guess
+                    let mut matches = users.iter().filter(|u| {
+                        u.display_name.as_deref().is_some_and(|n| n.to_lowercase() == name.to_lowercase())
+                    });
+                    match (matches.next(), matches.next()) {
+                        (Some(user), None) => Some(MessageEntity::Player {
+                            text,
+                            user_id: user.id,
+                            name: user.display_name.clone().unwrap_or(name),
+                        }),
+                        _ => None,
+                    }
+                }
+            })
+            .collect();
+
+        Ok(entities)
+    }
+
+    /// Find `(x|y)` coordinates and `@name` player mentions in a body, in order of
+    /// appearance, without duplicates and capped at `MAX_MESSAGE_ENTITIES`
+    fn parse_mentions(body: &str) -> Vec<Mention> {
+        let chars: Vec<char> = body.chars().collect();
+        let mut mentions = Vec::new();
+        let mut i = 0;
+
+        while i < chars.len() && mentions.len() < MAX_MESSAGE_ENTITIES {
+            let found = match chars[i] {
+                '(' => Self::parse_coordinates(&chars[i..]).map(|(x, y, len)| {
+                    (Mention::Coordinates { text: chars[i..i + len].iter().collect(), x, y }, len)
+                }),
+                // '@' only starts a mention at a word boundary, so e-mail addresses don't match
+                '@' if i == 0 || chars[i - 1].is_whitespace() => {
+                    let len = chars[i + 1..]
+                        .iter()
+                        .take_while(|c| c.is_alphanumeric() || **c == '_')
+                        .count();
+                    (len > 0).then(|| {
+                        let name: String = chars[i + 1..i + 1 + len].iter().collect();
+                        (Mention::Player { text: format!("@{}", name), name }, len + 1)
+                    })
+                }
+                _ => None,
+            };
+
+            match found {
+                Some((mention, len)) => {
+                    if !mentions.contains(&mention) {
+                        mentions.push(mention);
+                    }
+                    i += len;
+                }
+                None => i += 1,
+            }
+        }
+
+        mentions
+    }
+
+    /// Parse `(x|y)` at the start of `chars`, allowing spaces and negative values.
+    /// Returns the coordinates and the number of chars consumed.
+    fn parse_coordinates(chars: &[char]) -> Option<(i32, i32, usize)> {
+        let mut i = 1; // past '('
+        let x = Self::parse_coordinate(chars, &mut i)?;
+        if chars.get(i) != Some(&'|') {
+            return None;
+        }
+        i += 1;
+        let y = Self::parse_coordinate(chars, &mut i)?;
+        if chars.get(i) != Some(&')') {
+            return None;
+        }
+        Some((x, y, i + 1))
+    }
+
+    /// Parse one optionally signed coordinate surrounded by spaces, advancing `i`
+    fn parse_coordinate(chars: &[char], i: &mut usize) -> Option<i32> {
+        while chars.get(*i) == Some(&' ') {
+            *i += 1;
+        }
+        let negative = chars.get(*i) == Some(&'-');
+        if negative {
+            *i += 1;
+        }
+        let digits = chars[*i..].iter().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 || digits > MAX_COORDINATE_DIGITS {
+            return None;
+        }
+        let value: i32 = chars[*i..*i + digits].iter().collect::<String>().parse().ok()?;
+        *i += digits;
+        while chars.get(*i) == Some(&' ') {
+            *i += 1;
+        }
+        Some(if negative { -value } else { value })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::message::MessageType;
+    use crate::test_support;
+
+    fn coordinates(text: &str, x: i32, y: i32) -> Mention {
+        Mention::Coordinates { text: text.into(), x, y }
+    }
+
+    fn player(name: &str) -> Mention {
+        Mention::Player { text: format!("@{}", name), name: name.into() }
+    }
+
+    #[test]
+    fn mentions_stop_at_punctuation_and_skip_email_addresses() {
+        let mentions = MessageService::parse_mentions("Thanks @Buffalo_1, ask @Ivory! Mail me at chief@horns.example");
+        assert_eq!(mentions, vec![player("Buffalo_1"), player("Ivory")]);
+
+        assert!(MessageService::parse_mentions("@ alone, @@ and @!").is_empty());
+    }
+
+    #[test]
+    fn repeated_mentions_are_listed_once() {
+        let mentions = MessageService::parse_mentions("@Ivory (1|2) @Ivory (1|2) (2|1)");
+        assert_eq!(mentions, vec![player("Ivory"), coordinates("(1|2)", 1, 2), coordinates("(2|1)", 2, 1)]);
+    }
+
+    #[test]
+    fn coordinates_allow_signs_and_spaces() {
+        let mentions = MessageService::parse_mentions("Meet at (-12|7), then ( 3 | -45 ).");
+        assert_eq!(mentions, vec![coordinates("(-12|7)", -12, 7), coordinates("( 3 | -45 )", 3, -45)]);
+    }
+
+    #[test]
+    fn out_of_range_and_malformed_coordinates_are_ignored() {
+        let rejected = [
+            "(12345|0)", "(0|-99999)", "(99999999999|1)",
+            "(1|2", "(|2)", "(1|)", "(1,2)", "(--1|2)", "(1|2|3)", "(a|b)", "(",
+        ];
+        for body in rejected {
+            assert!(MessageService::parse_mentions(body).is_empty(), "{body} should not parse");
+        }
+
+        let widest: Vec<char> = "(9999|-9999)".chars().collect();
+        assert_eq!(MessageService::parse_coordinates(&widest), Some((9999, -9999, 12)));
+    }
+
+    #[test]
+    fn mentions_are_capped() {
+        let body: String = (0..MAX_MESSAGE_ENTITIES + 5).map(|n| format!("({}|0) ", n)).collect();
+        assert_eq!(MessageService::parse_mentions(&body).len(), MAX_MESSAGE_ENTITIES);
+    }
+
+    #[sqlx::test]
+    async fn system_notices_reach_the_inbox_without_a_sender(pool: PgPool) {
+        let user_id = test_support::create_user(&pool, 0).await;
+        MessageRepository::create_system_messages(&pool, &[user_id], &["Welcome".into()], &["Good luck!".into()])
+            .await
+            .unwrap();
+
+        let inbox = MessageRepository::get_inbox(&pool, user_id, 20, 0).await.unwrap();
+        assert_eq!(inbox.len(), 1);
+        assert_eq!(inbox[0].sender_id, None);
+        assert_eq!(inbox[0].sender_name, "System");
+        assert_eq!(MessageRepository::get_unread_count(&pool, user_id).await.unwrap(), 1);
+        assert_eq!(MessageRepository::count_sent(&pool, user_id).await.unwrap(), 0);
+
+        let message = MessageService::get_message(&pool, user_id, inbox[0].id).await.unwrap();
+        assert_eq!(message.message_type, MessageType::System);
+        assert_eq!(message.recipient_id, Some(user_id));
+
+        let other_id = test_support::create_user(&pool, 0).await;
+        let result = MessageService::get_message(&pool, other_id, inbox[0].id).await;
+        assert!(matches!(result, Err(AppError::Forbidden(_))));
+    }
+
+    #[sqlx::test]
+    async fn conversation_with_a_deleted_player_is_still_listed(pool: PgPool) {
+        let user_id = test_support::create_user(&pool, 0).await;
+        let other_id = test_support::create_user(&pool, 0).await;
+        let conversation = MessageRepository::get_or_create_conversation(&pool, user_id, other_id).await.unwrap();
+        let message =
+            MessageRepository::create_private_message(&pool, other_id, user_id, conversation.id, "Hello", "Trade?")
+                .await
+                .unwrap();
+        MessageRepository::update_conversation_last_message(&pool, conversation.id, message.id).await.unwrap();
+
+        test_support::delete_user(&pool, other_id).await;
+
+        let conversations = MessageRepository::get_conversations(&pool, user_id, 20, 0).await.unwrap();
+        assert_eq!(conversations.len(), 1);
+        assert_eq!(conversations[0].other_user_id, other_id);
+        assert_eq!(conversations[0].other_user_name, "Unknown player");
+    }
 }
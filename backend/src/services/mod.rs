@@ -3,12 +3,17 @@ pub mod alliance_service;
 pub mod army_service;
 pub mod background_jobs;
 pub mod building_service;
+pub mod economy_cache;
 pub mod hero_service;
+pub mod market_cache;
+pub mod market_service;
 pub mod message_service;
 pub mod ranking_service;
 pub mod resource_service;
 pub mod shop_service;
+pub mod trade_rate_limiter;
 pub mod trade_service;
 pub mod troop_service;
+pub mod ttl_cache;
 pub mod village_service;
 pub mod ws_service;
@@ -1,26 +1,53 @@
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::error::AppResult;
+use crate::error::{AppError, AppResult};
 use crate::models::ranking::{
     AllianceRanking, HeroRanking, PlayerAttackRanking, PlayerDefenseRanking,
-    PlayerPopulationRanking, RankingListResponse,
+    PlayerPopulationRanking, PlayerProfile, PlayerProfileVillage, RankingListResponse,
 };
+use crate::repositories::alliance_repo::AllianceRepository;
 use crate::repositories::ranking_repo::RankingRepository;
+use crate::repositories::user_repo::UserRepository;
+use crate::repositories::village_repo::VillageRepository;
+
+/// Maximum rows returned when searching a ranking by name
+const MAX_SEARCH_RESULTS: i64 = 20;
 
 pub struct RankingService;
 
 impl RankingService {
+    /// Non-empty, trimmed search term
+    fn search_term(search: Option<&str>) -> Option<&str> {
+        search.map(str::trim).filter(|q| !q.is_empty())
+    }
+
+    /// Wrap search matches as a single page; their ranks are still global
+    fn search_results<T>(rankings: Vec<T>) -> RankingListResponse<T> {
+        RankingListResponse {
+            total: rankings.len() as i64,
+            rankings,
+            page: 1,
+            per_page: MAX_SEARCH_RESULTS,
+        }
+    }
+
     // ==================== Player Population Ranking ====================
 
-    /// Get player population rankings with pagination
+    /// Get player population rankings with pagination, or the players matching `search`
     pub async fn get_population_ranking(
         pool: &PgPool,
         page: i64,
         per_page: i64,
+        search: Option<&str>,
     ) -> AppResult<RankingListResponse<PlayerPopulationRanking>> {
+        if let Some(q) = Self::search_term(search) {
+            let rankings = RankingRepository::get_population_ranking(pool, MAX_SEARCH_RESULTS, 0, Some(q)).await?;
+            return Ok(Self::search_results(rankings));
+        }
+
         let offset = (page - 1) * per_page;
-        let rankings = RankingRepository::get_population_ranking(pool, per_page, offset).await?;
+        let rankings = RankingRepository::get_population_ranking(pool, per_page, offset, None).await?;
         let total = RankingRepository::count_population_ranking(pool).await?;
 
         Ok(RankingListResponse {
@@ -33,14 +60,20 @@ impl RankingService {
 
     // ==================== Player Attack Ranking ====================
 
-    /// Get player attack rankings with pagination
+    /// Get player attack rankings with pagination, or the players matching `search`
     pub async fn get_attack_ranking(
         pool: &PgPool,
         page: i64,
         per_page: i64,
+        search: Option<&str>,
     ) -> AppResult<RankingListResponse<PlayerAttackRanking>> {
+        if let Some(q) = Self::search_term(search) {
+            let rankings = RankingRepository::get_attack_ranking(pool, MAX_SEARCH_RESULTS, 0, Some(q)).await?;
+            return Ok(Self::search_results(rankings));
+        }
+
         let offset = (page - 1) * per_page;
-        let rankings = RankingRepository::get_attack_ranking(pool, per_page, offset).await?;
+        let rankings = RankingRepository::get_attack_ranking(pool, per_page, offset, None).await?;
         let total = RankingRepository::count_attack_ranking(pool).await?;
 
         Ok(RankingListResponse {
@@ -53,14 +86,20 @@ impl RankingService {
 
     // ==================== Player Defense Ranking ====================
 
-    /// Get player defense rankings with pagination
+    /// Get player defense rankings with pagination, or the players matching `search`
     pub async fn get_defense_ranking(
         pool: &PgPool,
         page: i64,
         per_page: i64,
+        search: Option<&str>,
     ) -> AppResult<RankingListResponse<PlayerDefenseRanking>> {
+        if let Some(q) = Self::search_term(search) {
+            let rankings = RankingRepository::get_defense_ranking(pool, MAX_SEARCH_RESULTS, 0, Some(q)).await?;
+            return Ok(Self::search_results(rankings));
+        }
+
         let offset = (page - 1) * per_page;
-        let rankings = RankingRepository::get_defense_ranking(pool, per_page, offset).await?;
+        let rankings = RankingRepository::get_defense_ranking(pool, per_page, offset, None).await?;
         let total = RankingRepository::count_defense_ranking(pool).await?;
 
         Ok(RankingListResponse {
@@ -73,14 +112,20 @@ impl RankingService {
 
     // ==================== Hero Ranking ====================
 
-    /// Get hero rankings with pagination
+    /// Get hero rankings with pagination, or the heroes matching `search`
     pub async fn get_hero_ranking(
         pool: &PgPool,
         page: i64,
         per_page: i64,
+        search: Option<&str>,
     ) -> AppResult<RankingListResponse<HeroRanking>> {
+        if let Some(q) = Self::search_term(search) {
+            let rankings = RankingRepository::get_hero_ranking(pool, MAX_SEARCH_RESULTS, 0, Some(q)).await?;
+            return Ok(Self::search_results(rankings));
+        }
+
         let offset = (page - 1) * per_page;
-        let rankings = RankingRepository::get_hero_ranking(pool, per_page, offset).await?;
+        let rankings = RankingRepository::get_hero_ranking(pool, per_page, offset, None).await?;
         let total = RankingRepository::count_hero_ranking(pool).await?;
 
         Ok(RankingListResponse {
@@ -93,14 +138,20 @@ impl RankingService {
 
     // ==================== Alliance Ranking ====================
 
-    /// Get alliance rankings with pagination
+    /// Get alliance rankings with pagination, or the alliances matching `search`
     pub async fn get_alliance_ranking(
         pool: &PgPool,
         page: i64,
         per_page: i64,
+        search: Option<&str>,
     ) -> AppResult<RankingListResponse<AllianceRanking>> {
+        if let Some(q) = Self::search_term(search) {
+            let rankings = RankingRepository::get_alliance_ranking(pool, MAX_SEARCH_RESULTS, 0, Some(q)).await?;
+            return Ok(Self::search_results(rankings));
+        }
+
         let offset = (page - 1) * per_page;
-        let rankings = RankingRepository::get_alliance_ranking(pool, per_page, offset).await?;
+        let rankings = RankingRepository::get_alliance_ranking(pool, per_page, offset, None).await?;
         let total = RankingRepository::count_alliance_ranking(pool).await?;
 
         Ok(RankingListResponse {
@@ -117,4 +168,49 @@ impl RankingService {
     pub async fn get_player_rank(pool: &PgPool, user_id: Uuid) -> AppResult<Option<i64>> {
         RankingRepository::get_player_population_rank(pool, user_id).await
     }
+
+    // ==================== Player Profile ====================
+
+    /// Get the public profile of a player. Private data (email, gold, resources) is left out.
+    pub async fn get_player_profile(pool: &PgPool, user_id: Uuid) -> AppResult<PlayerProfile> {
+        let user = UserRepository::find_by_id(pool, user_id)
+            .await?
+            .filter(|u| u.deleted_at.is_none() && u.banned_at.is_none())
+            .ok_or_else(|| AppError::NotFound("Player not found".into()))?;
+
+        let alliance = match AllianceRepository::get_user_alliance(pool, user_id).await? {
+            Some(member) => AllianceRepository::find_by_id(pool, member.alliance_id).await?,
+            None => None,
+        };
+
+        let villages: Vec<PlayerProfileVillage> = VillageRepository::find_by_user_id(pool, user_id)
+            .await?
+            .into_iter()
+            .map(|v| PlayerProfileVillage {
+                id: v.id,
+                name: v.name,
+                x: v.x,
+                y: v.y,
+                population: v.population,
+                is_capital: v.is_capital,
+            })
+            .collect();
+
+        let population_rank = RankingRepository::get_player_population_rank(pool, user_id).await?;
+        let (attack_points, defense_points) = RankingRepository::get_player_points(pool, user_id).await?;
+
+        Ok(PlayerProfile {
+            user_id: user.id,
+            display_name: user.display_name,
+            alliance_id: alliance.as_ref().map(|a| a.id),
+            alliance_tag: alliance.map(|a| a.tag),
+            population: villages.iter().map(|v| v.population as i64).sum(),
+            village_count: villages.len() as i64,
+            population_rank,
+            attack_points,
+            defense_points,
+            villages,
+            joined_at: user.created_at,
+        })
+    }
 }
@@ -1,15 +1,47 @@
-use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+
 use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::error::AppResult;
-use crate::models::building::BuildingType;
-use crate::models::village::Village;
+use crate::models::building::{Building, BuildingType};
+use crate::models::village::{UpkeepOverviewResponse, Village, VillageUpkeep};
 use crate::repositories::building_repo::BuildingRepository;
+use crate::repositories::hero_repo::HeroRepository;
+use crate::repositories::shop_repo::ShopRepository;
+use crate::repositories::troop_repo::TroopRepository;
 use crate::repositories::village_repo::VillageRepository;
 
+/// Production bonus from Travian Plus (all resources)
+pub const PLUS_PRODUCTION_BONUS_PERCENT: i32 = 25;
+
+/// Production bonus bought with gold for a single resource
+pub const RESOURCE_PRODUCTION_BONUS_PERCENT: i32 = 25;
+
+/// Production bonus from Book of Wisdom (all resources)
+pub const BOOK_OF_WISDOM_BONUS_PERCENT: i32 = 100;
+
 pub struct ResourceService;
 
+/// Percent bonuses a village gets on top of its own buildings:
+/// a stationed hero plus any active gold boosts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProductionBoosts {
+    pub wood_percent: i32,
+    pub clay_percent: i32,
+    pub iron_percent: i32,
+    pub crop_percent: i32,
+}
+
+impl ProductionBoosts {
+    fn add_all(&mut self, percent: i32) {
+        self.wood_percent += percent;
+        self.clay_percent += percent;
+        self.iron_percent += percent;
+        self.crop_percent += percent;
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ProductionRates {
     pub wood_per_hour: i32,
@@ -18,6 +50,28 @@ pub struct ProductionRates {
     pub crop_per_hour: i32,
     pub crop_consumption: i32,  // Population eats crop
     pub net_crop_per_hour: i32, // crop_per_hour - crop_consumption
+    /// Percent bonus applied by processing buildings (Sawmill, Brickyard, ...), a stationed hero and gold boosts
+    pub wood_bonus_percent: i32,
+    pub clay_bonus_percent: i32,
+    pub iron_bonus_percent: i32,
+    pub crop_bonus_percent: i32,
+}
+
+impl From<ProductionRates> for crate::models::village::ProductionRates {
+    fn from(p: ProductionRates) -> Self {
+        Self {
+            wood_per_hour: p.wood_per_hour,
+            clay_per_hour: p.clay_per_hour,
+            iron_per_hour: p.iron_per_hour,
+            crop_per_hour: p.crop_per_hour,
+            crop_consumption: p.crop_consumption,
+            net_crop_per_hour: p.net_crop_per_hour,
+            wood_bonus_percent: p.wood_bonus_percent,
+            clay_bonus_percent: p.clay_bonus_percent,
+            iron_bonus_percent: p.iron_bonus_percent,
+            crop_bonus_percent: p.crop_bonus_percent,
+        }
+    }
 }
 
 impl ResourceService {
@@ -31,78 +85,175 @@ impl ResourceService {
             .ok_or_else(|| crate::error::AppError::NotFound("Village not found".to_string()))?;
 
         let buildings = BuildingRepository::find_by_village_id(pool, village_id).await?;
+        let boosts = Self::production_boosts(pool, &[village_id])
+            .await?
+            .remove(&village_id)
+            .unwrap_or_default();
+
+        Ok(Self::production_from_buildings(village.population, &buildings, &boosts))
+    }
 
+    /// Resolve the hero and gold production boosts of several villages at once.
+    /// Every path that accrues or reports production goes through here, so the
+    /// rates never depend on which path did the update.
+    /// Villages without any boost are absent from the map.
+    pub async fn production_boosts(
+        pool: &PgPool,
+        village_ids: &[Uuid],
+    ) -> AppResult<HashMap<Uuid, ProductionBoosts>> {
+        let mut boosts: HashMap<Uuid, ProductionBoosts> = HashMap::new();
+
+        // Only the best stationed hero counts
+        let mut hero_bonuses: HashMap<Uuid, i32> = HashMap::new();
+        for hero in HeroRepository::find_stationed_by_villages(pool, village_ids).await? {
+            let bonus = hero_bonuses.entry(hero.home_village_id).or_insert(0);
+            *bonus = (*bonus).max(hero.production_bonus_percent());
+        }
+        for (village_id, bonus) in hero_bonuses {
+            boosts.entry(village_id).or_default().add_all(bonus);
+        }
+
+        // Each gold effect counts once, even if overlapping purchases exist
+        let mut seen = HashSet::new();
+        for (village_id, source, resource_type) in
+            ShopRepository::get_active_production_effects(pool, village_ids).await?
+        {
+            if !seen.insert((village_id, source.clone(), resource_type.clone())) {
+                continue;
+            }
+
+            let entry = boosts.entry(village_id).or_default();
+            match (source.as_str(), resource_type.as_deref()) {
+                ("travian_plus", _) => entry.add_all(PLUS_PRODUCTION_BONUS_PERCENT),
+                ("book_of_wisdom", _) => entry.add_all(BOOK_OF_WISDOM_BONUS_PERCENT),
+                ("production_bonus", Some("wood")) => entry.wood_percent += RESOURCE_PRODUCTION_BONUS_PERCENT,
+                ("production_bonus", Some("clay")) => entry.clay_percent += RESOURCE_PRODUCTION_BONUS_PERCENT,
+                ("production_bonus", Some("iron")) => entry.iron_percent += RESOURCE_PRODUCTION_BONUS_PERCENT,
+                ("production_bonus", Some("crop")) => entry.crop_percent += RESOURCE_PRODUCTION_BONUS_PERCENT,
+                _ => {}
+            }
+        }
+
+        Ok(boosts)
+    }
+
+    /// Production rates from already-loaded buildings and the village's resolved boosts
+    pub fn production_from_buildings(population: i32, buildings: &[Building], boosts: &ProductionBoosts) -> ProductionRates {
         let mut wood_per_hour = 3; // Base production
         let mut clay_per_hour = 3;
         let mut iron_per_hour = 3;
         let mut crop_per_hour = 3;
 
+        // Processing buildings don't stack: the highest one of each kind counts
+        let mut wood_bonus_percent = 0;
+        let mut clay_bonus_percent = 0;
+        let mut iron_bonus_percent = 0;
+        let mut crop_bonus_percent = 0;
+
         for building in buildings {
             if building.level == 0 {
                 continue;
             }
 
             let production = building.building_type.production_per_hour(building.level);
+            let bonus = building.building_type.production_bonus_percent(building.level);
 
             match building.building_type {
                 BuildingType::Woodcutter => wood_per_hour += production,
                 BuildingType::ClayPit => clay_per_hour += production,
                 BuildingType::IronMine => iron_per_hour += production,
                 BuildingType::CropField => crop_per_hour += production,
+                BuildingType::Sawmill => wood_bonus_percent = wood_bonus_percent.max(bonus),
+                BuildingType::Brickyard => clay_bonus_percent = clay_bonus_percent.max(bonus),
+                BuildingType::IronFoundry => iron_bonus_percent = iron_bonus_percent.max(bonus),
+                BuildingType::GrainMill => crop_bonus_percent = crop_bonus_percent.max(bonus),
                 _ => {}
             }
         }
 
+        // Hero and gold boosts stack on top of the processing buildings
+        let wood_bonus_percent = wood_bonus_percent + boosts.wood_percent;
+        let clay_bonus_percent = clay_bonus_percent + boosts.clay_percent;
+        let iron_bonus_percent = iron_bonus_percent + boosts.iron_percent;
+        let crop_bonus_percent = crop_bonus_percent + boosts.crop_percent;
+
+        let wood_per_hour = wood_per_hour * (100 + wood_bonus_percent) / 100;
+        let clay_per_hour = clay_per_hour * (100 + clay_bonus_percent) / 100;
+        let iron_per_hour = iron_per_hour * (100 + iron_bonus_percent) / 100;
+        let crop_per_hour = crop_per_hour * (100 + crop_bonus_percent) / 100;
+
         // Population consumes crop (1 crop per population per hour)
-        let crop_consumption = village.population;
+        let crop_consumption = population;
         let net_crop_per_hour = crop_per_hour - crop_consumption;
 
-        Ok(ProductionRates {
+        ProductionRates {
             wood_per_hour,
             clay_per_hour,
             iron_per_hour,
             crop_per_hour,
             crop_consumption,
             net_crop_per_hour,
-        })
+            wood_bonus_percent,
+            clay_bonus_percent,
+            iron_bonus_percent,
+            crop_bonus_percent,
+        }
     }
 
-    /// Update resources for a village based on time elapsed
-    pub async fn update_village_resources(pool: &PgPool, village_id: Uuid) -> AppResult<Village> {
-        let village = VillageRepository::find_by_id(pool, village_id)
-            .await?
-            .ok_or_else(|| crate::error::AppError::NotFound("Village not found".to_string()))?;
+    /// Crop production versus building and troop upkeep for all of a player's villages
+    pub async fn upkeep_overview(pool: &PgPool, user_id: Uuid) -> AppResult<UpkeepOverviewResponse> {
+        let villages = VillageRepository::find_by_user_id(pool, user_id).await?;
 
-        let now = Utc::now();
-        let elapsed_seconds = (now - village.resources_updated_at).num_seconds();
+        let mut upkeep = Vec::with_capacity(villages.len());
+        for village in villages {
+            let production = Self::calculate_production(pool, village.id).await?;
+            let troop_upkeep = TroopRepository::get_total_crop_consumption(pool, village.id).await?;
+            let net_crop_per_hour = production.crop_per_hour - production.crop_consumption - troop_upkeep;
 
-        if elapsed_seconds <= 0 {
-            return Ok(village);
+            upkeep.push(VillageUpkeep {
+                village_id: village.id,
+                name: village.name,
+                crop_per_hour: production.crop_per_hour,
+                building_upkeep: production.crop_consumption,
+                troop_upkeep,
+                net_crop_per_hour,
+                is_net_negative: net_crop_per_hour < 0,
+            });
         }
 
-        let production = Self::calculate_production(pool, village_id).await?;
+        Ok(UpkeepOverviewResponse {
+            total_crop_per_hour: upkeep.iter().map(|v| v.crop_per_hour).sum(),
+            total_building_upkeep: upkeep.iter().map(|v| v.building_upkeep).sum(),
+            total_troop_upkeep: upkeep.iter().map(|v| v.troop_upkeep).sum(),
+            total_net_crop_per_hour: upkeep.iter().map(|v| v.net_crop_per_hour).sum(),
+            net_negative_villages: upkeep.iter().filter(|v| v.is_net_negative).count() as i32,
+            villages: upkeep,
+        })
+    }
 
-        // Calculate resources produced
-        let hours_elapsed = elapsed_seconds as f64 / 3600.0;
+    /// Update resources for a village based on time elapsed.
+    /// The accrual happens in one UPDATE so concurrent calls can't double-credit.
+    pub async fn update_village_resources(pool: &PgPool, village_id: Uuid) -> AppResult<Village> {
+        let production = Self::calculate_production(pool, village_id).await?;
 
-        let wood_produced = (production.wood_per_hour as f64 * hours_elapsed) as i32;
-        let clay_produced = (production.clay_per_hour as f64 * hours_elapsed) as i32;
-        let iron_produced = (production.iron_per_hour as f64 * hours_elapsed) as i32;
         // Use net_crop which accounts for population consumption
-        let crop_change = (production.net_crop_per_hour as f64 * hours_elapsed) as i32;
-
-        // Calculate new resource amounts (capped at storage, min 0)
-        let new_wood = (village.wood + wood_produced).min(village.warehouse_capacity).max(0);
-        let new_clay = (village.clay + clay_produced).min(village.warehouse_capacity).max(0);
-        let new_iron = (village.iron + iron_produced).min(village.warehouse_capacity).max(0);
-        let new_crop = (village.crop + crop_change).min(village.granary_capacity).max(0);
-
-        // Update village resources
-        let updated =
-            VillageRepository::update_resources(pool, village_id, new_wood, new_clay, new_iron, new_crop)
-                .await?;
+        let updated = VillageRepository::accrue_resources(
+            pool,
+            village_id,
+            production.wood_per_hour,
+            production.clay_per_hour,
+            production.iron_per_hour,
+            production.net_crop_per_hour,
+        )
+        .await?;
 
-        Ok(updated)
+        match updated {
+            Some(village) => Ok(village),
+            // Nothing elapsed (another request just updated it)
+            None => VillageRepository::find_by_id(pool, village_id)
+                .await?
+                .ok_or_else(|| crate::error::AppError::NotFound("Village not found".to_string())),
+        }
     }
 
     /// Update resources for all villages (for background job)
@@ -128,3 +279,78 @@ impl ResourceService {
         Ok(updated_count)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::building::BuildingType;
+    use crate::services::shop_service::ShopService;
+    use crate::services::village_service::VillageService;
+    use crate::test_support;
+
+    #[sqlx::test]
+    async fn concurrent_updates_accrue_once(pool: PgPool) {
+        let user_id = test_support::create_user(&pool, 0).await;
+        let village = test_support::create_village(&pool, user_id, 0, 0).await;
+        test_support::add_building(&pool, village.id, BuildingType::Woodcutter, 101, 1).await;
+        sqlx::query("UPDATE villages SET resources_updated_at = NOW() - INTERVAL '1 hour' WHERE id = $1")
+            .bind(village.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let production = ResourceService::calculate_production(&pool, village.id).await.unwrap();
+        assert!(production.wood_per_hour > 0 && village.wood + production.wood_per_hour < village.warehouse_capacity);
+
+        let (a, b) = tokio::join!(
+            ResourceService::update_village_resources(&pool, village.id),
+            ResourceService::update_village_resources(&pool, village.id),
+        );
+        a.unwrap();
+        b.unwrap();
+
+        // One hour of production, not two
+        let updated = test_support::village(&pool, village.id).await;
+        assert_eq!(updated.wood, village.wood + production.wood_per_hour);
+    }
+
+    /// A village with one woodcutter, Book of Wisdom active and an hour of production pending
+    async fn boosted_village(pool: &PgPool, user_id: Uuid, x: i32) -> Village {
+        let village = test_support::create_village(pool, user_id, x, 0).await;
+        test_support::add_building(pool, village.id, BuildingType::Woodcutter, 101, 1).await;
+        ShopService::use_book_of_wisdom(pool, user_id, village.id).await.unwrap();
+        sqlx::query("UPDATE villages SET resources_updated_at = NOW() - INTERVAL '1 hour' WHERE id = $1")
+            .bind(village.id)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        village
+    }
+
+    #[sqlx::test]
+    async fn background_job_and_village_details_accrue_the_same(pool: PgPool) {
+        let user_id = test_support::create_user(&pool, 100).await;
+
+        // Background job path
+        let by_job = boosted_village(&pool, user_id, 0).await;
+        let production = ResourceService::calculate_production(&pool, by_job.id).await.unwrap();
+        let unboosted = ResourceService::production_from_buildings(
+            by_job.population,
+            &BuildingRepository::find_by_village_id(&pool, by_job.id).await.unwrap(),
+            &ProductionBoosts::default(),
+        );
+        assert!(production.wood_per_hour > unboosted.wood_per_hour);
+
+        ResourceService::update_all_village_resources(&pool).await.unwrap();
+        let after_job = test_support::village(&pool, by_job.id).await;
+        assert_eq!(after_job.wood, by_job.wood + production.wood_per_hour);
+
+        // On-read path, through the batch village details
+        let on_read = boosted_village(&pool, user_id, 5).await;
+        let details = VillageService::get_villages_detail(&pool, user_id, &[on_read.id]).await.unwrap();
+        assert_eq!(details[0].production.as_ref().unwrap().wood_per_hour, production.wood_per_hour);
+        assert_eq!(details[0].wood, on_read.wood + production.wood_per_hour);
+        assert_eq!(test_support::village(&pool, on_read.id).await.wood, after_job.wood);
+    }
+}
@@ -1,7 +1,7 @@
 use chrono::{Duration, Utc};
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction as DbTransaction};
 use stripe_rust::{
     CheckoutSession, CheckoutSessionMode, Client, CreateCheckoutSession,
     CreateCheckoutSessionLineItems, CreateCheckoutSessionLineItemsPriceData,
@@ -11,16 +11,51 @@ use uuid::Uuid;
 
 use crate::error::{AppError, AppResult};
 use crate::models::shop::{
-    CheckoutResponse, GoldBalanceResponse, GoldFeature, GoldPackage, SubscriptionPrice,
-    SubscriptionType, TransactionResponse, TransactionStatus, TransactionType, UseFeatureResponse,
+    CheckoutResponse, FeaturePricing, FinishNowPreviewResponse, FinishQueueResponse, GiftGoldResponse, GoldBalanceResponse, GoldFeature,
+    GoldFeatureListing, GoldPackage, GoldUsageFeatureSummary, GoldUsageSummaryResponse, SubscriptionPrice, SubscriptionType,
+    TransactionResponse, TransactionStatus, TransactionType, UseFeatureResponse,
 };
 use crate::repositories::building_repo::BuildingRepository;
+use crate::repositories::hero_repo::HeroRepository;
+use crate::repositories::message_repo::MessageRepository;
 use crate::repositories::shop_repo::ShopRepository;
 use crate::repositories::troop_repo::TroopRepository;
+use crate::repositories::user_repo::UserRepository;
+use crate::models::troop::TroopType;
 use crate::repositories::village_repo::VillageRepository;
+use crate::services::building_service::BuildingService;
+use crate::services::resource_service::ResourceService;
+use crate::services::troop_service::TroopService;
+use crate::services::ws_service::{GoldGiftData, WsEvent, WsManager};
 
 pub struct ShopService;
 
+/// Finish All discount for Plus subscribers finishing two or more builds (percent)
+const PLUS_FINISH_ALL_DISCOUNT_PERCENT: i32 = 20;
+
+/// Longest note that can go with a gold gift
+const MAX_GIFT_MESSAGE_LENGTH: usize = 200;
+
+/// Order in which gold features are listed in the shop: time savers first,
+/// then economy boosts, hero and troop items, and finally the subscription
+const FEATURE_MENU_ORDER: &[GoldFeature] = &[
+    GoldFeature::FinishNow,
+    GoldFeature::InstantTrain,
+    GoldFeature::NpcMerchant,
+    GoldFeature::ProductionBonus,
+    GoldFeature::BookOfWisdom,
+    GoldFeature::Artwork,
+    GoldFeature::Ointment,
+    GoldFeature::HeroSlot,
+    GoldFeature::PlusSubscription,
+];
+
+/// Price of finishing a building upgrade or training queue instantly
+struct FinishNowQuote {
+    remaining_seconds: i32,
+    gold_cost: i32,
+}
+
 impl ShopService {
     // ==================== Gold Packages ====================
 
@@ -44,6 +79,75 @@ impl ShopService {
         })
     }
 
+    // ==================== Gold Features ====================
+
+    /// Start a gold spend: opens a transaction holding the player's row lock and
+    /// returns the balance at that point. Every feature that spends gold goes through
+    /// here, so concurrent spends run one after another instead of both passing the
+    /// balance check on the same gold.
+    pub async fn begin_gold_spend(
+        pool: &PgPool,
+        user_id: Uuid,
+    ) -> AppResult<(DbTransaction<'static, Postgres>, i32)> {
+        let mut tx = pool.begin().await?;
+        let balance = ShopRepository::lock_gold_balance_tx(&mut tx, user_id).await?;
+        Ok((tx, balance))
+    }
+
+    /// Check the locked balance covers `gold_cost` and deduct it
+    pub async fn spend_gold_tx(
+        tx: &mut DbTransaction<'_, Postgres>,
+        user_id: Uuid,
+        balance: i32,
+        gold_cost: i32,
+    ) -> AppResult<i32> {
+        if balance < gold_cost {
+            return Err(AppError::BadRequest("Insufficient gold".into()));
+        }
+        ShopRepository::deduct_gold_tx(tx, user_id, gold_cost).await
+    }
+
+    /// List every gold feature with its current price for the user
+    pub async fn list_features(pool: &PgPool, user_id: Uuid) -> AppResult<Vec<GoldFeatureListing>> {
+        let costs = ShopRepository::get_feature_costs(pool).await?;
+
+        let next_slot = HeroRepository::get_user_slots(pool, user_id).await? + 1;
+        let next_slot_price = HeroRepository::get_slot_price(pool, next_slot)
+            .await?
+            .map(|p| p.gold_cost);
+
+        let mut listings = Vec::new();
+        for feature in FEATURE_MENU_ORDER {
+            let Some(cost) = costs.iter().find(|c| c.feature == *feature) else {
+                continue;
+            };
+
+            let (pricing, gold_cost) = match feature {
+                GoldFeature::FinishNow | GoldFeature::InstantTrain => {
+                    (FeaturePricing::PerFiveMinutes, Self::gold_for_seconds(300))
+                }
+                GoldFeature::HeroSlot => {
+                    // No price means every slot is already unlocked
+                    let Some(price) = next_slot_price else { continue };
+                    (FeaturePricing::PerSlot, price)
+                }
+                GoldFeature::ProductionBonus | GoldFeature::PlusSubscription => {
+                    (FeaturePricing::PerDay, cost.base_cost)
+                }
+                _ => (FeaturePricing::Fixed, cost.base_cost),
+            };
+
+            listings.push(GoldFeatureListing {
+                feature: *feature,
+                description: cost.description.clone(),
+                pricing,
+                gold_cost,
+            });
+        }
+
+        Ok(listings)
+    }
+
     // ==================== Stripe Checkout ====================
 
     /// Create Stripe checkout session for gold purchase
@@ -68,6 +172,15 @@ impl ShopService {
         let bonus_gold = (package.gold_amount * package.bonus_percent) / 100;
         let total_gold = package.gold_amount + bonus_gold;
 
+        let currency = Self::parse_currency(&package.currency).unwrap_or_else(|| {
+            tracing::warn!(
+                "Gold package {} has unknown currency {:?}, charging in USD",
+                package.id,
+                package.currency
+            );
+            Currency::USD
+        });
+
         // Create pending transaction
         let transaction = ShopRepository::create_transaction(
             pool,
@@ -75,7 +188,7 @@ impl ShopService {
             TransactionType::GoldPurchase,
             total_gold,
             Some(package.price_cents),
-            Some(&package.currency),
+            Some(&currency.to_string()),
             None, // Will be updated after checkout created
             Some(package_id),
             Some(&format!("Purchase {} Gold", total_gold)),
@@ -92,7 +205,7 @@ impl ShopService {
 
         let line_item = CreateCheckoutSessionLineItems {
             price_data: Some(CreateCheckoutSessionLineItemsPriceData {
-                currency: Currency::USD,
+                currency,
                 unit_amount: Some(package.price_cents as i64),
                 product_data: Some(CreateCheckoutSessionLineItemsPriceDataProductData {
                     name: format!("{} Gold", total_gold),
@@ -132,7 +245,13 @@ impl ShopService {
         })
     }
 
-    /// Handle Stripe webhook
+    /// Stripe currency for an ISO code like "eur" (case-insensitive)
+    pub fn parse_currency(code: &str) -> Option<Currency> {
+        code.trim().to_ascii_lowercase().parse().ok()
+    }
+
+    /// Handle Stripe webhook.
+    /// Each event id is handled once; a failed event is forgotten so Stripe's retry can redo it.
     pub async fn handle_webhook(
         pool: &PgPool,
         payload: &str,
@@ -147,7 +266,30 @@ impl ShopService {
             .map_err(|e| AppError::BadRequest(format!("Invalid JSON: {}", e)))?;
 
         let event_type = event["type"].as_str().unwrap_or("");
+        let event_id = event["id"]
+            .as_str()
+            .ok_or_else(|| AppError::BadRequest("Missing event id".into()))?;
 
+        // Stripe retries deliveries; only the first one of an event is handled
+        if !ShopRepository::try_record_stripe_event(pool, event_id, event_type).await? {
+            tracing::info!("Ignoring already handled Stripe event {}", event_id);
+            return Ok(());
+        }
+
+        if let Err(e) = Self::dispatch_webhook_event(pool, event_type, &event).await {
+            ShopRepository::forget_stripe_event(pool, event_id).await?;
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Run the handler for a verified, first-seen webhook event
+    async fn dispatch_webhook_event(
+        pool: &PgPool,
+        event_type: &str,
+        event: &serde_json::Value,
+    ) -> AppResult<()> {
         match event_type {
             "checkout.session.completed" => {
                 let session_id = event["data"]["object"]["id"].as_str().unwrap_or("");
@@ -158,6 +300,21 @@ impl ShopService {
                 let session_id = event["data"]["object"]["id"].as_str().unwrap_or("");
                 Self::expire_checkout_by_id(pool, session_id).await?;
             }
+            "charge.refunded" => {
+                let charge = &event["data"]["object"];
+                // Partial refunds also send this event; only a full refund takes the gold back
+                if charge["refunded"].as_bool().unwrap_or(false) {
+                    if let Some(payment_intent) = charge["payment_intent"].as_str() {
+                        Self::refund_purchase_by_payment_intent(pool, payment_intent).await?;
+                    }
+                } else {
+                    tracing::info!("Ignoring partial refund of charge {}", charge["id"]);
+                }
+            }
+            "payment_intent.refunded" => {
+                let payment_intent = event["data"]["object"]["id"].as_str().unwrap_or("");
+                Self::refund_purchase_by_payment_intent(pool, payment_intent).await?;
+            }
             _ => {
                 tracing::debug!("Unhandled webhook event: {}", event_type);
             }
@@ -273,6 +430,168 @@ impl ShopService {
         Ok(())
     }
 
+    /// Take back the gold of a refunded purchase.
+    /// Gold the player already spent can't be recovered: the balance stops at zero
+    /// and the shortfall is logged and noted on the refund record.
+    async fn refund_purchase_by_payment_intent(pool: &PgPool, payment_intent_id: &str) -> AppResult<()> {
+        let Some(transaction) = ShopRepository::get_transaction_by_payment_intent(pool, payment_intent_id).await?
+        else {
+            tracing::warn!("Refund for unknown payment intent {}", payment_intent_id);
+            return Ok(());
+        };
+
+        let (mut tx, balance) = Self::begin_gold_spend(pool, transaction.user_id).await?;
+
+        // Both refund events may arrive for the same payment; only the first one counts
+        if !ShopRepository::mark_transaction_refunded_tx(&mut tx, transaction.id).await? {
+            tracing::warn!(
+                "Transaction {} refunded while {:?}, nothing to take back",
+                transaction.id,
+                transaction.status
+            );
+            return Ok(());
+        }
+
+        let clawback = transaction.gold_amount.min(balance).max(0);
+        let shortfall = transaction.gold_amount - clawback;
+
+        if clawback > 0 {
+            ShopRepository::deduct_gold_tx(&mut tx, transaction.user_id, clawback).await?;
+        }
+
+        let description = if shortfall > 0 {
+            format!(
+                "Refund of purchase {} ({} gold already spent, not recovered)",
+                transaction.id, shortfall
+            )
+        } else {
+            format!("Refund of purchase {}", transaction.id)
+        };
+        ShopRepository::create_transaction_tx(
+            &mut tx,
+            transaction.user_id,
+            TransactionType::GoldRefund,
+            -clawback,
+            Some(&description),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        if shortfall > 0 {
+            tracing::warn!(
+                "Refund of transaction {}: user {} had already spent {} of {} gold",
+                transaction.id,
+                transaction.user_id,
+                shortfall,
+                transaction.gold_amount
+            );
+        } else {
+            tracing::info!(
+                "Gold purchase refunded: {} gold taken back from user {}",
+                clawback,
+                transaction.user_id
+            );
+        }
+
+        Ok(())
+    }
+
+    // ==================== Gold Gifts ====================
+
+    /// Send gold to another player.
+    /// Both balances change in one transaction, and each side gets a GoldGift record.
+    /// The recipient is told by an inbox notice and a WebSocket event.
+    pub async fn gift_gold(
+        pool: &PgPool,
+        ws_manager: &WsManager,
+        sender_id: Uuid,
+        recipient_id: Uuid,
+        amount: i32,
+        message: Option<String>,
+    ) -> AppResult<GiftGoldResponse> {
+        if amount <= 0 {
+            return Err(AppError::BadRequest("Gift amount must be positive".into()));
+        }
+
+        if sender_id == recipient_id {
+            return Err(AppError::BadRequest("Cannot gift gold to yourself".into()));
+        }
+
+        let message = message.map(|m| m.trim().to_string()).filter(|m| !m.is_empty());
+        if message.as_ref().is_some_and(|m| m.chars().count() > MAX_GIFT_MESSAGE_LENGTH) {
+            return Err(AppError::BadRequest(format!(
+                "Gift message cannot exceed {} characters",
+                MAX_GIFT_MESSAGE_LENGTH
+            )));
+        }
+
+        let recipient = UserRepository::find_by_id(pool, recipient_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Recipient not found".into()))?;
+
+        if recipient.banned_at.is_some() {
+            return Err(AppError::BadRequest("This player cannot receive gifts".into()));
+        }
+
+        let sender = UserRepository::find_by_id(pool, sender_id)
+            .await?
+            .ok_or(AppError::Unauthorized)?;
+
+        let sender_name = sender.display_name.unwrap_or_else(|| "Unknown player".to_string());
+        let recipient_name = recipient.display_name.unwrap_or_else(|| "Unknown player".to_string());
+
+        // Hold the sender's gold lock until commit
+        let (mut tx, balance) = Self::begin_gold_spend(pool, sender_id).await?;
+
+        let new_balance = Self::spend_gold_tx(&mut tx, sender_id, balance, amount).await?;
+        ShopRepository::add_gold_tx(&mut tx, recipient_id, amount).await?;
+
+        ShopRepository::create_transaction_tx(
+            &mut tx,
+            sender_id,
+            TransactionType::GoldGift,
+            -amount,
+            Some(&format!("Gift to {}", recipient_name)),
+        )
+        .await?;
+        ShopRepository::create_transaction_tx(
+            &mut tx,
+            recipient_id,
+            TransactionType::GoldGift,
+            amount,
+            Some(&format!("Gift from {}", sender_name)),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        // The gold has moved; failing to notify shouldn't undo that
+        let subject = format!("{} sent you {} gold", sender_name, amount);
+        let body = match &message {
+            Some(note) => format!("{} sent you a gift of {} gold:\n\n{}", sender_name, amount, note),
+            None => format!("{} sent you a gift of {} gold.", sender_name, amount),
+        };
+        if let Err(e) = MessageRepository::create_system_messages(pool, &[recipient_id], &[subject], &[body]).await {
+            tracing::error!("Failed to send gold gift notice to {}: {}", recipient_id, e);
+        }
+
+        let event = WsEvent::GoldGiftReceived(GoldGiftData {
+            sender_id,
+            sender_name,
+            amount,
+            message,
+        });
+        ws_manager.send_to_user(recipient_id, &event).await;
+
+        Ok(GiftGoldResponse {
+            recipient_id,
+            recipient_name,
+            amount,
+            new_balance,
+        })
+    }
+
     // ==================== Subscriptions ====================
 
     /// Get subscription prices
@@ -295,18 +614,15 @@ impl ShopService {
             .find(|p| p.duration_days == duration_days)
             .ok_or_else(|| AppError::BadRequest("Invalid subscription duration".into()))?;
 
-        // Check gold balance
-        let balance = ShopRepository::get_gold_balance(pool, user_id).await?;
-        if balance < price.gold_cost {
-            return Err(AppError::BadRequest("Insufficient gold".into()));
-        }
+        // Hold the player's gold lock until commit
+        let (mut tx, balance) = Self::begin_gold_spend(pool, user_id).await?;
 
         // Deduct gold
-        let new_balance = ShopRepository::deduct_gold(pool, user_id, price.gold_cost).await?;
+        let new_balance = Self::spend_gold_tx(&mut tx, user_id, balance, price.gold_cost).await?;
 
         // Create or extend subscription
-        let subscription = ShopRepository::create_or_extend_subscription(
-            pool,
+        let subscription = ShopRepository::create_or_extend_subscription_tx(
+            &mut tx,
             user_id,
             SubscriptionType::TravianPlus,
             duration_days,
@@ -314,15 +630,11 @@ impl ShopService {
         .await?;
 
         // Record transaction
-        ShopRepository::create_transaction(
-            pool,
+        ShopRepository::create_transaction_tx(
+            &mut tx,
             user_id,
             TransactionType::Subscription,
             -price.gold_cost,
-            None,
-            None,
-            None,
-            None,
             Some(&format!(
                 "Travian Plus {} days subscription",
                 duration_days
@@ -331,8 +643,8 @@ impl ShopService {
         .await?;
 
         // Record gold usage
-        ShopRepository::record_gold_usage(
-            pool,
+        ShopRepository::record_gold_usage_tx(
+            &mut tx,
             user_id,
             GoldFeature::PlusSubscription,
             price.gold_cost,
@@ -346,6 +658,8 @@ impl ShopService {
         )
         .await?;
 
+        tx.commit().await?;
+
         Ok(UseFeatureResponse {
             success: true,
             gold_spent: price.gold_cost,
@@ -359,13 +673,18 @@ impl ShopService {
 
     // ==================== Gold Features ====================
 
-    /// Use "Finish Now" to instantly complete a building or training
-    pub async fn use_finish_now(
+    /// Gold price of skipping a wait: 1 gold per 5 minutes (300 seconds), minimum 1 gold
+    fn gold_for_seconds(seconds: i32) -> i32 {
+        ((seconds as f64 / 300.0).ceil() as i32).max(1)
+    }
+
+    /// Work out the Finish Now price for a target the user owns, without charging
+    async fn quote_finish_now(
         pool: &PgPool,
         user_id: Uuid,
         target_type: &str,
         target_id: Uuid,
-    ) -> AppResult<UseFeatureResponse> {
+    ) -> AppResult<FinishNowQuote> {
         // Calculate cost based on remaining time
         let (remaining_seconds, village_id) = match target_type {
             "building" => {
@@ -405,46 +724,77 @@ impl ShopService {
             return Err(AppError::Forbidden("Access denied".into()));
         }
 
-        // Calculate gold cost: 1 gold per 5 minutes (300 seconds), minimum 1 gold
-        let gold_cost = ((remaining_seconds as f64 / 300.0).ceil() as i32).max(1);
+        Ok(FinishNowQuote {
+            remaining_seconds,
+            gold_cost: Self::gold_for_seconds(remaining_seconds),
+        })
+    }
 
-        // Check gold balance
+    /// Preview the Finish Now price without spending gold
+    pub async fn preview_finish_now(
+        pool: &PgPool,
+        user_id: Uuid,
+        target_type: &str,
+        target_id: Uuid,
+    ) -> AppResult<FinishNowPreviewResponse> {
+        let quote = Self::quote_finish_now(pool, user_id, target_type, target_id).await?;
         let balance = ShopRepository::get_gold_balance(pool, user_id).await?;
-        if balance < gold_cost {
-            return Err(AppError::BadRequest("Insufficient gold".into()));
-        }
+
+        Ok(FinishNowPreviewResponse {
+            target_type: target_type.to_string(),
+            target_id,
+            remaining_seconds: quote.remaining_seconds,
+            gold_cost: quote.gold_cost,
+            gold_balance: balance,
+            can_afford: balance >= quote.gold_cost,
+        })
+    }
+
+    /// Use "Finish Now" to instantly complete a building or training
+    pub async fn use_finish_now(
+        pool: &PgPool,
+        user_id: Uuid,
+        target_type: &str,
+        target_id: Uuid,
+    ) -> AppResult<UseFeatureResponse> {
+        // Hold the player's gold lock until commit
+        let (mut tx, balance) = Self::begin_gold_spend(pool, user_id).await?;
+
+        let FinishNowQuote {
+            remaining_seconds,
+            gold_cost,
+            ..
+        } = Self::quote_finish_now(pool, user_id, target_type, target_id).await?;
 
         // Deduct gold
-        let new_balance = ShopRepository::deduct_gold(pool, user_id, gold_cost).await?;
+        let new_balance = Self::spend_gold_tx(&mut tx, user_id, balance, gold_cost).await?;
 
-        // Complete the target instantly
+        // Complete the target instantly; if it finished on its own meanwhile, nothing is charged
         match target_type {
             "building" => {
-                BuildingRepository::complete_upgrade(pool, target_id).await?;
+                BuildingService::complete_upgrade_tx(&mut tx, target_id).await?;
             }
             "troop_queue" => {
-                TroopRepository::complete_training(pool, target_id).await?;
+                TroopRepository::complete_training_tx(&mut tx, target_id)
+                    .await?
+                    .ok_or_else(|| AppError::Conflict("Training has already completed".into()))?;
             }
             _ => {}
         }
 
         // Record transaction
-        ShopRepository::create_transaction(
-            pool,
+        ShopRepository::create_transaction_tx(
+            &mut tx,
             user_id,
             TransactionType::GoldSpend,
             -gold_cost,
-            None,
-            None,
-            None,
-            None,
             Some(&format!("Finish Now - {}", target_type)),
         )
         .await?;
 
         // Record usage
-        ShopRepository::record_gold_usage(
-            pool,
+        ShopRepository::record_gold_usage_tx(
+            &mut tx,
             user_id,
             GoldFeature::FinishNow,
             gold_cost,
@@ -455,6 +805,8 @@ impl ShopService {
         )
         .await?;
 
+        tx.commit().await?;
+
         Ok(UseFeatureResponse {
             success: true,
             gold_spent: gold_cost,
@@ -463,6 +815,252 @@ impl ShopService {
         })
     }
 
+    /// Use "Finish Now" on every build in progress in a village for one bundled price.
+    /// Each build is priced as it would be on its own, Plus subscribers get a bundle of
+    /// two or more discounted, and nothing is charged unless the whole bundle is affordable.
+    pub async fn use_finish_all(
+        pool: &PgPool,
+        user_id: Uuid,
+        village_id: Uuid,
+    ) -> AppResult<UseFeatureResponse> {
+        // Verify ownership
+        let village = VillageRepository::find_by_id(pool, village_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Village not found".into()))?;
+
+        if village.user_id != user_id {
+            return Err(AppError::Forbidden("Access denied".into()));
+        }
+
+        // Hold the player's gold lock until commit
+        let (mut tx, balance) = Self::begin_gold_spend(pool, user_id).await?;
+
+        // Lock the builds so the completion job can't finish one twice
+        let buildings = BuildingRepository::find_upgrading_by_village_for_update_tx(&mut tx, village_id).await?;
+        if buildings.is_empty() {
+            return Err(AppError::BadRequest("No builds in progress".into()));
+        }
+
+        let now = Utc::now();
+        let mut gold_cost = 0;
+        let mut saved_seconds = 0;
+        for building in &buildings {
+            let remaining = building
+                .upgrade_ends_at
+                .map(|ends| (ends - now).num_seconds().max(0) as i32)
+                .unwrap_or(0);
+            gold_cost += Self::gold_for_seconds(remaining);
+            saved_seconds += remaining;
+        }
+
+        // The discount is for bundling: a single build costs the same as Finish Now
+        let has_plus = ShopRepository::get_active_subscription_tx(&mut tx, user_id, SubscriptionType::TravianPlus)
+            .await?
+            .is_some();
+        if has_plus && buildings.len() >= 2 {
+            gold_cost = (gold_cost * (100 - PLUS_FINISH_ALL_DISCOUNT_PERCENT) / 100).max(1);
+        }
+
+        // Deduct gold for the whole bundle
+        let new_balance = Self::spend_gold_tx(&mut tx, user_id, balance, gold_cost).await?;
+
+        // Complete the builds in the order they would have finished
+        for building in &buildings {
+            BuildingService::complete_upgrade_tx(&mut tx, building.id).await?;
+        }
+
+        // Record transaction
+        ShopRepository::create_transaction_tx(
+            &mut tx,
+            user_id,
+            TransactionType::GoldSpend,
+            -gold_cost,
+            Some(&format!("Finish All - {} builds", buildings.len())),
+        )
+        .await?;
+
+        // Record usage
+        let building_ids: Vec<Uuid> = buildings.iter().map(|b| b.id).collect();
+        ShopRepository::record_gold_usage_tx(
+            &mut tx,
+            user_id,
+            GoldFeature::FinishNow,
+            gold_cost,
+            Some("village"),
+            Some(village_id),
+            Some(serde_json::json!({
+                "building_ids": building_ids,
+                "saved_seconds": saved_seconds,
+            })),
+            None,
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(UseFeatureResponse {
+            success: true,
+            gold_spent: gold_cost,
+            new_balance,
+            message: format!("{} builds completed instantly!", buildings.len()),
+        })
+    }
+
+    /// Use "Finish Now" on a village's whole training queue for a single price.
+    /// Batches train one after another, so only the time still ahead of each batch
+    /// counts and the total is priced like one Finish Now.
+    pub async fn use_finish_now_queue(
+        pool: &PgPool,
+        user_id: Uuid,
+        village_id: Uuid,
+    ) -> AppResult<FinishQueueResponse> {
+        // Verify ownership
+        let village = VillageRepository::find_by_id(pool, village_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Village not found".into()))?;
+
+        if village.user_id != user_id {
+            return Err(AppError::Forbidden("Access denied".into()));
+        }
+
+        // Hold the player's gold lock until commit
+        let (mut tx, balance) = Self::begin_gold_spend(pool, user_id).await?;
+
+        // Claim the whole queue so the completion job can't also finish a batch;
+        // if the gold falls short the rollback puts it back
+        let queue = TroopRepository::complete_village_training_tx(&mut tx, village_id).await?;
+        if queue.is_empty() {
+            return Ok(FinishQueueResponse {
+                success: true,
+                gold_spent: 0,
+                new_balance: balance,
+                batches_finished: 0,
+                message: "No troops in training".into(),
+            });
+        }
+
+        let now = Utc::now();
+        let remaining_seconds: i32 = queue
+            .iter()
+            .map(|entry| (entry.ends_at - entry.started_at.max(now)).num_seconds().max(0) as i32)
+            .sum();
+
+        let gold_cost = Self::gold_for_seconds(remaining_seconds);
+
+        // Deduct gold once for the whole queue
+        let new_balance = Self::spend_gold_tx(&mut tx, user_id, balance, gold_cost).await?;
+
+        // Record transaction
+        ShopRepository::create_transaction_tx(
+            &mut tx,
+            user_id,
+            TransactionType::GoldSpend,
+            -gold_cost,
+            Some(&format!("Finish Now - {} training batches", queue.len())),
+        )
+        .await?;
+
+        // Record usage
+        let queue_ids: Vec<Uuid> = queue.iter().map(|entry| entry.id).collect();
+        ShopRepository::record_gold_usage_tx(
+            &mut tx,
+            user_id,
+            GoldFeature::FinishNow,
+            gold_cost,
+            Some("troop_queue"),
+            Some(village_id),
+            Some(serde_json::json!({
+                "queue_ids": queue_ids,
+                "saved_seconds": remaining_seconds,
+            })),
+            None,
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(FinishQueueResponse {
+            success: true,
+            gold_spent: gold_cost,
+            new_balance,
+            batches_finished: queue.len() as i32,
+            message: format!("{} training batches completed instantly!", queue.len()),
+        })
+    }
+
+    /// Train troops instantly: pay the normal resources plus gold for the training time,
+    /// and the troops are added to the village without queueing
+    pub async fn use_instant_train(
+        pool: &PgPool,
+        user_id: Uuid,
+        village_id: Uuid,
+        troop_type: TroopType,
+        count: i32,
+        starvation_guard: bool,
+    ) -> AppResult<UseFeatureResponse> {
+        TroopService::validate_training_count(count)?;
+
+        // Same requirements and resource cost as normal training
+        let definition = TroopService::check_training_requirements(pool, village_id, troop_type).await?;
+        let cost = TroopService::training_cost(&definition, count)?;
+        let gold_cost = Self::gold_for_seconds(cost.time_seconds);
+
+        // Bring resources up to date before the village row is locked
+        ResourceService::update_village_resources(pool, village_id).await?;
+
+        // Hold the player's gold lock until commit
+        let (mut tx, balance) = Self::begin_gold_spend(pool, user_id).await?;
+
+        let village = VillageRepository::find_by_id_for_update(&mut tx, village_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Village not found".into()))?;
+
+        if village.user_id != user_id {
+            return Err(AppError::Forbidden("Access denied".into()));
+        }
+
+        // Deduct gold and resources, then add the troops immediately
+        let new_balance = Self::spend_gold_tx(&mut tx, user_id, balance, gold_cost).await?;
+        TroopService::pay_training_cost_tx(&mut tx, &village, &cost, starvation_guard).await?;
+        TroopRepository::add_troops_tx(&mut tx, village_id, troop_type, count).await?;
+
+        // Record transaction
+        ShopRepository::create_transaction_tx(
+            &mut tx,
+            user_id,
+            TransactionType::GoldSpend,
+            -gold_cost,
+            Some(&format!("Instant Train - {} {:?}", count, troop_type)),
+        )
+        .await?;
+
+        // Record usage
+        ShopRepository::record_gold_usage_tx(
+            &mut tx,
+            user_id,
+            GoldFeature::InstantTrain,
+            gold_cost,
+            Some("village"),
+            Some(village_id),
+            Some(serde_json::json!({
+                "troop_type": troop_type,
+                "count": count,
+                "saved_seconds": cost.time_seconds,
+            })),
+            None,
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(UseFeatureResponse {
+            success: true,
+            gold_spent: gold_cost,
+            new_balance,
+            message: format!("{} {:?} trained instantly!", count, troop_type),
+        })
+    }
+
     /// Use NPC Merchant to exchange resources
     pub async fn use_npc_merchant(
         pool: &PgPool,
@@ -475,8 +1073,11 @@ impl ShopService {
     ) -> AppResult<UseFeatureResponse> {
         let gold_cost = 3;
 
-        // Verify village ownership
-        let village = VillageRepository::find_by_id(pool, village_id)
+        // Hold the player's gold lock until commit
+        let (mut tx, balance) = Self::begin_gold_spend(pool, user_id).await?;
+
+        // Lock the village so the exchange is checked against the resources it replaces
+        let village = VillageRepository::find_by_id_for_update(&mut tx, village_id)
             .await?
             .ok_or_else(|| AppError::NotFound("Village not found".into()))?;
 
@@ -485,8 +1086,7 @@ impl ShopService {
         }
 
         // Validate that total resources remain the same
-        let current_total =
-            village.wood as i32 + village.clay as i32 + village.iron as i32 + village.crop as i32;
+        let current_total = village.wood + village.clay + village.iron + village.crop;
         let new_total = wood + clay + iron + crop;
 
         if new_total != current_total {
@@ -503,62 +1103,39 @@ impl ShopService {
         }
 
         // Check warehouse capacity
-        let max_storage = village.warehouse_capacity as i32;
+        let max_storage = village.warehouse_capacity;
         if wood > max_storage || clay > max_storage || iron > max_storage {
             return Err(AppError::BadRequest(
                 "Resources exceed warehouse capacity".into(),
             ));
         }
 
-        let max_granary = village.granary_capacity as i32;
+        let max_granary = village.granary_capacity;
         if crop > max_granary {
             return Err(AppError::BadRequest(
                 "Crop exceeds granary capacity".into(),
             ));
         }
 
-        // Check gold balance
-        let balance = ShopRepository::get_gold_balance(pool, user_id).await?;
-        if balance < gold_cost {
-            return Err(AppError::BadRequest("Insufficient gold".into()));
-        }
-
         // Deduct gold
-        let new_balance = ShopRepository::deduct_gold(pool, user_id, gold_cost).await?;
+        let new_balance = Self::spend_gold_tx(&mut tx, user_id, balance, gold_cost).await?;
 
         // Update village resources
-        sqlx::query(
-            r#"
-            UPDATE villages
-            SET wood = $2, clay = $3, iron = $4, crop = $5, updated_at = NOW()
-            WHERE id = $1
-            "#,
-        )
-        .bind(village_id)
-        .bind(wood as f64)
-        .bind(clay as f64)
-        .bind(iron as f64)
-        .bind(crop as f64)
-        .execute(pool)
-        .await?;
+        VillageRepository::set_resources_tx(&mut tx, village_id, wood, clay, iron, crop).await?;
 
         // Record transaction
-        ShopRepository::create_transaction(
-            pool,
+        ShopRepository::create_transaction_tx(
+            &mut tx,
             user_id,
             TransactionType::GoldSpend,
             -gold_cost,
-            None,
-            None,
-            None,
-            None,
             Some("NPC Merchant - Resource exchange"),
         )
         .await?;
 
         // Record usage
-        ShopRepository::record_gold_usage(
-            pool,
+        ShopRepository::record_gold_usage_tx(
+            &mut tx,
             user_id,
             GoldFeature::NpcMerchant,
             gold_cost,
@@ -582,6 +1159,8 @@ impl ShopService {
         )
         .await?;
 
+        tx.commit().await?;
+
         Ok(UseFeatureResponse {
             success: true,
             gold_spent: gold_cost,
@@ -614,6 +1193,9 @@ impl ShopService {
             return Err(AppError::Forbidden("Access denied".into()));
         }
 
+        // Hold the player's gold lock until commit
+        let (mut tx, balance) = Self::begin_gold_spend(pool, user_id).await?;
+
         // Check if already has active bonus for this resource
         if ShopRepository::has_active_production_bonus(pool, user_id, village_id, resource_type)
             .await?
@@ -623,34 +1205,24 @@ impl ShopService {
             ));
         }
 
-        // Check gold balance
-        let balance = ShopRepository::get_gold_balance(pool, user_id).await?;
-        if balance < gold_cost {
-            return Err(AppError::BadRequest("Insufficient gold".into()));
-        }
-
         // Deduct gold
-        let new_balance = ShopRepository::deduct_gold(pool, user_id, gold_cost).await?;
+        let new_balance = Self::spend_gold_tx(&mut tx, user_id, balance, gold_cost).await?;
 
         let expires_at = Utc::now() + Duration::hours(duration_hours);
 
         // Record transaction
-        ShopRepository::create_transaction(
-            pool,
+        ShopRepository::create_transaction_tx(
+            &mut tx,
             user_id,
             TransactionType::GoldSpend,
             -gold_cost,
-            None,
-            None,
-            None,
-            None,
             Some(&format!("+25% {} production bonus", resource_type)),
         )
         .await?;
 
         // Record usage
-        ShopRepository::record_gold_usage(
-            pool,
+        ShopRepository::record_gold_usage_tx(
+            &mut tx,
             user_id,
             GoldFeature::ProductionBonus,
             gold_cost,
@@ -661,6 +1233,8 @@ impl ShopService {
         )
         .await?;
 
+        tx.commit().await?;
+
         Ok(UseFeatureResponse {
             success: true,
             gold_spent: gold_cost,
@@ -690,6 +1264,9 @@ impl ShopService {
             return Err(AppError::Forbidden("Access denied".into()));
         }
 
+        // Hold the player's gold lock until commit
+        let (mut tx, balance) = Self::begin_gold_spend(pool, user_id).await?;
+
         // Check if already active
         if ShopRepository::has_active_book_of_wisdom(pool, user_id, village_id).await? {
             return Err(AppError::BadRequest(
@@ -697,34 +1274,24 @@ impl ShopService {
             ));
         }
 
-        // Check gold balance
-        let balance = ShopRepository::get_gold_balance(pool, user_id).await?;
-        if balance < gold_cost {
-            return Err(AppError::BadRequest("Insufficient gold".into()));
-        }
-
         // Deduct gold
-        let new_balance = ShopRepository::deduct_gold(pool, user_id, gold_cost).await?;
+        let new_balance = Self::spend_gold_tx(&mut tx, user_id, balance, gold_cost).await?;
 
         let expires_at = Utc::now() + Duration::hours(duration_hours);
 
         // Record transaction
-        ShopRepository::create_transaction(
-            pool,
+        ShopRepository::create_transaction_tx(
+            &mut tx,
             user_id,
             TransactionType::GoldSpend,
             -gold_cost,
-            None,
-            None,
-            None,
-            None,
             Some("Book of Wisdom - 2x production"),
         )
         .await?;
 
         // Record usage
-        ShopRepository::record_gold_usage(
-            pool,
+        ShopRepository::record_gold_usage_tx(
+            &mut tx,
             user_id,
             GoldFeature::BookOfWisdom,
             gold_cost,
@@ -735,6 +1302,8 @@ impl ShopService {
         )
         .await?;
 
+        tx.commit().await?;
+
         Ok(UseFeatureResponse {
             success: true,
             gold_spent: gold_cost,
@@ -756,4 +1325,315 @@ impl ShopService {
         let transactions = ShopRepository::get_user_transactions(pool, user_id, limit, offset).await?;
         Ok(transactions.into_iter().map(|t| t.into()).collect())
     }
+
+    /// Get a single transaction (receipt) owned by the user
+    pub async fn get_transaction(
+        pool: &PgPool,
+        user_id: Uuid,
+        transaction_id: Uuid,
+    ) -> AppResult<TransactionResponse> {
+        let transaction = ShopRepository::get_transaction_by_id(pool, transaction_id, user_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Transaction not found".into()))?;
+
+        Ok(transaction.into())
+    }
+
+    // ==================== Gold Usage ====================
+
+    /// Summarize gold spent per feature over the last `days` days, with paginated raw history
+    pub async fn get_gold_usage_summary(
+        pool: &PgPool,
+        user_id: Uuid,
+        days: i32,
+        limit: i32,
+        offset: i32,
+    ) -> AppResult<GoldUsageSummaryResponse> {
+        let days = days.clamp(1, 365);
+        let since = Utc::now() - Duration::days(days as i64);
+
+        let spent = ShopRepository::get_gold_usage_by_feature(pool, user_id, since).await?;
+
+        // Report every feature, including ones the player hasn't used
+        let by_feature: Vec<GoldUsageFeatureSummary> = GoldFeature::all()
+            .into_iter()
+            .map(|feature| {
+                spent
+                    .iter()
+                    .find(|s| s.feature == feature)
+                    .cloned()
+                    .unwrap_or(GoldUsageFeatureSummary {
+                        feature,
+                        total_spent: 0,
+                        usage_count: 0,
+                    })
+            })
+            .collect();
+
+        let limit = limit.clamp(1, 50);
+        let history = ShopRepository::get_user_gold_usage(pool, user_id, limit, offset.max(0)).await?;
+
+        Ok(GoldUsageSummaryResponse {
+            days,
+            total_spent: by_feature.iter().map(|f| f.total_spent).sum(),
+            usage_count: by_feature.iter().map(|f| f.usage_count).sum(),
+            by_feature,
+            history,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::building::BuildingType;
+    use crate::models::troop::TroopQueue;
+    use crate::test_support;
+
+    #[test]
+    fn package_currencies_map_to_stripe_currencies() {
+        assert_eq!(ShopService::parse_currency("eur"), Some(Currency::EUR));
+        assert_eq!(ShopService::parse_currency("GBP"), Some(Currency::GBP));
+        assert_eq!(ShopService::parse_currency(" thb "), Some(Currency::THB));
+        assert_eq!(ShopService::parse_currency("usd"), Some(Currency::USD));
+        assert_eq!(ShopService::parse_currency("gold"), None);
+        assert_eq!(ShopService::parse_currency(""), None);
+    }
+
+    #[sqlx::test]
+    async fn instant_train_cannot_spend_escrowed_resources(pool: PgPool) {
+        let user_id = test_support::create_user(&pool, 100).await;
+        let village = test_support::create_village(&pool, user_id, 0, 0).await;
+        test_support::add_building(&pool, village.id, BuildingType::Barracks, 19, 1).await;
+
+        // An open sell order holds 300 of the village's 500 wood
+        sqlx::query(
+            "INSERT INTO resource_locks (village_id, lock_type, reference_id, wood) VALUES ($1, 'trade_order', $2, 300)",
+        )
+        .bind(village.id)
+        .bind(Uuid::new_v4())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // Two infantry need 240 wood, more than the 200 left unlocked
+        let result = ShopService::use_instant_train(&pool, user_id, village.id, TroopType::Infantry, 2, true).await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+        assert_eq!(test_support::gold_balance(&pool, user_id).await, 100);
+        assert!(TroopRepository::find_by_village(&pool, village.id).await.unwrap().is_empty());
+
+        // One infantry (120 wood, 1200s of training) fits
+        let response = ShopService::use_instant_train(&pool, user_id, village.id, TroopType::Infantry, 1, true)
+            .await
+            .unwrap();
+        assert_eq!(response.gold_spent, 4);
+        assert_eq!(test_support::gold_balance(&pool, user_id).await, 96);
+
+        let troops = TroopRepository::find_by_village(&pool, village.id).await.unwrap();
+        assert_eq!(troops.len(), 1);
+        assert_eq!(troops[0].in_village, 1);
+        assert!(test_support::village(&pool, village.id).await.wood <= 400);
+    }
+
+    #[sqlx::test]
+    async fn concurrent_spends_cannot_overdraw_gold(pool: PgPool) {
+        // Enough for the 7 day subscription (70) or the Book of Wisdom (15), not both
+        let user_id = test_support::create_user(&pool, 80).await;
+        let village = test_support::create_village(&pool, user_id, 0, 0).await;
+
+        let (subscription, book) = tokio::join!(
+            ShopService::buy_subscription(&pool, user_id, 7),
+            ShopService::use_book_of_wisdom(&pool, user_id, village.id),
+        );
+        assert_eq!(subscription.is_ok() as u8 + book.is_ok() as u8, 1);
+
+        // Only the winner's effects were kept
+        let has_subscription = ShopRepository::get_active_subscription(&pool, user_id, SubscriptionType::TravianPlus)
+            .await
+            .unwrap()
+            .is_some();
+        let has_book = ShopRepository::has_active_book_of_wisdom(&pool, user_id, village.id).await.unwrap();
+        assert_eq!(has_subscription, subscription.is_ok());
+        assert_eq!(has_book, book.is_ok());
+
+        let spent = if has_subscription { 70 } else { 15 };
+        assert_eq!(test_support::gold_balance(&pool, user_id).await, 80 - spent);
+    }
+
+    #[sqlx::test]
+    async fn buying_plus_again_extends_the_subscription(pool: PgPool) {
+        let user_id = test_support::create_user(&pool, 200).await;
+
+        let active = |pool: PgPool| async move {
+            ShopRepository::get_active_subscription(&pool, user_id, SubscriptionType::TravianPlus)
+                .await
+                .unwrap()
+                .expect("active subscription")
+        };
+
+        ShopService::buy_subscription(&pool, user_id, 7).await.unwrap();
+        let first = active(pool.clone()).await;
+        ShopService::buy_subscription(&pool, user_id, 7).await.unwrap();
+        let second = active(pool.clone()).await;
+
+        assert_eq!(second.id, first.id);
+        assert_eq!(second.expires_at, first.expires_at + Duration::days(7));
+        assert_eq!(test_support::gold_balance(&pool, user_id).await, 200 - 2 * 70);
+    }
+
+    /// `builds` (up to two) builds with 3000s left, 10 gold each
+    async fn village_with_builds(pool: &PgPool, user_id: Uuid, x: i32, builds: usize) -> Uuid {
+        let village = test_support::create_village(pool, user_id, x, 0).await;
+        for (building_type, slot) in [(BuildingType::Warehouse, 20), (BuildingType::Granary, 21)].into_iter().take(builds) {
+            let id = test_support::add_building(pool, village.id, building_type, slot, 1).await;
+            sqlx::query(
+                "UPDATE buildings SET is_upgrading = TRUE, upgrade_started_at = NOW(), upgrade_ends_at = NOW() + INTERVAL '3000 seconds' WHERE id = $1",
+            )
+            .bind(id)
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+
+        village.id
+    }
+
+    #[sqlx::test]
+    async fn finish_all_is_discounted_for_plus_subscribers(pool: PgPool) {
+        let regular = test_support::create_user(&pool, 100).await;
+        let village_id = village_with_builds(&pool, regular, 0, 2).await;
+        let response = ShopService::use_finish_all(&pool, regular, village_id).await.unwrap();
+        assert_eq!(response.gold_spent, 20);
+
+        let subscriber = test_support::create_user(&pool, 100).await;
+        ShopService::buy_subscription(&pool, subscriber, 7).await.unwrap();
+        let village_id = village_with_builds(&pool, subscriber, 10, 2).await;
+        let response = ShopService::use_finish_all(&pool, subscriber, village_id).await.unwrap();
+        assert_eq!(response.gold_spent, 16);
+        assert_eq!(test_support::gold_balance(&pool, subscriber).await, 100 - 70 - 16);
+    }
+
+    #[sqlx::test]
+    async fn finishing_a_single_build_costs_the_same_either_way(pool: PgPool) {
+        let subscriber = test_support::create_user(&pool, 100).await;
+        ShopService::buy_subscription(&pool, subscriber, 7).await.unwrap();
+
+        let village_id = village_with_builds(&pool, subscriber, 0, 1).await;
+        let finish_all = ShopService::use_finish_all(&pool, subscriber, village_id).await.unwrap();
+
+        let village_id = village_with_builds(&pool, subscriber, 10, 1).await;
+        let (building_id,): (Uuid,) = sqlx::query_as("SELECT id FROM buildings WHERE village_id = $1")
+            .bind(village_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let finish_now = ShopService::use_finish_now(&pool, subscriber, "building", building_id).await.unwrap();
+
+        assert_eq!(finish_all.gold_spent, 10);
+        assert_eq!(finish_now.gold_spent, 10);
+    }
+
+    /// Deliver a `checkout.session.completed` event signed the way Stripe does
+    async fn deliver_checkout_completed(pool: &PgPool, event_id: &str, session_id: &str) -> AppResult<()> {
+        let secret = "whsec_test";
+        let payload = serde_json::json!({
+            "id": event_id,
+            "type": "checkout.session.completed",
+            "data": { "object": { "id": session_id, "payment_intent": "pi_test" } },
+        })
+        .to_string();
+
+        let timestamp = Utc::now().timestamp();
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+        let signature = format!("t={},v1={}", timestamp, hex::encode(mac.finalize().into_bytes()));
+
+        ShopService::handle_webhook(pool, &payload, &signature, secret).await
+    }
+
+    #[sqlx::test]
+    async fn retried_webhook_delivery_is_handled_once(pool: PgPool) {
+        let user_id = test_support::create_user(&pool, 0).await;
+
+        // The checkout isn't known yet, so the event fails and is forgotten for Stripe's retry
+        let result = deliver_checkout_completed(&pool, "evt_1", "cs_test").await;
+        assert!(matches!(result, Err(AppError::NotFound(_))));
+        let (recorded,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM stripe_events")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(recorded, 0);
+
+        sqlx::query(
+            "INSERT INTO transactions (user_id, transaction_type, gold_amount, stripe_session_id) VALUES ($1, 'gold_purchase', 100, 'cs_test')",
+        )
+        .bind(user_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        deliver_checkout_completed(&pool, "evt_1", "cs_test").await.unwrap();
+        deliver_checkout_completed(&pool, "evt_1", "cs_test").await.unwrap();
+        assert_eq!(test_support::gold_balance(&pool, user_id).await, 100);
+
+        // The handled event is on record, so any further retry is skipped before dispatch
+        assert!(!ShopRepository::try_record_stripe_event(&pool, "evt_1", "checkout.session.completed")
+            .await
+            .unwrap());
+    }
+
+    /// Queue a batch of `count` infantry that finishes in 20 minutes
+    async fn queue_infantry(pool: &PgPool, village_id: Uuid, count: i32) -> TroopQueue {
+        let mut tx = pool.begin().await.unwrap();
+        let now = Utc::now();
+        let entry = TroopRepository::add_to_queue_tx(
+            &mut tx,
+            village_id,
+            TroopType::Infantry,
+            count,
+            1200 / count,
+            now,
+            now + Duration::seconds(1200),
+        )
+        .await
+        .unwrap();
+        tx.commit().await.unwrap();
+        entry
+    }
+
+    #[sqlx::test]
+    async fn unaffordable_queue_finish_leaves_the_queue_alone(pool: PgPool) {
+        // 20 minutes of training costs 4 gold
+        let user_id = test_support::create_user(&pool, 3).await;
+        let village = test_support::create_village(&pool, user_id, 0, 0).await;
+        queue_infantry(&pool, village.id, 2).await;
+
+        let result = ShopService::use_finish_now_queue(&pool, user_id, village.id).await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+        assert_eq!(TroopRepository::get_queue_by_village(&pool, village.id).await.unwrap().len(), 1);
+        assert!(TroopRepository::find_by_village(&pool, village.id).await.unwrap().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn queue_finish_racing_the_completion_job_adds_troops_once(pool: PgPool) {
+        let user_id = test_support::create_user(&pool, 100).await;
+        let village = test_support::create_village(&pool, user_id, 0, 0).await;
+        let entry = queue_infantry(&pool, village.id, 2).await;
+
+        let (finished, completed) = tokio::join!(
+            ShopService::use_finish_now_queue(&pool, user_id, village.id),
+            TroopRepository::complete_training(&pool, entry.id),
+        );
+        let finished = finished.unwrap();
+        let completed = completed.unwrap();
+
+        // Whichever claimed the batch added its troops; gold is only spent if Finish Now did
+        assert_eq!(finished.batches_finished + completed.is_some() as i32, 1);
+        assert_eq!(finished.gold_spent, if completed.is_some() { 0 } else { 4 });
+        assert_eq!(test_support::gold_balance(&pool, user_id).await, 100 - finished.gold_spent);
+
+        let troops = TroopRepository::find_by_village(&pool, village.id).await.unwrap();
+        assert_eq!(troops.len(), 1);
+        assert_eq!(troops[0].count, 2);
+    }
 }
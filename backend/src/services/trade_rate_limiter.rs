@@ -0,0 +1,104 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+
+/// Once this many users are tracked, idle entries are dropped on the next check
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Sliding-window limit on order creation and cancellation per user.
+/// Keeps bots from churning the order book with create/cancel loops.
+#[derive(Clone)]
+pub struct TradeRateLimiter {
+    max_actions: usize,
+    window: Duration,
+    actions: Arc<Mutex<HashMap<Uuid, VecDeque<Instant>>>>,
+}
+
+impl TradeRateLimiter {
+    pub fn new(max_actions: usize, window: Duration) -> Self {
+        Self {
+            max_actions,
+            window,
+            actions: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Record an order action for the user, or reject it if the window is full.
+    /// A limit of 0 disables rate limiting.
+    pub async fn check(&self, user_id: Uuid) -> AppResult<()> {
+        if self.max_actions == 0 {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut actions = self.actions.lock().await;
+
+        if actions.len() >= PRUNE_THRESHOLD {
+            actions.retain(|_, times| times.back().is_some_and(|t| now.duration_since(*t) < self.window));
+        }
+
+        let times = actions.entry(user_id).or_default();
+        while times.front().is_some_and(|t| now.duration_since(*t) >= self.window) {
+            times.pop_front();
+        }
+
+        if times.len() >= self.max_actions {
+            let retry_after = times
+                .front()
+                .map(|t| self.window.saturating_sub(now.duration_since(*t)).as_secs().max(1))
+                .unwrap_or(1);
+            return Err(AppError::TooManyRequests(format!(
+                "Too many order actions. Try again in {} seconds",
+                retry_after
+            )));
+        }
+
+        times.push_back(now);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rapid_actions_trip_the_limit() {
+        let limiter = TradeRateLimiter::new(3, Duration::from_secs(60));
+        let user_id = Uuid::new_v4();
+
+        for _ in 0..3 {
+            limiter.check(user_id).await.unwrap();
+        }
+        assert!(matches!(limiter.check(user_id).await, Err(AppError::TooManyRequests(_))));
+
+        // Other users have their own window
+        limiter.check(Uuid::new_v4()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn actions_leave_the_window() {
+        let limiter = TradeRateLimiter::new(1, Duration::from_millis(50));
+        let user_id = Uuid::new_v4();
+
+        limiter.check(user_id).await.unwrap();
+        assert!(limiter.check(user_id).await.is_err());
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        limiter.check(user_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn zero_limit_disables_checks() {
+        let limiter = TradeRateLimiter::new(0, Duration::from_secs(60));
+        let user_id = Uuid::new_v4();
+
+        for _ in 0..100 {
+            limiter.check(user_id).await.unwrap();
+        }
+    }
+}
@@ -1,15 +1,23 @@
-use sqlx::PgPool;
+use sqlx::{Acquire, PgPool};
 use uuid::Uuid;
 
-use crate::error::{AppError, AppResult};
+use crate::config::GameConfig;
+use crate::error::{AppError, AppResult, FieldError};
 use crate::models::trade::{
-    AcceptOrderRequest, AcceptOrderResponse, CancelOrderResponse, CreateOrderRequest,
-    CreateOrderResponse, MarketSummary, TradeOrder, TradeOrderStatus, TradeOrderType,
-    TradeResourceType, Resources,
+    AcceptOrderRequest, AcceptOrderResponse, CancelAllOrdersResponse, CancelOrderFailure, CancelOrderResponse,
+    CreateOrderRequest,
+    CreateOrderResponse, MarketSummary, NewOrder, NewTransaction, OrderBookQuery, OrderBookResponse, OrderExecution, PriceCandle, PriceCandlesQuery, PriceCandlesResponse,
+    ResourceDelivery, TradeOrder, TradeOrderStatus, TradeOrderType,
+    TradeQuoteQuery, TradeQuoteResponse, TradeResourceType, TradeTransaction, Resources, UpdateOrderResponse,
+    WashTradeFilter,
 };
 use crate::models::village::Village;
+use crate::repositories::alliance_repo::AllianceRepository;
+use crate::repositories::shop_repo::ShopRepository;
 use crate::repositories::trade_repo::TradeRepository;
 use crate::repositories::village_repo::VillageRepository;
+use crate::services::market_service::MarketService;
+use crate::services::village_service::VillageService;
 
 // ==================== Constants ====================
 
@@ -25,12 +33,29 @@ pub const MIN_PRICE: i32 = 1;
 /// Maximum price per unit (gold)
 pub const MAX_PRICE: i32 = 10_000;
 
+/// Maximum total value of a single order (gold). Gold balances are 32-bit,
+/// so larger orders could never be paid for or credited in full.
+pub const MAX_ORDER_VALUE: i64 = i32::MAX as i64;
+
 /// Maximum open orders per user
 pub const MAX_OPEN_ORDERS_PER_USER: i64 = 50;
 
 /// Maximum expiry time in hours
 pub const MAX_EXPIRY_HOURS: i32 = 168; // 7 days
 
+/// Candle sizes offered for the price chart (minutes)
+pub const CANDLE_INTERVALS: [i32; 5] = [5, 15, 60, 240, 1440];
+
+/// Most candles returned in one request
+pub const MAX_CANDLES: i32 = 500;
+
+/// Most price levels per side of the order book
+pub const MAX_BOOK_LEVELS: i32 = 100;
+
+/// Market fee taken from the seller's proceeds, in basis points of the trade value.
+/// The fee leaves the economy (a gold sink); 0 disables it.
+pub const MARKET_FEE_BPS: i64 = 100;
+
 /// Lock type for trade orders
 pub const LOCK_TYPE_TRADE_ORDER: &str = "trade_order";
 
@@ -39,54 +64,118 @@ pub struct TradeService;
 impl TradeService {
     // ==================== Validation Functions ====================
 
-    /// Validate create order request
-    pub fn validate_create_order_request(request: &CreateOrderRequest) -> AppResult<()> {
+    /// Validate create order request.
+    /// When the resource has been traded before, the price must also stay within
+    /// `price_band_percent` of the last trade price.
+    /// Every failing field is reported, at most one error per field.
+    /// Merchant capacity depends on the village and is checked when a sell order is placed.
+    pub fn validate_create_order_request(
+        request: &CreateOrderRequest,
+        last_trade_price: Option<i32>,
+        price_band_percent: i32,
+    ) -> AppResult<()> {
+        let mut errors = Vec::new();
+
         // Validate quantity
         if request.quantity < MIN_QUANTITY {
-            return Err(AppError::BadRequest(format!(
-                "Minimum quantity is {}",
-                MIN_QUANTITY
-            )));
-        }
-        if request.quantity > MAX_QUANTITY {
-            return Err(AppError::BadRequest(format!(
-                "Maximum quantity is {}",
-                MAX_QUANTITY
-            )));
+            errors.push(FieldError::new(
+                "quantity",
+                "too_small",
+                format!("Minimum quantity is {}", MIN_QUANTITY),
+            ));
+        } else if request.quantity > MAX_QUANTITY {
+            errors.push(FieldError::new(
+                "quantity",
+                "too_large",
+                format!("Maximum quantity is {}", MAX_QUANTITY),
+            ));
         }
 
         // Validate price
         if request.price_per_unit < MIN_PRICE {
-            return Err(AppError::BadRequest(format!(
-                "Minimum price is {} gold per unit",
-                MIN_PRICE
-            )));
+            errors.push(FieldError::new(
+                "price_per_unit",
+                "too_small",
+                format!("Minimum price is {} gold per unit", MIN_PRICE),
+            ));
+        } else if request.price_per_unit > MAX_PRICE {
+            errors.push(FieldError::new(
+                "price_per_unit",
+                "too_large",
+                format!("Maximum price is {} gold per unit", MAX_PRICE),
+            ));
+        } else if let Some(last_price) = last_trade_price {
+            if let Some((min, max)) = Self::price_band(last_price, price_band_percent) {
+                if request.price_per_unit < min || request.price_per_unit > max {
+                    errors.push(FieldError::new(
+                        "price_per_unit",
+                        "outside_price_band",
+                        format!(
+                            "Price must be between {} and {} gold per unit (within {}% of the last trade price of {})",
+                            min, max, price_band_percent, last_price
+                        ),
+                    ));
+                }
+            }
         }
-        if request.price_per_unit > MAX_PRICE {
-            return Err(AppError::BadRequest(format!(
-                "Maximum price is {} gold per unit",
-                MAX_PRICE
-            )));
+
+        // Validate total order value
+        if errors.is_empty() && request.total_cost() > MAX_ORDER_VALUE {
+            errors.push(FieldError::new(
+                "quantity",
+                "order_value_too_large",
+                format!("Total order value cannot exceed {} gold", MAX_ORDER_VALUE),
+            ));
         }
 
         // Validate expiry
         if let Some(hours) = request.expires_in_hours {
             if hours < 1 {
-                return Err(AppError::BadRequest(
-                    "Expiry time must be at least 1 hour".into(),
+                errors.push(FieldError::new(
+                    "expires_in_hours",
+                    "too_small",
+                    "Expiry time must be at least 1 hour",
+                ));
+            } else if hours > MAX_EXPIRY_HOURS {
+                errors.push(FieldError::new(
+                    "expires_in_hours",
+                    "too_large",
+                    format!("Maximum expiry time is {} hours", MAX_EXPIRY_HOURS),
                 ));
             }
-            if hours > MAX_EXPIRY_HOURS {
-                return Err(AppError::BadRequest(format!(
-                    "Maximum expiry time is {} hours",
-                    MAX_EXPIRY_HOURS
-                )));
-            }
+        }
+
+        if !errors.is_empty() {
+            return Err(AppError::ValidationError(errors));
         }
 
         Ok(())
     }
 
+    /// Expiry to apply to a new order: the requested one, or the server default
+    /// (kept within 1..=MAX_EXPIRY_HOURS) when none was given
+    pub fn effective_expiry_hours(requested: Option<i32>, default_hours: i32) -> i32 {
+        requested.unwrap_or_else(|| default_hours.clamp(1, MAX_EXPIRY_HOURS))
+    }
+
+    /// Allowed price range around the last trade price, clamped to the static limits.
+    /// Returns None when the band is disabled.
+    pub fn price_band(last_trade_price: i32, price_band_percent: i32) -> Option<(i32, i32)> {
+        if price_band_percent <= 0 {
+            return None;
+        }
+
+        let last = last_trade_price as i64;
+        let band = price_band_percent as i64;
+        let min = last * (100 - band) / 100;
+        let max = (last * (100 + band) + 99) / 100;
+
+        Some((
+            min.clamp(MIN_PRICE as i64, MAX_PRICE as i64) as i32,
+            max.clamp(MIN_PRICE as i64, MAX_PRICE as i64) as i32,
+        ))
+    }
+
     /// Validate village ownership
     pub fn validate_village_ownership(village: &Village, user_id: Uuid) -> AppResult<()> {
         if village.user_id != user_id {
@@ -144,9 +233,11 @@ impl TradeService {
         Ok(())
     }
 
-    /// Validate buy order - check if user has enough gold
+    /// Validate buy order - check if user has enough gold.
+    /// Locks the user's row until the transaction ends, so concurrent orders can't
+    /// both pass the check against the same balance.
     pub async fn validate_buy_order_gold(
-        pool: &PgPool,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
         user_id: Uuid,
         quantity: i32,
         price_per_unit: i32,
@@ -154,23 +245,124 @@ impl TradeService {
         let total_cost = (quantity as i64) * (price_per_unit as i64);
 
         // Get user's gold balance
-        let balance: (i32,) = sqlx::query_as(
-            r#"SELECT gold_balance FROM users WHERE id = $1"#,
-        )
-        .bind(user_id)
-        .fetch_one(pool)
-        .await?;
+        let balance = ShopRepository::lock_gold_balance_tx(tx, user_id).await?;
 
-        if (balance.0 as i64) < total_cost {
+        if (balance as i64) < total_cost {
             return Err(AppError::BadRequest(format!(
                 "Insufficient gold. Available: {}, Required: {}",
-                balance.0, total_cost
+                balance, total_cost
             )));
         }
 
         Ok(())
     }
 
+    /// Alliance-only orders are restricted to members of the order owner's alliance
+    pub async fn validate_alliance_access(pool: &PgPool, order: &TradeOrder, user_id: Uuid) -> AppResult<()> {
+        if !order.alliance_only {
+            return Ok(());
+        }
+
+        let owner_alliance = AllianceRepository::get_user_alliance(pool, order.user_id).await?;
+        let user_alliance = AllianceRepository::get_user_alliance(pool, user_id).await?;
+
+        match (owner_alliance, user_alliance) {
+            (Some(owner), Some(member)) if owner.alliance_id == member.alliance_id => Ok(()),
+            _ => Err(AppError::Forbidden(
+                "This order is only available to alliance members".into(),
+            )),
+        }
+    }
+
+    /// Validate new terms for an open order.
+    /// The quantity must stay above what has already been filled, and a changed price
+    /// must respect the same limits and price band as a new order.
+    pub fn validate_update_order(
+        order: &TradeOrder,
+        user_id: Uuid,
+        quantity: i32,
+        price_per_unit: i32,
+        last_trade_price: Option<i32>,
+        price_band_percent: i32,
+    ) -> AppResult<()> {
+        if order.user_id != user_id {
+            return Err(AppError::Forbidden("You do not own this order".into()));
+        }
+
+        if !order.can_cancel() {
+            return Err(AppError::BadRequest(format!(
+                "Cannot update order with status: {:?}",
+                order.status
+            )));
+        }
+
+        if order.is_expired() {
+            return Err(AppError::BadRequest("This order has expired".into()));
+        }
+
+        let mut errors = Vec::new();
+
+        if quantity - order.quantity_filled < MIN_QUANTITY {
+            errors.push(FieldError::new(
+                "quantity",
+                "too_small",
+                format!(
+                    "Quantity must be at least {} above the {} already filled",
+                    MIN_QUANTITY, order.quantity_filled
+                ),
+            ));
+        } else if quantity > MAX_QUANTITY {
+            errors.push(FieldError::new(
+                "quantity",
+                "too_large",
+                format!("Maximum quantity is {}", MAX_QUANTITY),
+            ));
+        }
+
+        if price_per_unit != order.price_per_unit {
+            if price_per_unit < MIN_PRICE {
+                errors.push(FieldError::new(
+                    "price_per_unit",
+                    "too_small",
+                    format!("Minimum price is {} gold per unit", MIN_PRICE),
+                ));
+            } else if price_per_unit > MAX_PRICE {
+                errors.push(FieldError::new(
+                    "price_per_unit",
+                    "too_large",
+                    format!("Maximum price is {} gold per unit", MAX_PRICE),
+                ));
+            } else if let Some((min, max)) =
+                last_trade_price.and_then(|last| Self::price_band(last, price_band_percent))
+            {
+                if price_per_unit < min || price_per_unit > max {
+                    errors.push(FieldError::new(
+                        "price_per_unit",
+                        "outside_price_band",
+                        format!(
+                            "Price must be between {} and {} gold per unit (within {}% of the last trade price)",
+                            min, max, price_band_percent
+                        ),
+                    ));
+                }
+            }
+        }
+
+        if errors.is_empty() && (quantity as i64) * (price_per_unit as i64) > MAX_ORDER_VALUE {
+            errors.push(FieldError::new(
+                "quantity",
+                "order_value_too_large",
+                format!("Total order value cannot exceed {} gold", MAX_ORDER_VALUE),
+            ));
+        }
+
+        if !errors.is_empty() {
+            return Err(AppError::ValidationError(errors));
+        }
+
+        Ok(())
+    }
+
     /// Validate accept order request
     pub fn validate_accept_order(
         order: &TradeOrder,
@@ -247,14 +439,25 @@ impl TradeService {
 
     // ==================== Create Order Functions ====================
 
-    /// Create a new trade order (buy or sell)
+    /// Create a new trade order (buy or sell).
+    /// `client_ip` is stored with the order as a wash trade signal.
     pub async fn create_order(
         pool: &PgPool,
         user_id: Uuid,
-        request: CreateOrderRequest,
+        mut request: CreateOrderRequest,
+        game: &GameConfig,
+        client_ip: Option<&str>,
     ) -> AppResult<CreateOrderResponse> {
-        // Validate request parameters
-        Self::validate_create_order_request(&request)?;
+        // Validate request parameters (price band falls back to static limits without history)
+        let last_trade_price =
+            TradeRepository::get_last_trade_price(pool, request.resource_type, &game.wash_trade_filter()).await?;
+        Self::validate_create_order_request(&request, last_trade_price, game.trade_price_band_percent)?;
+
+        // Every order expires eventually so abandoned ones get refunded
+        request.expires_in_hours = Some(Self::effective_expiry_hours(
+            request.expires_in_hours,
+            game.trade_default_expiry_hours,
+        ));
 
         // Check order limit
         Self::check_order_limit(pool, user_id).await?;
@@ -266,19 +469,36 @@ impl TradeService {
 
         Self::validate_village_ownership(&village, user_id)?;
 
+        if request.alliance_only && AllianceRepository::get_user_alliance(pool, user_id).await?.is_none() {
+            return Err(AppError::BadRequest(
+                "You must be in an alliance to create an alliance-only order".into(),
+            ));
+        }
+
+        if game.starvation_guard && request.order_type == TradeOrderType::Sell {
+            VillageService::ensure_not_starving(&village)?;
+        }
+
+        if request.execution.is_immediate() {
+            return Self::execute_immediate_order(pool, user_id, &village, request, client_ip).await;
+        }
+
         // Route to appropriate handler based on order type
         match request.order_type {
-            TradeOrderType::Sell => Self::create_sell_order(pool, user_id, &village, request).await,
-            TradeOrderType::Buy => Self::create_buy_order(pool, user_id, &village, request).await,
+            TradeOrderType::Sell => Self::create_sell_order(pool, user_id, &village, request, client_ip).await,
+            TradeOrderType::Buy => Self::create_buy_order(pool, user_id, &village, request, client_ip).await,
         }
     }
 
-    /// Create a sell order (selling resources for gold)
+    /// Create a sell order (selling resources for gold).
+    /// It first fills against crossing buy orders; only the remainder rests on the
+    /// book with its resources locked.
     async fn create_sell_order(
         pool: &PgPool,
         user_id: Uuid,
         village: &Village,
         request: CreateOrderRequest,
+        client_ip: Option<&str>,
     ) -> AppResult<CreateOrderResponse> {
         // Validate resources available
         Self::validate_sell_order_resources(
@@ -288,173 +508,781 @@ impl TradeService {
             request.quantity,
         )
         .await?;
+        MarketService::validate_merchant_capacity(pool, village.id, request.quantity).await?;
 
         // Start transaction
         let mut tx = pool.begin().await?;
 
         // Create the order
-        let order = TradeRepository::create_order(
-            pool,
-            user_id,
-            request.village_id,
-            TradeOrderType::Sell,
-            request.resource_type,
-            request.quantity,
-            request.price_per_unit,
-            request.expires_in_hours,
+        let order = TradeRepository::create_order_tx(
+            &mut tx,
+            NewOrder {
+                user_id,
+                village_id: village.id,
+                order_type: TradeOrderType::Sell,
+                resource_type: request.resource_type,
+                quantity: request.quantity,
+                price_per_unit: request.price_per_unit,
+                expires_in_hours: request.expires_in_hours,
+                alliance_only: request.alliance_only,
+                client_ip,
+                is_taker_fill: false,
+            },
         )
         .await?;
 
-        // Lock the resources
-        let (wood, clay, iron, crop) = match request.resource_type {
-            TradeResourceType::Wood => (request.quantity, 0, 0, 0),
-            TradeResourceType::Clay => (0, request.quantity, 0, 0),
-            TradeResourceType::Iron => (0, 0, request.quantity, 0),
-            TradeResourceType::Crop => (0, 0, 0, request.quantity),
+        // Sell into crossing buy orders before anything is locked, so the
+        // fills' resource check isn't blocked by this order's own lock
+        let book = Self::lock_crossing_orders_tx(pool, &mut tx, user_id, &request).await?;
+        let planned = Self::match_book(&book, request.quantity);
+        let (order, fills) = Self::settle_fills_tx(&mut tx, order, village, planned, client_ip).await?;
+
+        // Lock the resources for whatever rests on the book
+        let resting = Self::resting_quantity(&order);
+        let locked_resources = if resting > 0 {
+            let lock = Self::single_resource(request.resource_type, resting);
+            TradeRepository::create_resource_lock_tx(
+                &mut tx,
+                village.id,
+                LOCK_TYPE_TRADE_ORDER,
+                order.id,
+                lock.wood,
+                lock.clay,
+                lock.iron,
+                lock.crop,
+            )
+            .await?;
+            Some(lock)
+        } else {
+            None
         };
 
-        TradeRepository::create_resource_lock_tx(
-            &mut tx,
-            village.id,
-            LOCK_TYPE_TRADE_ORDER,
-            order.id,
-            wood,
-            clay,
-            iron,
-            crop,
-        )
-        .await?;
-
         // Commit transaction
         tx.commit().await?;
 
-        let locked_resources = Self::single_resource(request.resource_type, request.quantity);
-
         Ok(CreateOrderResponse {
             order,
-            locked_resources: Some(locked_resources),
+            locked_resources,
             locked_gold: None,
+            fills,
         })
     }
 
-    /// Create a buy order (buying resources with gold)
+    /// Create a buy order (buying resources with gold).
+    /// It first fills against crossing sell orders at their prices; only the
+    /// remainder rests on the book with its gold escrowed at the limit price.
     async fn create_buy_order(
         pool: &PgPool,
         user_id: Uuid,
         village: &Village,
         request: CreateOrderRequest,
+        client_ip: Option<&str>,
     ) -> AppResult<CreateOrderResponse> {
-        let total_cost = (request.quantity as i64) * (request.price_per_unit as i64);
+        // Start transaction
+        let mut tx = pool.begin().await?;
 
-        // Validate gold balance
+        // Validate gold balance (holding the balance until commit)
         Self::validate_buy_order_gold(
-            pool,
+            &mut tx,
             user_id,
             request.quantity,
             request.price_per_unit,
         )
         .await?;
 
-        // Start transaction
+        // Create the order
+        let order = TradeRepository::create_order_tx(
+            &mut tx,
+            NewOrder {
+                user_id,
+                village_id: village.id,
+                order_type: TradeOrderType::Buy,
+                resource_type: request.resource_type,
+                quantity: request.quantity,
+                price_per_unit: request.price_per_unit,
+                expires_in_hours: request.expires_in_hours,
+                alliance_only: request.alliance_only,
+                client_ip,
+                is_taker_fill: false,
+            },
+        )
+        .await?;
+
+        // Buy from crossing sell orders; each fill is paid at the sell order's price
+        let book = Self::lock_crossing_orders_tx(pool, &mut tx, user_id, &request).await?;
+        let planned = Self::match_book(&book, request.quantity);
+        let (order, fills) = Self::settle_fills_tx(&mut tx, order, village, planned, client_ip).await?;
+
+        // Deduct gold from user (lock it) for whatever rests on the book
+        let resting = Self::resting_quantity(&order);
+        let locked_gold = (resting as i64) * (request.price_per_unit as i64);
+        if locked_gold > 0 {
+            let deduct_result = sqlx::query(
+                r#"
+                UPDATE users
+                SET gold_balance = gold_balance - $2
+                WHERE id = $1 AND gold_balance >= $2
+                "#,
+            )
+            .bind(user_id)
+            .bind(locked_gold as i32)
+            .execute(&mut *tx)
+            .await?;
+
+            if deduct_result.rows_affected() == 0 {
+                return Err(AppError::BadRequest(
+                    "Insufficient gold balance".into(),
+                ));
+            }
+        }
+
+        // Commit transaction
+        tx.commit().await?;
+
+        Ok(CreateOrderResponse {
+            order,
+            locked_resources: None,
+            locked_gold: (locked_gold > 0).then_some(locked_gold as i32),
+            fills,
+        })
+    }
+
+    /// Execute an immediate-or-cancel or fill-or-kill order against the book.
+    /// The order never rests: each fill settles exactly as if the owner had accepted
+    /// the crossing order, and nothing is escrowed for the part that doesn't fill,
+    /// so a cancelled remainder or a killed order has nothing left to release.
+    async fn execute_immediate_order(
+        pool: &PgPool,
+        user_id: Uuid,
+        village: &Village,
+        request: CreateOrderRequest,
+        client_ip: Option<&str>,
+    ) -> AppResult<CreateOrderResponse> {
+        // Make sure the owner could cover the whole order before touching the book
+        if request.order_type == TradeOrderType::Sell {
+            Self::validate_sell_order_resources(pool, village, request.resource_type, request.quantity).await?;
+            MarketService::validate_merchant_capacity(pool, village.id, request.quantity).await?;
+        }
+
         let mut tx = pool.begin().await?;
 
-        // Deduct gold from user (lock it)
-        let deduct_result = sqlx::query(
-            r#"
-            UPDATE users
-            SET gold_balance = gold_balance - $2
-            WHERE id = $1 AND gold_balance >= $2
-            "#,
+        if request.order_type == TradeOrderType::Buy {
+            Self::validate_buy_order_gold(&mut tx, user_id, request.quantity, request.price_per_unit).await?;
+        }
+
+        let book = Self::lock_crossing_orders_tx(pool, &mut tx, user_id, &request).await?;
+
+        let planned = Self::match_book(&book, request.quantity);
+        let matchable: i32 = planned.iter().map(|(_, quantity)| quantity).sum();
+        if request.execution == OrderExecution::FillOrKill && matchable < request.quantity {
+            return Err(AppError::BadRequest(format!(
+                "Fill-or-kill order cannot be filled: only {} of {} units available at this price",
+                matchable, request.quantity
+            )));
+        }
+
+        let order = TradeRepository::create_order_tx(
+            &mut tx,
+            NewOrder {
+                user_id,
+                village_id: village.id,
+                order_type: request.order_type,
+                resource_type: request.resource_type,
+                quantity: request.quantity,
+                price_per_unit: request.price_per_unit,
+                expires_in_hours: None,
+                alliance_only: request.alliance_only,
+                client_ip,
+                is_taker_fill: false,
+            },
         )
-        .bind(user_id)
-        .bind(total_cost as i32)
-        .execute(&mut *tx)
         .await?;
 
-        if deduct_result.rows_affected() == 0 {
-            return Err(AppError::BadRequest(
-                "Insufficient gold balance".into(),
-            ));
+        let (mut order, fills) = Self::settle_fills_tx(&mut tx, order, village, planned, client_ip).await?;
+
+        // Whatever didn't match is cancelled rather than left on the book
+        if order.status != TradeOrderStatus::Filled {
+            order = TradeRepository::update_order_status_tx(&mut tx, order.id, TradeOrderStatus::Cancelled)
+                .await?;
         }
 
-        // Create the order
-        let order = sqlx::query_as::<_, TradeOrder>(
-            r#"
-            INSERT INTO trade_orders (
-                user_id, village_id, order_type, resource_type,
-                quantity, price_per_unit, expires_at
-            )
-            VALUES ($1, $2, $3, $4, $5, $6,
-                CASE WHEN $7::INT IS NOT NULL
-                    THEN NOW() + ($7 || ' hours')::INTERVAL
-                    ELSE NULL
-                END
-            )
-            RETURNING *
-            "#,
+        tx.commit().await?;
+
+        Ok(CreateOrderResponse {
+            order,
+            locked_resources: None,
+            locked_gold: None,
+            fills,
+        })
+    }
+
+    // ==================== Matching ====================
+
+    /// Lock the resting orders an incoming order would cross, best price first
+    /// and oldest first within a price. The user's own orders are never matched.
+    /// An alliance-only incoming order only trades with its own alliance.
+    async fn lock_crossing_orders_tx(
+        pool: &PgPool,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        user_id: Uuid,
+        request: &CreateOrderRequest,
+    ) -> AppResult<Vec<TradeOrder>> {
+        let viewer_alliance_id = AllianceRepository::get_user_alliance(pool, user_id)
+            .await?
+            .map(|member| member.alliance_id);
+        let counter_type = match request.order_type {
+            TradeOrderType::Sell => TradeOrderType::Buy,
+            TradeOrderType::Buy => TradeOrderType::Sell,
+        };
+
+        let mut book = TradeRepository::get_crossing_orders_for_update(
+            tx,
+            request.resource_type,
+            counter_type,
+            request.price_per_unit,
+            user_id,
+            viewer_alliance_id,
         )
-        .bind(user_id)
-        .bind(village.id)
-        .bind(TradeOrderType::Buy)
-        .bind(request.resource_type)
-        .bind(request.quantity)
-        .bind(request.price_per_unit)
-        .bind(request.expires_in_hours)
-        .fetch_one(&mut *tx)
         .await?;
 
+        if request.alliance_only {
+            let members = match viewer_alliance_id {
+                Some(alliance_id) => AllianceRepository::list_members(pool, alliance_id).await?,
+                None => Vec::new(),
+            };
+            book.retain(|counter| members.iter().any(|m| m.user_id == counter.user_id));
+        }
+
+        Ok(book)
+    }
+
+    /// Settle `planned` fills of a just-created taker `order` against resting orders,
+    /// each at the resting order's price, then record the taker's filled quantity.
+    /// Returns the updated order and one transaction per counterparty.
+    async fn settle_fills_tx(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        order: TradeOrder,
+        village: &Village,
+        planned: Vec<(&TradeOrder, i32)>,
+        client_ip: Option<&str>,
+    ) -> AppResult<(TradeOrder, Vec<TradeTransaction>)> {
+        if planned.is_empty() {
+            return Ok((order, Vec::new()));
+        }
+
+        let mut quantity_filled = 0;
+        let mut fills = Vec::new();
+
+        for (counter, quantity) in planned {
+            let gold_amount = (quantity as i64) * (counter.price_per_unit as i64);
+            let (_, _, transaction) = match counter.order_type {
+                TradeOrderType::Sell => {
+                    Self::process_accept_sell_order(
+                        tx,
+                        counter,
+                        order.user_id,
+                        village,
+                        quantity,
+                        gold_amount,
+                        order.id,
+                    )
+                    .await?
+                }
+                TradeOrderType::Buy => {
+                    Self::process_accept_buy_order(
+                        tx,
+                        counter,
+                        order.user_id,
+                        village,
+                        quantity,
+                        gold_amount,
+                        order.id,
+                    )
+                    .await?
+                }
+            };
+
+            Self::apply_fill_tx(tx, counter, quantity).await?;
+            Self::record_trade_ips_tx(tx, &transaction, counter, client_ip).await?;
+            MarketService::schedule_delivery_tx(tx, &transaction).await?;
+
+            quantity_filled += quantity;
+            fills.push(transaction);
+        }
+
+        let status = Self::calculate_order_status(order.quantity, quantity_filled);
+        let order = TradeRepository::update_order_filled_tx(tx, order.id, quantity_filled, status).await?;
+
+        Ok((order, fills))
+    }
+
+    /// Quantity of a freshly matched order left to rest on the book.
+    /// A remainder below `MIN_QUANTITY` closes the order as filled and never rests.
+    fn resting_quantity(order: &TradeOrder) -> i32 {
+        if order.status == TradeOrderStatus::Filled {
+            0
+        } else {
+            order.quantity_remaining()
+        }
+    }
+
+    /// Walk `book` (best price first) and split `quantity` across it.
+    /// Returns each resting order that would trade with the amount it would fill;
+    /// anything beyond the book's depth is left unfilled.
+    fn match_book(book: &[TradeOrder], quantity: i32) -> Vec<(&TradeOrder, i32)> {
+        let mut remaining = quantity;
+        let mut fills = Vec::new();
+
+        for counter in book {
+            if remaining <= 0 {
+                break;
+            }
+            let fill = remaining.min(counter.quantity_remaining());
+            if fill > 0 {
+                fills.push((counter, fill));
+                remaining -= fill;
+            }
+        }
+
+        fills
+    }
+
+    /// OHLC price history of a resource, skipping likely wash trades
+    pub async fn get_price_candles(
+        pool: &PgPool,
+        query: PriceCandlesQuery,
+        filter: &WashTradeFilter,
+    ) -> AppResult<PriceCandlesResponse> {
+        let interval_minutes = query.interval_minutes.unwrap_or(60);
+        if !CANDLE_INTERVALS.contains(&interval_minutes) {
+            return Err(AppError::BadRequest(format!(
+                "Interval must be one of {:?} minutes",
+                CANDLE_INTERVALS
+            )));
+        }
+        let limit = query.limit.unwrap_or(100).clamp(1, MAX_CANDLES);
+
+        let buckets =
+            TradeRepository::get_price_candles(pool, query.resource, interval_minutes, limit, filter).await?;
+
+        // Empty buckets carry the last close forward, starting from the last trade before the window
+        let mut last_close = match buckets.first() {
+            Some(first) => {
+                TradeRepository::get_last_trade_price_before(pool, query.resource, first.bucket_start, filter)
+                    .await?
+            }
+            None => None,
+        };
+
+        let mut candles = Vec::with_capacity(buckets.len());
+        for bucket in buckets {
+            let candle = match (bucket.open, bucket.high, bucket.low, bucket.close) {
+                (Some(open), Some(high), Some(low), Some(close)) => PriceCandle {
+                    bucket_start: bucket.bucket_start,
+                    open,
+                    high,
+                    low,
+                    close,
+                    volume: bucket.volume,
+                },
+                _ => match last_close {
+                    Some(price) => PriceCandle {
+                        bucket_start: bucket.bucket_start,
+                        open: price,
+                        high: price,
+                        low: price,
+                        close: price,
+                        volume: 0,
+                    },
+                    // Nothing has traded yet, so there is no price to show
+                    None => continue,
+                },
+            };
+            last_close = Some(candle.close);
+            candles.push(candle);
+        }
+
+        Ok(PriceCandlesResponse {
+            resource_type: query.resource,
+            interval_minutes,
+            candles,
+        })
+    }
+
+    /// Price a taker order against the current book without executing anything
+    pub async fn quote(
+        pool: &PgPool,
+        user_id: Uuid,
+        query: TradeQuoteQuery,
+    ) -> AppResult<TradeQuoteResponse> {
+        if query.quantity <= 0 {
+            return Err(AppError::BadRequest("Quantity must be positive".into()));
+        }
+
+        let viewer_alliance_id = AllianceRepository::get_user_alliance(pool, user_id)
+            .await?
+            .map(|member| member.alliance_id);
+        let counter_type = match query.side {
+            TradeOrderType::Sell => TradeOrderType::Buy,
+            TradeOrderType::Buy => TradeOrderType::Sell,
+        };
+
+        let book = TradeRepository::get_crossing_orders(
+            pool,
+            query.resource,
+            counter_type,
+            None,
+            user_id,
+            viewer_alliance_id,
+        )
+        .await?;
+
+        let planned = Self::match_book(&book, query.quantity);
+        let quantity_filled: i32 = planned.iter().map(|(_, quantity)| quantity).sum();
+        let total_gold: i64 = planned
+            .iter()
+            .map(|(counter, quantity)| (*quantity as i64) * (counter.price_per_unit as i64))
+            .sum();
+
+        Ok(TradeQuoteResponse {
+            resource_type: query.resource,
+            side: query.side,
+            quantity_requested: query.quantity,
+            quantity_filled,
+            quantity_unfilled: query.quantity - quantity_filled,
+            total_gold,
+            average_price: (quantity_filled > 0).then(|| total_gold as f64 / quantity_filled as f64),
+            best_price: planned.first().map(|(counter, _)| counter.price_per_unit),
+            worst_price: planned.last().map(|(counter, _)| counter.price_per_unit),
+            orders_matched: planned.len() as i32,
+        })
+    }
+
+    // ==================== Cancel Order Function ====================
+
+    /// Cancel a trade order and refund resources/gold.
+    /// Only the unfilled remainder comes back; fills already on the road with
+    /// merchants are final and keep travelling.
+    pub async fn cancel_order(
+        pool: &PgPool,
+        user_id: Uuid,
+        order_id: Uuid,
+    ) -> AppResult<CancelOrderResponse> {
+        // Start transaction
+        let mut tx = pool.begin().await?;
+
+        // Get order with lock so a concurrent fill can't change what's left to refund
+        let order = TradeRepository::get_order_for_update(&mut tx, order_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Order not found".into()))?;
+
+        // Validate cancel request
+        Self::validate_cancel_order(&order, user_id)?;
+
+        let response = Self::close_order_with_refund_tx(&mut tx, &order, TradeOrderStatus::Cancelled).await?;
+
         // Commit transaction
         tx.commit().await?;
 
-        Ok(CreateOrderResponse {
-            order,
-            locked_resources: None,
-            locked_gold: Some(total_cost as i32),
-        })
+        Ok(response)
+    }
+
+    // ==================== Update Order Function ====================
+
+    /// Change the price and/or quantity of an open order in place.
+    /// Sell orders lock more or release some of their resources, buy orders pay in or
+    /// get back the escrow difference. The order keeps its place in the queue unless
+    /// the price changes. A new price may not cross the book; take those orders instead.
+    pub async fn update_order(
+        pool: &PgPool,
+        user_id: Uuid,
+        order_id: Uuid,
+        new_price: Option<i32>,
+        new_quantity: Option<i32>,
+        game: &GameConfig,
+    ) -> AppResult<UpdateOrderResponse> {
+        if new_price.is_none() && new_quantity.is_none() {
+            return Err(AppError::BadRequest("Nothing to update".into()));
+        }
+
+        let mut tx = pool.begin().await?;
+
+        let order = TradeRepository::get_order_for_update(&mut tx, order_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Order not found".into()))?;
+
+        let price_per_unit = new_price.unwrap_or(order.price_per_unit);
+        let quantity = new_quantity.unwrap_or(order.quantity);
+        let price_changed = price_per_unit != order.price_per_unit;
+
+        let last_trade_price =
+            TradeRepository::get_last_trade_price(pool, order.resource_type, &game.wash_trade_filter()).await?;
+        Self::validate_update_order(
+            &order,
+            user_id,
+            quantity,
+            price_per_unit,
+            last_trade_price,
+            game.trade_price_band_percent,
+        )?;
+
+        if price_changed {
+            Self::ensure_not_crossing(pool, &order, price_per_unit).await?;
+        }
+
+        let old_remaining = order.quantity_remaining();
+        let new_remaining = quantity - order.quantity_filled;
+
+        let (locked_resources, locked_gold) = match order.order_type {
+            TradeOrderType::Sell => {
+                let delta = new_remaining - old_remaining;
+                if delta > 0 {
+                    let village = VillageRepository::find_by_id(pool, order.village_id)
+                        .await?
+                        .ok_or_else(|| AppError::NotFound("Village not found".into()))?;
+                    if game.starvation_guard {
+                        VillageService::ensure_not_starving(&village)?;
+                    }
+                    Self::validate_sell_order_resources(pool, &village, order.resource_type, delta).await?;
+                    MarketService::validate_merchant_capacity(pool, village.id, delta).await?;
+
+                    TradeRepository::increase_resource_lock_tx(
+                        &mut tx,
+                        LOCK_TYPE_TRADE_ORDER,
+                        order.id,
+                        &Self::single_resource(order.resource_type, delta),
+                    )
+                    .await?;
+                } else if delta < 0 {
+                    TradeRepository::reduce_resource_lock_tx(
+                        &mut tx,
+                        LOCK_TYPE_TRADE_ORDER,
+                        order.id,
+                        &Self::single_resource(order.resource_type, -delta),
+                    )
+                    .await?;
+                }
+                (Some(Self::single_resource(order.resource_type, new_remaining)), None)
+            }
+            TradeOrderType::Buy => {
+                let old_escrow = (old_remaining as i64) * (order.price_per_unit as i64);
+                let new_escrow = (new_remaining as i64) * (price_per_unit as i64);
+                let delta = new_escrow - old_escrow;
+
+                if delta > 0 {
+                    let result = sqlx::query(
+                        r#"
+                        UPDATE users
+                        SET gold_balance = gold_balance - $2
+                        WHERE id = $1 AND gold_balance >= $2
+                        "#,
+                    )
+                    .bind(user_id)
+                    .bind(delta as i32)
+                    .execute(&mut *tx)
+                    .await?;
+
+                    if result.rows_affected() == 0 {
+                        return Err(AppError::BadRequest(format!(
+                            "Insufficient gold. Required: {} more",
+                            delta
+                        )));
+                    }
+                } else if delta < 0 {
+                    sqlx::query(
+                        r#"
+                        UPDATE users
+                        SET gold_balance = gold_balance + $2
+                        WHERE id = $1
+                        "#,
+                    )
+                    .bind(user_id)
+                    .bind((-delta) as i32)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                (None, Some(new_escrow as i32))
+            }
+        };
+
+        let updated_order =
+            TradeRepository::update_order_terms_tx(&mut tx, order.id, quantity, price_per_unit, price_changed)
+                .await?;
+
+        tx.commit().await?;
+
+        Ok(UpdateOrderResponse {
+            order: updated_order,
+            locked_resources,
+            locked_gold,
+        })
+    }
+
+    /// Reject a new price at which the order would immediately trade against the book
+    async fn ensure_not_crossing(pool: &PgPool, order: &TradeOrder, price_per_unit: i32) -> AppResult<()> {
+        let viewer_alliance_id = AllianceRepository::get_user_alliance(pool, order.user_id)
+            .await?
+            .map(|member| member.alliance_id);
+        let counter_type = match order.order_type {
+            TradeOrderType::Sell => TradeOrderType::Buy,
+            TradeOrderType::Buy => TradeOrderType::Sell,
+        };
+
+        let mut crossing = TradeRepository::get_crossing_orders(
+            pool,
+            order.resource_type,
+            counter_type,
+            Some(price_per_unit),
+            order.user_id,
+            viewer_alliance_id,
+        )
+        .await?;
+
+        if order.alliance_only {
+            let members = match viewer_alliance_id {
+                Some(alliance_id) => AllianceRepository::list_members(pool, alliance_id).await?,
+                None => Vec::new(),
+            };
+            crossing.retain(|counter| members.iter().any(|m| m.user_id == counter.user_id));
+        }
+
+        if let Some(best) = crossing.first() {
+            return Err(AppError::BadRequest(format!(
+                "A price of {} would trade against an existing order at {}; accept that order instead",
+                price_per_unit, best.price_per_unit
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Cancel all of a user's open orders, optionally only for one resource.
+    /// Runs in one transaction with a savepoint per order, so an order that fails to
+    /// cancel is reported and left open while the others still go through.
+    pub async fn cancel_all_orders(
+        pool: &PgPool,
+        user_id: Uuid,
+        resource_type: Option<TradeResourceType>,
+    ) -> AppResult<CancelAllOrdersResponse> {
+        let orders: Vec<TradeOrder> = TradeRepository::get_user_orders(pool, user_id, None)
+            .await?
+            .into_iter()
+            .filter(|o| o.can_cancel() && resource_type.is_none_or(|r| o.resource_type == r))
+            .collect();
+
+        let mut response = CancelAllOrdersResponse {
+            cancelled: Vec::new(),
+            refunded_resources: Resources::default(),
+            refunded_gold: 0,
+            failed: Vec::new(),
+        };
+
+        let mut tx = pool.begin().await?;
+
+        for order in orders {
+            let mut savepoint = (&mut *tx).begin().await?;
+
+            match Self::cancel_locked_order_tx(&mut savepoint, order.id, user_id).await {
+                Ok(cancelled) => {
+                    savepoint.commit().await?;
+                    if let Some(resources) = &cancelled.refunded_resources {
+                        let resource = cancelled.order.resource_type;
+                        response.refunded_resources.add(resource, resources.get(resource));
+                    }
+                    response.refunded_gold += cancelled.refunded_gold.unwrap_or(0) as i64;
+                    response.cancelled.push(cancelled.order);
+                }
+                Err(e) => {
+                    savepoint.rollback().await?;
+                    tracing::warn!("Bulk cancel of order {} failed: {}", order.id, e);
+                    response.failed.push(CancelOrderFailure {
+                        order_id: order.id,
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(response)
     }
 
-    // ==================== Cancel Order Function ====================
+    /// Lock, validate and cancel one of the user's orders
+    async fn cancel_locked_order_tx(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        order_id: Uuid,
+        user_id: Uuid,
+    ) -> AppResult<CancelOrderResponse> {
+        // Re-read under lock; the order may have been filled since it was listed
+        let order = TradeRepository::get_order_for_update(tx, order_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Order not found".into()))?;
 
-    /// Cancel a trade order and refund resources/gold
-    pub async fn cancel_order(
+        Self::validate_cancel_order(&order, user_id)?;
+
+        Self::close_order_with_refund_tx(tx, &order, TradeOrderStatus::Cancelled).await
+    }
+
+    /// Close any open order as cancelled or expired, regardless of owner (admin use).
+    /// Refunds go to the order owner exactly as a normal cancel would.
+    pub async fn force_close_order(
         pool: &PgPool,
-        user_id: Uuid,
         order_id: Uuid,
+        status: TradeOrderStatus,
     ) -> AppResult<CancelOrderResponse> {
-        // Get the order
-        let order = TradeRepository::get_order_by_id(pool, order_id)
+        if !matches!(status, TradeOrderStatus::Cancelled | TradeOrderStatus::Expired) {
+            return Err(AppError::BadRequest("Orders can only be force-cancelled or expired".into()));
+        }
+
+        let mut tx = pool.begin().await?;
+
+        let order = TradeRepository::get_order_for_update(&mut tx, order_id)
             .await?
             .ok_or_else(|| AppError::NotFound("Order not found".into()))?;
 
-        // Validate cancel request
-        Self::validate_cancel_order(&order, user_id)?;
+        if !order.can_cancel() {
+            return Err(AppError::BadRequest(format!(
+                "Cannot close order with status: {:?}",
+                order.status
+            )));
+        }
+
+        let response = Self::close_order_with_refund_tx(&mut tx, &order, status).await?;
+
+        tx.commit().await?;
 
+        Ok(response)
+    }
+
+    /// Set the closing status of a locked order and refund its unfilled remainder
+    pub async fn close_order_with_refund_tx(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        order: &TradeOrder,
+        status: TradeOrderStatus,
+    ) -> AppResult<CancelOrderResponse> {
         // Calculate refund amount (only unfilled portion)
         let remaining_quantity = order.quantity_remaining();
 
-        // Start transaction
-        let mut tx = pool.begin().await?;
-
-        // Update order status to cancelled
-        let updated_order = TradeRepository::update_order_status_tx(
-            &mut tx,
-            order_id,
-            TradeOrderStatus::Cancelled,
-        )
-        .await?;
+        let updated_order = TradeRepository::update_order_status_tx(tx, order.id, status).await?;
 
         // Process refund based on order type
         let (refunded_resources, refunded_gold) = match order.order_type {
             TradeOrderType::Sell => {
                 // Release resource lock
                 let lock = TradeRepository::release_resource_lock_tx(
-                    &mut tx,
+                    tx,
                     LOCK_TYPE_TRADE_ORDER,
-                    order_id,
+                    order.id,
                 )
                 .await?;
 
-                let resources = lock.map(|l| l.to_resources());
+                // Only the unfilled remainder still held by the lock goes back;
+                // filled quantities already belong to (or are on their way to) buyers
+                let resources = lock.map(|l| {
+                    let locked = l.to_resources().get(order.resource_type);
+                    Self::single_resource(order.resource_type, remaining_quantity.min(locked).max(0))
+                });
                 (resources, None)
             }
             TradeOrderType::Buy => {
@@ -469,9 +1297,9 @@ impl TradeService {
                         WHERE id = $1
                         "#,
                     )
-                    .bind(user_id)
+                    .bind(order.user_id)
                     .bind(refund_amount as i32)
-                    .execute(&mut *tx)
+                    .execute(&mut **tx)
                     .await?;
                 }
 
@@ -479,9 +1307,6 @@ impl TradeService {
             }
         };
 
-        // Commit transaction
-        tx.commit().await?;
-
         Ok(CancelOrderResponse {
             order: updated_order,
             refunded_resources,
@@ -491,12 +1316,15 @@ impl TradeService {
 
     // ==================== Accept Order Function ====================
 
-    /// Accept (fill) a trade order
+    /// Accept (fill) a trade order.
+    /// `client_ip` is the acceptor's, stored with the trade as a wash trade signal.
     pub async fn accept_order(
         pool: &PgPool,
         user_id: Uuid,
         order_id: Uuid,
         request: AcceptOrderRequest,
+        game: &GameConfig,
+        client_ip: Option<&str>,
     ) -> AppResult<AcceptOrderResponse> {
         // Start transaction
         let mut tx = pool.begin().await?;
@@ -508,6 +1336,7 @@ impl TradeService {
 
         // Validate accept request and get fill quantity
         let fill_quantity = Self::validate_accept_order(&order, user_id, request.quantity)?;
+        Self::validate_alliance_access(pool, &order, user_id).await?;
 
         // Get acceptor's village
         let acceptor_village = VillageRepository::find_by_id(pool, request.village_id)
@@ -516,9 +1345,42 @@ impl TradeService {
 
         Self::validate_village_ownership(&acceptor_village, user_id)?;
 
+        // Accepting a buy order ships the acceptor's resources
+        if order.order_type == TradeOrderType::Buy {
+            if game.starvation_guard {
+                VillageService::ensure_not_starving(&acceptor_village)?;
+            }
+            MarketService::validate_merchant_capacity(pool, acceptor_village.id, fill_quantity).await?;
+        }
+
         // Calculate gold amount
         let gold_amount = (fill_quantity as i64) * (order.price_per_unit as i64);
 
+        // The acceptor's side gets its own order row, filled on the spot, so the
+        // trade records a distinct buy and sell order
+        let taker_type = match order.order_type {
+            TradeOrderType::Sell => TradeOrderType::Buy,
+            TradeOrderType::Buy => TradeOrderType::Sell,
+        };
+        let taker_order = TradeRepository::create_order_tx(
+            &mut tx,
+            NewOrder {
+                user_id,
+                village_id: acceptor_village.id,
+                order_type: taker_type,
+                resource_type: order.resource_type,
+                quantity: fill_quantity,
+                price_per_unit: order.price_per_unit,
+                expires_in_hours: None,
+                alliance_only: false,
+                client_ip,
+                is_taker_fill: true,
+            },
+        )
+        .await?;
+        TradeRepository::update_order_filled_tx(&mut tx, taker_order.id, fill_quantity, TradeOrderStatus::Filled)
+            .await?;
+
         // Process based on order type
         let (resources_received, gold_received, transaction) = match order.order_type {
             TradeOrderType::Sell => {
@@ -531,6 +1393,7 @@ impl TradeService {
                     &acceptor_village,
                     fill_quantity,
                     gold_amount,
+                    taker_order.id,
                 )
                 .await?
             }
@@ -544,41 +1407,114 @@ impl TradeService {
                     &acceptor_village,
                     fill_quantity,
                     gold_amount,
+                    taker_order.id,
                 )
                 .await?
             }
         };
 
-        // Update order filled quantity and status
+        let updated_order = Self::apply_fill_tx(&mut tx, &order, fill_quantity).await?;
+        Self::record_trade_ips_tx(&mut tx, &transaction, &order, client_ip).await?;
+
+        // Resources travel to the buyer with the seller's merchants
+        let delivery = MarketService::schedule_delivery_tx(&mut tx, &transaction).await?;
+
+        // Commit transaction
+        tx.commit().await?;
+
+        Ok(AcceptOrderResponse {
+            order_status: updated_order.status,
+            resources_received,
+            gold_received,
+            fee_paid: transaction.fee_gold,
+            transaction,
+            arrives_at: delivery.arrives_at,
+        })
+    }
+
+    /// Store the client IPs of both sides of a trade: the resting order's from when it
+    /// was placed and the taker's from the current request
+    async fn record_trade_ips_tx(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        transaction: &TradeTransaction,
+        resting: &TradeOrder,
+        taker_ip: Option<&str>,
+    ) -> AppResult<()> {
+        let resting_ip = resting.client_ip.as_deref();
+        let (buyer_ip, seller_ip) = match resting.order_type {
+            TradeOrderType::Buy => (resting_ip, taker_ip),
+            TradeOrderType::Sell => (taker_ip, resting_ip),
+        };
+
+        TradeRepository::record_transaction_ips_tx(tx, transaction.id, buyer_ip, seller_ip).await
+    }
+
+    /// Record a fill against a locked order and update its status
+    async fn apply_fill_tx(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        order: &TradeOrder,
+        fill_quantity: i32,
+    ) -> AppResult<TradeOrder> {
         let new_quantity_filled = order.quantity_filled + fill_quantity;
         let new_status = Self::calculate_order_status(order.quantity, new_quantity_filled);
 
         let updated_order = TradeRepository::update_order_filled_tx(
-            &mut tx,
-            order_id,
+            tx,
+            order.id,
             new_quantity_filled,
             new_status,
         )
         .await?;
 
-        // If order is fully filled and it's a sell order, release the resource lock
-        if new_status == TradeOrderStatus::Filled && order.order_type == TradeOrderType::Sell {
-            TradeRepository::release_resource_lock_tx(&mut tx, LOCK_TYPE_TRADE_ORDER, order_id)
+        // The sold slice leaves the seller's village and its share of the lock
+        if order.order_type == TradeOrderType::Sell {
+            Self::deduct_resource_from_village(tx, order.village_id, order.resource_type, fill_quantity)
+                .await?;
+            if new_status != TradeOrderStatus::Filled {
+                TradeRepository::reduce_resource_lock_tx(
+                    tx,
+                    LOCK_TYPE_TRADE_ORDER,
+                    order.id,
+                    &Self::single_resource(order.resource_type, fill_quantity),
+                )
                 .await?;
+            }
         }
 
-        // Commit transaction
-        tx.commit().await?;
+        // Once the order is closed, release whatever escrow is left: the resource lock
+        // for sell orders, and gold for an unfillable remainder of a buy order
+        if new_status == TradeOrderStatus::Filled {
+            match order.order_type {
+                TradeOrderType::Sell => {
+                    TradeRepository::release_resource_lock_tx(tx, LOCK_TYPE_TRADE_ORDER, order.id)
+                        .await?;
+                }
+                TradeOrderType::Buy => {
+                    let sliver = order.quantity - new_quantity_filled;
+                    let refund_amount = (sliver as i64) * (order.price_per_unit as i64);
+
+                    if refund_amount > 0 {
+                        sqlx::query(
+                            r#"
+                            UPDATE users
+                            SET gold_balance = gold_balance + $2
+                            WHERE id = $1
+                            "#,
+                        )
+                        .bind(order.user_id)
+                        .bind(refund_amount as i32)
+                        .execute(&mut **tx)
+                        .await?;
+                    }
+                }
+            }
+        }
 
-        Ok(AcceptOrderResponse {
-            transaction,
-            order_status: updated_order.status,
-            resources_received,
-            gold_received,
-        })
+        Ok(updated_order)
     }
 
-    /// Process accepting a sell order (buyer side)
+    /// Process accepting a sell order (buyer side).
+    /// Settles gold only; the caller sends the resources with `MarketService::schedule_delivery_tx`.
     async fn process_accept_sell_order(
         tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
         order: &TradeOrder,
@@ -586,6 +1522,7 @@ impl TradeService {
         buyer_village: &Village,
         quantity: i32,
         gold_amount: i64,
+        buy_order_id: Uuid,
     ) -> AppResult<(Option<Resources>, Option<i32>, crate::models::trade::TradeTransaction)> {
         // Deduct gold from buyer
         let deduct_result = sqlx::query(
@@ -604,7 +1541,8 @@ impl TradeService {
             return Err(AppError::BadRequest("Insufficient gold balance".into()));
         }
 
-        // Add gold to seller
+        // Add gold to seller, minus the market fee
+        let fee = Self::market_fee(gold_amount);
         sqlx::query(
             r#"
             UPDATE users
@@ -613,25 +1551,25 @@ impl TradeService {
             "#,
         )
         .bind(order.user_id)
-        .bind(gold_amount as i32)
+        .bind((gold_amount - fee) as i32)
         .execute(&mut **tx)
         .await?;
 
-        // Add resources to buyer's village
-        Self::add_resource_to_village(tx, buyer_village.id, order.resource_type, quantity).await?;
-
         // Create transaction record
         let trade_tx = TradeRepository::create_transaction_tx(
             tx,
-            order.id, // This sell order becomes the sell_order_id
-            order.id, // For now, using same ID - in real matching we'd have separate buy order
-            buyer_id,
-            order.user_id,
-            buyer_village.id,
-            order.village_id,
-            order.resource_type,
-            quantity,
-            order.price_per_unit,
+            NewTransaction {
+                buy_order_id,
+                sell_order_id: order.id,
+                buyer_id,
+                seller_id: order.user_id,
+                buyer_village_id: buyer_village.id,
+                seller_village_id: order.village_id,
+                resource_type: order.resource_type,
+                quantity,
+                price_per_unit: order.price_per_unit,
+                fee_gold: fee as i32,
+            },
         )
         .await?;
 
@@ -640,7 +1578,8 @@ impl TradeService {
         Ok((Some(resources), None, trade_tx))
     }
 
-    /// Process accepting a buy order (seller side)
+    /// Process accepting a buy order (seller side).
+    /// Settles gold only; the caller sends the resources with `MarketService::schedule_delivery_tx`.
     async fn process_accept_buy_order(
         tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
         order: &TradeOrder,
@@ -648,6 +1587,7 @@ impl TradeService {
         seller_village: &Village,
         quantity: i32,
         gold_amount: i64,
+        sell_order_id: Uuid,
     ) -> AppResult<(Option<Resources>, Option<i32>, crate::models::trade::TradeTransaction)> {
         // Check seller has enough resources
         let available = Self::get_village_resource(seller_village, order.resource_type);
@@ -676,11 +1616,10 @@ impl TradeService {
         Self::deduct_resource_from_village(tx, seller_village.id, order.resource_type, quantity)
             .await?;
 
-        // Add resources to buyer's village (order owner)
-        Self::add_resource_to_village(tx, order.village_id, order.resource_type, quantity).await?;
-
         // Gold was already deducted from buyer when they created the buy order
-        // Add gold to seller
+        // Add gold to seller, minus the market fee
+        let fee = Self::market_fee(gold_amount);
+        let proceeds = (gold_amount - fee) as i32;
         sqlx::query(
             r#"
             UPDATE users
@@ -689,26 +1628,29 @@ impl TradeService {
             "#,
         )
         .bind(seller_id)
-        .bind(gold_amount as i32)
+        .bind(proceeds)
         .execute(&mut **tx)
         .await?;
 
         // Create transaction record
         let trade_tx = TradeRepository::create_transaction_tx(
             tx,
-            order.id, // This buy order becomes the buy_order_id
-            order.id, // For now, using same ID
-            order.user_id,
-            seller_id,
-            order.village_id,
-            seller_village.id,
-            order.resource_type,
-            quantity,
-            order.price_per_unit,
+            NewTransaction {
+                buy_order_id: order.id,
+                sell_order_id,
+                buyer_id: order.user_id,
+                seller_id,
+                buyer_village_id: order.village_id,
+                seller_village_id: seller_village.id,
+                resource_type: order.resource_type,
+                quantity,
+                price_per_unit: order.price_per_unit,
+                fee_gold: fee as i32,
+            },
         )
         .await?;
 
-        Ok((None, Some(gold_amount as i32), trade_tx))
+        Ok((None, Some(proceeds), trade_tx))
     }
 
     /// Add resources to a village
@@ -796,15 +1738,22 @@ impl TradeService {
         resources
     }
 
-    /// Get market summary for all resources
-    pub async fn get_market_summary(pool: &PgPool) -> AppResult<Vec<MarketSummary>> {
+    /// Market fee on a trade worth `gold_amount`, rounded down
+    pub fn market_fee(gold_amount: i64) -> i64 {
+        gold_amount * MARKET_FEE_BPS / 10_000
+    }
+
+    /// Get market summary for all resources.
+    /// Last price and volume leave out trades `filter` considers wash trades.
+    pub async fn get_market_summary(pool: &PgPool, filter: &WashTradeFilter) -> AppResult<Vec<MarketSummary>> {
         let mut summaries = Vec::new();
 
         for resource_type in TradeResourceType::all() {
-            let best_buy = TradeRepository::get_best_buy_price(pool, resource_type).await?;
-            let best_sell = TradeRepository::get_best_sell_price(pool, resource_type).await?;
-            let last_price = TradeRepository::get_last_trade_price(pool, resource_type).await?;
-            let (volume, trade_count) = TradeRepository::get_24h_volume(pool, resource_type).await?;
+            let (bids, asks) = TradeRepository::get_order_book(pool, resource_type, 1).await?;
+            let best_buy = bids.first().map(|level| level.price_per_unit);
+            let best_sell = asks.first().map(|level| level.price_per_unit);
+            let last_price = TradeRepository::get_last_trade_price(pool, resource_type, filter).await?;
+            let (volume, trade_count) = TradeRepository::get_24h_volume(pool, resource_type, filter).await?;
 
             let spread = match (best_sell, best_buy) {
                 (Some(sell), Some(buy)) => Some(sell - buy),
@@ -825,9 +1774,31 @@ impl TradeService {
         Ok(summaries)
     }
 
-    /// Calculate new order status based on filled quantity
+    /// Order book depth of a resource, aggregated by price
+    pub async fn get_order_book(pool: &PgPool, query: OrderBookQuery) -> AppResult<OrderBookResponse> {
+        let levels = query.levels.unwrap_or(20).clamp(1, MAX_BOOK_LEVELS);
+        let (bids, asks) = TradeRepository::get_order_book(pool, query.resource, levels).await?;
+
+        let spread = match (asks.first(), bids.first()) {
+            (Some(ask), Some(bid)) => Some(ask.price_per_unit - bid.price_per_unit),
+            _ => None,
+        };
+
+        Ok(OrderBookResponse {
+            resource_type: query.resource,
+            bids,
+            asks,
+            spread,
+        })
+    }
+
+    /// Calculate new order status based on filled quantity.
+    /// A partially filled order whose remainder drops below `MIN_QUANTITY` is closed as
+    /// filled, since such a sliver could only ever be taken in full.
     pub fn calculate_order_status(quantity: i32, quantity_filled: i32) -> TradeOrderStatus {
-        if quantity_filled >= quantity {
+        if quantity_filled >= quantity
+            || (quantity_filled > 0 && quantity - quantity_filled < MIN_QUANTITY)
+        {
             TradeOrderStatus::Filled
         } else if quantity_filled > 0 {
             TradeOrderStatus::PartiallyFilled
@@ -943,4 +1914,635 @@ impl TradeService {
 
         Ok(refunded_gold)
     }
+
+    /// Unload merchants that have reached the buyer's village - called by background job.
+    /// Storage caps apply on arrival, so cargo beyond the warehouse or granary is lost.
+    pub async fn process_arrived_deliveries(pool: &PgPool, limit: i32) -> anyhow::Result<Vec<ResourceDelivery>> {
+        let mut tx = pool.begin().await?;
+
+        let deliveries = TradeRepository::take_arrived_deliveries_tx(&mut tx, limit).await?;
+        for delivery in &deliveries {
+            Self::add_resource_to_village(&mut tx, delivery.to_village_id, delivery.resource_type, delivery.quantity)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(deliveries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::building::BuildingType;
+    use crate::test_support;
+
+    fn order_request(
+        village_id: Uuid,
+        order_type: TradeOrderType,
+        quantity: i32,
+        price_per_unit: i32,
+    ) -> CreateOrderRequest {
+        CreateOrderRequest {
+            village_id,
+            order_type,
+            resource_type: TradeResourceType::Wood,
+            quantity,
+            price_per_unit,
+            expires_in_hours: None,
+            alliance_only: false,
+            execution: OrderExecution::Resting,
+        }
+    }
+
+    #[test]
+    fn expiry_falls_back_to_the_server_default() {
+        assert_eq!(TradeService::effective_expiry_hours(Some(24), 168), 24);
+        assert_eq!(TradeService::effective_expiry_hours(None, 48), 48);
+        // A misconfigured default still gives a valid expiry
+        assert_eq!(TradeService::effective_expiry_hours(None, 0), 1);
+        assert_eq!(TradeService::effective_expiry_hours(None, 1000), MAX_EXPIRY_HOURS);
+    }
+
+    #[test]
+    fn max_size_order_value_is_rejected_without_overflow() {
+        let village_id = Uuid::new_v4();
+
+        // 1,000,000 units at 10,000 gold is far past i32 but a valid quantity and price on its own
+        let largest = order_request(village_id, TradeOrderType::Buy, MAX_QUANTITY, MAX_PRICE);
+        match TradeService::validate_create_order_request(&largest, None, 50) {
+            Err(AppError::ValidationError(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].code, "order_value_too_large");
+            }
+            other => panic!("expected an order value error, got {:?}", other),
+        }
+
+        // Just under the cap is fine
+        let quantity = (MAX_ORDER_VALUE / MAX_PRICE as i64) as i32;
+        let at_cap = order_request(village_id, TradeOrderType::Buy, quantity, MAX_PRICE);
+        TradeService::validate_create_order_request(&at_cap, None, 50).unwrap();
+    }
+
+    /// A seller with a market at (0|0)
+    async fn seller(pool: &PgPool) -> (Uuid, Village) {
+        let user_id = test_support::create_user(pool, 0).await;
+        let village = test_support::create_village(pool, user_id, 0, 0).await;
+        test_support::add_building(pool, village.id, BuildingType::Market, 5, 5).await;
+        (user_id, village)
+    }
+
+    #[sqlx::test]
+    async fn cancel_after_partial_fill_keeps_delivery_in_flight(pool: PgPool) {
+        let game = test_support::game_config();
+        let (seller_id, seller_village) = seller(&pool).await;
+        let buyer_id = test_support::create_user(&pool, 10_000).await;
+        let buyer_village = test_support::create_village(&pool, buyer_id, 10, 0).await;
+
+        let created = TradeService::create_order(
+            &pool,
+            seller_id,
+            order_request(seller_village.id, TradeOrderType::Sell, 300, 10),
+            &game,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let accepted = TradeService::accept_order(
+            &pool,
+            buyer_id,
+            created.order.id,
+            AcceptOrderRequest { village_id: buyer_village.id, quantity: Some(100) },
+            &game,
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(accepted.arrives_at > chrono::Utc::now());
+
+        let cancelled = TradeService::cancel_order(&pool, seller_id, created.order.id).await.unwrap();
+
+        // Only the 200 still locked come back; the 100 sold are on the road
+        assert_eq!(cancelled.refunded_resources.unwrap().wood, 200);
+        assert_eq!(test_support::village(&pool, seller_village.id).await.wood, 400);
+        let (locked_wood, _, _, _) = TradeRepository::get_village_locked_resources(&pool, seller_village.id)
+            .await
+            .unwrap();
+        assert_eq!(locked_wood, 0);
+
+        let (in_flight,): (i32,) =
+            sqlx::query_as("SELECT quantity FROM resource_deliveries WHERE to_village_id = $1")
+                .bind(buyer_village.id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(in_flight, 100);
+        assert_eq!(test_support::village(&pool, buyer_village.id).await.wood, 500);
+    }
+
+    #[test]
+    fn remainder_below_minimum_closes_order() {
+        assert_eq!(TradeService::calculate_order_status(250, 0), TradeOrderStatus::Open);
+        assert_eq!(TradeService::calculate_order_status(250, 150), TradeOrderStatus::PartiallyFilled);
+        assert_eq!(TradeService::calculate_order_status(250, 200), TradeOrderStatus::Filled);
+        assert_eq!(TradeService::calculate_order_status(250, 250), TradeOrderStatus::Filled);
+    }
+
+    #[sqlx::test]
+    async fn fill_leaving_50_closes_sell_order_and_releases_lock(pool: PgPool) {
+        let game = test_support::game_config();
+        let (seller_id, seller_village) = seller(&pool).await;
+        let buyer_id = test_support::create_user(&pool, 10_000).await;
+        let buyer_village = test_support::create_village(&pool, buyer_id, 10, 0).await;
+
+        let created = TradeService::create_order(
+            &pool,
+            seller_id,
+            order_request(seller_village.id, TradeOrderType::Sell, 250, 10),
+            &game,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let accepted = TradeService::accept_order(
+            &pool,
+            buyer_id,
+            created.order.id,
+            AcceptOrderRequest { village_id: buyer_village.id, quantity: Some(200) },
+            &game,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(accepted.order_status, TradeOrderStatus::Filled);
+        let (locked_wood, _, _, _) = TradeRepository::get_village_locked_resources(&pool, seller_village.id)
+            .await
+            .unwrap();
+        assert_eq!(locked_wood, 0);
+        // The 50 left over never left the village and is free to use again
+        assert_eq!(test_support::village(&pool, seller_village.id).await.wood, 300);
+    }
+
+    #[sqlx::test]
+    async fn fill_leaving_50_closes_buy_order_and_refunds_gold(pool: PgPool) {
+        let game = test_support::game_config();
+        let buyer_id = test_support::create_user(&pool, 10_000).await;
+        let buyer_village = test_support::create_village(&pool, buyer_id, 10, 0).await;
+        let (seller_id, seller_village) = seller(&pool).await;
+
+        let created = TradeService::create_order(
+            &pool,
+            buyer_id,
+            order_request(buyer_village.id, TradeOrderType::Buy, 250, 10),
+            &game,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(test_support::gold_balance(&pool, buyer_id).await, 7_500);
+
+        let accepted = TradeService::accept_order(
+            &pool,
+            seller_id,
+            created.order.id,
+            AcceptOrderRequest { village_id: seller_village.id, quantity: Some(200) },
+            &game,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(accepted.order_status, TradeOrderStatus::Filled);
+        // 2,000 paid for the 200 bought, the 500 escrowed for the last 50 comes back
+        assert_eq!(test_support::gold_balance(&pool, buyer_id).await, 8_000);
+    }
+
+    #[sqlx::test]
+    async fn starving_village_cannot_create_sell_order(pool: PgPool) {
+        let mut game = test_support::game_config();
+        let (seller_id, seller_village) = seller(&pool).await;
+        sqlx::query("UPDATE villages SET crop = 0 WHERE id = $1")
+            .bind(seller_village.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let sell = order_request(seller_village.id, TradeOrderType::Sell, 100, 10);
+        let result = TradeService::create_order(&pool, seller_id, sell.clone(), &game, None).await;
+        match result {
+            Err(AppError::BadRequest(message)) => assert!(message.contains("starving")),
+            other => panic!("expected a starvation error, got {:?}", other.map(|r| r.order.id)),
+        }
+
+        let (open_orders,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM trade_orders WHERE village_id = $1")
+            .bind(seller_village.id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(open_orders, 0);
+
+        // Servers that turn the guard off let the order through
+        game.starvation_guard = false;
+        TradeService::create_order(&pool, seller_id, sell, &game, None).await.unwrap();
+    }
+
+    #[sqlx::test]
+    async fn starving_village_cannot_accept_a_buy_order(pool: PgPool) {
+        let game = test_support::game_config();
+        let buyer_id = test_support::create_user(&pool, 10_000).await;
+        let buyer_village = test_support::create_village(&pool, buyer_id, 10, 0).await;
+        let buy = order_request(buyer_village.id, TradeOrderType::Buy, 100, 10);
+        let order = TradeService::create_order(&pool, buyer_id, buy, &game, None).await.unwrap().order;
+
+        let (seller_id, seller_village) = seller(&pool).await;
+        sqlx::query("UPDATE villages SET crop = 0 WHERE id = $1")
+            .bind(seller_village.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let request = AcceptOrderRequest { village_id: seller_village.id, quantity: None };
+        let result = TradeService::accept_order(&pool, seller_id, order.id, request, &game, None).await;
+        assert!(matches!(result, Err(AppError::BadRequest(message)) if message.contains("starving")));
+
+        let order = TradeRepository::get_order_by_id(&pool, order.id).await.unwrap().unwrap();
+        assert_eq!(order.quantity_filled, 0);
+    }
+
+    #[sqlx::test]
+    async fn updating_an_order_respects_starvation_and_expiry(pool: PgPool) {
+        let game = test_support::game_config();
+        let (seller_id, seller_village) = seller(&pool).await;
+        let sell = order_request(seller_village.id, TradeOrderType::Sell, 200, 10);
+        let order = TradeService::create_order(&pool, seller_id, sell, &game, None).await.unwrap().order;
+
+        sqlx::query("UPDATE villages SET crop = 0 WHERE id = $1")
+            .bind(seller_village.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Shipping out more is blocked, shrinking the order is not
+        let result = TradeService::update_order(&pool, seller_id, order.id, None, Some(300), &game).await;
+        assert!(matches!(result, Err(AppError::BadRequest(message)) if message.contains("starving")));
+        TradeService::update_order(&pool, seller_id, order.id, None, Some(100), &game).await.unwrap();
+
+        sqlx::query("UPDATE trade_orders SET expires_at = NOW() - INTERVAL '1 minute' WHERE id = $1")
+            .bind(order.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let result = TradeService::update_order(&pool, seller_id, order.id, Some(12), None, &game).await;
+        assert!(matches!(result, Err(AppError::BadRequest(message)) if message.contains("expired")));
+    }
+
+    #[sqlx::test]
+    async fn repricing_moves_an_order_to_the_back_but_keeps_its_creation_time(pool: PgPool) {
+        let game = test_support::game_config();
+        let (seller_id, seller_village) = seller(&pool).await;
+        let other_seller_id = test_support::create_user(&pool, 0).await;
+        let other_village = test_support::create_village(&pool, other_seller_id, 0, 5).await;
+        test_support::add_building(&pool, other_village.id, BuildingType::Market, 5, 5).await;
+
+        let older = order_request(seller_village.id, TradeOrderType::Sell, 200, 11);
+        let older = TradeService::create_order(&pool, seller_id, older, &game, None).await.unwrap().order;
+        let newer = order_request(other_village.id, TradeOrderType::Sell, 200, 10);
+        let newer = TradeService::create_order(&pool, other_seller_id, newer, &game, None).await.unwrap().order;
+
+        let repriced = TradeService::update_order(&pool, seller_id, older.id, Some(10), None, &game).await.unwrap();
+        assert_eq!(repriced.order.created_at, older.created_at);
+
+        let buyer_id = test_support::create_user(&pool, 10_000).await;
+        let buyer_village = test_support::create_village(&pool, buyer_id, 10, 0).await;
+        let buy = order_request(buyer_village.id, TradeOrderType::Buy, 200, 10);
+        let response = TradeService::create_order(&pool, buyer_id, buy, &game, None).await.unwrap();
+
+        assert_eq!(response.fills.len(), 1);
+        assert_eq!(response.fills[0].sell_order_id, Some(newer.id));
+    }
+
+    #[sqlx::test]
+    async fn accepting_an_order_does_not_list_a_taker_order(pool: PgPool) {
+        let game = test_support::game_config();
+        let (seller_id, seller_village) = seller(&pool).await;
+        let sell = order_request(seller_village.id, TradeOrderType::Sell, 100, 10);
+        let order = TradeService::create_order(&pool, seller_id, sell, &game, None).await.unwrap().order;
+
+        let buyer_id = test_support::create_user(&pool, 10_000).await;
+        let buyer_village = test_support::create_village(&pool, buyer_id, 10, 0).await;
+        let request = AcceptOrderRequest { village_id: buyer_village.id, quantity: None };
+        TradeService::accept_order(&pool, buyer_id, order.id, request, &game, None).await.unwrap();
+
+        assert!(TradeRepository::get_user_orders(&pool, buyer_id, None).await.unwrap().is_empty());
+        assert!(TradeRepository::get_village_orders(&pool, buyer_village.id).await.unwrap().is_empty());
+        let counts = TradeRepository::count_user_orders_by_status(&pool, buyer_id).await.unwrap();
+        assert_eq!((counts.filled, counts.total), (0, 0));
+
+        let seller_counts = TradeRepository::count_user_orders_by_status(&pool, seller_id).await.unwrap();
+        assert_eq!((seller_counts.filled, seller_counts.total), (1, 1));
+    }
+
+    #[sqlx::test]
+    async fn order_of_a_deleted_player_is_still_shown(pool: PgPool) {
+        let game = test_support::game_config();
+        let (seller_id, seller_village) = seller(&pool).await;
+        let sell = order_request(seller_village.id, TradeOrderType::Sell, 100, 10);
+        let order = TradeService::create_order(&pool, seller_id, sell, &game, None).await.unwrap().order;
+
+        test_support::delete_user(&pool, seller_id).await;
+
+        let details = TradeRepository::get_order_with_details(&pool, order.id).await.unwrap().unwrap();
+        assert_eq!(details.order.id, order.id);
+        assert_eq!(details.user_display_name, None);
+    }
+
+    /// A resting sell of 300 wood at 10 and a buyer with 10,000 gold
+    async fn partially_matchable_book(pool: &PgPool) -> (Uuid, Village, TradeOrder) {
+        let game = test_support::game_config();
+        let (seller_id, seller_village) = seller(pool).await;
+        let resting = TradeService::create_order(
+            pool,
+            seller_id,
+            order_request(seller_village.id, TradeOrderType::Sell, 300, 10),
+            &game,
+            None,
+        )
+        .await
+        .unwrap()
+        .order;
+
+        let buyer_id = test_support::create_user(pool, 10_000).await;
+        let buyer_village = test_support::create_village(pool, buyer_id, 10, 0).await;
+        (buyer_id, buyer_village, resting)
+    }
+
+    fn buy_500(village_id: Uuid, execution: OrderExecution) -> CreateOrderRequest {
+        CreateOrderRequest { execution, ..order_request(village_id, TradeOrderType::Buy, 500, 10) }
+    }
+
+    #[sqlx::test]
+    async fn immediate_or_cancel_takes_what_is_there_and_cancels_the_rest(pool: PgPool) {
+        let game = test_support::game_config();
+        let (buyer_id, buyer_village, resting) = partially_matchable_book(&pool).await;
+
+        let response = TradeService::create_order(
+            &pool,
+            buyer_id,
+            buy_500(buyer_village.id, OrderExecution::ImmediateOrCancel),
+            &game,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.fills.len(), 1);
+        assert_eq!(response.order.quantity_filled, 300);
+        assert_eq!(response.order.status, TradeOrderStatus::Cancelled);
+        // Only the 300 bought are paid for; nothing stays escrowed for the other 200
+        assert_eq!(test_support::gold_balance(&pool, buyer_id).await, 7_000);
+
+        let resting = TradeRepository::get_order_by_id(&pool, resting.id).await.unwrap().unwrap();
+        assert_eq!(resting.status, TradeOrderStatus::Filled);
+    }
+
+    #[sqlx::test]
+    async fn fill_or_kill_does_nothing_unless_fully_matched(pool: PgPool) {
+        let game = test_support::game_config();
+        let (buyer_id, buyer_village, resting) = partially_matchable_book(&pool).await;
+
+        let result = TradeService::create_order(
+            &pool,
+            buyer_id,
+            buy_500(buyer_village.id, OrderExecution::FillOrKill),
+            &game,
+            None,
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+        assert_eq!(test_support::gold_balance(&pool, buyer_id).await, 10_000);
+        let untouched = TradeRepository::get_order_by_id(&pool, resting.id).await.unwrap().unwrap();
+        assert_eq!(untouched.quantity_filled, 0);
+
+        // The same book can fill 300 in full
+        let fill_300 = CreateOrderRequest {
+            execution: OrderExecution::FillOrKill,
+            ..order_request(buyer_village.id, TradeOrderType::Buy, 300, 10)
+        };
+        let response = TradeService::create_order(&pool, buyer_id, fill_300, &game, None).await.unwrap();
+        assert_eq!(response.order.status, TradeOrderStatus::Filled);
+        assert_eq!(test_support::gold_balance(&pool, buyer_id).await, 7_000);
+    }
+
+    #[sqlx::test]
+    async fn resting_order_keeps_its_unmatched_remainder_on_the_book(pool: PgPool) {
+        let game = test_support::game_config();
+        let (buyer_id, buyer_village, _) = partially_matchable_book(&pool).await;
+
+        let response = TradeService::create_order(
+            &pool,
+            buyer_id,
+            buy_500(buyer_village.id, OrderExecution::Resting),
+            &game,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.order.quantity_filled, 300);
+        assert_eq!(response.order.status, TradeOrderStatus::PartiallyFilled);
+        // 3,000 paid for the fill and 2,000 escrowed for the 200 still wanted
+        assert_eq!(test_support::gold_balance(&pool, buyer_id).await, 5_000);
+    }
+
+    /// An open wood sell order with `quantity_filled` already taken
+    fn resting_sell(quantity: i32, quantity_filled: i32) -> TradeOrder {
+        let now = chrono::Utc::now();
+        TradeOrder {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            village_id: Uuid::new_v4(),
+            order_type: TradeOrderType::Sell,
+            resource_type: TradeResourceType::Wood,
+            quantity,
+            quantity_filled,
+            price_per_unit: 10,
+            status: TradeOrderStatus::Open,
+            expires_at: None,
+            created_at: now,
+            updated_at: now,
+            filled_at: None,
+            cancelled_at: None,
+            alliance_only: false,
+            client_ip: None,
+        }
+    }
+
+    #[test]
+    fn match_book_fills_in_book_order_up_to_each_remainder() {
+        let book = vec![resting_sell(300, 0), resting_sell(300, 150), resting_sell(400, 0)];
+
+        let fills: Vec<i32> = TradeService::match_book(&book, 500).iter().map(|(_, fill)| *fill).collect();
+        assert_eq!(fills, vec![300, 150, 50]);
+
+        // Past the book's depth the rest stays unfilled
+        let fills: Vec<i32> = TradeService::match_book(&book, 2_000).iter().map(|(_, fill)| *fill).collect();
+        assert_eq!(fills, vec![300, 150, 400]);
+
+        assert!(TradeService::match_book(&[], 500).is_empty());
+    }
+
+    #[sqlx::test]
+    async fn taker_fills_best_price_first_then_oldest(pool: PgPool) {
+        let game = test_support::game_config();
+        let (seller_id, seller_village) = seller(&pool).await;
+        let other_seller_id = test_support::create_user(&pool, 0).await;
+        let other_village = test_support::create_village(&pool, other_seller_id, 0, 5).await;
+        test_support::add_building(&pool, other_village.id, BuildingType::Market, 5, 5).await;
+
+        let sell = |user_id, village_id, price| {
+            let request = order_request(village_id, TradeOrderType::Sell, 200, price);
+            let game = &game;
+            let pool = &pool;
+            async move { TradeService::create_order(pool, user_id, request, game, None).await.unwrap().order }
+        };
+        let oldest_at_10 = sell(seller_id, seller_village.id, 10).await;
+        let cheapest = sell(seller_id, seller_village.id, 9).await;
+        let newest_at_10 = sell(other_seller_id, other_village.id, 10).await;
+
+        let buyer_id = test_support::create_user(&pool, 10_000).await;
+        let buyer_village = test_support::create_village(&pool, buyer_id, 10, 0).await;
+        let response = TradeService::create_order(
+            &pool,
+            buyer_id,
+            order_request(buyer_village.id, TradeOrderType::Buy, 300, 10),
+            &game,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let fills: Vec<(Option<Uuid>, i32, i32)> =
+            response.fills.iter().map(|t| (t.sell_order_id, t.quantity, t.price_per_unit)).collect();
+        assert_eq!(fills, vec![(Some(cheapest.id), 200, 9), (Some(oldest_at_10.id), 100, 10)]);
+        assert_eq!(response.order.status, TradeOrderStatus::Filled);
+        assert_eq!(test_support::gold_balance(&pool, buyer_id).await, 10_000 - 1_800 - 1_000);
+
+        let untouched = TradeRepository::get_order_by_id(&pool, newest_at_10.id).await.unwrap().unwrap();
+        assert_eq!(untouched.quantity_filled, 0);
+    }
+
+    #[sqlx::test]
+    async fn candles_bucket_trades_and_carry_the_close_forward(pool: PgPool) {
+        let game = test_support::game_config();
+        let (seller_id, seller_village) = seller(&pool).await;
+        for price in [12, 8, 10] {
+            let sell = order_request(seller_village.id, TradeOrderType::Sell, 100, price);
+            TradeService::create_order(&pool, seller_id, sell, &game, None).await.unwrap();
+        }
+
+        // Buying 300 at up to 12 trades at 8, then 10, then 12
+        let buyer_id = test_support::create_user(&pool, 10_000).await;
+        let buyer_village = test_support::create_village(&pool, buyer_id, 10, 0).await;
+        let buy = order_request(buyer_village.id, TradeOrderType::Buy, 300, 12);
+        TradeService::create_order(&pool, buyer_id, buy, &game, None).await.unwrap();
+
+        // Move them into the previous hour, with the 12 first and the 10 last
+        sqlx::query(
+            r#"
+            UPDATE trade_transactions
+            SET created_at = date_trunc('hour', NOW()) - INTERVAL '1 hour'
+                + CASE price_per_unit WHEN 12 THEN INTERVAL '5 minutes'
+                                      WHEN 8 THEN INTERVAL '10 minutes'
+                                      ELSE INTERVAL '20 minutes' END
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let filter = WashTradeFilter { exclude_same_ip: true, max_pair_trades_per_day: 10 };
+        let query = |interval_minutes| PriceCandlesQuery {
+            resource: TradeResourceType::Wood,
+            interval_minutes: Some(interval_minutes),
+            limit: Some(3),
+        };
+
+        let response = TradeService::get_price_candles(&pool, query(60), &filter).await.unwrap();
+        // The oldest bucket has no trade before it, so it is left out
+        assert_eq!(response.candles.len(), 2);
+        let (traded, quiet) = (&response.candles[0], &response.candles[1]);
+        assert_eq!((traded.open, traded.high, traded.low, traded.close, traded.volume), (12, 12, 8, 10, 300));
+        assert_eq!((quiet.open, quiet.high, quiet.low, quiet.close, quiet.volume), (10, 10, 10, 10, 0));
+        assert_eq!(quiet.bucket_start - traded.bucket_start, chrono::Duration::hours(1));
+
+        // Buckets line up with multiples of the interval since the epoch
+        for interval_minutes in CANDLE_INTERVALS {
+            let response = TradeService::get_price_candles(&pool, query(interval_minutes), &filter).await.unwrap();
+            for candle in response.candles {
+                assert_eq!(candle.bucket_start.timestamp() % (interval_minutes as i64 * 60), 0);
+            }
+        }
+
+        let unsupported = TradeService::get_price_candles(&pool, query(30), &filter).await;
+        assert!(matches!(unsupported, Err(AppError::BadRequest(_))));
+    }
+
+    #[test]
+    fn market_fee_rounds_down_in_the_sellers_favour() {
+        assert_eq!(TradeService::market_fee(0), 0);
+        assert_eq!(TradeService::market_fee(99), 0);
+        assert_eq!(TradeService::market_fee(100), 1);
+        assert_eq!(TradeService::market_fee(199), 1);
+        assert_eq!(TradeService::market_fee(1_050), 10);
+        // The largest order value doesn't overflow
+        assert_eq!(TradeService::market_fee(MAX_ORDER_VALUE), MAX_ORDER_VALUE / 100);
+    }
+
+    #[sqlx::test]
+    async fn seller_is_paid_the_trade_value_less_the_fee(pool: PgPool) {
+        let game = test_support::game_config();
+        let (seller_id, seller_village) = seller(&pool).await;
+        let sell = order_request(seller_village.id, TradeOrderType::Sell, 150, 7);
+        TradeService::create_order(&pool, seller_id, sell, &game, None).await.unwrap();
+
+        let buyer_id = test_support::create_user(&pool, 10_000).await;
+        let buyer_village = test_support::create_village(&pool, buyer_id, 10, 0).await;
+        let buy = order_request(buyer_village.id, TradeOrderType::Buy, 150, 7);
+        let response = TradeService::create_order(&pool, buyer_id, buy, &game, None).await.unwrap();
+
+        // 1% of 1,050 is 10.5, rounded down to 10; the buyer pays the full value
+        assert_eq!(response.fills[0].total_gold, 1_050);
+        assert_eq!(response.fills[0].fee_gold, 10);
+        assert_eq!(test_support::gold_balance(&pool, seller_id).await, 1_040);
+        assert_eq!(test_support::gold_balance(&pool, buyer_id).await, 10_000 - 1_050);
+    }
+
+    #[sqlx::test]
+    async fn concurrent_buy_orders_cannot_overdraw_gold(pool: PgPool) {
+        let game = test_support::game_config();
+        // Enough to escrow one 1,000 gold order, not two
+        let buyer_id = test_support::create_user(&pool, 1_500).await;
+        let buyer_village = test_support::create_village(&pool, buyer_id, 10, 0).await;
+        let buy = order_request(buyer_village.id, TradeOrderType::Buy, 100, 10);
+
+        let (first, second) = tokio::join!(
+            TradeService::create_order(&pool, buyer_id, buy.clone(), &game, None),
+            TradeService::create_order(&pool, buyer_id, buy.clone(), &game, None),
+        );
+        assert_eq!(first.is_ok() as u8 + second.is_ok() as u8, 1);
+        let rejected = if first.is_err() { first } else { second };
+        assert!(matches!(rejected, Err(AppError::BadRequest(_))));
+
+        assert_eq!(test_support::gold_balance(&pool, buyer_id).await, 500);
+        let (orders,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM trade_orders WHERE user_id = $1")
+            .bind(buyer_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(orders, 1);
+    }
 }
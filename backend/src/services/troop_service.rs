@@ -1,15 +1,21 @@
 use chrono::{Duration, Utc};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::error::{AppError, AppResult};
 use crate::models::troop::{Troop, TroopCost, TroopDefinition, TroopQueue, TroopType, TrainTroopsResponse};
+use crate::models::village::Village;
 use crate::repositories::building_repo::BuildingRepository;
+use crate::repositories::trade_repo::TradeRepository;
 use crate::repositories::troop_repo::TroopRepository;
 use crate::repositories::village_repo::VillageRepository;
+use crate::services::village_service::VillageService;
 
 pub struct TroopService;
 
+/// Most troops one training order can ask for
+pub const MAX_TRAINING_COUNT: i32 = 10_000;
+
 impl TroopService {
     /// Get all available troop definitions
     pub async fn get_definitions(pool: &PgPool) -> AppResult<Vec<TroopDefinition>> {
@@ -58,58 +64,34 @@ impl TroopService {
         village_id: Uuid,
         troop_type: TroopType,
         count: i32,
+        starvation_guard: bool,
     ) -> AppResult<TrainTroopsResponse> {
-        if count <= 0 {
-            return Err(AppError::BadRequest("Count must be positive".into()));
-        }
+        Self::validate_training_count(count)?;
 
         // Check requirements
         let definition = Self::check_training_requirements(pool, village_id, troop_type).await?;
 
-        // Calculate total cost
-        let total_cost = TroopCost {
-            wood: definition.wood_cost * count,
-            clay: definition.clay_cost * count,
-            iron: definition.iron_cost * count,
-            crop: definition.crop_cost * count,
-            time_seconds: definition.training_time_seconds * count,
-        };
+        let total_cost = Self::training_cost(&definition, count)?;
 
-        // Check and deduct resources
-        let village = VillageRepository::find_by_id(pool, village_id)
+        // Lock the village so the affordability check and the deduction see the same row
+        let mut tx = pool.begin().await?;
+        let village = VillageRepository::find_by_id_for_update(&mut tx, village_id)
             .await?
             .ok_or_else(|| AppError::NotFound("Village not found".into()))?;
 
-        if village.wood < total_cost.wood
-            || village.clay < total_cost.clay
-            || village.iron < total_cost.iron
-            || village.crop < total_cost.crop
-        {
-            return Err(AppError::BadRequest("Not enough resources".into()));
-        }
-
-        // Deduct resources
-        VillageRepository::deduct_resources(
-            pool,
-            village_id,
-            total_cost.wood,
-            total_cost.clay,
-            total_cost.iron,
-            total_cost.crop,
-        )
-        .await?;
+        Self::pay_training_cost_tx(&mut tx, &village, &total_cost, starvation_guard).await?;
 
         // Calculate start and end time
         // If there's already a queue, start after the last item
         let now = Utc::now();
-        let started_at = TroopRepository::get_last_queue_end_time(pool, village_id)
+        let started_at = TroopRepository::get_last_queue_end_time_tx(&mut tx, village_id)
             .await?
             .unwrap_or(now);
         let ends_at = started_at + Duration::seconds(total_cost.time_seconds as i64);
 
         // Add to queue
-        let queue_entry = TroopRepository::add_to_queue(
-            pool,
+        let queue_entry = TroopRepository::add_to_queue_tx(
+            &mut tx,
             village_id,
             troop_type,
             count,
@@ -119,24 +101,94 @@ impl TroopService {
         )
         .await?;
 
+        tx.commit().await?;
+
         Ok(TrainTroopsResponse {
             queue_entry: queue_entry.into(),
             cost: total_cost,
         })
     }
 
+    /// Check the number of troops asked for in one training order
+    pub fn validate_training_count(count: i32) -> AppResult<()> {
+        if count <= 0 {
+            return Err(AppError::BadRequest("Count must be positive".into()));
+        }
+        if count > MAX_TRAINING_COUNT {
+            return Err(AppError::field(
+                "count",
+                "too_large",
+                format!("At most {} troops can be trained at once", MAX_TRAINING_COUNT),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Resources and time to train `count` troops
+    pub fn training_cost(definition: &TroopDefinition, count: i32) -> AppResult<TroopCost> {
+        let times = |per_troop: i32| {
+            per_troop
+                .checked_mul(count)
+                .ok_or_else(|| AppError::field("count", "cost_overflow", "Training cost is too large"))
+        };
+
+        Ok(TroopCost {
+            wood: times(definition.wood_cost)?,
+            clay: times(definition.clay_cost)?,
+            iron: times(definition.iron_cost)?,
+            crop: times(definition.crop_cost)?,
+            time_seconds: times(definition.training_time_seconds)?,
+        })
+    }
+
+    /// Deduct a training cost from a village locked FOR UPDATE in `tx`.
+    /// Resources escrowed by open sell orders cannot be spent on training.
+    pub async fn pay_training_cost_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        village: &Village,
+        cost: &TroopCost,
+        starvation_guard: bool,
+    ) -> AppResult<()> {
+        if starvation_guard {
+            VillageService::ensure_not_starving(village)?;
+        }
+
+        if village.wood < cost.wood
+            || village.clay < cost.clay
+            || village.iron < cost.iron
+            || village.crop < cost.crop
+        {
+            return Err(AppError::BadRequest("Not enough resources".into()));
+        }
+
+        let locked = TradeRepository::get_village_locked_resources_tx(tx, village.id).await?;
+
+        if !VillageRepository::deduct_resources_tx(
+            tx,
+            village.id,
+            cost.wood,
+            cost.clay,
+            cost.iron,
+            cost.crop,
+            locked,
+        )
+        .await?
+        {
+            return Err(AppError::BadRequest("Insufficient available resources".into()));
+        }
+
+        Ok(())
+    }
+
     /// Complete training from queue (called by background job)
     pub async fn complete_training(pool: &PgPool, queue_id: Uuid) -> AppResult<()> {
         // Get queue entry
         let queue = TroopRepository::get_queue_by_village(pool, Uuid::nil()).await?;
         let entry = queue.iter().find(|q| q.id == queue_id);
 
-        if let Some(entry) = entry {
-            // Add troops to village
-            TroopRepository::add_troops(pool, entry.village_id, entry.troop_type, entry.count).await?;
-
-            // Remove from queue
-            TroopRepository::remove_from_queue(pool, queue_id).await?;
+        if entry.is_some() {
+            TroopRepository::complete_training(pool, queue_id).await?;
         }
 
         Ok(())
@@ -148,11 +200,7 @@ impl TroopService {
         let count = completed.len() as i32;
 
         for entry in completed {
-            // Add troops to village
-            TroopRepository::add_troops(pool, entry.village_id, entry.troop_type, entry.count).await?;
-
-            // Remove from queue
-            TroopRepository::remove_from_queue(pool, entry.id).await?;
+            TroopRepository::complete_training(pool, entry.id).await?;
         }
 
         Ok(count)
@@ -201,3 +249,75 @@ impl TroopService {
         TroopRepository::get_total_crop_consumption(pool, village_id).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::building::BuildingType;
+    use crate::services::shop_service::ShopService;
+    use crate::test_support;
+
+    #[sqlx::test]
+    async fn training_cannot_spend_resources_locked_by_sell_orders(pool: PgPool) {
+        let user_id = test_support::create_user(&pool, 0).await;
+        let village = test_support::create_village(&pool, user_id, 0, 0).await;
+        test_support::add_building(&pool, village.id, BuildingType::Barracks, 19, 1).await;
+
+        // 500 iron on hand, but an open sell order holds 400 of it
+        sqlx::query(
+            "INSERT INTO resource_locks (village_id, lock_type, reference_id, iron) VALUES ($1, 'trade_order', $2, 400)",
+        )
+        .bind(village.id)
+        .bind(Uuid::new_v4())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // One infantry needs 150 iron: affordable from the raw balance, not from what is free
+        let result = TroopService::train_troops(&pool, village.id, TroopType::Infantry, 1, true).await;
+        match result {
+            Err(AppError::BadRequest(message)) => assert_eq!(message, "Insufficient available resources"),
+            other => panic!("expected insufficient available resources, got {:?}", other.map(|r| r.cost)),
+        }
+
+        let after = test_support::village(&pool, village.id).await;
+        assert_eq!(after.iron, 500);
+        assert!(TroopRepository::get_queue_by_village(&pool, village.id).await.unwrap().is_empty());
+
+        // Once the order is gone the same training goes through
+        sqlx::query("UPDATE resource_locks SET released_at = NOW() WHERE village_id = $1")
+            .bind(village.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        TroopService::train_troops(&pool, village.id, TroopType::Infantry, 1, true).await.unwrap();
+        assert_eq!(TroopRepository::get_queue_by_village(&pool, village.id).await.unwrap().len(), 1);
+    }
+
+    #[sqlx::test]
+    async fn oversized_training_orders_are_rejected_without_overflow(pool: PgPool) {
+        let infantry = TroopRepository::get_definition(&pool, TroopType::Infantry).await.unwrap().unwrap();
+
+        assert_eq!(TroopService::training_cost(&infantry, 2).unwrap().wood, 240);
+        // 120 wood times 20 million is past i32
+        match TroopService::training_cost(&infantry, 20_000_000) {
+            Err(AppError::ValidationError(errors)) => assert_eq!(errors[0].code, "cost_overflow"),
+            other => panic!("expected a cost overflow, got {:?}", other),
+        }
+
+        TroopService::validate_training_count(MAX_TRAINING_COUNT).unwrap();
+        for count in [MAX_TRAINING_COUNT + 1, i32::MAX] {
+            match TroopService::validate_training_count(count) {
+                Err(AppError::ValidationError(errors)) => assert_eq!(errors[0].code, "too_large"),
+                other => panic!("expected the count to be capped, got {:?}", other),
+            }
+        }
+
+        // Instant training is refused before any gold is priced
+        let user_id = test_support::create_user(&pool, 100).await;
+        let village = test_support::create_village(&pool, user_id, 0, 0).await;
+        let result = ShopService::use_instant_train(&pool, user_id, village.id, TroopType::Infantry, i32::MAX, true).await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+        assert_eq!(test_support::gold_balance(&pool, user_id).await, 100);
+    }
+}
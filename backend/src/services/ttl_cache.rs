@@ -0,0 +1,86 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use crate::error::AppResult;
+
+struct Cached<T> {
+    value: T,
+    cached_at: Instant,
+}
+
+/// A single value kept in memory for a fixed time.
+/// Concurrent callers that find it stale wait for one rebuild instead of each running their own.
+#[derive(Clone)]
+pub struct TtlCache<T> {
+    entry: Arc<RwLock<Option<Cached<T>>>>,
+    ttl: Duration,
+}
+
+impl<T: Clone> TtlCache<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entry: Arc::new(RwLock::new(None)),
+            ttl,
+        }
+    }
+
+    /// Return the cached value, rebuilding it with `fetch` when missing or stale
+    pub async fn get_or_refresh<F, Fut>(&self, fetch: F) -> AppResult<T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = AppResult<T>>,
+    {
+        if let Some(cached) = self.entry.read().await.as_ref() {
+            if cached.cached_at.elapsed() < self.ttl {
+                return Ok(cached.value.clone());
+            }
+        }
+
+        let mut entry = self.entry.write().await;
+
+        // Another request may have refreshed it while we waited for the lock
+        if let Some(cached) = entry.as_ref() {
+            if cached.cached_at.elapsed() < self.ttl {
+                return Ok(cached.value.clone());
+            }
+        }
+
+        let value = fetch().await?;
+
+        *entry = Some(Cached {
+            value: value.clone(),
+            cached_at: Instant::now(),
+        });
+
+        Ok(value)
+    }
+
+    /// Drop the cached value so the next caller rebuilds it
+    pub async fn invalidate(&self) {
+        *self.entry.write().await = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn value_is_rebuilt_after_the_ttl_or_an_invalidate() {
+        let cache = TtlCache::new(Duration::from_millis(100));
+        let fetches = AtomicU32::new(0);
+        let fetch = || async { Ok(fetches.fetch_add(1, Ordering::SeqCst) + 1) };
+
+        assert_eq!(cache.get_or_refresh(fetch).await.unwrap(), 1);
+        assert_eq!(cache.get_or_refresh(fetch).await.unwrap(), 1);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(cache.get_or_refresh(fetch).await.unwrap(), 2);
+
+        cache.invalidate().await;
+        assert_eq!(cache.get_or_refresh(fetch).await.unwrap(), 3);
+    }
+}
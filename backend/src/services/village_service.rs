@@ -1,33 +1,131 @@
-use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Duration, Utc};
+
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
-use crate::error::AppResult;
+use crate::error::{AppError, AppResult};
 use crate::models::building::{Building, BuildingType, CreateBuilding};
-use crate::models::village::{CreateVillage, Village};
+use crate::models::pagination::{PageQuery, Paginated};
+use crate::models::trade::Resources;
+use crate::models::village::{
+    ArmySlots, CreateVillage, ExpansionOverviewResponse, ResourceAccounting, Village,
+    VillageExpansionSlots, VillageResponse,
+};
+use crate::repositories::army_repo::ArmyRepository;
 use crate::repositories::building_repo::BuildingRepository;
+use crate::repositories::trade_repo::TradeRepository;
+use crate::repositories::user_repo::UserRepository;
 use crate::repositories::village_repo::VillageRepository;
+use crate::services::army_service::ArmyService;
+use crate::services::resource_service::ResourceService;
+
+/// Maximum number of villages per batch detail request
+const MAX_DETAIL_VILLAGES: usize = 100;
+
+/// Maximum villages per page of the paginated village list
+const MAX_VILLAGES_PER_PAGE: i32 = 100;
+
+/// Sort keys accepted by the village list
+const VILLAGE_SORT_KEYS: &[&str] = &["capital", "population", "created_at", "net_crop"];
+
+/// Maximum length of a village note in characters
+const MAX_NOTE_LENGTH: usize = 500;
 
 pub struct VillageService;
 
 impl VillageService {
+    /// Validate and normalize a village name for a player.
+    /// Returns the trimmed name.
+    pub async fn validate_name(
+        pool: &PgPool,
+        user_id: Uuid,
+        name: &str,
+        exclude_village_id: Option<Uuid>,
+    ) -> AppResult<String> {
+        let name = name.trim();
+
+        // Validate name length
+        let length = name.chars().count();
+        if !(1..=30).contains(&length) {
+            return Err(AppError::BadRequest("Village name must be 1-30 characters".into()));
+        }
+
+        if name.chars().any(|c| c.is_control()) {
+            return Err(AppError::BadRequest("Village name contains invalid characters".into()));
+        }
+
+        // Names must be unique among the player's own villages
+        if VillageRepository::is_name_taken_by_user(pool, user_id, name, exclude_village_id).await? {
+            return Err(AppError::BadRequest("You already have a village with this name".into()));
+        }
+
+        Ok(name.to_string())
+    }
+
+    /// Clean up a village note: control characters other than line breaks are
+    /// stripped and the result must fit in MAX_NOTE_LENGTH characters
+    pub fn sanitize_note(note: &str) -> AppResult<String> {
+        let note: String = note
+            .chars()
+            .filter(|c| *c == '\n' || !c.is_control())
+            .collect();
+        let note = note.trim();
+
+        if note.chars().count() > MAX_NOTE_LENGTH {
+            return Err(AppError::BadRequest(format!(
+                "Village note cannot exceed {} characters",
+                MAX_NOTE_LENGTH
+            )));
+        }
+
+        Ok(note.to_string())
+    }
+
     /// Create a new village with initial buildings
+    /// A player's first village starts their beginner protection.
     pub async fn create_village_with_buildings(
         pool: &PgPool,
         input: CreateVillage,
+        protection_hours: i64,
     ) -> AppResult<(Village, Vec<Building>)> {
-        // Create village
-        let village = VillageRepository::create(pool, input).await?;
+        let mut tx = pool.begin().await?;
+
+        if input.is_capital && protection_hours > 0 {
+            let until = Utc::now() + Duration::hours(protection_hours);
+            UserRepository::start_protection_tx(&mut tx, input.user_id, until).await?;
+        }
+
+        // Create village (the UNIQUE(x, y) constraint catches concurrent settles on the same tile)
+        let village = VillageRepository::create_tx(&mut tx, input)
+            .await
+            .map_err(Self::map_coordinate_conflict)?;
 
         // Create initial buildings
-        let buildings = Self::create_initial_buildings(pool, village.id).await?;
+        let buildings = Self::create_initial_buildings(&mut tx, village.id).await?;
+
+        tx.commit().await?;
 
         Ok((village, buildings))
     }
 
+    /// Translate a unique violation on village coordinates into a Conflict error
+    fn map_coordinate_conflict(err: AppError) -> AppError {
+        match &err {
+            AppError::DatabaseError(sqlx::Error::Database(db_err))
+                if db_err.is_unique_violation() && db_err.constraint() == Some("villages_x_y_key") =>
+            {
+                AppError::Conflict("Coordinates already occupied".into())
+            }
+            _ => err,
+        }
+    }
+
     /// Create initial buildings for a new village
     /// Based on Travian's starting layout
     async fn create_initial_buildings(
-        pool: &PgPool,
+        tx: &mut Transaction<'_, Postgres>,
         village_id: Uuid,
     ) -> AppResult<Vec<Building>> {
         let mut buildings = Vec::new();
@@ -40,7 +138,7 @@ impl VillageService {
         ];
 
         for (slot, building_type, level) in village_buildings {
-            let building = create_building_with_level(pool, village_id, slot, building_type, level).await?;
+            let building = create_building_with_level(tx, village_id, slot, building_type, level).await?;
             buildings.push(building);
         }
 
@@ -72,13 +170,313 @@ impl VillageService {
         ];
 
         for (slot, building_type) in resource_fields {
-            let building = create_building_with_level(pool, village_id, slot, building_type, 0).await?;
+            let building = create_building_with_level(tx, village_id, slot, building_type, 0).await?;
             buildings.push(building);
         }
 
         Ok(buildings)
     }
 
+    /// List a player's villages with production, filtered and sorted.
+    /// `sort` must be one of `VILLAGE_SORT_KEYS`; "capital" puts the capital first,
+    /// then the oldest villages.
+    pub async fn list_villages(
+        pool: &PgPool,
+        user_id: Uuid,
+        sort: &str,
+        descending: bool,
+        capital_only: bool,
+        under_attack: bool,
+    ) -> AppResult<Vec<VillageResponse>> {
+        if !VILLAGE_SORT_KEYS.contains(&sort) {
+            return Err(AppError::BadRequest(format!(
+                "Invalid sort key '{}'. Allowed: {}",
+                sort,
+                VILLAGE_SORT_KEYS.join(", ")
+            )));
+        }
+
+        let mut villages = VillageRepository::find_by_user_id(pool, user_id).await?;
+
+        if capital_only {
+            villages.retain(|v| v.is_capital);
+        }
+        if under_attack {
+            let attacked: HashSet<Uuid> = ArmyRepository::find_villages_under_attack(pool, user_id)
+                .await?
+                .into_iter()
+                .collect();
+            villages.retain(|v| attacked.contains(&v.id));
+        }
+
+        let ids: Vec<Uuid> = villages.iter().map(|v| v.id).collect();
+        let mut buildings_by_village: HashMap<Uuid, Vec<Building>> = HashMap::new();
+        for building in BuildingRepository::find_by_village_ids(pool, &ids).await? {
+            buildings_by_village.entry(building.village_id).or_default().push(building);
+        }
+
+        let boosts = ResourceService::production_boosts(pool, &ids).await?;
+
+        let mut rows: Vec<_> = villages
+            .into_iter()
+            .map(|v| {
+                let buildings = buildings_by_village.get(&v.id).map(Vec::as_slice).unwrap_or(&[]);
+                let village_boosts = boosts.get(&v.id).copied().unwrap_or_default();
+                let production = ResourceService::production_from_buildings(v.population, buildings, &village_boosts);
+                (v, production)
+            })
+            .collect();
+
+        match sort {
+            "population" => rows.sort_by_key(|(v, _)| v.population),
+            "created_at" => rows.sort_by_key(|(v, _)| v.created_at),
+            "net_crop" => rows.sort_by_key(|(_, p)| p.net_crop_per_hour),
+            _ => rows.sort_by_key(|(v, _)| (!v.is_capital, v.created_at)),
+        }
+        if descending {
+            rows.reverse();
+        }
+
+        Ok(rows
+            .into_iter()
+            .map(|(v, production)| VillageResponse::from(v).with_production(production.into()))
+            .collect())
+    }
+
+    /// `list_villages` as one page with total count
+    pub async fn list_villages_page(
+        pool: &PgPool,
+        user_id: Uuid,
+        sort: &str,
+        descending: bool,
+        capital_only: bool,
+        under_attack: bool,
+        page: &PageQuery,
+    ) -> AppResult<Paginated<VillageResponse>> {
+        let (page, limit, offset) = page.resolve(MAX_VILLAGES_PER_PAGE);
+        let villages = Self::list_villages(pool, user_id, sort, descending, capital_only, under_attack).await?;
+        let total = villages.len() as i64;
+        let items = villages.into_iter().skip(offset as usize).take(limit as usize).collect();
+
+        Ok(Paginated::new(items, total, page, limit))
+    }
+
+    /// Load, update and describe several of a player's villages at once.
+    /// Every id must belong to the player.
+    pub async fn get_villages_detail(
+        pool: &PgPool,
+        user_id: Uuid,
+        ids: &[Uuid],
+    ) -> AppResult<Vec<VillageResponse>> {
+        let mut seen = HashSet::new();
+        let ids: Vec<Uuid> = ids.iter().copied().filter(|id| seen.insert(*id)).collect();
+
+        if ids.is_empty() {
+            return Err(AppError::BadRequest("At least one village id is required".into()));
+        }
+        if ids.len() > MAX_DETAIL_VILLAGES {
+            return Err(AppError::BadRequest(format!(
+                "At most {} villages can be requested at once",
+                MAX_DETAIL_VILLAGES
+            )));
+        }
+
+        let villages = VillageRepository::find_by_ids_for_user(pool, user_id, &ids).await?;
+        if villages.len() != ids.len() {
+            return Err(AppError::Forbidden("Access denied".into()));
+        }
+
+        let mut buildings_by_village: HashMap<Uuid, Vec<Building>> = HashMap::new();
+        for building in BuildingRepository::find_by_village_ids(pool, &ids).await? {
+            buildings_by_village.entry(building.village_id).or_default().push(building);
+        }
+
+        let boosts = ResourceService::production_boosts(pool, &ids).await?;
+
+        let production: Vec<_> = villages
+            .iter()
+            .map(|v| {
+                let buildings = buildings_by_village.get(&v.id).map(Vec::as_slice).unwrap_or(&[]);
+                let village_boosts = boosts.get(&v.id).copied().unwrap_or_default();
+                ResourceService::production_from_buildings(v.population, buildings, &village_boosts)
+            })
+            .collect();
+
+        // Accrue resources for all villages in one statement (net crop accounts for population)
+        let village_ids: Vec<Uuid> = villages.iter().map(|v| v.id).collect();
+        let wood: Vec<i32> = production.iter().map(|p| p.wood_per_hour).collect();
+        let clay: Vec<i32> = production.iter().map(|p| p.clay_per_hour).collect();
+        let iron: Vec<i32> = production.iter().map(|p| p.iron_per_hour).collect();
+        let crop: Vec<i32> = production.iter().map(|p| p.net_crop_per_hour).collect();
+        let mut updated: HashMap<Uuid, Village> =
+            VillageRepository::accrue_resources_batch(pool, &village_ids, &wood, &clay, &iron, &crop)
+                .await?
+                .into_iter()
+                .map(|v| (v.id, v))
+                .collect();
+
+        let outgoing: HashMap<Uuid, i64> = ArmyRepository::count_outgoing_by_villages(pool, &village_ids)
+            .await?
+            .into_iter()
+            .collect();
+
+        let details = villages
+            .into_iter()
+            .zip(production)
+            .map(|(village, production)| {
+                let rally_point_level = buildings_by_village
+                    .get(&village.id)
+                    .into_iter()
+                    .flatten()
+                    .filter(|b| b.building_type == BuildingType::RallyPoint)
+                    .map(|b| b.level)
+                    .max()
+                    .unwrap_or(0);
+                let army_slots = ArmySlots {
+                    used: outgoing.get(&village.id).copied().unwrap_or(0) as i32,
+                    total: ArmyService::max_outgoing_armies(rally_point_level),
+                };
+                // Nothing elapsed for villages missing from the update; use what was loaded
+                let village = updated.remove(&village.id).unwrap_or(village);
+
+                VillageResponse::from(village)
+                    .with_production(production.into())
+                    .with_army_slots(army_slots)
+            })
+            .collect();
+
+        Ok(details)
+    }
+
+    /// Culture points needed to found the next village when already owning `village_count`
+    pub fn culture_points_for_next_village(village_count: i64) -> i64 {
+        2000 * village_count * village_count
+    }
+
+    /// Settlement slots granted by a Residence (levels 10 and 20)
+    /// or a Palace (levels 10, 15 and 20)
+    pub fn settlement_slots(residence_level: i32, palace_level: i32) -> i32 {
+        let residence = match residence_level {
+            20.. => 2,
+            10.. => 1,
+            _ => 0,
+        };
+        let palace = match palace_level {
+            20.. => 3,
+            15.. => 2,
+            10.. => 1,
+            _ => 0,
+        };
+        residence.max(palace)
+    }
+
+    /// Culture points, settlement slots and which villages can send settlers
+    pub async fn expansion_overview(pool: &PgPool, user_id: Uuid) -> AppResult<ExpansionOverviewResponse> {
+        let villages = VillageRepository::find_by_user_id(pool, user_id).await?;
+        let village_ids: Vec<Uuid> = villages.iter().map(|v| v.id).collect();
+
+        let buildings = BuildingRepository::find_by_village_ids(pool, &village_ids).await?;
+        let settling: HashMap<Uuid, i64> = ArmyRepository::count_settle_missions_by_villages(pool, &village_ids)
+            .await?
+            .into_iter()
+            .collect();
+
+        let level_of = |village_id: Uuid, building_type: BuildingType| {
+            buildings
+                .iter()
+                .filter(|b| b.village_id == village_id && b.building_type == building_type)
+                .map(|b| b.level)
+                .max()
+                .unwrap_or(0)
+        };
+
+        let slots: Vec<VillageExpansionSlots> = villages
+            .iter()
+            .map(|village| {
+                let residence_level = level_of(village.id, BuildingType::Residence);
+                let palace_level = level_of(village.id, BuildingType::Palace);
+                let total_slots = Self::settlement_slots(residence_level, palace_level);
+                let used_slots = settling.get(&village.id).copied().unwrap_or(0) as i32;
+
+                VillageExpansionSlots {
+                    village_id: village.id,
+                    name: village.name.clone(),
+                    residence_level,
+                    palace_level,
+                    total_slots,
+                    used_slots,
+                    available_slots: (total_slots - used_slots).max(0),
+                }
+            })
+            .collect();
+
+        let culture_points: i64 = villages.iter().map(|v| v.culture_points as i64).sum();
+        let village_count = villages.len() as i64;
+        let needed = Self::culture_points_for_next_village(village_count);
+        let eligible_village_ids: Vec<Uuid> = slots
+            .iter()
+            .filter(|s| s.available_slots > 0)
+            .map(|s| s.village_id)
+            .collect();
+
+        Ok(ExpansionOverviewResponse {
+            culture_points,
+            village_count: village_count as i32,
+            culture_points_for_next_village: needed,
+            culture_points_missing: (needed - culture_points).max(0),
+            can_found_village: culture_points >= needed && !eligible_village_ids.is_empty(),
+            eligible_village_ids,
+            villages: slots,
+        })
+    }
+
+    /// Split a village's resources into what is committed and what can still be spent.
+    /// Upgrade costs are deducted when construction starts, so they are reported separately
+    /// from the balance; trade locks are still part of the balance and are subtracted from it.
+    pub async fn resource_accounting(pool: &PgPool, village: &Village) -> AppResult<ResourceAccounting> {
+        let upgrading = BuildingRepository::find_upgrading_by_village(pool, village.id).await?;
+        let in_construction = upgrading.iter().fold(Resources::default(), |mut acc, b| {
+            let cost = b.building_type.cost_at_level(b.level + 1);
+            acc.wood += cost.wood;
+            acc.clay += cost.clay;
+            acc.iron += cost.iron;
+            acc.crop += cost.crop;
+            acc
+        });
+
+        let (wood, clay, iron, crop) = TradeRepository::get_village_locked_resources(pool, village.id).await?;
+        let locked_by_trade = Resources {
+            wood: wood as i32,
+            clay: clay as i32,
+            iron: iron as i32,
+            crop: crop as i32,
+        };
+
+        let available = Resources {
+            wood: (village.wood - locked_by_trade.wood).max(0),
+            clay: (village.clay - locked_by_trade.clay).max(0),
+            iron: (village.iron - locked_by_trade.iron).max(0),
+            crop: (village.crop - locked_by_trade.crop).max(0),
+        };
+
+        Ok(ResourceAccounting {
+            in_construction,
+            locked_by_trade,
+            available,
+        })
+    }
+
+    /// Reject new outgoing commitments (trades, transfers, training) from a starving village
+    pub fn ensure_not_starving(village: &Village) -> AppResult<()> {
+        if village.crop <= 0 {
+            return Err(AppError::BadRequest(format!(
+                "{} is starving (crop: {}); produce or receive crop before committing resources",
+                village.name, village.crop
+            )));
+        }
+        Ok(())
+    }
+
     /// Find a random available coordinate for new village
     pub async fn find_available_coordinates(
         pool: &PgPool,
@@ -108,7 +506,7 @@ impl VillageService {
 }
 
 async fn create_building_with_level(
-    pool: &PgPool,
+    tx: &mut Transaction<'_, Postgres>,
     village_id: Uuid,
     slot: i32,
     building_type: BuildingType,
@@ -120,28 +518,23 @@ async fn create_building_with_level(
         slot,
     };
 
-    // Create building (starts at level 1 by default)
-    let building = BuildingRepository::create(pool, create).await?;
-
-    // If level is different, update it
-    if level != 1 {
-        // For level 0, we need to set it directly
-        let updated = sqlx::query_as::<_, Building>(
-            r#"
-            UPDATE buildings
-            SET level = $2, updated_at = NOW()
-            WHERE id = $1
-            RETURNING id, village_id, building_type, slot, level,
-                      is_upgrading, upgrade_ends_at, created_at, updated_at
-            "#,
-        )
-        .bind(building.id)
-        .bind(level)
-        .fetch_one(pool)
-        .await?;
-
-        return Ok(updated);
-    }
+    BuildingRepository::create_with_level_tx(tx, create, level).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    #[sqlx::test]
+    async fn settling_an_occupied_tile_is_a_conflict(pool: PgPool) {
+        let first_id = test_support::create_user(&pool, 0).await;
+        test_support::create_village(&pool, first_id, 3, 4).await;
 
-    Ok(building)
+        let second_id = test_support::create_user(&pool, 0).await;
+        let input = CreateVillage { user_id: second_id, name: "Late Arrival".into(), x: 3, y: 4, is_capital: false };
+        let result = VillageService::create_village_with_buildings(&pool, input, 0).await;
+
+        assert!(matches!(result, Err(AppError::Conflict(message)) if message == "Coordinates already occupied"));
+    }
 }
@@ -5,6 +5,9 @@ use tokio::sync::{mpsc, RwLock};
 use tracing::{debug, error, info};
 use uuid::Uuid;
 
+use crate::models::army::ArmyTroops;
+use crate::models::shop::SubscriptionType;
+
 /// Message types for WebSocket events
 #[derive(Debug, Clone, serde::Serialize)]
 #[serde(tag = "type", content = "data")]
@@ -15,10 +18,19 @@ pub enum WsEvent {
     BuildingComplete(BuildingCompleteData),
     ArmyArrived(ArmyArrivedData),
     AttackIncoming(AttackIncomingData),
+    AttackLanded(AttackLandedData),
+    AllianceAttackAlert(AllianceAttackAlertData),
     TroopTrainingComplete(TroopTrainingCompleteData),
     TroopsStarved(TroopsStarvedData),
     TradeOrderExpired(TradeOrderExpiredData),
-    Connected { user_id: Uuid },
+    ResourcesDelivered(ResourcesDeliveredData),
+    NewMessage(NewMessageData),
+    GoldGiftReceived(GoldGiftData),
+    SubscriptionExpiring(SubscriptionExpiryData),
+    SubscriptionExpired(SubscriptionExpiryData),
+    Connected { user_id: Uuid, expires_at: chrono::DateTime<chrono::Utc> },
+    SessionRefreshed { expires_at: chrono::DateTime<chrono::Utc> },
+    SessionExpired,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -52,7 +64,30 @@ pub struct ArmyArrivedData {
 
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct AttackIncomingData {
+    pub army_id: Uuid,
     pub target_village_id: Uuid,
+    pub target_village_name: String,
+    pub mission: String,
+    pub arrival_time: chrono::DateTime<chrono::Utc>,
+    /// Fuzzed troop counts, only when the defender's Rally Point is high enough
+    pub estimated_troops: Option<ArmyTroops>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AttackLandedData {
+    pub army_id: Uuid,
+    pub target_village_id: Uuid,
+    pub mission: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AllianceAttackAlertData {
+    pub target_village_id: Uuid,
+    pub target_village_name: String,
+    pub x: i32,
+    pub y: i32,
+    pub defender_id: Uuid,
+    pub defender_name: String,
     pub arrival_time: chrono::DateTime<chrono::Utc>,
 }
 
@@ -79,8 +114,42 @@ pub struct TradeOrderExpiredData {
     pub refunded_gold: Option<i32>,
 }
 
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ResourcesDeliveredData {
+    pub delivery_id: Uuid,
+    pub village_id: Uuid,
+    pub resource_type: String,
+    pub quantity: i32,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct NewMessageData {
+    pub message_id: Uuid,
+    pub conversation_id: Uuid,
+    pub sender_id: Uuid,
+    pub sender_name: String,
+    pub subject: String,
+    /// Recipient muted the conversation, the client should not ping
+    pub muted: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct GoldGiftData {
+    pub sender_id: Uuid,
+    pub sender_name: String,
+    pub amount: i32,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SubscriptionExpiryData {
+    pub subscription_type: SubscriptionType,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
 /// Connection info for a single WebSocket connection
 struct Connection {
+    id: Uuid,
     sender: mpsc::UnboundedSender<Message>,
 }
 
@@ -99,28 +168,28 @@ impl WsManager {
         }
     }
 
-    /// Register a new connection for a user
-    pub async fn register(&self, user_id: Uuid) -> mpsc::UnboundedReceiver<Message> {
+    /// Register a new connection for a user.
+    /// Returns the connection id (needed to unregister) and the receiving end of its channel.
+    pub async fn register(&self, user_id: Uuid) -> (Uuid, mpsc::UnboundedReceiver<Message>) {
         let (tx, rx) = mpsc::unbounded_channel();
+        let connection_id = Uuid::new_v4();
 
         let mut connections = self.connections.write().await;
         let user_connections = connections.entry(user_id).or_insert_with(Vec::new);
-        user_connections.push(Connection { sender: tx });
+        user_connections.push(Connection { id: connection_id, sender: tx });
 
         info!("WebSocket connected: user_id={}, total_connections={}", user_id, user_connections.len());
 
-        rx
+        (connection_id, rx)
     }
 
     /// Remove a connection for a user
-    pub async fn unregister(&self, user_id: Uuid, connection_index: usize) {
+    pub async fn unregister(&self, user_id: Uuid, connection_id: Uuid) {
         let mut connections = self.connections.write().await;
 
         if let Some(user_connections) = connections.get_mut(&user_id) {
-            if connection_index < user_connections.len() {
-                user_connections.remove(connection_index);
-                info!("WebSocket disconnected: user_id={}, remaining={}", user_id, user_connections.len());
-            }
+            user_connections.retain(|c| c.id != connection_id);
+            info!("WebSocket disconnected: user_id={}, remaining={}", user_id, user_connections.len());
 
             if user_connections.is_empty() {
                 connections.remove(&user_id);
@@ -177,6 +246,11 @@ impl WsManager {
         }
     }
 
+    /// Check whether a user has at least one open connection
+    pub async fn is_online(&self, user_id: Uuid) -> bool {
+        self.connections.read().await.contains_key(&user_id)
+    }
+
     /// Get count of connected users
     pub async fn connected_users_count(&self) -> usize {
         self.connections.read().await.len()
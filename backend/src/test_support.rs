@@ -0,0 +1,116 @@
+//! Fixtures for database-backed tests (`#[sqlx::test]`), which run against a fresh
+//! database migrated from `migrations/` and need `DATABASE_URL` to point at a server.
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::GameConfig;
+use crate::models::building::BuildingType;
+use crate::models::village::Village;
+use crate::repositories::user_repo::UserRepository;
+
+/// Game settings with the same defaults `Config::from_env` falls back to
+pub fn game_config() -> GameConfig {
+    GameConfig {
+        hero_regen_on_adventure: true,
+        alliance_alert_radius: 20.0,
+        trade_price_band_percent: 50,
+        trade_expiry_message_on_sell: true,
+        battle_report_retention_days: 30,
+        nap_allows_raids: false,
+        invitation_cancel_notify: true,
+        starvation_guard: true,
+        beginner_protection_hours: 72,
+        beginner_protection_blocks_raids: true,
+        trade_rate_limit_actions: 20,
+        trade_rate_limit_window_secs: 60,
+        trade_default_expiry_hours: 168,
+        hero_auto_revive_delay_hours: 12,
+        hero_auto_revive_health: 50,
+        wash_trade_exclude_same_ip: true,
+        wash_trade_max_pair_trades_per_day: 10,
+    }
+}
+
+/// Insert a player with the given gold balance
+pub async fn create_user(pool: &PgPool, gold_balance: i32) -> Uuid {
+    let uid = Uuid::new_v4();
+    let (id,): (Uuid,) = sqlx::query_as(
+        r#"
+        INSERT INTO users (firebase_uid, email, display_name, gold_balance)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+    )
+    .bind(uid.to_string())
+    .bind(format!("{}@test.local", uid))
+    .bind(format!("player-{}", &uid.to_string()[..8]))
+    .bind(gold_balance)
+    .fetch_one(pool)
+    .await
+    .expect("insert user");
+
+    id
+}
+
+/// Delete a player's account the way the account deletion endpoint does
+pub async fn delete_user(pool: &PgPool, user_id: Uuid) {
+    let user = UserRepository::find_by_id(pool, user_id).await.unwrap().expect("user exists");
+    UserRepository::soft_delete(pool, &user.firebase_uid).await.expect("delete user");
+}
+
+/// Insert a village with 500 of each resource and the default 800 storage
+pub async fn create_village(pool: &PgPool, user_id: Uuid, x: i32, y: i32) -> Village {
+    sqlx::query_as::<_, Village>(
+        r#"
+        INSERT INTO villages (user_id, name, x, y, is_capital)
+        VALUES ($1, $2, $3, $4, TRUE)
+        RETURNING *
+        "#,
+    )
+    .bind(user_id)
+    .bind(format!("Village {}|{}", x, y))
+    .bind(x)
+    .bind(y)
+    .fetch_one(pool)
+    .await
+    .expect("insert village")
+}
+
+/// Insert a finished building at a slot
+pub async fn add_building(pool: &PgPool, village_id: Uuid, building_type: BuildingType, slot: i32, level: i32) -> Uuid {
+    let (id,): (Uuid,) = sqlx::query_as(
+        r#"
+        INSERT INTO buildings (village_id, building_type, slot, level)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+    )
+    .bind(village_id)
+    .bind(building_type)
+    .bind(slot)
+    .bind(level)
+    .fetch_one(pool)
+    .await
+    .expect("insert building");
+
+    id
+}
+
+pub async fn gold_balance(pool: &PgPool, user_id: Uuid) -> i32 {
+    let (gold,): (i32,) = sqlx::query_as("SELECT gold_balance FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .expect("read gold balance");
+
+    gold
+}
+
+pub async fn village(pool: &PgPool, village_id: Uuid) -> Village {
+    sqlx::query_as::<_, Village>("SELECT * FROM villages WHERE id = $1")
+        .bind(village_id)
+        .fetch_one(pool)
+        .await
+        .expect("read village")
+}
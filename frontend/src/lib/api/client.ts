@@ -2,6 +2,15 @@ import { auth } from "../firebase/config";
 
 const BASE_URL = import.meta.env.VITE_API_URL || 'http://localhost:8080';
 
+// Envelope returned by paginated list endpoints (`/paged` routes)
+export interface Paginated<T> {
+    items: T[];
+    total: number;
+    page: number;
+    limit: number;
+    has_more: boolean;
+}
+
 type RequestMethod = 'GET' | 'POST' | 'PUT' | 'DELETE';
 
 interface RequestOptions {
@@ -25,6 +25,9 @@ export interface ServerStatsResponse {
     total_villages: number;
     total_alliances: number;
     total_battles_today: number;
+    total_trade_fees_collected: number;
+    trade_fees_collected_24h: number;
+    trade_fees_collected_7d: number;
 }
 
 export interface AdminVillageResponse {
@@ -70,6 +73,28 @@ export interface AdjustResourcesRequest {
     reason: string;
 }
 
+export interface DeleteVillageResponse {
+    village_id: string;
+    name: string;
+    owner_id: string;
+    cancelled_orders: number;
+    redirected_deliveries: number;
+    refunded_deliveries: number;
+    refunded_gold: number;
+    removed: {
+        buildings: number;
+        troops: number;
+        troop_queue: number;
+        armies_removed: number;
+        armies_sent_home: number;
+        battle_reports_detached: number;
+        scout_reports_detached: number;
+        trade_transactions_detached: number;
+        heroes_rehomed: number;
+        heroes_removed: number;
+    };
+}
+
 interface AdminState {
     users: AdminUserResponse[];
     selectedUser: PlayerDetailResponse | null;
@@ -4,7 +4,7 @@ import { api } from "../api/client";
 
 // Enums
 export type AllianceRole = 'leader' | 'officer' | 'member';
-export type InvitationStatus = 'pending' | 'accepted' | 'rejected' | 'expired';
+export type InvitationStatus = 'pending' | 'accepted' | 'rejected' | 'expired' | 'cancelled';
 export type DiplomacyStatus = 'neutral' | 'ally' | 'nap' | 'enemy';
 
 // Types
@@ -38,6 +38,26 @@ export interface AllianceMember {
     joined_at: string;
 }
 
+export interface AllianceMemberStats {
+    user_id: string;
+    player_name: string | null;
+    role: AllianceRole;
+    population: number;
+    villages_gained: number;
+    attack_points: number;
+    defense_points: number;
+    treasury_gold: number;
+    treasury_resources: number;
+    total_contribution: number;
+}
+
+export interface AllianceStats {
+    alliance_id: string;
+    days: number;
+    since: string;
+    members: AllianceMemberStats[];
+}
+
 export interface AllianceInvitation {
     id: string;
     alliance_id: string;
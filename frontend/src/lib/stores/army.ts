@@ -36,7 +36,7 @@ export interface BattleReport {
     id: string;
     attacker_player_id: string;
     defender_player_id: string | null;
-    attacker_village_id: string;
+    attacker_village_id: string | null;
     defender_village_id: string | null;
     mission: MissionType;
     attacker_troops: TroopCounts;
@@ -47,13 +47,14 @@ export interface BattleReport {
     winner: 'attacker' | 'defender' | 'draw';
     occurred_at: string;
     is_read: boolean;
+    archived: boolean;
 }
 
 export interface ScoutReport {
     id: string;
     attacker_player_id: string;
     defender_player_id: string | null;
-    attacker_village_id: string;
+    attacker_village_id: string | null;
     defender_village_id: string | null;
     attacker_scouts: number;
     defender_scouts: number;
@@ -8,8 +8,15 @@ export interface MapVillageInfo {
     player_name: string | null;
     population: number;
     is_own: boolean;
+    protection_until: string | null;
+    alliance_id: string | null;
+    alliance_tag: string | null;
+    relation: MapRelation;
 }
 
+// Viewer's standing toward a village owner, used to color the map
+export type MapRelation = 'own' | 'ally' | 'nap' | 'war' | 'neutral';
+
 export interface MapTile {
     x: number;
     y: number;
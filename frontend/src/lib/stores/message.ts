@@ -3,12 +3,13 @@ import { toast } from "svelte-sonner";
 import { api } from "../api/client";
 
 // Types
-export type MessageType = 'private' | 'alliance';
+export type MessageType = 'private' | 'alliance' | 'system';
 
 export interface Message {
     id: string;
     message_type: MessageType;
-    sender_id: string;
+    // null for system messages
+    sender_id: string | null;
     sender_name: string;
     recipient_id: string | null;
     recipient_name: string | null;
@@ -18,11 +19,32 @@ export interface Message {
     body: string;
     is_read: boolean;
     created_at: string;
+    // Alliance messages only, for leaders and officers
+    read_receipts?: MessageReadReceipts;
+    // Mentions resolved when the message was sent
+    entities?: MessageEntity[];
+}
+
+export type MessageEntity =
+    | {
+          type: 'coordinates';
+          text: string;
+          x: number;
+          y: number;
+          village_id: string | null;
+          village_name: string | null;
+          player_name: string | null;
+      }
+    | { type: 'player'; text: string; user_id: string; name: string };
+
+export interface MessageReadReceipts {
+    read_count: number;
+    member_count: number;
 }
 
 export interface MessageListItem {
     id: string;
-    sender_id: string;
+    sender_id: string | null;
     sender_name: string;
     subject: string;
     is_read: boolean;
@@ -37,6 +59,7 @@ export interface Conversation {
     last_message_preview: string | null;
     last_message_at: string;
     unread_count: number;
+    is_muted: boolean;
 }
 
 export interface SendMessageRequest {
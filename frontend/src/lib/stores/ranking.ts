@@ -48,6 +48,29 @@ export interface AllianceRanking {
     total_population: number;
 }
 
+export interface PlayerProfileVillage {
+    id: string;
+    name: string;
+    x: number;
+    y: number;
+    population: number;
+    is_capital: boolean;
+}
+
+export interface PlayerProfile {
+    user_id: string;
+    display_name: string | null;
+    alliance_id: string | null;
+    alliance_tag: string | null;
+    population: number;
+    village_count: number;
+    population_rank: number | null;
+    attack_points: number;
+    defense_points: number;
+    villages: PlayerProfileVillage[];
+    joined_at: string;
+}
+
 export interface RankingListResponse<T> {
     rankings: T[];
     total: number;
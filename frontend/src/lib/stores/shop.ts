@@ -4,9 +4,9 @@ import { api } from "../api/client";
 
 // Enums
 export type TransactionStatus = 'pending' | 'completed' | 'failed' | 'refunded';
-export type TransactionType = 'gold_purchase' | 'subscription' | 'gold_spend' | 'gold_refund' | 'gold_gift';
+export type TransactionType = 'gold_purchase' | 'subscription' | 'gold_spend' | 'gold_refund' | 'gold_gift' | 'alliance_treasury';
 export type SubscriptionType = 'travian_plus';
-export type GoldFeature = 'finish_now' | 'npc_merchant' | 'production_bonus' | 'book_of_wisdom' | 'artwork' | 'ointment' | 'plus_subscription' | 'hero_slot';
+export type GoldFeature = 'finish_now' | 'npc_merchant' | 'production_bonus' | 'book_of_wisdom' | 'artwork' | 'ointment' | 'plus_subscription' | 'hero_slot' | 'instant_train';
 
 // Types
 export interface GoldPackage {
@@ -57,6 +57,15 @@ export interface UseFeatureResponse {
     message: string;
 }
 
+export type FeaturePricing = 'fixed' | 'per_day' | 'per_five_minutes' | 'per_slot';
+
+export interface GoldFeatureListing {
+    feature: GoldFeature;
+    description: string | null;
+    pricing: FeaturePricing;
+    gold_cost: number;
+}
+
 // Request types
 export interface PurchaseGoldRequest {
     package_id: string;
@@ -383,6 +392,7 @@ export function getTransactionTypeLabel(type: TransactionType): string {
         gold_spend: 'Gold Spent',
         gold_refund: 'Refund',
         gold_gift: 'Gift',
+        alliance_treasury: 'Alliance Treasury',
     };
     return labels[type] || type;
 }
@@ -394,6 +404,7 @@ export function getTransactionTypeIcon(type: TransactionType): string {
         gold_spend: '🔻',
         gold_refund: '↩️',
         gold_gift: '🎁',
+        alliance_treasury: '🏛️',
     };
     return icons[type] || '💰';
 }
@@ -11,6 +11,9 @@ export type TradeOrderType = 'buy' | 'sell';
 // Order status
 export type TradeOrderStatus = 'open' | 'partially_filled' | 'filled' | 'cancelled' | 'expired';
 
+// How a new order interacts with the book
+export type OrderExecution = 'resting' | 'immediate_or_cancel' | 'fill_or_kill';
+
 export interface TradeOrder {
     id: string;
     user_id: string;
@@ -25,6 +28,7 @@ export interface TradeOrder {
     expires_at: string | null;
     created_at: string;
     updated_at: string;
+    alliance_only: boolean;
     // Optional joined fields
     village_name?: string;
     user_display_name?: string;
@@ -64,6 +68,8 @@ export interface CreateOrderRequest {
     quantity: number;
     price_per_unit: number;
     expires_in_hours?: number;
+    alliance_only?: boolean;
+    execution?: OrderExecution; // Defaults to 'resting'
 }
 
 export interface AcceptOrderRequest {
@@ -71,6 +77,57 @@ export interface AcceptOrderRequest {
     quantity?: number; // For partial fill
 }
 
+// Simulated fill against the current book (GET /trade/quote)
+export interface TradeQuote {
+    resource_type: TradeResourceType;
+    side: TradeOrderType;
+    quantity_requested: number;
+    quantity_filled: number;
+    quantity_unfilled: number;
+    total_gold: number;
+    average_price: number | null;
+    best_price: number | null;
+    worst_price: number | null;
+    orders_matched: number;
+}
+
+export interface OrderBookLevel {
+    price_per_unit: number;
+    quantity: number;
+    order_count: number;
+}
+
+export interface OrderBook {
+    resource_type: TradeResourceType;
+    bids: OrderBookLevel[];
+    asks: OrderBookLevel[];
+    spread: number | null;
+}
+
+export interface PriceCandle {
+    bucket_start: string;
+    open: number;
+    high: number;
+    low: number;
+    close: number;
+    volume: number;
+}
+
+export interface PriceCandles {
+    resource_type: TradeResourceType;
+    interval_minutes: number;
+    candles: PriceCandle[];
+}
+
+export interface OrderStatusCounts {
+    open: number;
+    partially_filled: number;
+    filled: number;
+    cancelled: number;
+    expired: number;
+    total: number;
+}
+
 interface TradeState {
     marketSummary: MarketSummary | null;
     openOrders: TradeOrder[];
@@ -25,6 +25,10 @@ export type BuildingType =
     | 'cranny'
     | 'hero_mansion'
     | 'tavern'
+    | 'sawmill'
+    | 'brickyard'
+    | 'iron_foundry'
+    | 'grain_mill'
     | 'woodcutter'
     | 'clay_pit'
     | 'iron_mine'
@@ -54,6 +58,10 @@ export interface ProductionRates {
     crop_per_hour: number;
     crop_consumption: number;
     net_crop_per_hour: number;
+    wood_bonus_percent: number;
+    clay_bonus_percent: number;
+    iron_bonus_percent: number;
+    crop_bonus_percent: number;
 }
 
 export interface Village {
@@ -72,7 +80,32 @@ export interface Village {
     culture_points: number;
     loyalty: number;
     created_at: string;
+    note?: string;
     production?: ProductionRates;
+    resource_accounting?: ResourceAccounting;
+    assigned_hero?: AssignedHero;
+    protection_until?: string;
+}
+
+export interface AssignedHero {
+    id: string;
+    name: string;
+    level: number;
+    production_bonus_percent: number;
+    def_bonus_percent: number;
+}
+
+export interface ResourceAmounts {
+    wood: number;
+    clay: number;
+    iron: number;
+    crop: number;
+}
+
+export interface ResourceAccounting {
+    in_construction: ResourceAmounts;
+    locked_by_trade: ResourceAmounts;
+    available: ResourceAmounts;
 }
 
 interface BuildResponse {